@@ -175,6 +175,12 @@ pub trait Field:
     // Sets `self` to `self`'s inverse if it exists. Otherwise it is a no-op.
     fn inverse_in_place(&mut self) -> Option<&mut Self>;
 
+    /// The inverse of `self`, or zero if `self` is zero. This default branches on which, so a
+    /// field whose elements may be secret must override it to not reveal whether `self` is zero.
+    fn inverse_or_zero(&self) -> Self {
+        self.inverse().unwrap_or_else(Self::zero)
+    }
+
     /// Exponentiates this element by a power of the base prime modulus via
     /// the Frobenius automorphism.
     fn frobenius_map(&mut self, power: usize);
@@ -408,6 +414,17 @@ pub trait PrimeField:
     /// Returns the underlying representation of the prime field element.
     fn into_repr(&self) -> Self::BigInt;
 
+    /// The `MODULUS_BITS` bits of the representation of `self`, least significant first, as zeros
+    /// and ones of the field. Unlike [PrimeField::into_repr], this suits fields whose elements may be
+    /// secret.
+    fn to_field_bits_le(&self) -> Vec<Self> {
+        let mut bits = self.into_repr().to_bits_le();
+        bits.truncate(Self::Params::MODULUS_BITS as usize);
+        bits.into_iter()
+            .map(|b| if b { Self::one() } else { Self::zero() })
+            .collect()
+    }
+
     /// Reads bytes in big-endian, and converts them to a field element.
     /// If the bytes are larger than the modulus, it will reduce them.
     fn from_be_bytes_mod_order(bytes: &[u8]) -> Self {
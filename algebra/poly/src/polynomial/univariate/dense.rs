@@ -476,7 +476,12 @@ impl<'a, 'b, F: FftField> Mul<&'a DensePolynomial<F>> for &'b DensePolynomial<F>
             let mut self_evals = self.evaluate_over_domain_by_ref(domain);
             let other_evals = other.evaluate_over_domain_by_ref(domain);
             self_evals *= &other_evals;
-            self_evals.interpolate()
+            let mut product = self_evals.interpolate();
+            // The coefficients past the product's degree are zero, but `interpolate` cannot drop
+            // them when they are secret-shared: drop them here, so that products do not grow to the
+            // domain size.
+            product.coeffs.truncate(self.coeffs.len() + other.coeffs.len() - 1);
+            product
         }
     }
 }
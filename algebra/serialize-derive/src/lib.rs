@@ -1,11 +1,54 @@
 #![warn(unused, future_incompatible, nonstandard_style, rust_2018_idioms)]
 #![forbid(unsafe_code)]
 
-use proc_macro2::TokenStream;
-use syn::{parse_macro_input, Data, DeriveInput, Index, Type};
+use proc_macro2::{TokenStream, TokenTree};
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Generics, Index, Type};
 
 use quote::{quote, ToTokens};
 
+/// Whether `tokens` name any of `params`.
+fn mentions(tokens: TokenStream, params: &[syn::Ident]) -> bool {
+    tokens.into_iter().any(|t| match t {
+        TokenTree::Ident(i) => params.contains(&i),
+        TokenTree::Group(g) => mentions(g.stream(), params),
+        _ => false,
+    })
+}
+
+/// The struct's generics, with `ty: bound` for each (non-tuple) field type `ty`
+/// that names a type parameter.
+///
+/// Generic containers then need their contents to be serializable only where
+/// they are serialized, rather than everywhere they are used.
+fn bounded_generics(ast: &DeriveInput, bound: TokenStream) -> Generics {
+    fn leaves<'a>(ty: &'a Type, out: &mut Vec<&'a Type>) {
+        match ty {
+            Type::Tuple(tuple) => tuple.elems.iter().for_each(|t| leaves(t, out)),
+            _ => out.push(ty),
+        }
+    }
+    let params: Vec<syn::Ident> = ast
+        .generics
+        .type_params()
+        .map(|p| p.ident.clone())
+        .collect();
+    let mut generics = ast.generics.clone();
+    if let Data::Struct(ref data_struct) = ast.data {
+        let mut tys = Vec::new();
+        data_struct
+            .fields
+            .iter()
+            .for_each(|f| leaves(&f.ty, &mut tys));
+        let where_clause = generics.make_where_clause();
+        for ty in tys {
+            if mentions(ty.to_token_stream(), &params) {
+                where_clause.predicates.push(parse_quote! { #ty: #bound });
+            }
+        }
+    }
+    generics
+}
+
 #[proc_macro_derive(CanonicalSerialize)]
 pub fn derive_canonical_serialize(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
@@ -60,7 +103,8 @@ fn impl_serialize_field(
 fn impl_canonical_serialize(ast: &syn::DeriveInput) -> TokenStream {
     let name = &ast.ident;
 
-    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let generics = bounded_generics(ast, quote! { CanonicalSerialize });
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
     let mut serialize_body = Vec::<TokenStream>::new();
     let mut serialized_size_body = Vec::<TokenStream>::new();
@@ -172,7 +216,8 @@ fn impl_deserialize_field(ty: &Type) -> (TokenStream, TokenStream, TokenStream)
 fn impl_canonical_deserialize(ast: &syn::DeriveInput) -> TokenStream {
     let name = &ast.ident;
 
-    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let generics = bounded_generics(ast, quote! { CanonicalDeserialize });
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
     let deserialize_body;
     let deserialize_uncompressed_body;
@@ -41,7 +41,9 @@ pub trait CanonicalSerializeWithFlags: CanonicalSerialize {
 /// and that both readers read the same number of bytes.
 ///
 /// This trait can be derived if all fields of a struct implement
-/// `CanonicalSerialize` and the `derive` feature is enabled.
+/// `CanonicalSerialize` and the `derive` feature is enabled. For a generic
+/// struct, the derived impl requires this of each field type that names a type
+/// parameter.
 ///
 /// # Example
 /// ```
@@ -968,6 +970,21 @@ mod test {
         ]);
     }
 
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_derive_generic() {
+        // The derived impls bound the field types, so `T` needs no bound of its own
+        #[derive(PartialEq, Debug, CanonicalSerialize, CanonicalDeserialize)]
+        struct Pair<T> {
+            a: T,
+            b: (u64, Vec<T>),
+        }
+        test_serialize(Pair {
+            a: Dummy,
+            b: (3, vec![Dummy, Dummy]),
+        });
+    }
+
     #[test]
     fn test_option() {
         test_serialize(Some(Dummy));
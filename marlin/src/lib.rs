@@ -168,6 +168,8 @@ impl<F: PrimeField, PC: PolynomialCommitment<F, DensePolynomial<F>>, D: Digest>
         // --------------------------------------------------------------------
         // First round
 
+        #[cfg(feature = "mpc-trace")]
+        let round = mpc_net::op("first_round");
         let (mut prover_first_msg, prover_first_oracles, prover_state) =
             AHPForR1CS::prover_first_round(prover_init_state, zk_rng)?;
         prover_first_msg.publicize();
@@ -190,11 +192,15 @@ impl<F: PrimeField, PC: PolynomialCommitment<F, DensePolynomial<F>>, D: Digest>
         let (verifier_first_msg, verifier_state) =
             AHPForR1CS::verifier_first_round(index_pk.index_vk.index_info, &mut fs_rng)?;
         end_timer!(t);
+        #[cfg(feature = "mpc-trace")]
+        drop(round);
         // --------------------------------------------------------------------
 
         // --------------------------------------------------------------------
         // Second round
 
+        #[cfg(feature = "mpc-trace")]
+        let round = mpc_net::op("second_round");
         let (mut prover_second_msg, prover_second_oracles, prover_state) =
             AHPForR1CS::prover_second_round(&verifier_first_msg, prover_state, zk_rng);
 
@@ -213,10 +219,14 @@ impl<F: PrimeField, PC: PolynomialCommitment<F, DensePolynomial<F>>, D: Digest>
 
         let (verifier_second_msg, verifier_state) =
             AHPForR1CS::verifier_second_round(verifier_state, &mut fs_rng);
+        #[cfg(feature = "mpc-trace")]
+        drop(round);
         // --------------------------------------------------------------------
 
         // --------------------------------------------------------------------
         // Third round
+        #[cfg(feature = "mpc-trace")]
+        let round = mpc_net::op("third_round");
         let (mut prover_third_msg, prover_third_oracles) =
             AHPForR1CS::prover_third_round(&verifier_second_msg, prover_state, zk_rng)?;
 
@@ -234,6 +244,8 @@ impl<F: PrimeField, PC: PolynomialCommitment<F, DensePolynomial<F>>, D: Digest>
         fs_rng.absorb(&to_bytes![third_comms, prover_third_msg].unwrap());
 
         let verifier_state = AHPForR1CS::verifier_third_round(verifier_state, &mut fs_rng);
+        #[cfg(feature = "mpc-trace")]
+        drop(round);
         // --------------------------------------------------------------------
 
         // Gather prover polynomials in one vector.
@@ -283,6 +295,8 @@ impl<F: PrimeField, PC: PolynomialCommitment<F, DensePolynomial<F>>, D: Digest>
         )?;
 
         let eval_time = start_timer!(|| "Evaluating linear combinations over query set");
+        #[cfg(feature = "mpc-trace")]
+        let round = mpc_net::op("evaluate");
         let mut evaluations = Vec::new();
         for (label, (_, point)) in &query_set {
             let lc = lc_s
@@ -298,12 +312,16 @@ impl<F: PrimeField, PC: PolynomialCommitment<F, DensePolynomial<F>>, D: Digest>
         evaluations.sort_by(|a, b| a.0.cmp(&b.0));
         let mut evaluations = evaluations.into_iter().map(|x| x.1).collect::<Vec<F>>();
         evaluations.iter_mut().for_each(|e| e.publicize());
+        #[cfg(feature = "mpc-trace")]
+        drop(round);
         end_timer!(eval_time);
 
         fs_rng.absorb(&evaluations);
         let opening_challenge: F = u128::rand(&mut fs_rng).into();
 
         let open_time = start_timer!(|| "Openning combinations");
+        #[cfg(feature = "mpc-trace")]
+        let round = mpc_net::op("open_combinations");
         let pc_proof = PC::open_combinations(
             &index_pk.committer_key,
             &lc_s,
@@ -315,6 +333,8 @@ impl<F: PrimeField, PC: PolynomialCommitment<F, DensePolynomial<F>>, D: Digest>
             Some(zk_rng),
         )
         .map_err(Error::from_pc_err)?;
+        #[cfg(feature = "mpc-trace")]
+        drop(round);
         end_timer!(open_time);
 
         // Gather prover messages together.
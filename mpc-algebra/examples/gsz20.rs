@@ -1,12 +1,13 @@
 use ark_ec::{group::Group, AffineCurve, PairingEngine};
-use ark_ff::{FftField, Field, PrimeField, UniformRand};
+use ark_ff::{FftField, Field, FromBytes, PrimeField, UniformRand};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use log::debug;
 use mpc_algebra::gsz20::group::GszGroupShare;
 use mpc_algebra::{
-    msm::NaiveMsm, share::field::FieldShare, share::group::GroupShare, share::gsz20::*,
+    channel::MpcSerNet, msm::NaiveMsm, share::field::FieldShare, share::group::GroupShare, share::gsz20::*,
     share::pairing::PairingShare, Reveal,
 };
-use mpc_net::{MpcNet, MpcMultiNet as Net};
+use mpc_net::{MpcNet, MpcMultiNet as Net, Tag};
 
 use std::path::PathBuf;
 use structopt::StructOpt;
@@ -52,10 +53,14 @@ fn test_ip<F: FftField>() {
 
 fn test<F: FftField>() {
     let rng = &mut ark_std::test_rng();
-    let (a, b) = field::double_rand::<F>();
+    let (a, b) = field::double_rand::<F>().unwrap();
     let a_pub = field::open(&a);
     let b_pub = field::open(&b);
     assert_eq!(a_pub, b_pub);
+    let rs = field::batch_rand::<F>(2);
+    let rs_pub = GszFieldShare::batch_open(rs);
+    assert_ne!(rs_pub[0], rs_pub[1]);
+    assert_ne!(field::coin::<F>(), field::coin::<F>());
 
     for _i in 0..10 {
         let a_pub = F::rand(rng);
@@ -63,6 +68,9 @@ fn test<F: FftField>() {
         let a = GszFieldShare::from_public(a_pub);
         let b = GszFieldShare::from_public(b_pub);
         let c = field::mult(a, &b, true);
+        // King reshares with a random polynomial, not a constant one.
+        let c_shares = Net::broadcast(&c.val);
+        assert!(c_shares.iter().any(|s| *s != c_shares[0]));
         let c_pub = field::open(&c);
         assert_eq!(c_pub, a_pub * b_pub);
         assert_ne!(c_pub, a_pub * b_pub + F::one());
@@ -86,6 +94,54 @@ fn test<F: FftField>() {
     }
 }
 
+fn test_encoding<G: Group>() {
+    let rng = &mut ark_std::test_rng();
+    let a_pub = G::ScalarField::rand(rng);
+    let a = GszFieldShare::from_public(a_pub);
+    let a = field::mult(a, &field::rand(), true);
+    let mut bytes = Vec::new();
+    a.serialize(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), a.serialized_size());
+    assert_eq!(GszFieldShare::deserialize(&bytes[..]).unwrap(), a);
+    // An unknown version is rejected.
+    bytes[0] += 1;
+    assert!(GszFieldShare::<G::ScalarField>::deserialize(&bytes[..]).is_err());
+
+    let g = group::rand::<G, NaiveMsm<G>>();
+    let bytes = ark_ff::to_bytes![g].unwrap();
+    assert_eq!(GszGroupShare::read(&bytes[..]).unwrap(), g);
+
+    // Shares go through the network as themselves.
+    let all_a = Net::broadcast(&a);
+    assert_eq!(all_a[Net::party_id()], a);
+}
+
+fn test_tagged<F: FftField>() {
+    let rng = &mut ark_std::test_rng();
+    let a = field::mult(GszFieldShare::from_public(F::rand(rng)), &field::rand(), true);
+    let b = field::mult(GszFieldShare::from_public(F::rand(rng)), &field::rand(), true);
+    // Two tagged sessions run in other threads, each started in a party-dependent order, while
+    // this thread keeps using untagged messages.
+    let session = move |s: u64, x: GszFieldShare<F>| {
+        move || {
+            let all = Net::broadcast_tagged(Tag::new(s, 0), &x);
+            assert_eq!(all[Net::party_id()], x);
+            let sum = <Net as MpcSerNet>::king_compute_tagged(Tag::new(s, 1), &x.val, |xs| {
+                let sum = xs.iter().fold(F::zero(), |acc, x| acc + x);
+                vec![sum; xs.len()]
+            });
+            assert_eq!(sum, all.iter().fold(F::zero(), |acc, x| acc + x.val));
+        }
+    };
+    let (first, second) = if Net::party_id() % 2 == 0 { ((1, a), (2, b)) } else { ((2, b), (1, a)) };
+    let first = std::thread::spawn(session(first.0, first.1));
+    let second = std::thread::spawn(session(second.0, second.1));
+    let all_a = Net::broadcast(&a);
+    assert_eq!(all_a[Net::party_id()], a);
+    first.join().unwrap();
+    second.join().unwrap();
+}
+
 fn test_mul_field<E: PairingEngine>() {
     use mpc_algebra::share::PanicBeaverSource;
     let rng = &mut ark_std::test_rng();
@@ -109,10 +165,11 @@ fn test_mul_field<E: PairingEngine>() {
 
 fn test_group<G: Group>() {
     let rng = &mut ark_std::test_rng();
-    let (a, b) = group::double_rand::<G, NaiveMsm<G>>();
+    let (a, b) = group::double_rand::<G, NaiveMsm<G>>().unwrap();
     let a_pub = group::open(&a);
     let b_pub = group::open(&b);
     assert_eq!(a_pub, b_pub);
+    assert!(!a_pub.is_zero());
 
     for _i in 0..2 {
         let a_pub = G::ScalarField::rand(rng);
@@ -126,7 +183,7 @@ fn test_group<G: Group>() {
 
     let s1_pub = G::ScalarField::rand(rng);
     let s2_pub = G::ScalarField::rand(rng);
-    let s2 = GszFieldShare::from_public(s1_pub);
+    let s2 = GszFieldShare::from_public(s2_pub);
     let mut a = a;
     <GszGroupShare<G, NaiveMsm<G>> as GroupShare<G>>::scale_pub_scalar(&mut a, &s1_pub);
     let as1s2 = <GszGroupShare<G, NaiveMsm<G>> as GroupShare<G>>::scale(
@@ -217,6 +274,8 @@ fn main() {
     test_group::<ark_bls12_377::G2Projective>();
     test_group::<ark_bls12_377::G1Affine>();
     test_group::<ark_bls12_377::G2Affine>();
+    test_encoding::<ark_bls12_377::G1Projective>();
+    test_tagged::<ark_bls12_377::Fr>();
     test_mul_field::<ark_bls12_377::Bls12_377>();
     test_pairing::<ark_bls12_377::Bls12_377, GszPairingShare<ark_bls12_377::Bls12_377>>();
 
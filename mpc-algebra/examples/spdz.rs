@@ -0,0 +1,212 @@
+use ark_ec::group::Group;
+use ark_ff::{Field, FromBytes, One, PrimeField, UniformRand};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use log::debug;
+use mpc_algebra::malicious_majority::*;
+use mpc_algebra::{
+    channel::MpcSerNet,
+    encoding,
+    msm::NaiveMsm,
+    share::field::FieldShare,
+    share::group::GroupShare,
+    share::preprocessing::{self, Kind, Store},
+    share::spdz::*,
+    BeaverSource, FileFieldTripleSource, FileGroupTripleSource, Reveal,
+};
+use mpc_net::{MpcMultiNet as Net, MpcNet};
+
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "example", about = "An example of StructOpt usage.")]
+struct Opt {
+    /// Id
+    id: usize,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+}
+
+fn test_triples<F: Field>() {
+    let src = &mut SpdzTripleSource::<F>::default();
+    let (xs, ys, zs) = src.triples(10);
+    let xs = SpdzFieldShare::batch_open(xs);
+    let ys = SpdzFieldShare::batch_open(ys);
+    let zs = SpdzFieldShare::batch_open(zs);
+    for i in 0..xs.len() {
+        assert_eq!(xs[i] * ys[i], zs[i]);
+    }
+    let (xs, ys) = src.inv_pairs(10);
+    let xs = SpdzFieldShare::batch_open(xs);
+    let ys = SpdzFieldShare::batch_open(ys);
+    for i in 0..xs.len() {
+        assert_eq!(xs[i] * ys[i], F::one());
+    }
+}
+
+fn test_group_triples<G: Group>() {
+    let src = &mut SpdzGroupTripleSource::<G, NaiveMsm<G>>::default();
+    let (xs, ys, zs) = src.triples(10);
+    let xs = SpdzGroupShare::batch_open(xs);
+    let ys = SpdzFieldShare::batch_open(ys);
+    let zs = SpdzGroupShare::batch_open(zs);
+    for i in 0..xs.len() {
+        assert_eq!(xs[i].mul(&ys[i]), zs[i]);
+    }
+}
+
+fn test_store<G: Group>(path: &Path) {
+    type Fs<G> = SpdzFieldShare<<G as Group>::ScalarField>;
+    type Gs<G> = SpdzGroupShare<G, NaiveMsm<G>>;
+    let mut store = Store::new();
+    offline::save_key::<G::ScalarField>(&mut store);
+    store.push(Kind::Triple, &offline::triples::<G::ScalarField>(20));
+    store.push(Kind::InvPair, &offline::inv_pairs::<G::ScalarField>(5));
+    store.push(Kind::Triple, &offline::group_triples::<G, NaiveMsm<G>>(5));
+    store.write(path).unwrap();
+
+    // The key comes back from the store.
+    offline::deinit::<G::ScalarField>();
+    preprocessing::load(path).unwrap();
+    offline::init::<G::ScalarField>();
+
+    let src = &mut FileFieldTripleSource::<Fs<G>>::default();
+    let (xs, ys, zs) = src.triples(15);
+    let xs = SpdzFieldShare::batch_open(xs);
+    let ys = SpdzFieldShare::batch_open(ys);
+    let zs = SpdzFieldShare::batch_open(zs);
+    for i in 0..xs.len() {
+        assert_eq!(xs[i] * ys[i], zs[i]);
+    }
+    assert_eq!(
+        preprocessing::remaining::<(Fs<G>, Fs<G>, Fs<G>)>(Kind::Triple),
+        5
+    );
+    let (xs, ys) = src.inv_pairs(5);
+    let xs = SpdzFieldShare::batch_open(xs);
+    let ys = SpdzFieldShare::batch_open(ys);
+    for i in 0..xs.len() {
+        assert!((xs[i] * ys[i]).is_one());
+    }
+    let (xs, ys, zs) = FileGroupTripleSource::<Fs<G>, Gs<G>>::default().triples(5);
+    let xs = SpdzGroupShare::batch_open(xs);
+    let ys = SpdzFieldShare::batch_open(ys);
+    let zs = SpdzGroupShare::batch_open(zs);
+    for i in 0..xs.len() {
+        assert_eq!(xs[i].mul(&ys[i]), zs[i]);
+    }
+    preprocessing::unload();
+
+    // Everything handed out (or reserved) before is gone.
+    preprocessing::load(path).unwrap();
+    assert_eq!(
+        preprocessing::remaining::<(Fs<G>, Fs<G>, Fs<G>)>(Kind::Triple),
+        0
+    );
+    preprocessing::unload();
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(path.with_extension("prep.consumed")).unwrap();
+}
+
+fn test_encoding<G: Group>() {
+    let rng = &mut ark_std::test_rng();
+    let a_pub = G::ScalarField::rand(rng);
+    let a = SpdzFieldShare::<G::ScalarField>::king_share(a_pub, rng);
+    let mut bytes = Vec::new();
+    a.serialize(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), a.serialized_size());
+    assert_eq!(SpdzFieldShare::deserialize(&bytes[..]).unwrap(), a);
+    // An unknown version is rejected.
+    bytes[0] += 1;
+    assert!(SpdzFieldShare::<G::ScalarField>::deserialize(&bytes[..]).is_err());
+
+    let g = SpdzGroupShare::<G, NaiveMsm<G>>::king_share(G::rand(rng), rng);
+    let bytes = ark_ff::to_bytes![g].unwrap();
+    assert_eq!(SpdzGroupShare::read(&bytes[..]).unwrap(), g);
+
+    // Shares go through the network as themselves.
+    let all_a = Net::broadcast(&a);
+    assert_eq!(all_a[Net::party_id()], a);
+
+    // Wire values keep their state, when asked to.
+    let x = MpcField::<G::ScalarField>::king_share(a_pub, rng);
+    let mut bytes = Vec::new();
+    encoding::with_state(|| x.serialize(&mut bytes)).unwrap();
+    let y = encoding::with_state(|| MpcField::<G::ScalarField>::deserialize(&bytes[..])).unwrap();
+    assert!(matches!(y, MpcField::<G::ScalarField>::Shared(_)));
+    assert_eq!(y.reveal(), a_pub);
+    // Otherwise, public ones encode as the underlying field.
+    let mut plain = Vec::new();
+    a_pub.serialize(&mut plain).unwrap();
+    let mut bytes = Vec::new();
+    MpcField::<G::ScalarField>::from_public(a_pub)
+        .serialize(&mut bytes)
+        .unwrap();
+    assert_eq!(bytes, plain);
+}
+
+fn test_field<F: PrimeField>() {
+    let rng = &mut ark_std::test_rng();
+    for _i in 0..4 {
+        let a_pub = F::rand(rng);
+        let b_pub = F::rand(rng);
+        let a = MpcField::<F>::king_share(a_pub, rng);
+        let b = MpcField::<F>::king_share(b_pub, rng);
+        assert_eq!((a * b).reveal(), a_pub * b_pub);
+        assert_eq!((a / b).reveal(), a_pub / b_pub);
+        assert_eq!((a * b + a).reveal(), a_pub * b_pub + a_pub);
+    }
+
+    let size = 100;
+    let a_pubs: Vec<F> = (0..size).map(|_| F::rand(rng)).collect();
+    let b_pubs: Vec<F> = (0..size).map(|_| F::rand(rng)).collect();
+    let mut a = MpcField::<F>::king_share_batch(a_pubs.clone(), rng);
+    let b = MpcField::<F>::king_share_batch(b_pubs.clone(), rng);
+    Field::batch_product_in_place(&mut a, &b);
+    for i in 0..size {
+        assert_eq!(a[i].reveal(), a_pubs[i] * b_pubs[i]);
+    }
+}
+
+fn test_group<G: Group>() {
+    let rng = &mut ark_std::test_rng();
+    for _i in 0..2 {
+        let a_pub = G::ScalarField::rand(rng);
+        let g_pub = G::rand(rng);
+        let a = SpdzFieldShare::<G::ScalarField>::king_share(a_pub, rng);
+        let g = SpdzGroupShare::<G, NaiveMsm<G>>::king_share(g_pub, rng);
+        let mut ga = <SpdzGroupShare<G, NaiveMsm<G>> as GroupShare<G>>::scale_pub_group(g_pub, &a);
+        ga.add(&g);
+        assert_eq!(ga.reveal(), g_pub.mul(&a_pub) + g_pub);
+    }
+    let bases: Vec<G> = (0..4).map(|_| G::rand(rng)).collect();
+    let scalar_pubs: Vec<G::ScalarField> = (0..4).map(|_| G::ScalarField::rand(rng)).collect();
+    let scalars = SpdzFieldShare::king_share_batch(scalar_pubs.clone(), rng);
+    let msm =
+        <SpdzGroupShare<G, NaiveMsm<G>> as GroupShare<G>>::multi_scale_pub_group(&bases, &scalars);
+    let expected: G = bases.iter().zip(&scalar_pubs).map(|(b, s)| b.mul(s)).sum();
+    assert_eq!(msm.reveal(), expected);
+}
+
+fn main() {
+    env_logger::builder().format_timestamp(None).init();
+    debug!("Start");
+    let opt = Opt::from_args();
+    Net::init_from_file(opt.input.to_str().unwrap(), opt.id);
+    MpcField::<ark_bls12_377::Fr>::init_protocol();
+
+    test_triples::<ark_bls12_377::Fr>();
+    test_field::<ark_bls12_377::Fr>();
+    test_group::<ark_bls12_377::G1Projective>();
+    test_group_triples::<ark_bls12_377::G1Projective>();
+    test_encoding::<ark_bls12_377::G1Projective>();
+    test_store::<ark_bls12_377::G1Projective>(
+        &std::env::temp_dir().join(format!("spdz-{}.prep", Net::party_id())),
+    );
+
+    MpcField::<ark_bls12_377::Fr>::deinit_protocol();
+    debug!("Done");
+    Net::deinit();
+}
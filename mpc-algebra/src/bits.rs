@@ -0,0 +1,347 @@
+//! Bits of shared field elements, and what needs them: comparisons and range checks.
+//!
+//! Values are read as integers in `[0, p)`, with `MODULUS_BITS` bits, least significant first. A
+//! shared bit is a shared field element that is zero or one. Public inputs are handled locally.
+//!
+//! The circuits are ripple-carry: each costs one round per bit, and all the values of a batch share
+//! those rounds.
+use ark_ff::{BigInteger, Field, FpParameters, One, PrimeField, UniformRand, Zero};
+use mpc_trait::MpcWire;
+
+use crate::share::field::FieldShare;
+use crate::share::preprocessing::{self, Kind};
+use crate::wire::field::{prime_sqrt, MpcField};
+
+fn n_bits<F: PrimeField>() -> usize {
+    F::Params::MODULUS_BITS as usize
+}
+
+/// The low `MODULUS_BITS` bits of an integer.
+fn low_bits<B: BigInteger>(x: B, n: usize) -> Vec<bool> {
+    let mut bits = x.to_bits_le();
+    bits.truncate(n);
+    bits
+}
+
+/// `a + b mod 2^len`, for bit vectors of that length.
+fn add_plain(a: &[bool], b: &[bool]) -> Vec<bool> {
+    let mut carry = false;
+    a.iter()
+        .zip(b)
+        .map(|(&x, &y)| {
+            let s = x ^ y ^ carry;
+            carry = (x & y) | (carry & (x ^ y));
+            s
+        })
+        .collect()
+}
+
+fn public_bit<F: PrimeField, S: FieldShare<F>>(b: bool) -> MpcField<F, S> {
+    MpcField::from_public(if b { F::one() } else { F::zero() })
+}
+
+/// Pairwise products, with one round for all the shared-by-shared ones.
+fn products<F: PrimeField, S: FieldShare<F>>(
+    xs: &[MpcField<F, S>],
+    ys: &[MpcField<F, S>],
+) -> Vec<MpcField<F, S>> {
+    let (mut sxs, mut sys, mut at) = (Vec::new(), Vec::new(), Vec::new());
+    let mut out: Vec<MpcField<F, S>> = xs
+        .iter()
+        .zip(ys)
+        .enumerate()
+        .map(|(i, (x, y))| {
+            if x.is_shared() && y.is_shared() {
+                sxs.push(*x);
+                sys.push(*y);
+                at.push(i);
+                *x
+            } else {
+                *x * y
+            }
+        })
+        .collect();
+    if !sxs.is_empty() {
+        MpcField::batch_product_in_place(&mut sxs, &sys);
+        for (i, p) in at.into_iter().zip(sxs) {
+            out[i] = p;
+        }
+    }
+    out
+}
+
+/// Opens all of `xs`, in one round.
+fn open_all<F: PrimeField, S: FieldShare<F>>(xs: &[MpcField<F, S>]) -> Vec<F> {
+    let shared = xs.iter().filter_map(|x| match x {
+        MpcField::Shared(s) => Some(*s),
+        MpcField::Public(_) => None,
+    });
+    let mut opened = S::batch_open(shared).into_iter();
+    xs.iter()
+        .map(|x| match x {
+            MpcField::Public(v) => *v,
+            MpcField::Shared(_) => opened.next().unwrap(),
+        })
+        .collect()
+}
+
+/// `n` shared random bits: from the loaded preprocessing [Store](preprocessing::Store) if it has
+/// enough, and otherwise from [generate_random_bits].
+pub fn random_bits<F: PrimeField, S: FieldShare<F>>(n: usize) -> Vec<MpcField<F, S>> {
+    preprocessing::take::<S>(Kind::RandomBit, n)
+        .unwrap_or_else(|| generate_random_bits::<F, S>(n))
+        .into_iter()
+        .map(MpcField::Shared)
+        .collect()
+}
+
+/// `n` shared random bits, to use now or to store as [Kind::RandomBit].
+///
+/// Each is `(r / sqrt(r^2) + 1) / 2`, for a random shared `r`. Only `r^2` is opened, and it says
+/// nothing about the sign of `r`.
+pub fn generate_random_bits<F: PrimeField, S: FieldShare<F>>(n: usize) -> Vec<S> {
+    let _op = mpc_net::op("random_bits");
+    let rng = &mut rand::thread_rng();
+    let half = F::from(2u8).inverse().unwrap();
+    let mut bits = Vec::with_capacity(n);
+    while bits.len() < n {
+        let rs: Vec<MpcField<F, S>> = (bits.len()..n).map(|_| MpcField::rand(rng)).collect();
+        let squares = open_all(&products(&rs, &rs));
+        for (r, square) in rs.into_iter().zip(squares) {
+            if square.is_zero() {
+                continue;
+            }
+            let s = prime_sqrt(&square).unwrap();
+            match r * MpcField::from_public(half / s) + MpcField::from_public(half) {
+                MpcField::Shared(b) => bits.push(b),
+                MpcField::Public(_) => unreachable!("Public random value"),
+            }
+        }
+    }
+    bits
+}
+
+/// `sum_i 2^i bits[i]`. Local.
+pub fn from_bits<F: PrimeField, S: FieldShare<F>>(bits: &[MpcField<F, S>]) -> MpcField<F, S> {
+    bits.iter()
+        .rev()
+        .fold(MpcField::zero(), |acc, b| acc.double() + b)
+}
+
+/// `[a < b]` for bit vectors of one length: the most significant difference decides. One round
+/// for the XORs, then one per bit.
+fn bits_less_than<F: PrimeField, S: FieldShare<F>>(
+    a: &[Vec<MpcField<F, S>>],
+    b: &[Vec<MpcField<F, S>>],
+) -> Vec<MpcField<F, S>> {
+    let len = a.first().map_or(0, |a| a.len());
+    let flat_a: Vec<_> = a.iter().flatten().cloned().collect();
+    let flat_b: Vec<_> = b.iter().flatten().cloned().collect();
+    let xors: Vec<_> = products(&flat_a, &flat_b)
+        .into_iter()
+        .zip(flat_a.iter().zip(&flat_b))
+        .map(|(ab, (x, y))| *x + y - ab.double())
+        .collect();
+    let mut lt = vec![MpcField::zero(); a.len()];
+    for i in 0..len {
+        let xor: Vec<_> = (0..a.len()).map(|j| xors[j * len + i]).collect();
+        let diff: Vec<_> = (0..a.len()).map(|j| b[j][i] - lt[j]).collect();
+        for (lt, step) in lt.iter_mut().zip(products(&xor, &diff)) {
+            *lt += step;
+        }
+    }
+    lt
+}
+
+/// `a + k`, for shared bit vectors `a` and public ones `k`, all of one length: the sum's bits, and
+/// its carry out. One round per bit.
+fn add_public_bits<F: PrimeField, S: FieldShare<F>>(
+    a: &[Vec<MpcField<F, S>>],
+    k: &[Vec<bool>],
+) -> (Vec<Vec<MpcField<F, S>>>, Vec<MpcField<F, S>>) {
+    let len = a.first().map_or(0, |a| a.len());
+    let mut carry = vec![MpcField::zero(); a.len()];
+    let mut sums = vec![Vec::with_capacity(len); a.len()];
+    for i in 0..len {
+        let ai: Vec<_> = a.iter().map(|a| a[i]).collect();
+        let ac = products(&ai, &carry);
+        for j in 0..a.len() {
+            // The majority of a, k and the carry: a OR carry if k is set, else a AND carry.
+            let next = if k[j][i] {
+                ai[j] + carry[j] - ac[j]
+            } else {
+                ac[j]
+            };
+            sums[j].push(ai[j] + carry[j] + public_bit(k[j][i]) - next.double());
+            carry[j] = next;
+        }
+    }
+    (sums, carry)
+}
+
+/// `n` random values below `p`, as shared bits: random bit strings, less those that are at least
+/// `p`. Only whether each string is rejected is opened.
+fn random_below_modulus<F: PrimeField, S: FieldShare<F>>(n: usize) -> Vec<Vec<MpcField<F, S>>> {
+    let l = n_bits::<F>();
+    let p: Vec<MpcField<F, S>> = low_bits(F::Params::MODULUS, l)
+        .into_iter()
+        .map(public_bit)
+        .collect();
+    let mut out = Vec::with_capacity(n);
+    while out.len() < n {
+        let m = n - out.len();
+        let candidates: Vec<Vec<_>> = random_bits::<F, S>(m * l)
+            .chunks(l)
+            .map(|c| c.to_vec())
+            .collect();
+        let below = open_all(&bits_less_than(&candidates, &vec![p.clone(); m]));
+        out.extend(
+            candidates
+                .into_iter()
+                .zip(below)
+                .filter(|(_, b)| b.is_one())
+                .map(|(c, _)| c),
+        );
+    }
+    out
+}
+
+/// The bits of `x`, least significant first.
+pub fn to_bits<F: PrimeField, S: FieldShare<F>>(x: MpcField<F, S>) -> Vec<MpcField<F, S>> {
+    batch_to_bits(&[x]).pop().unwrap()
+}
+
+/// The bits of each of `xs`, least significant first.
+///
+/// A shared `x` is masked by a random `r < p`, made of random bits, and `c = x - r` is opened. The
+/// bits of `x` are those of `r + c`, unless that sum wraps, when they are those of `r + c - p`. Both
+/// are computed, the second as `r + (c + 2^l - p) mod 2^l`, whose carry out says whether `r + c`
+/// wraps.
+pub fn batch_to_bits<F: PrimeField, S: FieldShare<F>>(
+    xs: &[MpcField<F, S>],
+) -> Vec<Vec<MpcField<F, S>>> {
+    let _op = mpc_net::op("to_bits");
+    let l = n_bits::<F>();
+    let shared: Vec<_> = xs.iter().filter(|x| x.is_shared()).cloned().collect();
+    let m = shared.len();
+    let rs = random_below_modulus::<F, S>(m);
+    let masked: Vec<_> = shared
+        .iter()
+        .zip(&rs)
+        .map(|(x, r)| *x - from_bits(r))
+        .collect();
+    let cs: Vec<Vec<bool>> = open_all(&masked)
+        .into_iter()
+        .map(|c| low_bits(c.into_repr(), l))
+        .collect();
+    let mut minus_p: Vec<bool> = low_bits(F::Params::MODULUS, l)
+        .into_iter()
+        .map(|b| !b)
+        .collect();
+    let mut one = vec![false; l];
+    one[0] = true;
+    minus_p = add_plain(&minus_p, &one);
+    let mut ks = cs.clone();
+    ks.extend(cs.iter().map(|c| add_plain(c, &minus_p)));
+    let (sums, carries) = add_public_bits(&[rs.clone(), rs].concat(), &ks);
+    let (plain, wrapped) = sums.split_at(m);
+    let wraps: Vec<_> = carries[m..]
+        .iter()
+        .flat_map(|w| std::iter::repeat(*w).take(l))
+        .collect();
+    let diffs: Vec<_> = wrapped
+        .iter()
+        .flatten()
+        .zip(plain.iter().flatten())
+        .map(|(w, p)| *w - p)
+        .collect();
+    let mut selected = products(&wraps, &diffs)
+        .into_iter()
+        .zip(plain.iter().flatten())
+        .map(|(d, p)| *p + d);
+    xs.iter()
+        .map(|x| match x {
+            MpcField::Public(x) => low_bits(x.into_repr(), l)
+                .into_iter()
+                .map(public_bit)
+                .collect(),
+            MpcField::Shared(_) => (&mut selected).take(l).collect(),
+        })
+        .collect()
+}
+
+/// `[a < b]`, as integers in `[0, p)`: a shared bit, or a public one if both are public.
+pub fn is_less_than<F: PrimeField, S: FieldShare<F>>(
+    a: MpcField<F, S>,
+    b: MpcField<F, S>,
+) -> MpcField<F, S> {
+    batch_is_less_than(&[a], &[b]).pop().unwrap()
+}
+
+/// [is_less_than], pairwise.
+pub fn batch_is_less_than<F: PrimeField, S: FieldShare<F>>(
+    a: &[MpcField<F, S>],
+    b: &[MpcField<F, S>],
+) -> Vec<MpcField<F, S>> {
+    let _op = mpc_net::op("less_than");
+    assert_eq!(a.len(), b.len());
+    let bits = batch_to_bits(&[a, b].concat());
+    let (a_bits, b_bits) = bits.split_at(a.len());
+    bits_less_than(a_bits, b_bits)
+}
+
+/// `[x < 2^k]`: whether no bit from `k` up is set, as a shared bit. The product of their
+/// negations is taken as a tree.
+pub fn is_in_range<F: PrimeField, S: FieldShare<F>>(x: MpcField<F, S>, k: usize) -> MpcField<F, S> {
+    batch_is_in_range(&[x], k).pop().unwrap()
+}
+
+/// [is_in_range], for each of `xs`.
+pub fn batch_is_in_range<F: PrimeField, S: FieldShare<F>>(
+    xs: &[MpcField<F, S>],
+    k: usize,
+) -> Vec<MpcField<F, S>> {
+    let _op = mpc_net::op("range_check");
+    if xs.is_empty() || k >= n_bits::<F>() {
+        return vec![MpcField::one(); xs.len()];
+    }
+    let mut factors: Vec<Vec<MpcField<F, S>>> = batch_to_bits(xs)
+        .into_iter()
+        .map(|bits| bits[k..].iter().map(|b| MpcField::one() - b).collect())
+        .collect();
+    while factors[0].len() > 1 {
+        let pairs = || factors.iter().flat_map(|f| f.chunks_exact(2));
+        let left: Vec<_> = pairs().map(|p| p[0]).collect();
+        let right: Vec<_> = pairs().map(|p| p[1]).collect();
+        let mut prods = products(&left, &right).into_iter();
+        factors = factors
+            .iter()
+            .map(|f| {
+                f.chunks(2)
+                    .map(|p| {
+                        if p.len() == 2 {
+                            prods.next().unwrap()
+                        } else {
+                            p[0]
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+    }
+    factors.into_iter().map(|f| f[0]).collect()
+}
+
+/// `[x = 0]`: whether no bit of `x` is set, as a shared bit. Only the masked value that
+/// [batch_to_bits] opens is revealed, and it says nothing about `x`.
+pub fn is_zero<F: PrimeField, S: FieldShare<F>>(x: MpcField<F, S>) -> MpcField<F, S> {
+    batch_is_zero(&[x]).pop().unwrap()
+}
+
+/// [is_zero], for each of `xs`: [batch_is_in_range] below `2^0`.
+pub fn batch_is_zero<F: PrimeField, S: FieldShare<F>>(
+    xs: &[MpcField<F, S>],
+) -> Vec<MpcField<F, S>> {
+    let _op = mpc_net::op("is_zero");
+    batch_is_in_range(xs, 0)
+}
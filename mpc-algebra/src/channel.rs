@@ -3,85 +3,480 @@ use digest::Digest;
 use rand::RngCore;
 use sha2::Sha256;
 use std::cell::Cell;
+use std::marker::PhantomData;
 
 use mpc_net::two as net_two;
 
-use mpc_net::MpcNet;
+use mpc_net::{MpcNet, NetError, Session, Tag};
 
+/// Typed messages over an [MpcNet].
+///
+/// As there, the `try_` methods return a [NetError], and the others raise it. A message that does
+/// not deserialize is a [NetError::Deserialize], blamed on its sender.
+///
+/// These go to the calling thread's network. [SerNet] has the same methods on a network passed
+/// explicitly, such as a [Session].
 pub trait MpcSerNet: MpcNet {
     #[inline]
     fn broadcast<T: CanonicalDeserialize + CanonicalSerialize>(out: &T) -> Vec<T> {
-        let mut bytes_out = Vec::new();
-        out.serialize(&mut bytes_out).unwrap();
-        let bytes_in = Self::broadcast_bytes(&bytes_out);
-        bytes_in
-            .into_iter()
-            .map(|b| T::deserialize(&b[..]).unwrap())
-            .collect()
+        Self::broadcast_tagged(Self::next_tag(), out)
     }
 
     #[inline]
     fn send_to_king<T: CanonicalDeserialize + CanonicalSerialize>(out: &T) -> Option<Vec<T>> {
-        let mut bytes_out = Vec::new();
-        out.serialize(&mut bytes_out).unwrap();
-        Self::send_bytes_to_king(&bytes_out).map(|bytes_in| {
-            bytes_in
-                .into_iter()
-                .map(|b| T::deserialize(&b[..]).unwrap())
-                .collect()
-        })
+        Self::send_to_king_tagged(Self::next_tag(), out)
     }
 
     #[inline]
     fn recv_from_king<T: CanonicalDeserialize + CanonicalSerialize>(out: Option<Vec<T>>) -> T {
-        let bytes_in = Self::recv_bytes_from_king(out.map(|outs| {
-            outs.iter()
-                .map(|out| {
-                    let mut bytes_out = Vec::new();
-                    out.serialize(&mut bytes_out).unwrap();
-                    bytes_out
-                })
-                .collect()
-        }));
-        T::deserialize(&bytes_in[..]).unwrap()
+        Self::recv_from_king_tagged(Self::next_tag(), out)
+    }
+
+    /// Send `outs[i]` to party `i`; returns what each party sent us.
+    #[inline]
+    fn all_to_all<T: CanonicalDeserialize + CanonicalSerialize>(outs: &[T]) -> Vec<T> {
+        Self::all_to_all_tagged(Self::next_tag(), outs)
+    }
+
+    /// [broadcast](Self::broadcast), or the error.
+    #[inline]
+    fn try_broadcast<T: CanonicalDeserialize + CanonicalSerialize>(
+        out: &T,
+    ) -> Result<Vec<T>, NetError> {
+        Self::try_broadcast_tagged(Self::next_tag(), out)
+    }
+
+    /// [send_to_king](Self::send_to_king), or the error.
+    #[inline]
+    fn try_send_to_king<T: CanonicalDeserialize + CanonicalSerialize>(
+        out: &T,
+    ) -> Result<Option<Vec<T>>, NetError> {
+        Self::try_send_to_king_tagged(Self::next_tag(), out)
+    }
+
+    /// [recv_from_king](Self::recv_from_king), or the error.
+    #[inline]
+    fn try_recv_from_king<T: CanonicalDeserialize + CanonicalSerialize>(
+        out: Option<Vec<T>>,
+    ) -> Result<T, NetError> {
+        Self::try_recv_from_king_tagged(Self::next_tag(), out)
+    }
+
+    /// [all_to_all](Self::all_to_all), or the error.
+    #[inline]
+    fn try_all_to_all<T: CanonicalDeserialize + CanonicalSerialize>(
+        outs: &[T],
+    ) -> Result<Vec<T>, NetError> {
+        Self::try_all_to_all_tagged(Self::next_tag(), outs)
+    }
+
+    /// [broadcast](Self::broadcast), under `tag`. See [MpcNet] on tags.
+    #[inline]
+    fn broadcast_tagged<T: CanonicalDeserialize + CanonicalSerialize>(tag: Tag, out: &T) -> Vec<T> {
+        Self::try_broadcast_tagged(tag, out).unwrap_or_else(|e| e.raise())
+    }
+
+    /// [send_to_king](Self::send_to_king), under `tag`.
+    #[inline]
+    fn send_to_king_tagged<T: CanonicalDeserialize + CanonicalSerialize>(
+        tag: Tag,
+        out: &T,
+    ) -> Option<Vec<T>> {
+        Self::try_send_to_king_tagged(tag, out).unwrap_or_else(|e| e.raise())
+    }
+
+    /// [recv_from_king](Self::recv_from_king), under `tag`.
+    #[inline]
+    fn recv_from_king_tagged<T: CanonicalDeserialize + CanonicalSerialize>(
+        tag: Tag,
+        out: Option<Vec<T>>,
+    ) -> T {
+        Self::try_recv_from_king_tagged(tag, out).unwrap_or_else(|e| e.raise())
+    }
+
+    /// [all_to_all](Self::all_to_all), under `tag`.
+    #[inline]
+    fn all_to_all_tagged<T: CanonicalDeserialize + CanonicalSerialize>(
+        tag: Tag,
+        outs: &[T],
+    ) -> Vec<T> {
+        Self::try_all_to_all_tagged(tag, outs).unwrap_or_else(|e| e.raise())
+    }
+
+    /// [broadcast_tagged](Self::broadcast_tagged), or the error.
+    #[inline]
+    fn try_broadcast_tagged<T: CanonicalDeserialize + CanonicalSerialize>(
+        tag: Tag,
+        out: &T,
+    ) -> Result<Vec<T>, NetError> {
+        Static::<Self>::new().try_broadcast_tagged(tag, out)
+    }
+
+    /// [send_to_king_tagged](Self::send_to_king_tagged), or the error.
+    #[inline]
+    fn try_send_to_king_tagged<T: CanonicalDeserialize + CanonicalSerialize>(
+        tag: Tag,
+        out: &T,
+    ) -> Result<Option<Vec<T>>, NetError> {
+        Static::<Self>::new().try_send_to_king_tagged(tag, out)
+    }
+
+    /// [recv_from_king_tagged](Self::recv_from_king_tagged), or the error.
+    #[inline]
+    fn try_recv_from_king_tagged<T: CanonicalDeserialize + CanonicalSerialize>(
+        tag: Tag,
+        out: Option<Vec<T>>,
+    ) -> Result<T, NetError> {
+        Static::<Self>::new().try_recv_from_king_tagged(tag, out)
+    }
+
+    /// [all_to_all_tagged](Self::all_to_all_tagged), or the error.
+    #[inline]
+    fn try_all_to_all_tagged<T: CanonicalDeserialize + CanonicalSerialize>(
+        tag: Tag,
+        outs: &[T],
+    ) -> Result<Vec<T>, NetError> {
+        Static::<Self>::new().try_all_to_all_tagged(tag, outs)
     }
 
     #[inline]
     fn atomic_broadcast<T: CanonicalDeserialize + CanonicalSerialize>(out: &T) -> Vec<T> {
-        let mut bytes_out = Vec::new();
-        out.serialize(&mut bytes_out).unwrap();
+        Self::try_atomic_broadcast(out).unwrap_or_else(|e| e.raise())
+    }
+
+    /// [atomic_broadcast](Self::atomic_broadcast), or the error. A party that opens its commitment
+    /// wrongly causes a [NetError::Abort].
+    #[inline]
+    fn try_atomic_broadcast<T: CanonicalDeserialize + CanonicalSerialize>(
+        out: &T,
+    ) -> Result<Vec<T>, NetError> {
+        Static::<Self>::new().try_atomic_broadcast(out)
+    }
+
+    #[inline]
+    fn king_compute<T: CanonicalDeserialize + CanonicalSerialize>(
+        x: &T,
+        f: impl Fn(Vec<T>) -> Vec<T>,
+    ) -> T {
+        Self::try_king_compute(x, f).unwrap_or_else(|e| e.raise())
+    }
+
+    /// [king_compute](Self::king_compute), or the error.
+    #[inline]
+    fn try_king_compute<T: CanonicalDeserialize + CanonicalSerialize>(
+        x: &T,
+        f: impl Fn(Vec<T>) -> Vec<T>,
+    ) -> Result<T, NetError> {
+        Static::<Self>::new().try_king_compute(x, f)
+    }
+
+    /// [king_compute](Self::king_compute), under `tag` and then `tag.next()`.
+    #[inline]
+    fn king_compute_tagged<T: CanonicalDeserialize + CanonicalSerialize>(
+        tag: Tag,
+        x: &T,
+        f: impl Fn(Vec<T>) -> Vec<T>,
+    ) -> T {
+        Self::try_king_compute_tagged(tag, x, f).unwrap_or_else(|e| e.raise())
+    }
+
+    /// [king_compute_tagged](Self::king_compute_tagged), or the error.
+    #[inline]
+    fn try_king_compute_tagged<T: CanonicalDeserialize + CanonicalSerialize>(
+        tag: Tag,
+        x: &T,
+        f: impl Fn(Vec<T>) -> Vec<T>,
+    ) -> Result<T, NetError> {
+        Static::<Self>::new().try_king_compute_tagged(tag, x, f)
+    }
+}
+
+fn to_bytes<T: CanonicalSerialize>(x: &T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    x.serialize(&mut bytes).unwrap();
+    bytes
+}
+
+/// Deserialize a message from `party`, under `tag`.
+fn from_bytes<T: CanonicalDeserialize>(
+    party: usize,
+    tag: Tag,
+    bytes: &[u8],
+) -> Result<T, NetError> {
+    T::deserialize(bytes).map_err(|e| NetError::Deserialize {
+        party,
+        tag,
+        reason: e.to_string(),
+    })
+}
+
+/// Deserialize one message from each party, under `tag`.
+fn from_all_bytes<T: CanonicalDeserialize>(
+    tag: Tag,
+    all: Vec<Vec<u8>>,
+) -> Result<Vec<T>, NetError> {
+    all.iter()
+        .enumerate()
+        .map(|(party, bytes)| from_bytes(party, tag, bytes))
+        .collect()
+}
+
+impl<N: MpcNet> MpcSerNet for N {}
+
+/// One party's network, as a value: the bytes layer under [SerNet].
+pub trait ByteNet {
+    fn party_id(&self) -> usize;
+    /// The tag of the next untagged message.
+    fn next_tag(&self) -> Tag;
+    fn try_broadcast_bytes_tagged(&self, tag: Tag, bytes: &[u8]) -> Result<Vec<Vec<u8>>, NetError>;
+    fn try_send_bytes_to_king_tagged(
+        &self,
+        tag: Tag,
+        bytes: &[u8],
+    ) -> Result<Option<Vec<Vec<u8>>>, NetError>;
+    fn try_recv_bytes_from_king_tagged(
+        &self,
+        tag: Tag,
+        bytes: Option<Vec<Vec<u8>>>,
+    ) -> Result<Vec<u8>, NetError>;
+    fn try_all_to_all_bytes_tagged(
+        &self,
+        tag: Tag,
+        bytes: Vec<Vec<u8>>,
+    ) -> Result<Vec<Vec<u8>>, NetError>;
+}
+
+impl ByteNet for Session {
+    fn party_id(&self) -> usize {
+        Session::party_id(self)
+    }
+    fn next_tag(&self) -> Tag {
+        Session::next_tag(self)
+    }
+    fn try_broadcast_bytes_tagged(&self, tag: Tag, bytes: &[u8]) -> Result<Vec<Vec<u8>>, NetError> {
+        self.broadcast_bytes(tag, bytes)
+    }
+    fn try_send_bytes_to_king_tagged(
+        &self,
+        tag: Tag,
+        bytes: &[u8],
+    ) -> Result<Option<Vec<Vec<u8>>>, NetError> {
+        self.send_bytes_to_king(tag, bytes)
+    }
+    fn try_recv_bytes_from_king_tagged(
+        &self,
+        tag: Tag,
+        bytes: Option<Vec<Vec<u8>>>,
+    ) -> Result<Vec<u8>, NetError> {
+        self.recv_bytes_from_king(tag, bytes)
+    }
+    fn try_all_to_all_bytes_tagged(
+        &self,
+        tag: Tag,
+        bytes: Vec<Vec<u8>>,
+    ) -> Result<Vec<Vec<u8>>, NetError> {
+        self.all_to_all_bytes(tag, bytes)
+    }
+}
+
+/// The calling thread's network, reached through the static methods of `N`, as a value.
+pub struct Static<N: ?Sized>(PhantomData<N>);
+
+impl<N: ?Sized> Static<N> {
+    pub fn new() -> Self {
+        Static(PhantomData)
+    }
+}
+
+impl<N: ?Sized> Default for Static<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N: MpcNet + ?Sized> ByteNet for Static<N> {
+    fn party_id(&self) -> usize {
+        N::party_id()
+    }
+    fn next_tag(&self) -> Tag {
+        N::next_tag()
+    }
+    fn try_broadcast_bytes_tagged(&self, tag: Tag, bytes: &[u8]) -> Result<Vec<Vec<u8>>, NetError> {
+        N::try_broadcast_bytes_tagged(tag, bytes)
+    }
+    fn try_send_bytes_to_king_tagged(
+        &self,
+        tag: Tag,
+        bytes: &[u8],
+    ) -> Result<Option<Vec<Vec<u8>>>, NetError> {
+        N::try_send_bytes_to_king_tagged(tag, bytes)
+    }
+    fn try_recv_bytes_from_king_tagged(
+        &self,
+        tag: Tag,
+        bytes: Option<Vec<Vec<u8>>>,
+    ) -> Result<Vec<u8>, NetError> {
+        N::try_recv_bytes_from_king_tagged(tag, bytes)
+    }
+    fn try_all_to_all_bytes_tagged(
+        &self,
+        tag: Tag,
+        bytes: Vec<Vec<u8>>,
+    ) -> Result<Vec<Vec<u8>>, NetError> {
+        N::try_all_to_all_bytes_tagged(tag, bytes)
+    }
+}
+
+/// Typed messages over a network passed as a value, such as a [Session]: the `try_` methods of
+/// [MpcSerNet].
+pub trait SerNet: ByteNet {
+    #[inline]
+    fn try_broadcast<T: CanonicalDeserialize + CanonicalSerialize>(
+        &self,
+        out: &T,
+    ) -> Result<Vec<T>, NetError> {
+        self.try_broadcast_tagged(self.next_tag(), out)
+    }
+
+    #[inline]
+    fn try_send_to_king<T: CanonicalDeserialize + CanonicalSerialize>(
+        &self,
+        out: &T,
+    ) -> Result<Option<Vec<T>>, NetError> {
+        self.try_send_to_king_tagged(self.next_tag(), out)
+    }
+
+    #[inline]
+    fn try_recv_from_king<T: CanonicalDeserialize + CanonicalSerialize>(
+        &self,
+        out: Option<Vec<T>>,
+    ) -> Result<T, NetError> {
+        self.try_recv_from_king_tagged(self.next_tag(), out)
+    }
+
+    #[inline]
+    fn try_all_to_all<T: CanonicalDeserialize + CanonicalSerialize>(
+        &self,
+        outs: &[T],
+    ) -> Result<Vec<T>, NetError> {
+        self.try_all_to_all_tagged(self.next_tag(), outs)
+    }
+
+    #[inline]
+    fn try_broadcast_tagged<T: CanonicalDeserialize + CanonicalSerialize>(
+        &self,
+        tag: Tag,
+        out: &T,
+    ) -> Result<Vec<T>, NetError> {
+        let bytes_in = self.try_broadcast_bytes_tagged(tag, &to_bytes(out))?;
+        from_all_bytes(tag, bytes_in)
+    }
+
+    #[inline]
+    fn try_send_to_king_tagged<T: CanonicalDeserialize + CanonicalSerialize>(
+        &self,
+        tag: Tag,
+        out: &T,
+    ) -> Result<Option<Vec<T>>, NetError> {
+        self.try_send_bytes_to_king_tagged(tag, &to_bytes(out))?
+            .map(|bytes_in| from_all_bytes(tag, bytes_in))
+            .transpose()
+    }
+
+    #[inline]
+    fn try_recv_from_king_tagged<T: CanonicalDeserialize + CanonicalSerialize>(
+        &self,
+        tag: Tag,
+        out: Option<Vec<T>>,
+    ) -> Result<T, NetError> {
+        let bytes_in = self.try_recv_bytes_from_king_tagged(
+            tag,
+            out.map(|outs| outs.iter().map(to_bytes).collect()),
+        )?;
+        from_bytes(0, tag, &bytes_in)
+    }
+
+    #[inline]
+    fn try_all_to_all_tagged<T: CanonicalDeserialize + CanonicalSerialize>(
+        &self,
+        tag: Tag,
+        outs: &[T],
+    ) -> Result<Vec<T>, NetError> {
+        let bytes_in =
+            self.try_all_to_all_bytes_tagged(tag, outs.iter().map(to_bytes).collect())?;
+        from_all_bytes(tag, bytes_in)
+    }
+
+    #[inline]
+    fn try_atomic_broadcast<T: CanonicalDeserialize + CanonicalSerialize>(
+        &self,
+        out: &T,
+    ) -> Result<Vec<T>, NetError> {
+        let _op = mpc_net::op("atomic_broadcast");
+        let mut bytes_out = to_bytes(out);
         let ser_len = bytes_out.len();
         bytes_out.resize(ser_len + COMMIT_RAND_BYTES, 0);
         rand::thread_rng().fill_bytes(&mut bytes_out[ser_len..]);
         let commitment = CommitHash::new().chain(&bytes_out).finalize();
         // exchange commitments
-        let all_commits = Self::broadcast_bytes(&commitment[..]);
+        let all_commits = self.try_broadcast_bytes_tagged(self.next_tag(), &commitment[..])?;
         // exchange (data || randomness)
-        let all_data = Self::broadcast_bytes(&bytes_out);
-        let self_id = Self::party_id();
+        let tag = self.next_tag();
+        let all_data = self.try_broadcast_bytes_tagged(tag, &bytes_out)?;
+        let self_id = self.party_id();
         for i in 0..all_commits.len() {
             if i != self_id {
                 // check other commitment
-                assert_eq!(
-                    &all_commits[i][..],
-                    &CommitHash::new().chain(&all_data[i]).finalize()[..]
-                );
+                if all_commits[i][..] != CommitHash::new().chain(&all_data[i]).finalize()[..] {
+                    return Err(NetError::Abort {
+                        party: i,
+                        reason: format!("it opened its commitment wrongly in {}", tag),
+                    });
+                }
             }
         }
         all_data
             .into_iter()
-            .map(|d| T::deserialize(&d[..ser_len]).unwrap())
+            .enumerate()
+            .map(|(i, d)| {
+                if d.len() < COMMIT_RAND_BYTES {
+                    return Err(NetError::LengthMismatch {
+                        party: i,
+                        tag,
+                        len: d.len(),
+                    });
+                }
+                from_bytes(i, tag, &d[..d.len() - COMMIT_RAND_BYTES])
+            })
             .collect()
     }
 
     #[inline]
-    fn king_compute<T: CanonicalDeserialize + CanonicalSerialize>(x: &T, f: impl Fn(Vec<T>) -> Vec<T>) -> T {
-        let king_response = Self::send_to_king(x).map(f);
-        Self::recv_from_king(king_response)
+    fn try_king_compute<T: CanonicalDeserialize + CanonicalSerialize>(
+        &self,
+        x: &T,
+        f: impl Fn(Vec<T>) -> Vec<T>,
+    ) -> Result<T, NetError> {
+        let _op = mpc_net::op("king_compute");
+        let king_response = self.try_send_to_king(x)?.map(f);
+        self.try_recv_from_king(king_response)
+    }
+
+    /// Under `tag` and then `tag.next()`.
+    #[inline]
+    fn try_king_compute_tagged<T: CanonicalDeserialize + CanonicalSerialize>(
+        &self,
+        tag: Tag,
+        x: &T,
+        f: impl Fn(Vec<T>) -> Vec<T>,
+    ) -> Result<T, NetError> {
+        let _op = mpc_net::op("king_compute");
+        let king_response = self.try_send_to_king_tagged(tag, x)?.map(f);
+        self.try_recv_from_king_tagged(tag.next(), king_response)
     }
 }
 
-impl<N: MpcNet> MpcSerNet for N {}
+impl<N: ByteNet> SerNet for N {}
 
 const ALLOW_CHEATING: Cell<bool> = Cell::new(true);
 
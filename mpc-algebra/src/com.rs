@@ -4,12 +4,15 @@ use sha2;
 
 use ark_ff::prelude::*;
 use ark_ff::FftField;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
 use mpc_trait::MpcWire;
 
-use crate::{channel, Reveal};
-use mpc_net::two as net_two;
-use crate::wire::field::MpcField;
+use crate::channel::MpcSerNet;
+use crate::encoding;
 use crate::share::field::FieldShare;
+use crate::wire::field::MpcField;
+use crate::Reveal;
+use mpc_net::{MpcMultiNet as Net, MpcNet};
 
 /// Vector-Commitable Field
 pub trait ComField: FftField + MpcWire {
@@ -22,103 +25,89 @@ pub trait ComField: FftField + MpcWire {
     fn check_opening(c: &Self::Commitment, p: Self::OpeningProof, i: usize, v: Self) -> bool;
 }
 
-impl<Fr: PrimeField, S: FieldShare<Fr>>  ComField for MpcField<Fr, S> {
-    type Commitment = (Vec<u8>, Vec<u8>);
+/// Each party puts its own shares in a Merkle tree. The commitment is every party's root.
+///
+/// An opening is every party's share, and its path. To check one, each party checks all the paths
+/// and then opens the value from the share that it sent, so the value is the committed one.
+impl<Fr: PrimeField, S: FieldShare<Fr>> ComField for MpcField<Fr, S> {
+    /// The root of each party's tree, by party.
+    type Commitment = Vec<Vec<u8>>;
+    /// The levels of this party's tree, from the leaves up, without the root.
     type Key = Vec<Vec<Vec<u8>>>;
-    type OpeningProof = (
-        Fr,
-        Fr,
-        Vec<(Vec<u8>, Vec<u8>)>,
-    );
+    /// The serialized share of each party, and its siblings from the leaf up, by party.
+    type OpeningProof = Vec<(Vec<u8>, Vec<Vec<u8>>)>;
     fn public_rand<R: Rng>(r: &mut R) -> Self {
         Self::from_public(Fr::rand(r))
     }
     fn commit(vs: &[Self]) -> (Self::Key, Self::Commitment) {
+        let _op = mpc_net::op("merkle_commit");
         let mut tree = Vec::new();
         let mut hashes: Vec<Vec<u8>> = vs
-            .into_iter()
-            .enumerate()
-            .map(|(_i, v)| {
-                let mut bytes_out = Vec::new();
-                v.unwrap_as_public().serialize(&mut bytes_out).unwrap();
-                let o = sha2::Sha256::digest(&bytes_out[..]).as_slice().to_owned();
-                o
-            })
+            .iter()
+            .map(|v| sha2::Sha256::digest(&share_bytes(v)).as_slice().to_owned())
             .collect();
         assert!(hashes.len().is_power_of_two());
         while hashes.len() > 1 {
-            let n = hashes.len() / 2;
-            let mut new = Vec::new();
-            for i in 0..n {
-                let mut h = sha2::Sha256::default();
-                h.update(&hashes[2 * i]);
-                h.update(&hashes[2 * i + 1]);
-                new.push(h.finalize().as_slice().to_owned());
-            }
+            let new = hashes
+                .chunks(2)
+                .map(|pair| node_hash(&pair[0], &pair[1]))
+                .collect();
             tree.push(std::mem::replace(&mut hashes, new));
         }
-        let slf = hashes.pop().unwrap();
-        let other = net_two::exchange_bytes(&slf).unwrap();
-        if net_two::am_first() {
-            (tree, (other, slf))
-        } else {
-            (tree, (slf, other))
-        }
+        let roots = Net::broadcast(&hashes.pop().unwrap());
+        (tree, roots)
     }
-    fn open_at(inputs: &[Self], tree: &Self::Key, mut i: usize) -> (Self, Self::OpeningProof) {
-        let self_f = inputs[i].unwrap_as_public();
-        let other_f = channel::exchange(&self_f);
+    fn open_at(vs: &[Self], tree: &Self::Key, mut i: usize) -> (Self, Self::OpeningProof) {
+        let _op = mpc_net::op("merkle_open");
+        let share = share_bytes(&vs[i]);
         let mut siblings = Vec::new();
-        for level in 0..tree.len() {
-            siblings.push(tree[level][i ^ 1].clone());
+        for level in tree {
+            siblings.push(level[i ^ 1].clone());
             i /= 2;
         }
-        assert_eq!(i / 2, 0);
-        let other: Vec<_> = siblings
-            .iter()
-            .map(|s| net_two::exchange_bytes(s).unwrap())
-            .collect();
-        let p = if net_two::am_first() {
-            siblings.into_iter().zip(other.into_iter()).collect()
-        } else {
-            other.into_iter().zip(siblings.into_iter()).collect()
-        };
-        (
-            MpcField::from_public(self_f + other_f),
-            if net_two::am_first() {
-                (self_f, other_f, p)
-            } else {
-                (other_f, self_f, p)
-            },
-        )
+        let proof = Net::broadcast(&(share, siblings));
+        let mine: Self = from_share_bytes(&proof[Net::party_id()].0).unwrap();
+        (Self::from_public(mine.reveal()), proof)
     }
     fn check_opening(c: &Self::Commitment, p: Self::OpeningProof, i: usize, v: Self) -> bool {
-        if p.0 + p.1 != v.reveal() {
+        if c.len() != Net::n_parties() || p.len() != Net::n_parties() {
             return false;
         }
-        let mut hash0 = Vec::new();
-        p.0.serialize(&mut hash0).unwrap();
-        hash0 = sha2::Sha256::digest(&hash0).as_slice().to_owned();
-        let mut hash1 = Vec::new();
-        p.1.serialize(&mut hash1).unwrap();
-        hash1 = sha2::Sha256::digest(&hash1).as_slice().to_owned();
-        for (j, (sib0, sib1)) in p.2.into_iter().enumerate() {
-            let mut h0 = sha2::Sha256::default();
-            let mut h1 = sha2::Sha256::default();
-            if (i >> j) & 1 == 0 {
-                h0.update(&hash0);
-                h0.update(&sib0);
-                h1.update(&hash1);
-                h1.update(&sib1);
-            } else {
-                h0.update(&sib0);
-                h0.update(&hash0);
-                h1.update(&sib1);
-                h1.update(&hash1);
+        let paths_ok = p.iter().zip(c).all(|((share, siblings), root)| {
+            let mut hash = sha2::Sha256::digest(share).as_slice().to_owned();
+            for (j, sibling) in siblings.iter().enumerate() {
+                hash = if (i >> j) & 1 == 0 {
+                    node_hash(&hash, sibling)
+                } else {
+                    node_hash(sibling, &hash)
+                };
             }
-            hash0 = h0.finalize().as_slice().to_owned();
-            hash1 = h1.finalize().as_slice().to_owned();
-        }
-        &(hash1, hash0) == c
+            &hash == root
+        });
+        let mine: Self = match from_share_bytes(&p[Net::party_id()].0) {
+            Ok(mine) => mine,
+            Err(_) => return false,
+        };
+        // Everyone opens, even if some path is bad, to stay in step.
+        let value_ok = mine.reveal() == v.reveal();
+        paths_ok && value_ok
     }
 }
+
+/// A leaf: a wire value, with its state, so that a share decodes as a share.
+fn share_bytes<T: CanonicalSerialize>(v: &T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    encoding::with_state(|| v.serialize(&mut bytes)).unwrap();
+    bytes
+}
+
+fn from_share_bytes<T: CanonicalDeserialize>(bytes: &[u8]) -> Result<T, SerializationError> {
+    encoding::with_state(|| T::deserialize(bytes))
+}
+
+fn node_hash(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut h = sha2::Sha256::default();
+    h.update(left);
+    h.update(right);
+    h.finalize().as_slice().to_owned()
+}
@@ -0,0 +1,131 @@
+//! Byte encodings of shares, and of wire values that may hold them.
+//!
+//! Every share type encodes as a one-byte header, then its fields in declaration order: values
+//! first, then (for SPDZ) the MAC share, then (for GSZ20) the Shamir degree. The header holds
+//! [VERSION] in its low bits, and any [Flags] in its high bits, so the `*WithFlags` traits need no
+//! extra byte. Decoding rejects any other version.
+//!
+//! `ToBytes` and `FromBytes` use the same encoding as `CanonicalSerialize` and
+//! `CanonicalDeserialize`.
+//!
+//! Wire values ([MpcField](crate::MpcField), [MpcGroup](crate::MpcGroup), and their pairing
+//! wrappers) encode a public value exactly as the underlying type does, so transcripts hashed
+//! during an MPC match the ones a plain verifier computes. A shared value encodes as its share.
+//! Since those two cannot be told apart, wire values decode as public, unless decoded [with_state]:
+//! inside it, every wire value encodes and decodes a state byte (0 for public, 1 for shared) first.
+//! Use it to checkpoint shared witnesses, save shared proving keys, or send shared wire values.
+//! Outside of it, encoding a shared wire value fails.
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Flags, SerializationError};
+
+use std::cell::Cell;
+use std::io::{self, Read, Write};
+
+/// The share encoding version. Encodings with any other version are rejected.
+pub const VERSION: u8 = 1;
+
+thread_local! {
+    static WITH_STATE: Cell<bool> = Cell::new(false);
+}
+
+/// Run `f`, with every wire value encoding (and decoding) whether it is public or shared.
+pub fn with_state<O, Func: FnOnce() -> O>(f: Func) -> O {
+    let old = WITH_STATE.with(|w| w.replace(true));
+    let r = f();
+    WITH_STATE.with(|w| w.set(old));
+    r
+}
+
+/// Whether we are inside [with_state].
+pub fn is_with_state() -> bool {
+    WITH_STATE.with(|w| w.get())
+}
+
+/// Write the header of a share encoding.
+pub fn write_header<W: Write, F: Flags>(writer: W, flags: F) -> Result<(), SerializationError> {
+    (VERSION | flags.u8_bitmask()).serialize(writer)
+}
+
+/// Read the header of a share encoding, returning its flags.
+pub fn read_header<R: Read, F: Flags>(reader: R) -> Result<F, SerializationError> {
+    let mut header = u8::deserialize(reader)?;
+    let flags = F::from_u8_remove_flags(&mut header).ok_or(SerializationError::UnexpectedFlags)?;
+    if header != VERSION {
+        return Err(SerializationError::InvalidData);
+    }
+    Ok(flags)
+}
+
+/// Write the state byte of a wire value, if we are inside [with_state]. Outside of it, a shared
+/// value is an error, since it would decode as a public one.
+pub fn write_state<W: Write>(writer: W, shared: bool) -> Result<(), SerializationError> {
+    if is_with_state() {
+        (shared as u8).serialize(writer)
+    } else if shared {
+        Err(SerializationError::IoError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "shared wire values only encode inside encoding::with_state",
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// The size of the state byte of a wire value.
+pub fn state_size() -> usize {
+    is_with_state() as usize
+}
+
+/// Read the state byte of a wire value, returning whether it is shared. Outside of [with_state],
+/// wire values are public.
+pub fn read_state<R: Read>(reader: R) -> Result<bool, SerializationError> {
+    if is_with_state() {
+        match u8::deserialize(reader)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SerializationError::InvalidData),
+        }
+    } else {
+        Ok(false)
+    }
+}
+
+/// For implementing `ToBytes` and `FromBytes` on top of the canonical encoding.
+pub fn to_io_error(e: SerializationError) -> io::Error {
+    match e {
+        SerializationError::IoError(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::share::add::AdditiveFieldShare;
+    use crate::{MpcField, Reveal};
+    use ark_bls12_377::Fr;
+    use ark_ff::One;
+    use ark_serialize::{CanonicalDeserializeWithFlags, CanonicalSerializeWithFlags, SWFlags};
+
+    type F = MpcField<Fr, AdditiveFieldShare<Fr>>;
+
+    #[test]
+    fn public_values_take_flags() {
+        let x = F::from_public(Fr::one());
+        let mut bytes = Vec::new();
+        x.serialize_with_flags(&mut bytes, SWFlags::PositiveY)
+            .unwrap();
+        assert_eq!(bytes.len(), x.serialized_size_with_flags::<SWFlags>());
+        let (y, flags) = F::deserialize_with_flags::<_, SWFlags>(&bytes[..]).unwrap();
+        assert_eq!(y, x);
+        assert_eq!(flags.u8_bitmask(), SWFlags::PositiveY.u8_bitmask());
+    }
+
+    #[test]
+    fn shared_values_need_state() {
+        let x = F::Shared(AdditiveFieldShare::from_add_shared(Fr::one()));
+        assert!(x.serialize(&mut Vec::new()).is_err());
+        let mut bytes = Vec::new();
+        with_state(|| x.serialize(&mut bytes)).unwrap();
+        assert_eq!(with_state(|| F::deserialize(&bytes[..])).unwrap(), x);
+    }
+}
@@ -40,46 +40,47 @@ impl<T: Field, S: PrimeField> Display for MulFieldGroup<T, S> {
     }
 }
 impl<T: Field, S: PrimeField> ToBytes for MulFieldGroup<T, S> {
-    fn write<W: Write>(&self, _writer: W) -> io::Result<()> {
-        unimplemented!("write")
+    fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.val.write(writer)
     }
 }
 impl<T: Field, S: PrimeField> FromBytes for MulFieldGroup<T, S> {
-    fn read<R: Read>(_reader: R) -> io::Result<Self> {
-        unimplemented!("read")
+    fn read<R: Read>(reader: R) -> io::Result<Self> {
+        Ok(Self::new(T::read(reader)?))
     }
 }
 impl<T: Field, S: PrimeField> CanonicalSerialize for MulFieldGroup<T, S> {
-    fn serialize<W: Write>(&self, _writer: W) -> Result<(), SerializationError> {
-        unimplemented!("serialize")
+    fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+        self.val.serialize(writer)
     }
     fn serialized_size(&self) -> usize {
-        unimplemented!("serialized_size")
+        self.val.serialized_size()
     }
 }
 impl<T: Field, S: PrimeField> CanonicalSerializeWithFlags for MulFieldGroup<T, S> {
     fn serialize_with_flags<W: Write, F: Flags>(
         &self,
-        _writer: W,
-        _flags: F,
+        writer: W,
+        flags: F,
     ) -> Result<(), SerializationError> {
-        unimplemented!("serialize_with_flags")
+        self.val.serialize_with_flags(writer, flags)
     }
 
     fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-        unimplemented!("serialized_size_with_flags")
+        self.val.serialized_size_with_flags::<F>()
     }
 }
 impl<T: Field, S: PrimeField> CanonicalDeserialize for MulFieldGroup<T, S> {
-    fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-        unimplemented!("deserialize")
+    fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+        Ok(Self::new(T::deserialize(reader)?))
     }
 }
 impl<T: Field, S: PrimeField> CanonicalDeserializeWithFlags for MulFieldGroup<T, S> {
     fn deserialize_with_flags<R: Read, F: Flags>(
-        _reader: R,
+        reader: R,
     ) -> Result<(Self, F), SerializationError> {
-        unimplemented!("deserialize_with_flags")
+        let (val, flags) = T::deserialize_with_flags(reader)?;
+        Ok((Self::new(val), flags))
     }
 }
 impl<T: Field, S: PrimeField> UniformRand for MulFieldGroup<T, S> {
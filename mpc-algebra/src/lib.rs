@@ -3,22 +3,34 @@
 
 pub mod reveal;
 pub use reveal::*;
+pub mod bits;
 pub mod channel;
 pub mod com;
+pub mod encoding;
 pub mod group;
+pub mod ot;
 pub mod share;
 pub use share::*;
+pub mod sig;
 pub mod wire;
 pub use wire::*;
 
 pub mod honest_but_curious {
     use super::{
         share::add::*,
-        share::msm::NaiveMsm,
+        share::msm::{ParMsm, PippengerMsm},
+        sig,
         wire::{field, group, pairing},
     };
     pub type MpcField<F> = field::MpcField<F, AdditiveFieldShare<F>>;
-    pub type MpcGroup<G> = group::MpcGroup<G, AdditiveGroupShare<G, NaiveMsm<G>>>;
+    /// The MSM that [MpcGroup] uses for shares: Pippenger's, on every core with the `parallel`
+    /// feature.
+    pub type GroupMsm<G> = ParMsm<PippengerMsm<G>>;
+    pub type MpcGroup<G> = MpcGroupWith<G, GroupMsm<G>>;
+    /// [MpcGroup], with its MSMs done by `M` instead: e.g. [NaiveMsm](crate::msm::NaiveMsm).
+    pub type MpcGroupWith<G, M> = group::MpcGroup<G, AdditiveGroupShare<G, M>>;
+    /// A [sig] key, over [MpcGroup] shares.
+    pub type ThresholdKey<G> = sig::ThresholdKey<G, AdditiveGroupShare<G, GroupMsm<G>>>;
     pub type MpcG1Affine<E> = pairing::MpcG1Affine<E, AdditivePairingShare<E>>;
     pub type MpcG2Affine<E> = pairing::MpcG2Affine<E, AdditivePairingShare<E>>;
     pub type MpcG1Projective<E> = pairing::MpcG1Projective<E, AdditivePairingShare<E>>;
@@ -30,12 +42,20 @@ pub mod honest_but_curious {
 
 pub mod malicious_majority {
     use super::{
-        share::msm::NaiveMsm,
+        share::msm::{ParMsm, PippengerMsm},
         share::spdz::*,
+        sig,
         wire::{field, group, pairing},
     };
     pub type MpcField<F> = field::MpcField<F, SpdzFieldShare<F>>;
-    pub type MpcGroup<G> = group::MpcGroup<G, SpdzGroupShare<G, NaiveMsm<G>>>;
+    /// The MSM that [MpcGroup] uses for shares: Pippenger's, on every core with the `parallel`
+    /// feature.
+    pub type GroupMsm<G> = ParMsm<PippengerMsm<G>>;
+    pub type MpcGroup<G> = MpcGroupWith<G, GroupMsm<G>>;
+    /// [MpcGroup], with its MSMs done by `M` instead: e.g. [NaiveMsm](crate::msm::NaiveMsm).
+    pub type MpcGroupWith<G, M> = group::MpcGroup<G, SpdzGroupShare<G, M>>;
+    /// A [sig] key, over [MpcGroup] shares.
+    pub type ThresholdKey<G> = sig::ThresholdKey<G, SpdzGroupShare<G, GroupMsm<G>>>;
     pub type MpcG1Affine<E> = pairing::MpcG1Affine<E, SpdzPairingShare<E>>;
     pub type MpcG2Affine<E> = pairing::MpcG2Affine<E, SpdzPairingShare<E>>;
     pub type MpcG1Projective<E> = pairing::MpcG1Projective<E, SpdzPairingShare<E>>;
@@ -49,12 +69,20 @@ pub mod honest_majority {
     use crate::GszPairingShare;
 
     use super::{
-        share::msm::NaiveMsm,
         share::gsz20::{field::GszFieldShare, group::GszGroupShare},
+        share::msm::{ParMsm, PippengerMsm},
+        sig,
         wire::{field, group, pairing},
     };
     pub type MpcField<F> = field::MpcField<F, GszFieldShare<F>>;
-    pub type MpcGroup<G> = group::MpcGroup<G, GszGroupShare<G, NaiveMsm<G>>>;
+    /// The MSM that [MpcGroup] uses for shares: Pippenger's, on every core with the `parallel`
+    /// feature.
+    pub type GroupMsm<G> = ParMsm<PippengerMsm<G>>;
+    pub type MpcGroup<G> = MpcGroupWith<G, GroupMsm<G>>;
+    /// [MpcGroup], with its MSMs done by `M` instead: e.g. [NaiveMsm](crate::msm::NaiveMsm).
+    pub type MpcGroupWith<G, M> = group::MpcGroup<G, GszGroupShare<G, M>>;
+    /// A [sig] key, over [MpcGroup] shares.
+    pub type ThresholdKey<G> = sig::ThresholdKey<G, GszGroupShare<G, GroupMsm<G>>>;
     pub type MpcG1Affine<E> = pairing::MpcG1Affine<E, GszPairingShare<E>>;
     pub type MpcG2Affine<E> = pairing::MpcG2Affine<E, GszPairingShare<E>>;
     pub type MpcG1Projective<E> = pairing::MpcG1Projective<E, GszPairingShare<E>>;
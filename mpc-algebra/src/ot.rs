@@ -0,0 +1,419 @@
+//! Oblivious transfer between every pair of parties.
+//!
+//! This is the multiplication engine behind the SPDZ offline phase
+//! ([crate::share::spdz::offline]). Base OTs are Chou-Orlandi "simplest OT" over BLS12-377 G1,
+//! extended with IKNP. The extended OTs are consumed by Gilboa multiplication, as in MASCOT: the
+//! receiver's input is a bit-decomposition against a public gadget vector, and the sender's input
+//! is any element of a module over the gadget's ring (a field, or a group).
+//!
+//! Every message carries one slot per peer, sent privately to that peer.
+//!
+//! The extension has the KOS15 consistency check: the receiver pads its choice bits with random
+//! ones, and proves, for a random challenge from the sender, that it used the same choice bits in
+//! every column. A receiver that fails the check is blamed with [NetError::Abort], before the
+//! sender's Gilboa corrections, and so its selective failure learns nothing of the sender's input.
+use ark_bls12_377::{Fr, G1Projective};
+use ark_ec::ProjectiveCurve;
+use ark_ff::{PrimeField, UniformRand, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use blake2::{Blake2s, Digest};
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+
+use std::convert::TryInto;
+use std::ops::{Add, Sub};
+
+use crate::channel::MpcSerNet;
+use mpc_net::{MpcMultiNet as Net, MpcNet, NetError};
+
+/// Computational security parameter: the number of base OTs with each peer.
+const KAPPA: usize = 128;
+/// Statistical security parameter of the consistency check.
+const SIGMA: usize = 64;
+/// Random choice bits that the receiver appends, so that the check reveals nothing of its own.
+const PAD: usize = KAPPA + SIGMA;
+
+type Seed = [u8; 32];
+
+/// Our OT keys with one peer.
+struct PeerKeys {
+    /// As IKNP sender: our correlation. Bit `i` is our choice bit in base OT `i`.
+    delta: u128,
+    /// As IKNP sender: the base OT outputs we chose, `k_i^{delta_i}`.
+    chosen: Vec<Seed>,
+    /// As IKNP receiver: both base OT outputs, `(k_i^0, k_i^1)`.
+    pairs: Vec<(Seed, Seed)>,
+    /// Names this pair of parties in this session: a hash of both directions' base OT messages.
+    sid: Seed,
+}
+
+/// Our OT keys with every peer. Kept per [session](mpc_net::Session).
+struct OtState {
+    peers: Vec<Option<PeerKeys>>,
+    /// Number of extensions run so far. All parties advance this in lockstep.
+    nonce: u64,
+}
+
+fn hash(parts: &[&[u8]]) -> Seed {
+    let mut h = Blake2s::new();
+    for p in parts {
+        h.update(p);
+    }
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&h.finalize());
+    out
+}
+
+fn ser<T: CanonicalSerialize>(t: &T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    t.serialize(&mut bytes).unwrap();
+    bytes
+}
+
+/// Key for base OT `i`, from the sender message `a`, receiver message `b`, and DH value `k`.
+fn base_key(i: usize, a: &G1Projective, b: &G1Projective, k: &G1Projective) -> Seed {
+    hash(&[
+        &(i as u64).to_le_bytes(),
+        &ser(&a.into_affine()),
+        &ser(&b.into_affine()),
+        &ser(&k.into_affine()),
+    ])
+}
+
+/// Expand `seed` into `n_bytes` pseudorandom bytes, for extension number `nonce`.
+fn prg(seed: &Seed, nonce: u64, n_bytes: usize) -> Vec<u8> {
+    let mut rng = StdRng::from_seed(hash(&[seed, &nonce.to_le_bytes()]));
+    let mut out = vec![0u8; n_bytes];
+    rng.fill_bytes(&mut out);
+    out
+}
+
+/// The correlation-robust hash of IKNP, landing in `M`: for row `j` of extension number `nonce`,
+/// from `sender` to `receiver`, in the session `sid` of that pair.
+fn hash_to<M: UniformRand>(
+    sid: &Seed,
+    sender: usize,
+    receiver: usize,
+    nonce: u64,
+    j: usize,
+    row: u128,
+) -> M {
+    let mut rng = StdRng::from_seed(hash(&[
+        sid,
+        &(sender as u64).to_le_bytes(),
+        &(receiver as u64).to_le_bytes(),
+        &nonce.to_le_bytes(),
+        &(j as u64).to_le_bytes(),
+        &row.to_le_bytes(),
+    ]));
+    M::rand(&mut rng)
+}
+
+/// Multiplication in GF(2^128), modulo `x^128 + x^7 + x^2 + x + 1`.
+fn gf_mul(mut a: u128, mut b: u128) -> u128 {
+    let mut out = 0;
+    while b != 0 {
+        if b & 1 == 1 {
+            out ^= a;
+        }
+        b >>= 1;
+        let carry = a >> 127;
+        a <<= 1;
+        if carry == 1 {
+            a ^= 0x87;
+        }
+    }
+    out
+}
+
+/// The KOS15 check value of `rows`: `(sum_j chi_j bits_j, sum_j chi_j rows_j)`, for the challenges
+/// `chi_j` that `seed` expands to. The first part is only meaningful to the receiver, who knows the
+/// bits.
+fn kos_combine(seed: &[u8], rows: &[u128], bits: &[bool]) -> (u128, u128) {
+    let mut rng = StdRng::from_seed(hash(&[b"kos challenge", seed]));
+    let mut x = 0;
+    let mut t = 0;
+    for (j, row) in rows.iter().enumerate() {
+        let chi: u128 = rng.gen();
+        if bits.get(j) == Some(&true) {
+            x ^= chi;
+        }
+        t ^= gf_mul(chi, *row);
+    }
+    (x, t)
+}
+
+fn pack(bits: &[bool]) -> Vec<u8> {
+    let mut out = vec![0u8; (bits.len() + 7) / 8];
+    for (j, b) in bits.iter().enumerate() {
+        if *b {
+            out[j / 8] |= 1 << (j % 8);
+        }
+    }
+    out
+}
+
+fn xor_into(acc: &mut [u8], other: &[u8]) {
+    for (a, b) in acc.iter_mut().zip(other) {
+        *a ^= b;
+    }
+}
+
+/// Turn `KAPPA` columns of `m` bits into `m` rows of `KAPPA` bits.
+fn transpose(cols: &[Vec<u8>], m: usize) -> Vec<u128> {
+    let mut rows = vec![0u128; m];
+    for (i, col) in cols.iter().enumerate() {
+        for (j, row) in rows.iter_mut().enumerate() {
+            *row |= (((col[j / 8] >> (j % 8)) & 1) as u128) << i;
+        }
+    }
+    rows
+}
+
+pub fn is_init() -> bool {
+    Net::with_local(|ot: &mut Option<OtState>| ot.is_some())
+}
+
+/// Run base OTs with every peer, if we have not already.
+///
+/// Every party must call this at the same point in the protocol.
+pub fn init() {
+    if is_init() {
+        return;
+    }
+    let n = Net::n_parties();
+    let me = Net::party_id();
+    let rng = &mut rand::thread_rng();
+    let g = G1Projective::prime_subgroup_generator();
+
+    // As base OT sender (IKNP receiver): one `A = aG` for each peer.
+    let a: Vec<Fr> = (0..n).map(|_| Fr::rand(rng)).collect();
+    let big_a: Vec<G1Projective> = a.iter().map(|a| g.mul(a.into_repr())).collect();
+    let their_big_a: Vec<G1Projective> = Net::all_to_all(&big_a);
+
+    // As base OT receiver (IKNP sender): `B_i = b_i G + delta_i A` for each base OT.
+    let deltas: Vec<u128> = (0..n).map(|_| rng.gen()).collect();
+    let bs: Vec<Vec<Fr>> = (0..n)
+        .map(|p| {
+            if p == me {
+                Vec::new()
+            } else {
+                (0..KAPPA).map(|_| Fr::rand(rng)).collect()
+            }
+        })
+        .collect();
+    let big_bs: Vec<Vec<G1Projective>> = (0..n)
+        .map(|p| {
+            bs[p]
+                .iter()
+                .enumerate()
+                .map(|(i, b)| {
+                    let mut big_b = g.mul(b.into_repr());
+                    if (deltas[p] >> i) & 1 == 1 {
+                        big_b += &their_big_a[p];
+                    }
+                    big_b
+                })
+                .collect()
+        })
+        .collect();
+    let their_big_bs: Vec<Vec<G1Projective>> = Net::all_to_all(&big_bs);
+
+    let peers = (0..n)
+        .map(|p| {
+            if p == me {
+                return None;
+            }
+            let their_a = their_big_a[p];
+            let chosen = (0..KAPPA)
+                .map(|i| {
+                    let k = their_a.mul(bs[p][i].into_repr());
+                    base_key(i, &their_a, &big_bs[p][i], &k)
+                })
+                .collect();
+            let my_a = big_a[p];
+            let (lo_to_hi, hi_to_lo) = if me < p {
+                (my_a, their_a)
+            } else {
+                (their_a, my_a)
+            };
+            let sid = hash(&[&ser(&lo_to_hi.into_affine()), &ser(&hi_to_lo.into_affine())]);
+            let pairs = (0..KAPPA)
+                .map(|i| {
+                    let b = their_big_bs[p][i];
+                    let k0 = b.mul(a[p].into_repr());
+                    let k1 = (b - my_a).mul(a[p].into_repr());
+                    (base_key(i, &my_a, &b, &k0), base_key(i, &my_a, &b, &k1))
+                })
+                .collect();
+            Some(PeerKeys {
+                delta: deltas[p],
+                chosen,
+                pairs,
+                sid,
+            })
+        })
+        .collect();
+    Net::with_local(|ot: &mut Option<OtState>| *ot = Some(OtState { peers, nonce: 0 }));
+}
+
+pub fn deinit() {
+    Net::with_local(|ot: &mut Option<OtState>| *ot = None);
+}
+
+/// Gilboa multiplication with every peer.
+///
+/// We are the sender with inputs `xs`, and the receiver with `bits`, which holds `gadget.len()`
+/// bits per input, encoding `y_k = sum_j bits[k * gadget.len() + j] * gadget[j]`. `scale`
+/// multiplies a sender input by a gadget entry.
+///
+/// Returns additive shares of the cross terms: summed over all parties, together with each party's
+/// local `xs[k] * y_k`, the outputs give `(sum of xs[k]) * (sum of y_k)`.
+pub fn gilboa<M, K>(xs: &[M], bits: &[bool], gadget: &[K], scale: impl Fn(&M, &K) -> M) -> Vec<M>
+where
+    M: Copy
+        + Zero
+        + Add<Output = M>
+        + Sub<Output = M>
+        + UniformRand
+        + CanonicalSerialize
+        + CanonicalDeserialize,
+{
+    let l = gadget.len();
+    let m = xs.len() * l;
+    assert_eq!(bits.len(), m, "Need {} choice bits per sender input", l);
+    let rng = &mut rand::thread_rng();
+    // Our choice bits, padded for the consistency check
+    let padded: Vec<bool> = bits
+        .iter()
+        .cloned()
+        .chain((0..PAD).map(|_| rng.gen()))
+        .collect();
+    let m_padded = padded.len();
+    let n_bytes = (m_padded + 7) / 8;
+    let r = pack(&padded);
+    let n = Net::n_parties();
+    let me = Net::party_id();
+
+    Net::with_local(|ot: &mut Option<OtState>| {
+        let state = ot
+            .as_mut()
+            .expect("OT used before ot::init. Did you call init_protocol?");
+        let nonce = state.nonce;
+        state.nonce += 1;
+
+        // As IKNP receiver: t_i = G(k_i^0), u_i = t_i ^ G(k_i^1) ^ r
+        let mut ts: Vec<Vec<Vec<u8>>> = vec![Vec::new(); n];
+        let mut us: Vec<Vec<u8>> = vec![Vec::new(); n];
+        for (p, keys) in state.peers.iter().enumerate() {
+            if let Some(keys) = keys {
+                for (k0, k1) in &keys.pairs {
+                    let t = prg(k0, nonce, n_bytes);
+                    let mut u = prg(k1, nonce, n_bytes);
+                    xor_into(&mut u, &t);
+                    xor_into(&mut u, &r);
+                    us[p].extend_from_slice(&u);
+                    ts[p].push(t);
+                }
+            }
+        }
+        let their_us: Vec<Vec<u8>> = Net::all_to_all(&us);
+
+        // As IKNP sender: a challenge for the consistency check
+        let seeds: Vec<Vec<u8>> = (0..n)
+            .map(|p| {
+                if p == me {
+                    Vec::new()
+                } else {
+                    rng.gen::<Seed>().to_vec()
+                }
+            })
+            .collect();
+        let their_seeds: Vec<Vec<u8>> = Net::all_to_all(&seeds);
+
+        // As IKNP receiver: the answer, from our rows of t
+        let mut t_rows: Vec<Vec<u128>> = vec![Vec::new(); n];
+        let mut answers: Vec<Vec<u8>> = vec![Vec::new(); n];
+        for p in 0..n {
+            if p != me {
+                t_rows[p] = transpose(&ts[p], m_padded);
+                let (x, t) = kos_combine(&their_seeds[p], &t_rows[p], &padded);
+                answers[p] = [x.to_le_bytes(), t.to_le_bytes()].concat();
+            }
+        }
+        let their_answers: Vec<Vec<u8>> = Net::all_to_all(&answers);
+
+        // As IKNP sender: q_i = G(k_i^{delta_i}) ^ delta_i u_i = t_i ^ delta_i r, so that row j of q is
+        // either the receiver's row of t, or that xor delta.
+        let mut out = vec![M::zero(); xs.len()];
+        let mut cs: Vec<Vec<u8>> = vec![Vec::new(); n];
+        for (p, keys) in state.peers.iter().enumerate() {
+            if let Some(keys) = keys {
+                let u = &their_us[p];
+                let answer = &their_answers[p];
+                if u.len() != KAPPA * n_bytes || answer.len() != 32 {
+                    blame(p, "malformed OT extension message");
+                }
+                let qs: Vec<Vec<u8>> = keys
+                    .chosen
+                    .iter()
+                    .enumerate()
+                    .map(|(i, k)| {
+                        let mut q = prg(k, nonce, n_bytes);
+                        if (keys.delta >> i) & 1 == 1 {
+                            xor_into(&mut q, &u[i * n_bytes..(i + 1) * n_bytes]);
+                        }
+                        q
+                    })
+                    .collect();
+                let q_rows = transpose(&qs, m_padded);
+                // q_j = t_j ^ r_j delta, so sum_j chi_j q_j = t + x delta if the receiver's choice
+                // bits agree across columns.
+                let (_, q) = kos_combine(&seeds[p], &q_rows, &[]);
+                let x = u128::from_le_bytes(answer[..16].try_into().unwrap());
+                let t = u128::from_le_bytes(answer[16..].try_into().unwrap());
+                if q != t ^ gf_mul(x, keys.delta) {
+                    blame(p, "OT extension consistency check failed");
+                }
+                let c: Vec<M> = q_rows[..m]
+                    .iter()
+                    .enumerate()
+                    .map(|(j, row)| {
+                        let rho: M = hash_to(&keys.sid, me, p, nonce, j, *row);
+                        let rho1: M = hash_to(&keys.sid, me, p, nonce, j, row ^ keys.delta);
+                        out[j / l] = out[j / l] - rho;
+                        rho1 - rho - scale(&xs[j / l], &gadget[j % l])
+                    })
+                    .collect();
+                cs[p] = ser(&c);
+            }
+        }
+        let their_cs: Vec<Vec<u8>> = Net::all_to_all(&cs);
+
+        // As IKNP receiver: H(t_j) - r_j c_j
+        for (p, keys) in state.peers.iter().enumerate() {
+            let keys = match keys {
+                Some(keys) => keys,
+                None => continue,
+            };
+            let c: Vec<M> = Vec::deserialize(&their_cs[p][..]).unwrap();
+            assert_eq!(c.len(), m, "Bad Gilboa message from {}", p);
+            for (j, row) in t_rows[p][..m].iter().enumerate() {
+                let mut v: M = hash_to(&keys.sid, p, me, nonce, j, *row);
+                if bits[j] {
+                    v = v - c[j];
+                }
+                out[j / l] = out[j / l] + v;
+            }
+        }
+        out
+    })
+}
+
+fn blame(party: usize, reason: &str) -> ! {
+    NetError::Abort {
+        party,
+        reason: reason.to_owned(),
+    }
+    .raise()
+}
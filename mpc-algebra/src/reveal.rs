@@ -1,6 +1,9 @@
 #![macro_use]
 use ark_std::{collections::BTreeMap, marker::PhantomData, rc::Rc};
 use rand::Rng;
+use std::sync::Arc;
+
+pub use mpc_net::Session;
 
 /// A type should implement [Reveal] if it represents the MPC abstraction of some base type.
 ///
@@ -17,6 +20,12 @@ pub trait Reveal: Sized {
 
     /// Reveal shared data, yielding plain data.
     fn reveal(self) -> Self::Base;
+    /// [reveal](Self::reveal), in `session` rather than the calling thread's.
+    ///
+    /// Types that do not override this reveal inside [Session::enter].
+    fn reveal_in(self, session: &Arc<Session>) -> Self::Base {
+        session.enter(|| self.reveal())
+    }
     /// Construct a share of the sum of the `b` over all machines in the protocol.
     fn from_add_shared(b: Self::Base) -> Self;
     /// Lift public data (same in all machines) into shared data.
@@ -49,6 +58,10 @@ impl Reveal for usize {
         self
     }
 
+    fn reveal_in(self, _session: &Arc<Session>) -> Self::Base {
+        self
+    }
+
     fn from_add_shared(b: Self::Base) -> Self {
         b
     }
@@ -73,6 +86,10 @@ impl<T: Reveal> Reveal for PhantomData<T> {
         PhantomData::default()
     }
 
+    fn reveal_in(self, _session: &Arc<Session>) -> Self::Base {
+        PhantomData
+    }
+
     fn from_add_shared(_b: Self::Base) -> Self {
         PhantomData::default()
     }
@@ -101,6 +118,9 @@ impl<T: Reveal> Reveal for Vec<T> {
     fn reveal(self) -> Self::Base {
         self.into_iter().map(|x| x.reveal()).collect()
     }
+    fn reveal_in(self, session: &Arc<Session>) -> Self::Base {
+        self.into_iter().map(|x| x.reveal_in(session)).collect()
+    }
     fn from_public(other: Self::Base) -> Self {
         other
             .into_iter()
@@ -140,6 +160,9 @@ where
     fn reveal(self) -> Self::Base {
         self.into_iter().map(|x| x.reveal()).collect()
     }
+    fn reveal_in(self, session: &Arc<Session>) -> Self::Base {
+        self.into_iter().map(|x| x.reveal_in(session)).collect()
+    }
     fn from_public(other: Self::Base) -> Self {
         other.into_iter().map(|x| Reveal::from_public(x)).collect()
     }
@@ -172,6 +195,9 @@ impl<T: Reveal> Reveal for Option<T> {
     fn reveal(self) -> Self::Base {
         self.map(|x| x.reveal())
     }
+    fn reveal_in(self, session: &Arc<Session>) -> Self::Base {
+        self.map(|x| x.reveal_in(session))
+    }
     fn from_public(other: Self::Base) -> Self {
         other.map(|x| <T as Reveal>::from_public(x))
     }
@@ -198,6 +224,9 @@ where
     fn reveal(self) -> Self::Base {
         Rc::new((*self).clone().reveal())
     }
+    fn reveal_in(self, session: &Arc<Session>) -> Self::Base {
+        Rc::new((*self).clone().reveal_in(session))
+    }
     fn from_public(other: Self::Base) -> Self {
         Rc::new(Reveal::from_public((*other).clone()))
     }
@@ -220,6 +249,9 @@ impl<A: Reveal, B: Reveal> Reveal for (A, B) {
     fn reveal(self) -> Self::Base {
         (self.0.reveal(), self.1.reveal())
     }
+    fn reveal_in(self, session: &Arc<Session>) -> Self::Base {
+        (self.0.reveal_in(session), self.1.reveal_in(session))
+    }
     fn from_public(other: Self::Base) -> Self {
         (
             <A as Reveal>::from_public(other.0),
@@ -256,6 +288,13 @@ macro_rules! struct_reveal_impl {
                 )*
             }
         }
+        fn reveal_in(self, session: &std::sync::Arc<$crate::reveal::Session>) -> Self::Base {
+            $con {
+                $(
+                    $x: self.$x.reveal_in(session),
+                )*
+            }
+        }
         fn from_public(other: Self::Base) -> Self {
             $con {
                 $(
@@ -290,6 +329,13 @@ macro_rules! struct_reveal_simp_impl {
                 )*
             }
         }
+        fn reveal_in(self, session: &std::sync::Arc<$crate::reveal::Session>) -> Self::Base {
+            $con {
+                $(
+                    $x: self.$x.reveal_in(session),
+                )*
+            }
+        }
         fn from_public(other: Self::Base) -> Self {
             $con {
                 $(
@@ -9,7 +9,7 @@ use ark_ff::prelude::*;
 use ark_poly::UVPolynomial;
 use ark_serialize::{
     CanonicalDeserialize, CanonicalDeserializeWithFlags, CanonicalSerialize,
-    CanonicalSerializeWithFlags, Flags, SerializationError,
+    CanonicalSerializeWithFlags, EmptyFlags, Flags, SerializationError,
 };
 
 use std::borrow::Cow;
@@ -18,15 +18,18 @@ use std::fmt::{self, Debug, Display, Formatter};
 use std::hash::Hash;
 use std::io::{self, Read, Write};
 use std::marker::PhantomData;
+use std::sync::Arc;
 
-use mpc_net::{MpcNet, MpcMultiNet as Net};
-use crate::channel::MpcSerNet;
+use mpc_net::{MpcNet, MpcMultiNet as Net, NetError, Session};
+use crate::channel::{MpcSerNet, SerNet};
+use crate::encoding;
 
 use super::field::{
     DenseOrSparsePolynomial, DensePolynomial, ExtFieldShare, FieldShare, SparsePolynomial,
 };
 use super::group::GroupShare;
 use super::pairing::{AffProjShare, PairingShare};
+use super::preprocessing::{field_label, group_label, Item};
 use super::BeaverSource;
 use crate::msm::*;
 use crate::Reveal;
@@ -79,12 +82,24 @@ impl<F: Field> AdditiveFieldShare<F> {
     }
 }
 
+impl<F: Field> Item for AdditiveFieldShare<F> {
+    fn label() -> String {
+        format!("add<{}>", field_label::<F>())
+    }
+}
+
 impl<F: Field> Reveal for AdditiveFieldShare<F> {
     type Base = F;
 
     fn reveal(self) -> F {
         Net::broadcast(&self.val).into_iter().sum()
     }
+    fn reveal_in(self, session: &Arc<Session>) -> F {
+        Self::try_batch_open_in(Some(self), session)
+            .unwrap_or_else(|e| e.raise())
+            .pop()
+            .unwrap()
+    }
     fn from_public(f: F) -> Self {
         Self {
             val: if Net::am_king() { f } else { F::zero() },
@@ -118,10 +133,14 @@ impl<F: Field> Reveal for AdditiveFieldShare<F> {
 }
 
 impl<F: Field> FieldShare<F> for AdditiveFieldShare<F> {
-    fn batch_open(selfs: impl IntoIterator<Item = Self>) -> Vec<F> {
+    fn try_batch_open_in(
+        selfs: impl IntoIterator<Item = Self>,
+        session: &Arc<Session>,
+    ) -> Result<Vec<F>, NetError> {
+        let _op = mpc_net::op("batch_open");
         let self_vec: Vec<F> = selfs.into_iter().map(|s| s.val).collect();
-        let all_vals = Net::broadcast(&self_vec);
-        (0..self_vec.len()).map(|i| all_vals.iter().map(|v| &v[i]).sum()).collect()
+        let all_vals = session.try_broadcast(&self_vec)?;
+        Ok((0..self_vec.len()).map(|i| all_vals.iter().map(|v| &v[i]).sum()).collect())
     }
     fn add(&mut self, other: &Self) -> &mut Self {
         self.val += &other.val;
@@ -172,12 +191,25 @@ pub struct AdditiveGroupShare<T, M> {
     _phants: PhantomData<M>,
 }
 
+impl<G: Group, M> Item for AdditiveGroupShare<G, M> {
+    fn label() -> String {
+        format!("add<{}>", group_label::<G>())
+    }
+}
+
 impl<G: Group, M> Reveal for AdditiveGroupShare<G, M> {
     type Base = G;
 
     fn reveal(self) -> G {
         Net::broadcast(&self.val).into_iter().sum()
     }
+    fn reveal_in(self, session: &Arc<Session>) -> G {
+        session
+            .try_broadcast(&self.val)
+            .unwrap_or_else(|e| e.raise())
+            .into_iter()
+            .sum()
+    }
     fn from_public(f: G) -> Self {
         Self {
             val: if Net::am_king() { f } else { G::zero() },
@@ -217,10 +249,14 @@ impl<G: Group, M> Reveal for AdditiveGroupShare<G, M> {
 impl<G: Group, M: Msm<G, G::ScalarField>> GroupShare<G> for AdditiveGroupShare<G, M> {
     type FieldShare = AdditiveFieldShare<G::ScalarField>;
 
-    fn batch_open(selfs: impl IntoIterator<Item = Self>) -> Vec<G> {
+    fn try_batch_open_in(
+        selfs: impl IntoIterator<Item = Self>,
+        session: &Arc<Session>,
+    ) -> Result<Vec<G>, NetError> {
+        let _op = mpc_net::op("batch_open");
         let self_vec: Vec<G> = selfs.into_iter().map(|s| s.val).collect();
-        let all_vals = Net::broadcast(&self_vec);
-        (0..self_vec.len()).map(|i| all_vals.iter().map(|v| &v[i]).sum()).collect()
+        let all_vals = session.try_broadcast(&self_vec)?;
+        Ok((0..self_vec.len()).map(|i| all_vals.iter().map(|v| &v[i]).sum()).collect())
     }
 
     fn add(&mut self, other: &Self) -> &mut Self {
@@ -257,6 +293,11 @@ impl<G: Group, M: Msm<G, G::ScalarField>> GroupShare<G> for AdditiveGroupShare<G
         let scalars: Vec<G::ScalarField> = scalars.into_iter().map(|s| s.val.clone()).collect();
         Self::from_add_shared(M::msm(bases, &scalars))
     }
+
+    fn multi_scale_pub_scalar(bases: &[Self], scalars: &[G::ScalarField]) -> Self {
+        let bases: Vec<G> = bases.iter().map(|b| b.val).collect();
+        Self::from_add_shared(M::msm(&bases, scalars))
+    }
 }
 
 macro_rules! impl_basics {
@@ -272,46 +313,49 @@ macro_rules! impl_basics {
             }
         }
         impl<T: $bound> ToBytes for $share<T> {
-            fn write<W: Write>(&self, _writer: W) -> io::Result<()> {
-                unimplemented!("write")
+            fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+                self.serialize(writer).map_err(encoding::to_io_error)
             }
         }
         impl<T: $bound> FromBytes for $share<T> {
-            fn read<R: Read>(_reader: R) -> io::Result<Self> {
-                unimplemented!("read")
+            fn read<R: Read>(reader: R) -> io::Result<Self> {
+                Self::deserialize(reader).map_err(encoding::to_io_error)
             }
         }
         impl<T: $bound> CanonicalSerialize for $share<T> {
-            fn serialize<W: Write>(&self, _writer: W) -> Result<(), SerializationError> {
-                unimplemented!("serialize")
+            fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+                self.serialize_with_flags(writer, EmptyFlags)
             }
             fn serialized_size(&self) -> usize {
-                unimplemented!("serialized_size")
+                self.serialized_size_with_flags::<EmptyFlags>()
             }
         }
         impl<T: $bound> CanonicalSerializeWithFlags for $share<T> {
             fn serialize_with_flags<W: Write, F: Flags>(
                 &self,
-                _writer: W,
-                _flags: F,
+                mut writer: W,
+                flags: F,
             ) -> Result<(), SerializationError> {
-                unimplemented!("serialize_with_flags")
+                encoding::write_header(&mut writer, flags)?;
+                self.val.serialize(&mut writer)
             }
 
             fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-                unimplemented!("serialized_size_with_flags")
+                1 + self.val.serialized_size()
             }
         }
         impl<T: $bound> CanonicalDeserialize for $share<T> {
-            fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-                unimplemented!("deserialize")
+            fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+                Self::deserialize_with_flags::<_, EmptyFlags>(reader).map(|(s, _)| s)
             }
         }
         impl<T: $bound> CanonicalDeserializeWithFlags for $share<T> {
             fn deserialize_with_flags<R: Read, F: Flags>(
-                _reader: R,
+                mut reader: R,
             ) -> Result<(Self, F), SerializationError> {
-                unimplemented!("deserialize_with_flags")
+                let flags = encoding::read_header(&mut reader)?;
+                let val = T::deserialize(&mut reader)?;
+                Ok((Self::from_add_shared(val), flags))
             }
         }
         impl<T: $bound> UniformRand for $share<T> {
@@ -334,46 +378,49 @@ macro_rules! impl_basics_2_param {
             }
         }
         impl<T: $bound, M> ToBytes for $share<T, M> {
-            fn write<W: Write>(&self, _writer: W) -> io::Result<()> {
-                unimplemented!("write")
+            fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+                self.serialize(writer).map_err(encoding::to_io_error)
             }
         }
         impl<T: $bound, M> FromBytes for $share<T, M> {
-            fn read<R: Read>(_reader: R) -> io::Result<Self> {
-                unimplemented!("read")
+            fn read<R: Read>(reader: R) -> io::Result<Self> {
+                Self::deserialize(reader).map_err(encoding::to_io_error)
             }
         }
         impl<T: $bound, M> CanonicalSerialize for $share<T, M> {
-            fn serialize<W: Write>(&self, _writer: W) -> Result<(), SerializationError> {
-                unimplemented!("serialize")
+            fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+                self.serialize_with_flags(writer, EmptyFlags)
             }
             fn serialized_size(&self) -> usize {
-                unimplemented!("serialized_size")
+                self.serialized_size_with_flags::<EmptyFlags>()
             }
         }
         impl<T: $bound, M> CanonicalSerializeWithFlags for $share<T, M> {
             fn serialize_with_flags<W: Write, F: Flags>(
                 &self,
-                _writer: W,
-                _flags: F,
+                mut writer: W,
+                flags: F,
             ) -> Result<(), SerializationError> {
-                unimplemented!("serialize_with_flags")
+                encoding::write_header(&mut writer, flags)?;
+                self.val.serialize(&mut writer)
             }
 
             fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-                unimplemented!("serialized_size_with_flags")
+                1 + self.val.serialized_size()
             }
         }
         impl<T: $bound, M> CanonicalDeserialize for $share<T, M> {
-            fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-                unimplemented!("deserialize")
+            fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+                Self::deserialize_with_flags::<_, EmptyFlags>(reader).map(|(s, _)| s)
             }
         }
         impl<T: $bound, M> CanonicalDeserializeWithFlags for $share<T, M> {
             fn deserialize_with_flags<R: Read, F: Flags>(
-                _reader: R,
+                mut reader: R,
             ) -> Result<(Self, F), SerializationError> {
-                unimplemented!("deserialize_with_flags")
+                let flags = encoding::read_header(&mut reader)?;
+                let val = T::deserialize(&mut reader)?;
+                Ok((Self::from_add_shared(val), flags))
             }
         }
         impl<T: $bound, M> UniformRand for $share<T, M> {
@@ -408,12 +455,24 @@ pub struct MulFieldShare<T> {
     pub val: T,
 }
 
+impl<F: Field> Item for MulFieldShare<F> {
+    fn label() -> String {
+        format!("add-mul<{}>", field_label::<F>())
+    }
+}
+
 impl<F: Field> Reveal for MulFieldShare<F> {
     type Base = F;
 
     fn reveal(self) -> F {
         Net::broadcast(&self.val).into_iter().product()
     }
+    fn reveal_in(self, session: &Arc<Session>) -> F {
+        Self::try_batch_open_in(Some(self), session)
+            .unwrap_or_else(|e| e.raise())
+            .pop()
+            .unwrap()
+    }
     fn from_public(f: F) -> Self {
         Self {
             val: if Net::am_king() { f } else { F::one() },
@@ -431,10 +490,14 @@ impl<F: Field> FieldShare<F> for MulFieldShare<F> {
     fn map_homo<FF: Field, SS: FieldShare<FF>, Fun: Fn(F) -> FF>(self, _f: Fun) -> SS {
         unimplemented!()
     }
-    fn batch_open(selfs: impl IntoIterator<Item = Self>) -> Vec<F> {
+    fn try_batch_open_in(
+        selfs: impl IntoIterator<Item = Self>,
+        session: &Arc<Session>,
+    ) -> Result<Vec<F>, NetError> {
+        let _op = mpc_net::op("batch_open");
         let self_vec: Vec<F> = selfs.into_iter().map(|s| s.val).collect();
-        let all_vals = Net::broadcast(&self_vec);
-        (0..self_vec.len()).map(|i| all_vals.iter().map(|v| &v[i]).product()).collect()
+        let all_vals = session.try_broadcast(&self_vec)?;
+        Ok((0..self_vec.len()).map(|i| all_vals.iter().map(|v| &v[i]).product()).collect())
     }
 
     fn add(&mut self, _other: &Self) -> &mut Self {
@@ -503,9 +566,8 @@ macro_rules! groups_share {
 
         impl<E: PairingEngine> AffProjShare<E::Fr, E::$affine, E::$proj> for $struct_name<E> {
             type FrShare = AdditiveFieldShare<E::Fr>;
-            type AffineShare = AdditiveGroupShare<E::$affine, crate::msm::AffineMsm<E::$affine>>;
-            type ProjectiveShare =
-                AdditiveGroupShare<E::$proj, crate::msm::ProjectiveMsm<E::$proj>>;
+            type AffineShare = AdditiveGroupShare<E::$affine, ParMsm<AffineMsm<E::$affine>>>;
+            type ProjectiveShare = AdditiveGroupShare<E::$proj, ParMsm<ProjectiveMsm<E::$proj>>>;
 
             fn sh_aff_to_proj(g: Self::AffineShare) -> Self::ProjectiveShare {
                 g.map_homo(|s| s.into())
@@ -558,12 +620,30 @@ impl<E: PairingEngine> PairingShare<E> for AdditivePairingShare<E> {
     type FqeShare = AdditiveExtFieldShare<E::Fqe>;
     // Not a typo. We want a multiplicative subgroup.
     type FqkShare = MulExtFieldShare<E::Fqk>;
-    type G1AffineShare = AdditiveGroupShare<E::G1Affine, crate::msm::AffineMsm<E::G1Affine>>;
-    type G2AffineShare = AdditiveGroupShare<E::G2Affine, crate::msm::AffineMsm<E::G2Affine>>;
+    type G1AffineShare = AdditiveGroupShare<E::G1Affine, ParMsm<AffineMsm<E::G1Affine>>>;
+    type G2AffineShare = AdditiveGroupShare<E::G2Affine, ParMsm<AffineMsm<E::G2Affine>>>;
     type G1ProjectiveShare =
-        AdditiveGroupShare<E::G1Projective, crate::msm::ProjectiveMsm<E::G1Projective>>;
+        AdditiveGroupShare<E::G1Projective, ParMsm<ProjectiveMsm<E::G1Projective>>>;
     type G2ProjectiveShare =
-        AdditiveGroupShare<E::G2Projective, crate::msm::ProjectiveMsm<E::G2Projective>>;
+        AdditiveGroupShare<E::G2Projective, ParMsm<ProjectiveMsm<E::G2Projective>>>;
     type G1 = AdditiveG1Share<E>;
     type G2 = AdditiveG2Share<E>;
+
+    fn miller_loop_sh_pub(a: &Self::G1AffineShare, b: &E::G2Prepared) -> MulFieldShare<E::Fqk> {
+        MulFieldShare {
+            val: E::miller_loop(&[(a.val.into(), b.clone())]),
+        }
+    }
+
+    fn miller_loop_pub_sh(a: &E::G1Prepared, b: &Self::G2AffineShare) -> MulFieldShare<E::Fqk> {
+        MulFieldShare {
+            val: E::miller_loop(&[(a.clone(), b.val.into())]),
+        }
+    }
+
+    fn final_exponentiation(f: MulFieldShare<E::Fqk>) -> MulFieldShare<E::Fqk> {
+        MulFieldShare {
+            val: E::final_exponentiation(&f.val).expect("Zero Miller loop share"),
+        }
+    }
 }
@@ -9,9 +9,12 @@ use core::ops::*;
 use std::cmp::Ord;
 use std::fmt::{Debug, Display};
 use std::hash::Hash;
+use std::sync::Arc;
 
+use super::preprocessing::Item;
 use super::BeaverSource;
 use crate::Reveal;
+use mpc_net::{MpcMultiNet as Net, NetError, Session};
 
 pub trait FieldShare<F: Field>:
     Clone
@@ -32,7 +35,12 @@ pub trait FieldShare<F: Field>:
     + FromBytes
     + 'static
     + Reveal<Base = F>
+    + Item
 {
+    /// Where [MpcField](crate::MpcField) gets its Beaver triples and inverse pairs.
+    type TripleSource: BeaverSource<Self, Self, Self> + Default =
+        crate::wire::field::DummyFieldTripleSource<F, Self>;
+
     fn open(&self) -> F {
         <Self as Reveal>::reveal(*self)
     }
@@ -41,10 +49,27 @@ pub trait FieldShare<F: Field>:
         SS::from_add_shared(f(self.unwrap_as_public()))
     }
 
+    /// [open](Self::open), or the error.
+    fn try_open(&self) -> Result<F, NetError> {
+        Ok(Self::try_batch_open(Some(*self))?.pop().unwrap())
+    }
+
     fn batch_open(selfs: impl IntoIterator<Item = Self>) -> Vec<F> {
-        selfs.into_iter().map(|s| s.open()).collect()
+        Self::try_batch_open(selfs).unwrap_or_else(|e| e.raise())
     }
 
+    /// [batch_open](Self::batch_open), or the error. A failed check on the opened values, like a
+    /// SPDZ MAC check, is a [NetError::Abort].
+    fn try_batch_open(selfs: impl IntoIterator<Item = Self>) -> Result<Vec<F>, NetError> {
+        Self::try_batch_open_in(selfs, &Net::session())
+    }
+
+    /// [try_batch_open](Self::try_batch_open), in `session` rather than the calling thread's.
+    fn try_batch_open_in(
+        selfs: impl IntoIterator<Item = Self>,
+        session: &Arc<Session>,
+    ) -> Result<Vec<F>, NetError>;
+
     fn add(&mut self, other: &Self) -> &mut Self;
 
     fn sub(&mut self, other: &Self) -> &mut Self {
@@ -127,14 +152,16 @@ pub trait FieldShare<F: Field>:
     }
 
     fn inv<S: BeaverSource<Self, Self, Self>>(self, source: &mut S) -> Self {
-        let (x, mut y) = source.inv_pair();
+        // 1/a = r * 1/(ra), for a random r
+        let (mut x, _) = source.inv_pair();
         let xa = x.mul(self, source).open().inverse().unwrap();
-        *y.scale(&xa)
+        *x.scale(&xa)
     }
 
     fn batch_inv<S: BeaverSource<Self, Self, Self>>(xs: Vec<Self>, source: &mut S) -> Vec<Self> {
-        let (bs, cs) = source.inv_pairs(xs.len());
-        cs.into_iter()
+        let (bs, _) = source.inv_pairs(xs.len());
+        bs.clone()
+            .into_iter()
             .zip(
                 Self::batch_open(Self::batch_mul(xs, bs, source))
                     .into_iter()
@@ -9,10 +9,13 @@ use ark_std::{end_timer, start_timer};
 use core::ops::*;
 use std::fmt::{Debug, Display};
 use std::hash::Hash;
+use std::sync::Arc;
 
 use super::field::FieldShare;
+use super::preprocessing::Item;
 use super::BeaverSource;
 use crate::Reveal;
+use mpc_net::{MpcMultiNet as Net, NetError, Session};
 
 /// Secret sharing scheme which support affine functions of secrets.
 pub trait GroupShare<G: Group>:
@@ -33,9 +36,14 @@ pub trait GroupShare<G: Group>:
     + FromBytes
     + 'static
     + Reveal<Base = G>
+    + Item
 {
     type FieldShare: FieldShare<G::ScalarField>;
 
+    /// Where [MpcGroup](crate::MpcGroup) gets its triples for shared-by-shared scaling.
+    type TripleSource: BeaverSource<Self, Self::FieldShare, Self> + Default =
+        crate::wire::group::DummyGroupTripleSource<G, Self>;
+
     fn open(&self) -> G {
         <Self as Reveal>::reveal(*self)
     }
@@ -44,10 +52,27 @@ pub trait GroupShare<G: Group>:
         S2::from_add_shared(f(self.unwrap_as_public()))
     }
 
+    /// [open](Self::open), or the error.
+    fn try_open(&self) -> Result<G, NetError> {
+        Ok(Self::try_batch_open(Some(*self))?.pop().unwrap())
+    }
+
     fn batch_open(selfs: impl IntoIterator<Item = Self>) -> Vec<G> {
-        selfs.into_iter().map(|s| s.open()).collect()
+        Self::try_batch_open(selfs).unwrap_or_else(|e| e.raise())
+    }
+
+    /// [batch_open](Self::batch_open), or the error. A failed check on the opened values, like a
+    /// SPDZ MAC check, is a [NetError::Abort].
+    fn try_batch_open(selfs: impl IntoIterator<Item = Self>) -> Result<Vec<G>, NetError> {
+        Self::try_batch_open_in(selfs, &Net::session())
     }
 
+    /// [try_batch_open](Self::try_batch_open), in `session` rather than the calling thread's.
+    fn try_batch_open_in(
+        selfs: impl IntoIterator<Item = Self>,
+        session: &Arc<Session>,
+    ) -> Result<Vec<G>, NetError>;
+
     fn add(&mut self, other: &Self) -> &mut Self;
 
     fn sub(&mut self, other: &Self) -> &mut Self {
@@ -120,4 +145,21 @@ pub trait GroupShare<G: Group>:
                 acc
             })
     }
+
+    /// Compute \sum_i (s_i * g_i)
+    /// where the s_i are public and the g_i are shared.
+    fn multi_scale_pub_scalar(bases: &[Self], scalars: &[G::ScalarField]) -> Self {
+        bases
+            .iter()
+            .zip(scalars)
+            .map(|(g, s)| {
+                let mut g = *g;
+                g.scale_pub_scalar(s);
+                g
+            })
+            .fold(Self::from_public(G::zero()), |mut acc, n| {
+                acc.add(&n);
+                acc
+            })
+    }
 }
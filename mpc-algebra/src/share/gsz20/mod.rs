@@ -17,7 +17,7 @@ macro_rules! dd {
     };
 }
 
-use crate::channel::MpcSerNet;
+use crate::channel::{MpcSerNet, SerNet};
 use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
 use ark_ff::{
     bytes::{FromBytes, ToBytes},
@@ -30,12 +30,11 @@ use ark_poly::{
 };
 use ark_serialize::{
     CanonicalDeserialize, CanonicalDeserializeWithFlags, CanonicalSerialize,
-    CanonicalSerializeWithFlags, Flags, SerializationError,
+    CanonicalSerializeWithFlags, EmptyFlags, Flags, SerializationError,
 };
 use ark_std::{end_timer, start_timer};
-use mpc_net::{MpcMultiNet as Net, MpcNet};
+use mpc_net::{MpcMultiNet as Net, MpcNet, NetError, Session};
 
-use once_cell::sync::OnceCell;
 use std::any::{Any, TypeId};
 use std::borrow::Cow;
 use std::cmp::Ord;
@@ -44,7 +43,8 @@ use std::fmt::{self, Debug, Display, Formatter};
 use std::hash::Hash;
 use std::io::{self, Read, Write};
 use std::marker::PhantomData;
-use std::sync::Mutex;
+use std::ops::{AddAssign, MulAssign};
+use std::sync::{Arc, Mutex};
 
 use derivative::Derivative;
 use lazy_static::lazy_static;
@@ -54,35 +54,42 @@ use rand::Rng;
 use super::field::{
     DenseOrSparsePolynomial, DensePolynomial, ExtFieldShare, FieldShare, SparsePolynomial,
 };
+use super::preprocessing::{self, field_label, group_label, Exhausted, Item, Kind};
 use super::BeaverSource;
 use crate::msm::Msm;
 use crate::share::pairing::{AffProjShare, PairingShare};
+use crate::encoding;
 use crate::Reveal;
 
 lazy_static! {
-    static ref TYPE_LISTS: Mutex<HashMap<TypeId, Vec<Box<dyn Any + Send>>>> =
+    /// Evaluation domains, by field and number of parties.
+    static ref SHARE_DOMAINS: Mutex<HashMap<(TypeId, usize), &'static (dyn Any + Send + Sync)>> =
         Mutex::new(HashMap::new());
-    static ref SHARE_DOMAIN: OnceCell<Box<dyn Any + Send + Sync>> = OnceCell::new();
 }
 
+/// Values awaiting a batched check, by type. Kept per [session](mpc_net::Session).
+#[derive(Default)]
+struct TypeLists(HashMap<TypeId, Vec<Box<dyn Any + Send>>>);
+
+/// Pools of random sharings, by type. Kept per [session](mpc_net::Session).
+#[derive(Default)]
+struct Randomness(HashMap<TypeId, Box<dyn Any + Send>>);
+
 fn take_types<T: Any + Send>() -> Vec<T> {
-    let mut lists = TYPE_LISTS.lock().unwrap();
-    let type_id = TypeId::of::<T>();
-    let list = lists.remove(&type_id).unwrap_or_else(|| Vec::new());
-    list.into_iter()
+    let list = Net::with_local(|lists: &mut TypeLists| lists.0.remove(&TypeId::of::<T>()));
+    list.unwrap_or_else(|| Vec::new())
+        .into_iter()
         .map(|x| *x.downcast::<T>().unwrap())
         .collect()
 }
 fn add_type<T: Any + Send>(t: T) {
-    let mut lists = TYPE_LISTS.lock().unwrap();
-    let type_id = TypeId::of::<T>();
-    use std::collections::hash_map::Entry;
-    match lists.entry(type_id) {
-        Entry::Occupied(o) => o.into_mut().push(Box::new(t)),
-        Entry::Vacant(v) => {
-            v.insert(vec![Box::new(t)]);
-        }
-    };
+    Net::with_local(|lists: &mut TypeLists| {
+        lists
+            .0
+            .entry(TypeId::of::<T>())
+            .or_insert_with(Vec::new)
+            .push(Box::new(t))
+    });
 }
 fn add_types<T: Any + Send>(ts: Vec<T>) {
     for t in ts {
@@ -96,12 +103,132 @@ pub fn t() -> usize {
 }
 
 pub fn domain<F: FftField>() -> &'static MixedRadixEvaluationDomain<F> {
-    SHARE_DOMAIN.get_or_init(|| {
-        let d = MixedRadixEvaluationDomain::<F>::new(Net::n_parties()).unwrap();
-        assert_eq!(d.size(), Net::n_parties(),
-            "Attempted to build an evaluation domain of size {}, but could only get one of size {}.\nThis domain is needed in order to support Shamir shares for this many parties", Net::n_parties(), d.size(), );
-        Box::new(d)
-    }).downcast_ref().unwrap()
+    domain_of(Net::n_parties())
+}
+
+/// The domain of Shamir shares among `n` parties.
+pub fn domain_of<F: FftField>(n: usize) -> &'static MixedRadixEvaluationDomain<F> {
+    let mut domains = SHARE_DOMAINS.lock().unwrap();
+    let d = *domains.entry((TypeId::of::<F>(), n)).or_insert_with(|| {
+        let d = MixedRadixEvaluationDomain::<F>::new(n).unwrap();
+        assert_eq!(d.size(), n,
+            "Attempted to build an evaluation domain of size {}, but could only get one of size {}.\nThis domain is needed in order to support Shamir shares for this many parties", n, d.size(), );
+        Box::leak(Box::new(d))
+    });
+    d.downcast_ref().unwrap()
+}
+
+/// How many random sharings to generate whenever a pool of them runs dry.
+const RAND_BATCH_SIZE: usize = 128;
+
+/// Take `n` items from the pool for `T`, refilling it with `generate(at_least)` when it runs dry.
+fn take_random<T: Any + Send>(n: usize, generate: impl FnOnce(usize) -> Vec<T>) -> Vec<T> {
+    Net::with_local(|pools: &mut Randomness| {
+        let pool = pools
+            .0
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Vec::<T>::new()))
+            .downcast_mut::<Vec<T>>()
+            .unwrap();
+        if pool.len() < n {
+            let need = n - pool.len();
+            pool.extend(generate(std::cmp::max(need, RAND_BATCH_SIZE)));
+        }
+        pool.split_off(pool.len() - n)
+    })
+}
+
+/// Evaluate the polynomial with coefficients `coeffs` (lowest first) at `x`.
+fn eval_poly<F: Field, T: Copy + Zero + AddAssign + MulAssign<F>>(coeffs: &[T], x: F) -> T {
+    let mut acc = T::zero();
+    for c in coeffs.iter().rev() {
+        acc *= x;
+        acc += *c;
+    }
+    acc
+}
+
+/// Deal a fresh, random degree-`degree` Shamir sharing of `secret`: entry `j` is party `j`'s share.
+fn deal<F, T, R>(secret: T, degree: usize, rng: &mut R) -> Vec<T>
+where
+    F: FftField,
+    T: Copy + Zero + AddAssign + MulAssign<F> + UniformRand,
+    R: Rng + ?Sized,
+{
+    let coeffs: Vec<T> = std::iter::once(secret)
+        .chain((0..degree).map(|_| T::rand(rng)))
+        .collect();
+    domain::<F>()
+        .elements()
+        .map(|x| eval_poly(&coeffs, x))
+        .collect()
+}
+
+/// Batch generation of random sharings, from ["Scalable and Unconditionally Secure Multiparty
+/// Computation"](https://ia.cr/2007/029) by Damgård and Nielsen.
+///
+/// Each party deals Shamir sharings of random secrets: one sharing of each secret for every degree
+/// in `degrees`. Each round of `n` dealt secrets is then combined with the `(n - t) x n`
+/// Vandermonde matrix `V[k][i] = w_i^k` over [domain]. Any `n - t` columns of `V` are invertible,
+/// so the `n - t` outputs of a round are uniform, even given the `t` dealings of corrupt parties.
+///
+/// Returns at least `count` entries, each holding our share of one random value at every degree.
+fn random_sharings<F, T>(count: usize, degrees: &[usize]) -> Vec<Vec<T>>
+where
+    F: FftField,
+    T: Copy
+        + Zero
+        + AddAssign
+        + MulAssign<F>
+        + UniformRand
+        + CanonicalSerialize
+        + CanonicalDeserialize,
+{
+    let n = Net::n_parties();
+    let per_round = n - t();
+    let rounds = (count + per_round - 1) / per_round;
+    let domain = domain::<F>();
+    let rng = &mut rand::thread_rng();
+
+    // dealt[j][r * degrees.len() + d]: party j's share of our r-th secret, at degree degrees[d]
+    let mut dealt: Vec<Vec<T>> = vec![Vec::with_capacity(rounds * degrees.len()); n];
+    for _ in 0..rounds {
+        let secret = T::rand(rng);
+        for d in degrees {
+            for (dealt, share) in dealt.iter_mut().zip(deal(secret, *d, rng)) {
+                dealt.push(share);
+            }
+        }
+    }
+    let received: Vec<Vec<T>> = Net::all_to_all(&dealt);
+    for (i, r) in received.iter().enumerate() {
+        assert_eq!(
+            r.len(),
+            rounds * degrees.len(),
+            "Party {} dealt the wrong number of random sharings",
+            i
+        );
+    }
+
+    let mut out = Vec::with_capacity(rounds * per_round);
+    for r in 0..rounds {
+        for k in 0..per_round {
+            out.push(
+                (0..degrees.len())
+                    .map(|d| {
+                        let mut acc = T::zero();
+                        for (i, received) in received.iter().enumerate() {
+                            let mut term = received[r * degrees.len() + d];
+                            term *= domain.element((i * k) % n);
+                            acc += term;
+                        }
+                        acc
+                    })
+                    .collect(),
+            );
+        }
+    }
+    out
 }
 
 pub mod field {
@@ -128,46 +255,51 @@ pub mod field {
         }
     }
     impl<T: FftField> ToBytes for GszFieldShare<T> {
-        fn write<W: Write>(&self, _writer: W) -> io::Result<()> {
-            unimplemented!("write")
+        fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+            self.serialize(writer).map_err(encoding::to_io_error)
         }
     }
     impl<T: FftField> FromBytes for GszFieldShare<T> {
-        fn read<R: Read>(_reader: R) -> io::Result<Self> {
-            unimplemented!("read")
+        fn read<R: Read>(reader: R) -> io::Result<Self> {
+            Self::deserialize(reader).map_err(encoding::to_io_error)
         }
     }
     impl<T: FftField> CanonicalSerialize for GszFieldShare<T> {
-        fn serialize<W: Write>(&self, _writer: W) -> Result<(), SerializationError> {
-            unimplemented!("serialize")
+        fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+            self.serialize_with_flags(writer, EmptyFlags)
         }
         fn serialized_size(&self) -> usize {
-            unimplemented!("serialized_size")
+            self.serialized_size_with_flags::<EmptyFlags>()
         }
     }
     impl<T: FftField> CanonicalSerializeWithFlags for GszFieldShare<T> {
         fn serialize_with_flags<W: Write, F: Flags>(
             &self,
-            _writer: W,
-            _flags: F,
+            mut writer: W,
+            flags: F,
         ) -> Result<(), SerializationError> {
-            unimplemented!("serialize_with_flags")
+            encoding::write_header(&mut writer, flags)?;
+            self.val.serialize(&mut writer)?;
+            self.degree.serialize(&mut writer)
         }
 
         fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-            unimplemented!("serialized_size_with_flags")
+            1 + self.val.serialized_size() + self.degree.serialized_size()
         }
     }
     impl<T: FftField> CanonicalDeserialize for GszFieldShare<T> {
-        fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-            unimplemented!("deserialize")
+        fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+            Self::deserialize_with_flags::<_, EmptyFlags>(reader).map(|(s, _)| s)
         }
     }
     impl<T: FftField> CanonicalDeserializeWithFlags for GszFieldShare<T> {
         fn deserialize_with_flags<R: Read, F: Flags>(
-            _reader: R,
+            mut reader: R,
         ) -> Result<(Self, F), SerializationError> {
-            unimplemented!("deserialize_with_flags")
+            let flags = encoding::read_header(&mut reader)?;
+            let val = T::deserialize(&mut reader)?;
+            let degree = usize::deserialize(&mut reader)?;
+            Ok((Self { val, degree }, flags))
         }
     }
     impl<T: FftField> UniformRand for GszFieldShare<T> {
@@ -176,6 +308,12 @@ pub mod field {
         }
     }
 
+    impl<F: FftField> Item for GszFieldShare<F> {
+        fn label() -> String {
+            format!("gsz<{}>", field_label::<F>())
+        }
+    }
+
     impl<F: FftField> Reveal for GszFieldShare<F> {
         type Base = F;
 
@@ -283,11 +421,15 @@ pub mod field {
             self
         }
 
-        fn batch_open(selfs: impl IntoIterator<Item = Self>) -> Vec<F> {
+        fn try_batch_open_in(
+            selfs: impl IntoIterator<Item = Self>,
+            session: &Arc<Session>,
+        ) -> Result<Vec<F>, NetError> {
+            let _op = mpc_net::op("batch_open");
             let (self_vec, mut deg_vec): (Vec<F>, Vec<usize>) =
                 selfs.into_iter().map(|s| (s.val, s.degree)).unzip();
             let timer = start_timer!(|| format!("Batch open: {}", self_vec.len()));
-            let mut all_vals = Net::broadcast(&self_vec);
+            let mut all_vals = session.try_broadcast(&self_vec)?;
             let mut out = Vec::new();
             while all_vals[0].len() > 0 {
                 let vals: Vec<F> = all_vals.iter_mut().map(|v| v.pop().unwrap()).collect();
@@ -295,7 +437,7 @@ pub mod field {
             }
             out.reverse();
             end_timer!(timer);
-            out
+            Ok(out)
         }
 
         /// Multiply two t-shares, consuming a double-share.
@@ -326,7 +468,7 @@ pub mod field {
             xs: Vec<Self>,
             source: &mut S,
         ) -> Vec<Self> {
-            let rs: Vec<_> = (0..xs.len()).map(|_| rand::<F>()).collect();
+            let rs = batch_rand::<F>(xs.len());
             let self_rs = Self::batch_mul(xs, rs.clone(), source);
             let mut self_rs = Self::batch_open(self_rs);
             for x in &mut self_rs {
@@ -345,7 +487,7 @@ pub mod field {
             src: &mut S,
         ) -> Vec<Self> {
             let n = x.len();
-            let m: Vec<Self> = (0..(n + 1)).map(|_| rand::<F>()).collect();
+            let m = batch_rand::<F>(n + 1);
             let m_inv = Self::batch_inv(m.clone(), src);
             let mx = Self::batch_mul(m[..n].iter().cloned().collect(), x, src);
             let mxm = Self::batch_mul(mx, m_inv[1..].iter().cloned().collect(), src);
@@ -377,36 +519,63 @@ pub mod field {
 
     /// Yields a t-share of a random r.
     ///
-    /// Stubbed b/c it can be pre-processed.
-    ///
     /// Protocol 3.
-    pub fn rand<F: Field>() -> GszFieldShare<F> {
-        GszFieldShare {
-            val: F::one(),
-            degree: t(),
-        }
+    pub fn rand<F: FftField>() -> GszFieldShare<F> {
+        batch_rand(1).pop().unwrap()
+    }
+
+    /// Yields `n` t-shares of random values.
+    pub fn batch_rand<F: FftField>(n: usize) -> Vec<GszFieldShare<F>> {
+        take_random(n, |n| {
+            random_sharings::<F, F>(n, &[t()])
+                .into_iter()
+                .map(|s| GszFieldShare {
+                    val: s[0],
+                    degree: t(),
+                })
+                .collect()
+        })
     }
 
     /// Yields two shares of a random `r`, one of degree t, one of degree 2t
     ///
-    /// Stubbed b/c it can be pre-processed.
+    /// Taken from the loaded preprocessing [Store](crate::share::preprocessing::Store) if it has
+    /// a section for them, and an error if that section has run out; otherwise generated in
+    /// batches.
     ///
     /// Protocol 4.
-    pub fn double_rand<F: Field>() -> (GszFieldShare<F>, GszFieldShare<F>) {
-        (
-            GszFieldShare {
-                val: F::one(),
-                degree: t(),
-            },
-            GszFieldShare {
-                val: F::one(),
-                degree: 2 * t(),
-            },
-        )
+    pub fn double_rand<F: FftField>() -> Result<(GszFieldShare<F>, GszFieldShare<F>), Exhausted> {
+        let (mut r, mut r2) = batch_double_rand(1)?;
+        Ok((r.pop().unwrap(), r2.pop().unwrap()))
     }
 
-    pub fn batch_double_rand<F: Field>(n: usize) -> (Vec<GszFieldShare<F>>, Vec<GszFieldShare<F>>) {
-        (0..n).map(|_| double_rand()).unzip()
+    pub fn batch_double_rand<F: FftField>(
+        n: usize,
+    ) -> Result<(Vec<GszFieldShare<F>>, Vec<GszFieldShare<F>>), Exhausted> {
+        if let Some(rs) =
+            preprocessing::take_stored::<(GszFieldShare<F>, GszFieldShare<F>)>(Kind::DoubleRand, n)?
+        {
+            return Ok(rs.into_iter().unzip());
+        }
+        Ok(take_random(n, |n| {
+            random_sharings::<F, F>(n, &[t(), 2 * t()])
+                .into_iter()
+                .map(|s| {
+                    (
+                        GszFieldShare {
+                            val: s[0],
+                            degree: t(),
+                        },
+                        GszFieldShare {
+                            val: s[1],
+                            degree: 2 * t(),
+                        },
+                    )
+                })
+                .collect()
+        })
+        .into_iter()
+        .unzip())
     }
 
     pub fn check_accumulated_field_products<F: FftField>() {
@@ -439,7 +608,7 @@ pub mod field {
 
     fn open_degree_vec<F: FftField>(mut shares: Vec<F>, d: usize) -> F {
         //let build_domain_timer = start_timer!(|| "domain");
-        let domain = domain::<F>();
+        let domain = domain_of::<F>(shares.len());
         //end_timer!(build_domain_timer);
         //let ifft_timer = start_timer!(|| "ifft");
         domain.ifft_in_place(&mut shares);
@@ -464,18 +633,19 @@ pub mod field {
     ///
     /// 1. Opens the share to King.
     /// 2. King performs the function.
-    /// 3. King reshares the result.
+    /// 3. King reshares the result, as a fresh random sharing of degree `new_degree`.
+    ///
+    /// King sees the opened value, so callers mask it first (e.g. with a [double_rand] pair).
     pub fn king_compute<F: FftField, Func: FnOnce(F) -> F>(
         share: &GszFieldShare<F>,
         new_degree: usize,
         f: Func,
     ) -> GszFieldShare<F> {
+        let _op = mpc_net::op("king_compute");
         let king_answer = Net::send_to_king(&share.val).map(|shares| {
-            let n = shares.len();
             let value = open_degree_vec(shares, share.degree);
             let output = f(value);
-            // TODO: randomize
-            vec![output; n]
+            deal::<F, _, _>(output, new_degree, &mut rand::thread_rng())
         });
         let from_king = Net::recv_from_king(king_answer);
         GszFieldShare {
@@ -488,9 +658,11 @@ pub mod field {
     /// * Shares `share`
     /// * A function over plain data, `f`
     ///
-    /// 1. Opens the share to King.
-    /// 2. King performs the function.
-    /// 3. King reshares the result.
+    /// 1. Opens the shares to King.
+    /// 2. King performs the function on each.
+    /// 3. King reshares the results, as fresh random sharings of degree `new_degree`.
+    ///
+    /// King sees the opened values, so callers mask them first (e.g. with [double_rand] pairs).
     pub fn batch_king_compute<F: FftField, Func: Fn(F) -> F>(
         shares: &[GszFieldShare<F>],
         new_degree: usize,
@@ -500,13 +672,15 @@ pub mod field {
         let king_answer = Net::send_to_king(&values).map(|all_shares| {
             let kc_timer = start_timer!(|| format!("King computation"));
             let n = all_shares.len();
+            let rng = &mut rand::thread_rng();
             let mut outputs = vec![Vec::new(); n];
             for i in 0..all_shares[0].len() {
                 let these_shares: Vec<F> = all_shares.iter().map(|s| s[i]).collect();
                 let value = open_degree_vec(these_shares, shares[i].degree);
                 let output = f(value);
-                // TODO: randomize
-                outputs.iter_mut().for_each(|o| o.push(output));
+                for (o, share) in outputs.iter_mut().zip(deal::<F, _, _>(output, new_degree, rng)) {
+                    o.push(share);
+                }
             }
             assert_eq!(outputs.len(), all_shares.len());
             assert_eq!(outputs[0].len(), all_shares[0].len());
@@ -538,13 +712,13 @@ pub mod field {
         y: &GszFieldShare<F>,
         queue_check: bool,
     ) -> GszFieldShare<F> {
-        let (r, r2) = double_rand::<F>();
+        let (r, r2) = double_rand::<F>().expect("Out of preprocessed double-random sharings");
         let mut x_cp = x.clone();
         x_cp.val *= y.val;
-        x_cp.degree *= 2;
+        x_cp.degree = std::cmp::max(x.degree + y.degree, r2.degree);
         x_cp.val += r2.val;
         // king just reduces the sharing degree
-        let mut shift_res = king_compute(&x_cp, x_cp.degree / 2, |r| r);
+        let mut shift_res = king_compute(&x_cp, r.degree, |r| r);
         shift_res.val -= r.val;
         if queue_check {
             let triple = GszFieldTriple(x, y.clone(), shift_res);
@@ -563,19 +737,22 @@ pub mod field {
     ) -> Vec<GszFieldShare<F>> {
         let timer = start_timer!(|| format!("Batch mult: {}", x.len()));
         let n = x.len();
-        let d = x[0].degree;
         assert_eq!(x.len(), y.len());
-        let (r, r2) = batch_double_rand::<F>(n);
+        if n == 0 {
+            end_timer!(timer);
+            return Vec::new();
+        }
+        let (r, r2) =
+            batch_double_rand::<F>(n).expect("Out of preprocessed double-random sharings");
         let mut x_cp = x.clone();
         for ((x, y), r2) in x_cp.iter_mut().zip(y).zip(r2) {
-            assert_eq!(x.degree, d);
             x.val *= y.val;
-            x.degree *= 2;
+            x.degree = std::cmp::max(x.degree + y.degree, r2.degree);
             x.val += r2.val;
         }
         // king just reduces the sharing degree
         let kc_timer = start_timer!(|| format!("King compute wrapper"));
-        let mut shift_res = batch_king_compute(&x_cp, x_cp[0].degree / 2, |r| r);
+        let mut shift_res = batch_king_compute(&x_cp, r[0].degree, |r| r);
         end_timer!(kc_timer);
         for (shift_res, r) in shift_res.iter_mut().zip(r) {
             shift_res.val -= r.val;
@@ -601,6 +778,7 @@ pub mod field {
         ys: Vec<GszFieldShare<F>>,
         zs: Vec<GszFieldShare<F>>,
     ) {
+        let _op = mpc_net::op("hadamard_check");
         let r = coin::<F>();
         let mut rzs_sum = GszFieldShare::from_public(F::zero());
         let mut r_i = F::one();
@@ -794,15 +972,15 @@ pub mod field {
         debug_assert!(ys.iter().all(|x| x.degree <= t()));
         assert_eq!(xs.len(), ys.len());
         let mut acc = F::zero();
-        let mut degree = 0;
+        let (r, r2) = double_rand::<F>().expect("Out of preprocessed double-random sharings");
+        let mut degree = r2.degree;
         for (x, y) in xs.iter().zip(ys) {
             acc += x.val * &y.val;
-            degree = std::cmp::max(degree, 2 * std::cmp::max(x.degree, y.degree));
+            degree = std::cmp::max(degree, x.degree + y.degree);
         }
-        let (r, r2) = double_rand::<F>();
         acc += r2.val;
         let acc_share = GszFieldShare { val: acc, degree };
-        let mut shifted_result = king_compute(&acc_share, degree / 2, |r| r);
+        let mut shifted_result = king_compute(&acc_share, r.degree, |r| r);
         shifted_result.sub(&r);
         shifted_result
     }
@@ -848,51 +1026,69 @@ pub mod group {
         }
     }
     impl<T: Group, M> ToBytes for GszGroupShare<T, M> {
-        fn write<W: Write>(&self, _writer: W) -> io::Result<()> {
-            unimplemented!("write")
+        fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+            self.serialize(writer).map_err(encoding::to_io_error)
         }
     }
     impl<T: Group, M> FromBytes for GszGroupShare<T, M> {
-        fn read<R: Read>(_reader: R) -> io::Result<Self> {
-            unimplemented!("read")
+        fn read<R: Read>(reader: R) -> io::Result<Self> {
+            Self::deserialize(reader).map_err(encoding::to_io_error)
         }
     }
     impl<T: Group, M> CanonicalSerialize for GszGroupShare<T, M> {
-        fn serialize<W: Write>(&self, _writer: W) -> Result<(), SerializationError> {
-            unimplemented!("serialize")
+        fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+            self.serialize_with_flags(writer, EmptyFlags)
         }
         fn serialized_size(&self) -> usize {
-            unimplemented!("serialized_size")
+            self.serialized_size_with_flags::<EmptyFlags>()
         }
     }
     impl<T: Group, M> CanonicalSerializeWithFlags for GszGroupShare<T, M> {
         fn serialize_with_flags<W: Write, F: Flags>(
             &self,
-            _writer: W,
-            _flags: F,
+            mut writer: W,
+            flags: F,
         ) -> Result<(), SerializationError> {
-            unimplemented!("serialize_with_flags")
+            encoding::write_header(&mut writer, flags)?;
+            self.val.serialize(&mut writer)?;
+            self.degree.serialize(&mut writer)
         }
 
         fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-            unimplemented!("serialized_size_with_flags")
+            1 + self.val.serialized_size() + self.degree.serialized_size()
         }
     }
     impl<T: Group, M> CanonicalDeserialize for GszGroupShare<T, M> {
-        fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-            unimplemented!("deserialize")
+        fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+            Self::deserialize_with_flags::<_, EmptyFlags>(reader).map(|(s, _)| s)
         }
     }
     impl<T: Group, M> CanonicalDeserializeWithFlags for GszGroupShare<T, M> {
         fn deserialize_with_flags<R: Read, F: Flags>(
-            _reader: R,
+            mut reader: R,
         ) -> Result<(Self, F), SerializationError> {
-            unimplemented!("deserialize_with_flags")
+            let flags = encoding::read_header(&mut reader)?;
+            let val = T::deserialize(&mut reader)?;
+            let degree = usize::deserialize(&mut reader)?;
+            Ok((
+                Self {
+                    val,
+                    degree,
+                    _phants: PhantomData::default(),
+                },
+                flags,
+            ))
+        }
+    }
+    impl<T: Group, M: Send + 'static> UniformRand for GszGroupShare<T, M> {
+        fn rand<R: Rng + ?Sized>(_rng: &mut R) -> Self {
+            rand()
         }
     }
-    impl<T: Group, M> UniformRand for GszGroupShare<T, M> {
-        fn rand<R: Rng + ?Sized>(_rng: &mut R) -> Self {
-            todo!()
+
+    impl<G: Group, M> Item for GszGroupShare<G, M> {
+        fn label() -> String {
+            format!("gsz<{}>", group_label::<G>())
         }
     }
 
@@ -942,6 +1138,22 @@ pub mod group {
     impl<G: Group, M: Msm<G, G::ScalarField>> GroupShare<G> for GszGroupShare<G, M> {
         type FieldShare = GszFieldShare<G::ScalarField>;
 
+        fn try_batch_open_in(
+            selfs: impl IntoIterator<Item = Self>,
+            session: &Arc<Session>,
+        ) -> Result<Vec<G>, NetError> {
+            session.enter(M::pre_reveal_check);
+            let _op = mpc_net::op("batch_open");
+            let (vals, degrees): (Vec<G>, Vec<usize>) =
+                selfs.into_iter().map(|s| (s.val, s.degree)).unzip();
+            let all_vals = session.try_broadcast(&vals)?;
+            Ok(degrees
+                .into_iter()
+                .enumerate()
+                .map(|(i, d)| open_degree_vec(all_vals.iter().map(|v| v[i]).collect(), d))
+                .collect())
+        }
+
         fn add(&mut self, other: &Self) -> &mut Self {
             self.val += &other.val;
             self
@@ -998,39 +1210,62 @@ pub mod group {
                 _phants: Default::default(),
             }
         }
+
+        fn multi_scale_pub_scalar(bases: &[Self], scalars: &[G::ScalarField]) -> Self {
+            let degree = bases.first().map_or(0, |b| b.degree);
+            assert!(bases.iter().all(|b| b.degree == degree));
+            let vals: Vec<G> = bases.iter().map(|b| b.val).collect();
+            Self {
+                val: M::msm(&vals, scalars),
+                degree,
+                _phants: Default::default(),
+            }
+        }
     }
 
     /// Yields a t-share of a random r.
     ///
-    /// Stubbed b/c it can be pre-processed.
-    ///
     /// Protocol 3.
-    pub fn rand<G: Group, M>() -> GszGroupShare<G, M> {
-        GszGroupShare {
-            val: G::zero(),
-            degree: t(),
-            _phants: Default::default(),
-        }
+    pub fn rand<G: Group, M: Send + 'static>() -> GszGroupShare<G, M> {
+        take_random(1, |n| {
+            random_sharings::<G::ScalarField, G>(n, &[t()])
+                .into_iter()
+                .map(|s| GszGroupShare {
+                    val: s[0],
+                    degree: t(),
+                    _phants: Default::default(),
+                })
+                .collect()
+        })
+        .pop()
+        .unwrap()
     }
 
     /// Yields two shares of a random `r`, one of degree t, one of degree 2t
     ///
-    /// Stubbed b/c it can be pre-processed.
+    /// Taken from the loaded preprocessing [Store](crate::share::preprocessing::Store) if it has
+    /// a section for them, and an error if that section has run out; otherwise generated in
+    /// batches.
     ///
     /// Protocol 4.
-    pub fn double_rand<G: Group, M>() -> (GszGroupShare<G, M>, GszGroupShare<G, M>) {
-        (
-            GszGroupShare {
-                val: G::zero(),
-                degree: t(),
-                _phants: Default::default(),
-            },
-            GszGroupShare {
-                val: G::zero(),
-                degree: 2 * t(),
-                _phants: Default::default(),
-            },
-        )
+    pub fn double_rand<G: Group, M: Send + 'static>(
+    ) -> Result<(GszGroupShare<G, M>, GszGroupShare<G, M>), Exhausted> {
+        if let Some(mut r) = preprocessing::take_stored(Kind::DoubleRand, 1)? {
+            return Ok(r.pop().unwrap());
+        }
+        let share = |val, degree| GszGroupShare {
+            val,
+            degree,
+            _phants: Default::default(),
+        };
+        Ok(take_random(1, |n| {
+            random_sharings::<G::ScalarField, G>(n, &[t(), 2 * t()])
+                .into_iter()
+                .map(|s| (share(s[0], t()), share(s[1], 2 * t())))
+                .collect()
+        })
+        .pop()
+        .unwrap())
     }
 
     pub struct GszGroupTriple<G: Group, M>(
@@ -1046,20 +1281,20 @@ pub mod group {
     }
 
     fn open_degree_vec<G: Group>(shares: Vec<G>, d: usize) -> G {
-        let domain = domain::<G::ScalarField>();
-        let n = Net::n_parties();
+        let n = shares.len();
+        let domain = domain_of::<G::ScalarField>(n);
         let n_inv = G::ScalarField::from(n as u32).inverse().unwrap();
         let w = domain.element(1);
         let w_inv = w.inverse().unwrap();
         // w^{-i}
         let mut w_inv_i = G::ScalarField::one();
         let coeffs: Vec<G> = (0..n)
-            .map(|i| {
+            .map(|_| {
                 let mut coeff = G::zero();
                 // 1/N * w^{-ij}
                 let mut w_inv_ij = n_inv;
-                for _j in 0..n {
-                    coeff += shares[i].mul(&w_inv_ij);
+                for share in &shares {
+                    coeff += share.mul(&w_inv_ij);
                     w_inv_ij *= &w_inv_i;
                 }
                 w_inv_i *= &w_inv;
@@ -1086,18 +1321,19 @@ pub mod group {
     ///
     /// 1. Opens the share to King.
     /// 2. King performs the function.
-    /// 3. King reshares the result.
+    /// 3. King reshares the result, as a fresh random sharing of degree `new_degree`.
+    ///
+    /// King sees the opened value, so callers mask it first (e.g. with a [double_rand] pair).
     pub fn king_compute<G: Group, M, Func: FnOnce(G) -> G>(
         share: &GszGroupShare<G, M>,
         new_degree: usize,
         f: Func,
     ) -> GszGroupShare<G, M> {
+        let _op = mpc_net::op("king_compute");
         let king_answer = Net::send_to_king(&share.val).map(|shares| {
-            let n = shares.len();
             let value = open_degree_vec(shares, share.degree);
             let output = f(value);
-            // TODO: randomize
-            vec![output; n]
+            deal::<G::ScalarField, _, _>(output, new_degree, &mut rand::thread_rng())
         });
         let from_king = Net::recv_from_king(king_answer);
         GszGroupShare {
@@ -1115,12 +1351,12 @@ pub mod group {
         queue_check: bool,
     ) -> GszGroupShare<G, M> {
         let mut y_cp = y.clone();
-        let (r, r2) = double_rand::<G, M>();
+        let (r, r2) = double_rand::<G, M>().expect("Out of preprocessed double-random sharings");
         y_cp.val *= x.val;
-        y_cp.degree *= 2;
+        y_cp.degree = std::cmp::max(x.degree + y.degree, r2.degree);
         y_cp.val += r2.val;
         // king just reduces the sharing degree
-        let mut shift_res = king_compute(&y_cp, x.degree / 2, |r| r);
+        let mut shift_res = king_compute(&y_cp, r.degree, |r| r);
         shift_res.val -= r.val;
         if queue_check {
             let t = GszGroupTriple(x.clone(), y, shift_res);
@@ -1137,19 +1373,19 @@ pub mod group {
         debug_assert!(ys.iter().all(|x| x.degree <= t()));
         assert_eq!(xs.len(), ys.len());
         let mut acc = G::zero();
-        let mut degree = 0;
+        let (r, r2) = double_rand::<G, M>().expect("Out of preprocessed double-random sharings");
+        let mut degree = r2.degree;
         for (x, y) in xs.iter().zip(ys) {
             acc += y.val.mul(&x.val);
-            degree = std::cmp::max(degree, 2 * std::cmp::max(x.degree, y.degree));
+            degree = std::cmp::max(degree, x.degree + y.degree);
         }
-        let (r, r2) = double_rand::<G, M>();
         acc += r2.val;
         let acc_share = GszGroupShare {
             val: acc,
             degree,
             _phants: Default::default(),
         };
-        let mut shifted_result = king_compute(&acc_share, degree / 2, |r| r);
+        let mut shifted_result = king_compute(&acc_share, r.degree, |r| r);
         shifted_result.sub(&r);
         shifted_result
     }
@@ -1336,6 +1572,7 @@ pub mod group {
         ys: Vec<GszGroupShare<G, M>>,
         zs: Vec<GszGroupShare<G, M>>,
     ) {
+        let _op = mpc_net::op("hadamard_check");
         let r = field::coin::<G::ScalarField>();
         let mut rzs_sum = GszGroupShare::from_public(G::zero());
         let mut r_i = G::ScalarField::one();
@@ -1489,46 +1726,58 @@ pub mod mul_field {
                 }
             }
             impl<T: $bound, M> ToBytes for $share<T, M> {
-                fn write<W: Write>(&self, _writer: W) -> io::Result<()> {
-                    unimplemented!("write")
+                fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+                    self.serialize(writer).map_err(encoding::to_io_error)
                 }
             }
             impl<T: $bound, M> FromBytes for $share<T, M> {
-                fn read<R: Read>(_reader: R) -> io::Result<Self> {
-                    unimplemented!("read")
+                fn read<R: Read>(reader: R) -> io::Result<Self> {
+                    Self::deserialize(reader).map_err(encoding::to_io_error)
                 }
             }
             impl<T: $bound, M> CanonicalSerialize for $share<T, M> {
-                fn serialize<W: Write>(&self, _writer: W) -> Result<(), SerializationError> {
-                    unimplemented!("serialize")
+                fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+                    self.serialize_with_flags(writer, EmptyFlags)
                 }
                 fn serialized_size(&self) -> usize {
-                    unimplemented!("serialized_size")
+                    self.serialized_size_with_flags::<EmptyFlags>()
                 }
             }
             impl<T: $bound, M> CanonicalSerializeWithFlags for $share<T, M> {
                 fn serialize_with_flags<W: Write, F: Flags>(
                     &self,
-                    _writer: W,
-                    _flags: F,
+                    mut writer: W,
+                    flags: F,
                 ) -> Result<(), SerializationError> {
-                    unimplemented!("serialize_with_flags")
+                    encoding::write_header(&mut writer, flags)?;
+                    self.val.serialize(&mut writer)?;
+                    self.degree.serialize(&mut writer)
                 }
 
                 fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-                    unimplemented!("serialized_size_with_flags")
+                    1 + self.val.serialized_size() + self.degree.serialized_size()
                 }
             }
             impl<T: $bound, M> CanonicalDeserialize for $share<T, M> {
-                fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-                    unimplemented!("deserialize")
+                fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+                    Self::deserialize_with_flags::<_, EmptyFlags>(reader).map(|(s, _)| s)
                 }
             }
             impl<T: $bound, M> CanonicalDeserializeWithFlags for $share<T, M> {
                 fn deserialize_with_flags<R: Read, F: Flags>(
-                    _reader: R,
+                    mut reader: R,
                 ) -> Result<(Self, F), SerializationError> {
-                    unimplemented!("deserialize_with_flags")
+                    let flags = encoding::read_header(&mut reader)?;
+                    let val = T::deserialize(&mut reader)?;
+                    let degree = usize::deserialize(&mut reader)?;
+                    Ok((
+                        Self {
+                            val,
+                            degree,
+                            _phants: PhantomData::default(),
+                        },
+                        flags,
+                    ))
                 }
             }
             impl<T: $bound, M> UniformRand for $share<T, M> {
@@ -1542,6 +1791,12 @@ pub mod mul_field {
 
     impl_basics_2_param!(MulFieldShare, Field);
 
+    impl<F: Field, S: PrimeField> Item for MulFieldShare<F, S> {
+        fn label() -> String {
+            format!("gsz-mul<{},{}>", field_label::<F>(), field_label::<S>())
+        }
+    }
+
     impl<F: Field, S: PrimeField> Reveal for MulFieldShare<F, S> {
         type Base = F;
 
@@ -1564,6 +1819,21 @@ pub mod mul_field {
     }
 
     impl<F: Field, S: PrimeField> FieldShare<F> for MulFieldShare<F, S> {
+        fn try_batch_open_in(
+            selfs: impl IntoIterator<Item = Self>,
+            session: &Arc<Session>,
+        ) -> Result<Vec<F>, NetError> {
+            let _op = mpc_net::op("batch_open");
+            let (vals, degrees): (Vec<F>, Vec<usize>) =
+                selfs.into_iter().map(|s| (s.val, s.degree)).unzip();
+            let all_vals = session.try_broadcast(&vals)?;
+            Ok(degrees
+                .into_iter()
+                .enumerate()
+                .map(|(i, d)| open_degree_vec::<F, S>(all_vals.iter().map(|v| v[i]).collect(), d))
+                .collect())
+        }
+
         fn map_homo<FF: Field, SS: FieldShare<FF>, Fun: Fn(F) -> FF>(self, _f: Fun) -> SS {
             unimplemented!()
         }
@@ -1619,20 +1889,20 @@ pub mod mul_field {
     }
 
     fn open_degree_vec<F: Field, S: PrimeField>(shares: Vec<F>, d: usize) -> F {
-        let domain = domain::<S>();
-        let n = Net::n_parties();
+        let n = shares.len();
+        let domain = domain_of::<S>(n);
         let n_inv = S::from(n as u32).inverse().unwrap();
         let w = domain.element(1);
         let w_inv = w.inverse().unwrap();
         // w^{-i}
         let mut w_inv_i = S::one();
         let coeffs: Vec<F> = (0..n)
-            .map(|i| {
+            .map(|_| {
                 let mut coeff = F::one();
                 // 1/N * w^{-ij}
                 let mut w_inv_ij = n_inv;
-                for _j in 0..n {
-                    coeff *= shares[i].pow(&w_inv_ij.into_repr());
+                for share in &shares {
+                    coeff *= share.pow(&w_inv_ij.into_repr());
                     w_inv_ij *= &w_inv_i;
                 }
                 w_inv_i *= &w_inv;
@@ -1699,6 +1969,7 @@ pub struct GszPairingShare<E: PairingEngine>(pub PhantomData<E>);
 
 pub mod msm {
     use super::*;
+    use crate::msm::{AffineMsm, ParMsm, ProjectiveMsm};
 
     fn run_all_checks<E: PairingEngine>() {
         let t = start_timer!(|| "All opening checks");
@@ -1717,7 +1988,7 @@ pub mod msm {
 
     impl<E: PairingEngine> Msm<E::G1Affine, E::Fr> for GszG1AffineMsm<E> {
         fn msm(bases: &[E::G1Affine], scalars: &[E::Fr]) -> E::G1Affine {
-            E::G1Affine::multi_scalar_mul(bases, scalars).into()
+            ParMsm::<AffineMsm<E::G1Affine>>::msm(bases, scalars)
         }
         fn pre_reveal_check() {
             run_all_checks::<E>();
@@ -1729,7 +2000,7 @@ pub mod msm {
     pub struct GszG2AffineMsm<E: PairingEngine>(pub PhantomData<E>);
     impl<E: PairingEngine> Msm<E::G2Affine, E::Fr> for GszG2AffineMsm<E> {
         fn msm(bases: &[E::G2Affine], scalars: &[E::Fr]) -> E::G2Affine {
-            E::G2Affine::multi_scalar_mul(bases, scalars).into()
+            ParMsm::<AffineMsm<E::G2Affine>>::msm(bases, scalars)
         }
         fn pre_reveal_check() {
             run_all_checks::<E>();
@@ -1742,8 +2013,7 @@ pub mod msm {
 
     impl<E: PairingEngine> Msm<E::G1Projective, E::Fr> for GszG1ProjectiveMsm<E> {
         fn msm(bases: &[E::G1Projective], scalars: &[E::Fr]) -> E::G1Projective {
-            let bases: Vec<E::G1Affine> = bases.iter().map(|s| s.clone().into()).collect();
-            <E::G1Affine as AffineCurve>::multi_scalar_mul(&bases, scalars)
+            ParMsm::<ProjectiveMsm<E::G1Projective>>::msm(bases, scalars)
         }
         fn pre_reveal_check() {
             run_all_checks::<E>();
@@ -1755,8 +2025,7 @@ pub mod msm {
     pub struct GszG2ProjectiveMsm<E: PairingEngine>(pub PhantomData<E>);
     impl<E: PairingEngine> Msm<E::G2Projective, E::Fr> for GszG2ProjectiveMsm<E> {
         fn msm(bases: &[E::G2Projective], scalars: &[E::Fr]) -> E::G2Projective {
-            let bases: Vec<E::G2Affine> = bases.iter().map(|s| s.clone().into()).collect();
-            <E::G2Affine as AffineCurve>::multi_scalar_mul(&bases, scalars)
+            ParMsm::<ProjectiveMsm<E::G2Projective>>::msm(bases, scalars)
         }
         fn pre_reveal_check() {
             run_all_checks::<E>();
@@ -1788,4 +2057,35 @@ impl<E: PairingEngine> PairingShare<E> for GszPairingShare<E> {
     type G2ProjectiveShare = GszGroupShare<E::G2Projective, msm::GszG2ProjectiveMsm<E>>;
     type G1 = GszG1Share<E>;
     type G2 = GszG2Share<E>;
+
+    // Shares of `a` are points on a polynomial; their loops are points on that polynomial, in the
+    // exponent, of the same degree.
+    fn miller_loop_sh_pub(
+        a: &Self::G1AffineShare,
+        b: &E::G2Prepared,
+    ) -> mul_field::MulFieldShare<E::Fqk, E::Fr> {
+        mul_field::MulFieldShare {
+            val: E::miller_loop(&[(a.val.into(), b.clone())]),
+            degree: a.degree,
+            _phants: PhantomData::default(),
+        }
+    }
+
+    fn miller_loop_pub_sh(
+        a: &E::G1Prepared,
+        b: &Self::G2AffineShare,
+    ) -> mul_field::MulFieldShare<E::Fqk, E::Fr> {
+        mul_field::MulFieldShare {
+            val: E::miller_loop(&[(a.clone(), b.val.into())]),
+            degree: b.degree,
+            _phants: PhantomData::default(),
+        }
+    }
+
+    fn final_exponentiation(
+        mut f: mul_field::MulFieldShare<E::Fqk, E::Fr>,
+    ) -> mul_field::MulFieldShare<E::Fqk, E::Fr> {
+        f.val = E::final_exponentiation(&f.val).expect("Zero Miller loop share");
+        f
+    }
 }
@@ -11,6 +11,9 @@ pub mod spdz;
 pub use spdz::*;
 pub mod gsz20;
 pub use gsz20::*;
+pub mod packed;
+pub mod preprocessing;
+pub use preprocessing::{FileBeaverSource, FileFieldTripleSource, FileGroupTripleSource};
 
 use std::marker::PhantomData;
 use derivative::Derivative;
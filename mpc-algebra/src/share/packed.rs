@@ -0,0 +1,472 @@
+//! Packed Shamir sharing: `l` secrets in one share.
+//!
+//! This is the packed secret sharing of Franklin and Yung, which zkSaaS ("zkSaaS: Zero-Knowledge
+//! SNARKs as a Service", by Garg et al.) uses to split a prover's work between many parties. A
+//! degree-`d` sharing of `(x_0, .., x_{l-1})` is a random polynomial `p` of degree `d` with
+//! `p(s_k) = x_k`, and party `i` holds `p(w^i)`, where `w` generates the
+//! [GSZ20 domain](super::gsz20::domain). The secret points are `s_k = g^(k + 1)` for the field's
+//! multiplicative generator `g`, so they are never share points. Any `d + 1 - l` shares reveal
+//! nothing about the secrets, so fresh sharings have [degree] `t + l - 1`, where [t] bounds the
+//! corrupt parties.
+//!
+//! Sharings add and scale locally. Products have degree `2(t + l - 1)`, which must be below the
+//! number of parties for them to open, and [repack] brings them back to [degree]. A vector of
+//! length `N` is shared as `N / l` packed shares of its consecutive chunks; [fft] and [ifft]
+//! transform such vectors with `O(N / l log N)` local work per party.
+use ark_ff::{FftField, Field};
+use ark_poly::{
+    domain::{EvaluationDomain, Radix2EvaluationDomain},
+    univariate::DensePolynomial,
+    Polynomial, UVPolynomial,
+};
+use mpc_net::{MpcMultiNet as Net, MpcNet};
+use rand::Rng;
+
+use super::gsz20::domain;
+use crate::channel::MpcSerNet;
+
+/// One party's share of a packed sharing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PackedFieldShare<F: Field> {
+    pub val: F,
+    /// The degree of the sharing polynomial.
+    pub degree: usize,
+    /// How many secrets the sharing holds.
+    pub packing: usize,
+}
+
+impl<F: Field> PackedFieldShare<F> {
+    pub fn add(&mut self, other: &Self) -> &mut Self {
+        assert_eq!(
+            self.packing, other.packing,
+            "Adding sharings of different packings"
+        );
+        self.val += other.val;
+        self.degree = std::cmp::max(self.degree, other.degree);
+        self
+    }
+
+    pub fn sub(&mut self, other: &Self) -> &mut Self {
+        assert_eq!(
+            self.packing, other.packing,
+            "Subtracting sharings of different packings"
+        );
+        self.val -= other.val;
+        self.degree = std::cmp::max(self.degree, other.degree);
+        self
+    }
+
+    /// Scale every secret by `scalar`.
+    pub fn scale(&mut self, scalar: &F) -> &mut Self {
+        self.val *= scalar;
+        self
+    }
+
+    /// The secret-wise product, of degree the sum of the degrees. See [mult].
+    pub fn mul_local(&self, other: &Self) -> Self {
+        assert_eq!(
+            self.packing, other.packing,
+            "Multiplying sharings of different packings"
+        );
+        Self {
+            val: self.val * other.val,
+            degree: self.degree + other.degree,
+            packing: self.packing,
+        }
+    }
+}
+
+/// How many corrupt parties packed sharings are private against: a quarter of the parties, which
+/// leaves room to pack up to a quarter of them worth of secrets in each sharing.
+pub fn t() -> usize {
+    (Net::n_parties() - 1) / 4
+}
+
+/// The degree of fresh sharings of `l` secrets: high enough that any [t] shares reveal nothing,
+/// and low enough that products can be opened.
+pub fn degree(l: usize) -> usize {
+    let d = t() + l - 1;
+    assert!(
+        Net::n_parties() > 2 * d,
+        "Cannot pack {} secrets against {} corruptions among {} parties",
+        l,
+        t(),
+        Net::n_parties()
+    );
+    d
+}
+
+/// Deals and opens sharings of `l` secrets at one degree.
+struct Packer<F: FftField> {
+    l: usize,
+    degree: usize,
+    points: Vec<F>,
+    /// `basis[k]`: the coefficients of the polynomial of degree `l - 1` that is one at `points[k]`
+    /// and zero at the other points.
+    basis: Vec<Vec<F>>,
+    /// The coefficients of the polynomial that is zero at every point.
+    vanishing: Vec<F>,
+}
+
+impl<F: FftField> Packer<F> {
+    fn new(l: usize, degree: usize) -> Self {
+        assert!(l >= 1, "Packing no secrets");
+        assert!(
+            l <= degree + 1 && degree < Net::n_parties(),
+            "Cannot pack {} secrets at degree {} among {} parties",
+            l,
+            degree,
+            Net::n_parties()
+        );
+        let g = F::multiplicative_generator();
+        let points: Vec<F> = std::iter::successors(Some(g), |x| Some(*x * g))
+            .take(l)
+            .collect();
+        let basis = (0..l)
+            .map(|k| {
+                let others: Vec<F> = (0..l).filter(|j| *j != k).map(|j| points[j]).collect();
+                let denom: F = others.iter().map(|x| points[k] - x).product();
+                let mut coeffs = poly_from_roots(&others);
+                let denom_inv = denom.inverse().unwrap();
+                coeffs.iter_mut().for_each(|c| *c *= denom_inv);
+                coeffs
+            })
+            .collect();
+        let vanishing = poly_from_roots(&points);
+        Self {
+            l,
+            degree,
+            points,
+            basis,
+            vanishing,
+        }
+    }
+
+    /// A fresh, random sharing of `secrets`: entry `i` is party `i`'s share.
+    fn deal<R: Rng + ?Sized>(&self, secrets: &[F], rng: &mut R) -> Vec<F> {
+        assert_eq!(secrets.len(), self.l);
+        let mut coeffs = vec![F::zero(); Net::n_parties()];
+        for (basis, secret) in self.basis.iter().zip(secrets) {
+            for (c, b) in coeffs.iter_mut().zip(basis) {
+                *c += *b * secret;
+            }
+        }
+        // Plus the vanishing polynomial times a random one, up to the degree.
+        for i in 0..self.degree + 1 - self.l {
+            let r = F::rand(rng);
+            for (j, z) in self.vanishing.iter().enumerate() {
+                coeffs[i + j] += r * z;
+            }
+        }
+        domain::<F>().fft_in_place(&mut coeffs);
+        coeffs
+    }
+
+    /// Deal `secrets`, `l` to a sharing: entry `i` is party `i`'s shares.
+    fn deal_all<R: Rng + ?Sized>(&self, secrets: &[F], rng: &mut R) -> Vec<Vec<F>> {
+        assert_eq!(
+            secrets.len() % self.l,
+            0,
+            "Cannot pack {} secrets {} to a share",
+            secrets.len(),
+            self.l
+        );
+        let mut out = vec![Vec::with_capacity(secrets.len() / self.l); Net::n_parties()];
+        for chunk in secrets.chunks(self.l) {
+            for (out, share) in out.iter_mut().zip(self.deal(chunk, rng)) {
+                out.push(share);
+            }
+        }
+        out
+    }
+
+    /// The secrets of the sharings whose shares are `all[i]`, for party `i`.
+    fn open_all(&self, all: Vec<Vec<F>>) -> Vec<F> {
+        let count = all.first().map_or(0, |a| a.len());
+        let mut out = Vec::with_capacity(count * self.l);
+        for c in 0..count {
+            let mut coeffs: Vec<F> = all.iter().map(|a| a[c]).collect();
+            domain::<F>().ifft_in_place(&mut coeffs);
+            let p = DensePolynomial::from_coefficients_vec(coeffs);
+            assert!(
+                p.degree() <= self.degree,
+                "Packed sharing has degree {} (> degree bound {})",
+                p.degree(),
+                self.degree
+            );
+            out.extend(self.points.iter().map(|x| p.evaluate(x)));
+        }
+        out
+    }
+}
+
+/// The coefficients, lowest first, of the monic polynomial with `roots`.
+fn poly_from_roots<F: Field>(roots: &[F]) -> Vec<F> {
+    let mut coeffs = vec![F::one()];
+    for r in roots {
+        coeffs.insert(0, F::zero());
+        for i in 0..coeffs.len() - 1 {
+            let next = coeffs[i + 1];
+            coeffs[i] -= next * r;
+        }
+    }
+    coeffs
+}
+
+/// The common packing and the highest degree of `shares`.
+fn params<F: Field>(shares: &[PackedFieldShare<F>]) -> (usize, usize) {
+    let packing = shares.first().map_or(1, |s| s.packing);
+    assert!(
+        shares.iter().all(|s| s.packing == packing),
+        "Sharings of different packings"
+    );
+    (packing, shares.iter().map(|s| s.degree).max().unwrap_or(0))
+}
+
+/// The king shares `secrets`, `l` to a sharing, at degree `degree`. Only the king's `secrets` are
+/// used.
+pub fn king_share<F: FftField>(secrets: &[F], l: usize, degree: usize) -> Vec<PackedFieldShare<F>> {
+    let packer = Packer::new(l, degree);
+    let vals: Vec<F> = Net::recv_from_king(if Net::am_king() {
+        Some(packer.deal_all(secrets, &mut rand::thread_rng()))
+    } else {
+        None
+    });
+    vals.into_iter()
+        .map(|val| PackedFieldShare {
+            val,
+            degree,
+            packing: l,
+        })
+        .collect()
+}
+
+/// Open `shares` to everyone, as the vector of their secrets.
+pub fn open<F: FftField>(shares: &[PackedFieldShare<F>]) -> Vec<F> {
+    let _op = mpc_net::op("packed_open");
+    let (l, degree) = params(shares);
+    let vals: Vec<F> = shares.iter().map(|s| s.val).collect();
+    Packer::new(l, degree).open_all(Net::broadcast(&vals))
+}
+
+/// Apply the public linear map `f` to the vector that `shares` hold, and share the result afresh,
+/// `l` to a sharing, at [degree]`(l)`.
+///
+/// Every party deals a random vector `r`, packed like `shares`, and `f(r)`, packed `l` to a
+/// sharing; the sums of the dealt vectors are the masks. The king opens the masked vector, applies
+/// `f` to it, and deals the result, from which every party subtracts its share of the masks'
+/// image. The king learns nothing, as long as one party's `r` is random. `shares` may have any
+/// degree below the number of parties, so this also reduces the degree of products; the masks are
+/// dealt at the degree of `shares`, so that they hide every coefficient the king sees.
+pub fn repack<F: FftField>(
+    shares: &[PackedFieldShare<F>],
+    l: usize,
+    f: impl Fn(Vec<F>) -> Vec<F>,
+) -> Vec<PackedFieldShare<F>> {
+    let _op = mpc_net::op("repack");
+    let d = degree(l);
+    let output = Packer::new(l, d);
+    let rng = &mut rand::thread_rng();
+    let (input, masked, f_mask) = mask(shares, &output, &f, rng);
+
+    let king_answer = Net::send_to_king(&masked).map(|all| {
+        let opened = input.open_all(all);
+        output.deal_all(&f(opened), rng)
+    });
+    let from_king: Vec<F> = Net::recv_from_king(king_answer);
+    from_king
+        .into_iter()
+        .zip(f_mask)
+        .map(|(val, mask)| PackedFieldShare {
+            val: val - mask,
+            degree: d,
+            packing: l,
+        })
+        .collect()
+}
+
+/// Our shares of `shares` plus the masks, and of `f` of the masks, dealt by `output`, along with
+/// the packer that opens the masked sharings. See [repack].
+fn mask<F: FftField, R: Rng>(
+    shares: &[PackedFieldShare<F>],
+    output: &Packer<F>,
+    f: impl Fn(Vec<F>) -> Vec<F>,
+    rng: &mut R,
+) -> (Packer<F>, Vec<F>, Vec<F>) {
+    let (l_in, d_in) = params(shares);
+    let input = Packer::new(l_in, std::cmp::max(d_in, degree(l_in)));
+    let r: Vec<F> = (0..shares.len() * input.l).map(|_| F::rand(rng)).collect();
+    let f_r = f(r.clone());
+    let n_out = f_r.len() / output.l;
+    let mut dealt = input.deal_all(&r, rng);
+    for (dealt, f_r) in dealt.iter_mut().zip(output.deal_all(&f_r, rng)) {
+        dealt.extend(f_r);
+    }
+    let received: Vec<Vec<F>> = Net::all_to_all(&dealt);
+
+    let mut masked: Vec<F> = shares.iter().map(|s| s.val).collect();
+    let mut f_mask = vec![F::zero(); n_out];
+    for (i, r) in received.iter().enumerate() {
+        assert_eq!(
+            r.len(),
+            shares.len() + n_out,
+            "Party {} dealt the wrong number of masks",
+            i
+        );
+        for (m, r) in masked.iter_mut().zip(&r[..shares.len()]) {
+            *m += r;
+        }
+        for (m, r) in f_mask.iter_mut().zip(&r[shares.len()..]) {
+            *m += r;
+        }
+    }
+    (input, masked, f_mask)
+}
+
+/// The secret-wise products of `a` and `b`, at [degree] again.
+pub fn mult<F: FftField>(
+    a: &[PackedFieldShare<F>],
+    b: &[PackedFieldShare<F>],
+) -> Vec<PackedFieldShare<F>> {
+    assert_eq!(a.len(), b.len());
+    let products: Vec<_> = a.iter().zip(b).map(|(a, b)| a.mul_local(b)).collect();
+    let (l, _) = params(&products);
+    repack(&products, l, |v| v)
+}
+
+/// The FFT over `domain` of the vector that `shares` hold, packed in consecutive chunks. The
+/// result is packed the same way.
+pub fn fft<F: FftField>(
+    shares: &[PackedFieldShare<F>],
+    domain: &Radix2EvaluationDomain<F>,
+) -> Vec<PackedFieldShare<F>> {
+    four_step(shares, domain, false)
+}
+
+/// The inverse of [fft].
+pub fn ifft<F: FftField>(
+    shares: &[PackedFieldShare<F>],
+    domain: &Radix2EvaluationDomain<F>,
+) -> Vec<PackedFieldShare<F>> {
+    four_step(shares, domain, true)
+}
+
+/// The four-step FFT. With `N = m l`, share `a` packs `x[l a + b]` in lane `b`, and
+///
+/// `X[c + m d] = sum_b w_l^(b d) w^(b c) sum_a w_m^(a c) x[l a + b]`
+///
+/// The inner sums are the same size-`m` FFT in every lane, so parties compute them locally on
+/// their shares. The outer sums mix lanes, so the king computes them, in a [repack]. The inverse
+/// is the same, with inverse roots.
+fn four_step<F: FftField>(
+    shares: &[PackedFieldShare<F>],
+    domain: &Radix2EvaluationDomain<F>,
+    inverse: bool,
+) -> Vec<PackedFieldShare<F>> {
+    let _op = mpc_net::op(if inverse { "packed_ifft" } else { "packed_fft" });
+    let (l, _) = params(shares);
+    let n = domain.size();
+    let m = shares.len();
+    assert_eq!(
+        m * l,
+        n,
+        "FFT of {} sharings of {} over a domain of size {}",
+        m,
+        l,
+        n
+    );
+    let inner = Radix2EvaluationDomain::<F>::new(m).unwrap();
+    let lanes = Radix2EvaluationDomain::<F>::new(l).unwrap();
+    assert_eq!(lanes.size(), l, "Packing {} is not a power of two", l);
+
+    let mut vals: Vec<F> = shares.iter().map(|s| s.val).collect();
+    if inverse {
+        inner.ifft_in_place(&mut vals);
+    } else {
+        inner.fft_in_place(&mut vals);
+    }
+    let inner_sums: Vec<_> = vals
+        .into_iter()
+        .zip(shares)
+        .map(|(val, s)| PackedFieldShare { val, ..*s })
+        .collect();
+
+    let w = if inverse {
+        domain.group_gen_inv
+    } else {
+        domain.group_gen
+    };
+    repack(&inner_sums, l, |y| {
+        let mut x = vec![F::zero(); n];
+        // w^c
+        let mut w_c = F::one();
+        for (c, chunk) in y.chunks(l).enumerate() {
+            // w^(b c)
+            let mut w_bc = F::one();
+            let mut z: Vec<F> = chunk
+                .iter()
+                .map(|y| {
+                    let t = *y * w_bc;
+                    w_bc *= w_c;
+                    t
+                })
+                .collect();
+            if inverse {
+                lanes.ifft_in_place(&mut z);
+            } else {
+                lanes.fft_in_place(&mut z);
+            }
+            for (d, z) in z.into_iter().enumerate() {
+                x[c + m * d] = z;
+            }
+            w_c *= w;
+        }
+        x
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_377::Fr;
+    use ark_ff::{UniformRand, Zero};
+    use mpc_net::LocalSimNet;
+
+    /// The coefficients of the polynomials whose shares are `all[i]`, for party `i`.
+    fn coefficients(all: Vec<Vec<Fr>>) -> Vec<Vec<Fr>> {
+        (0..all[0].len())
+            .map(|c| domain::<Fr>().ifft(&all.iter().map(|a| a[c]).collect::<Vec<_>>()))
+            .collect()
+    }
+
+    #[test]
+    fn products_are_fully_masked() {
+        LocalSimNet::run(8, |_| {
+            let rng = &mut ark_std::test_rng();
+            let xs: Vec<Fr> = (0..8).map(|_| Fr::rand(rng)).collect();
+            let d = degree(2);
+            assert_eq!(d, t() + 1);
+            let shares = king_share(&xs, 2, d);
+            let vals: Vec<Fr> = shares.iter().map(|s| s.val).collect();
+            for fresh in coefficients(Net::broadcast(&vals)) {
+                assert!(!fresh[d].is_zero());
+                assert!(fresh[d + 1..].iter().all(|c| c.is_zero()));
+            }
+            let products: Vec<_> = shares.iter().map(|s| s.mul_local(s)).collect();
+            let d_in = 2 * d;
+            let output = Packer::new(2, d);
+            let (_, masked, _) = mask(&products, &output, |v| v, rng);
+
+            let vals: Vec<Fr> = products.iter().map(|s| s.val).collect();
+            let before = coefficients(Net::broadcast(&vals));
+            let after = coefficients(Net::broadcast(&masked));
+            for (before, after) in before.iter().zip(&after) {
+                assert!(before[d_in + 1..].iter().all(|c| c.is_zero()));
+                assert!(after[d_in + 1..].iter().all(|c| c.is_zero()));
+                for k in d + 1..=d_in {
+                    assert_ne!(before[k], after[k], "Coefficient {} is unmasked", k);
+                }
+            }
+        });
+    }
+}
@@ -60,4 +60,21 @@ pub trait PairingShare<E: PairingEngine>:
         AffineShare = Self::G2AffineShare,
         ProjectiveShare = Self::G2ProjectiveShare,
     >;
+
+    /// The Miller loop of a shared G1 point and a public G2 point, on this party's share.
+    ///
+    /// The shares combine as the shared point does, but only up to the kernel of the final
+    /// exponentiation, so open the result only after [PairingShare::final_exponentiation].
+    fn miller_loop_sh_pub(a: &Self::G1AffineShare, b: &E::G2Prepared) -> FqkShare<E, Self>;
+
+    /// The Miller loop of a public G1 point and a shared G2 point. See
+    /// [PairingShare::miller_loop_sh_pub].
+    fn miller_loop_pub_sh(a: &E::G1Prepared, b: &Self::G2AffineShare) -> FqkShare<E, Self>;
+
+    /// The final exponentiation, which is a homomorphism, on this party's share.
+    fn final_exponentiation(f: FqkShare<E, Self>) -> FqkShare<E, Self>;
 }
+
+/// The share of a target group element.
+pub type FqkShare<E, S> =
+    <<S as PairingShare<E>>::FqkShare as ExtFieldShare<<E as PairingEngine>::Fqk>>::Ext;
@@ -0,0 +1,468 @@
+//! Preprocessing material, stored on disk.
+//!
+//! Each party runs the offline phase once, collects its output in a [Store], and writes it to a
+//! file. Later, the online phase [load]s that file, and consumes the material through
+//! [FileBeaverSource], or through code that knows to look for it (SPDZ triples and inverse pairs,
+//! GSZ20 double-random sharings, [random bits](crate::bits::random_bits)). This separates online
+//! time from preprocessing time, and lets preprocessing be shipped to parties ahead of time.
+//!
+//! A store holds typed sections, keyed by a [Kind] and the [label](Item::label) of the items. Items are
+//! handed out in order. Before any item is handed out, it is reserved in a consumption log next to
+//! the file (`<path>.consumed`), so material is never reused, even if the process dies; the price
+//! is that reserved but unused material is lost.
+use derivative::Derivative;
+use rand::Rng;
+
+use ark_ec::group::Group;
+use ark_ff::Field;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use super::BeaverSource;
+use crate::channel::MpcSerNet;
+use mpc_net::{MpcMultiNet as Net, MpcNet};
+
+const MAGIC: [u8; 8] = *b"MPCPREP\0";
+
+/// The file format version. Files with any other version are rejected.
+pub const VERSION: u32 = 3;
+
+/// How many items past the current demand to reserve at once, to avoid rewriting the consumption
+/// log on every take.
+const RESERVE_CHUNK: u64 = 1024;
+
+/// What a section holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// Beaver triples `(a, b, ab)`, as `(A, B, C)` tuples.
+    Triple,
+    /// Pairs `(r, 1/r)`.
+    InvPair,
+    /// Pairs of sharings of one random value, at degrees `t` and `2t`.
+    DoubleRand,
+    /// Long-lived key material, like a MAC key share. Read with [peek], never consumed.
+    Key,
+    /// Sharings of random bits.
+    RandomBit,
+}
+
+impl Kind {
+    fn tag(self) -> u8 {
+        match self {
+            Kind::Triple => 0,
+            Kind::InvPair => 1,
+            Kind::DoubleRand => 2,
+            Kind::Key => 3,
+            Kind::RandomBit => 4,
+        }
+    }
+}
+
+/// Something that can be stored in a section.
+///
+/// The label names the type in the file, so it must stay the same across builds and toolchains,
+/// and must differ between types whose items are not interchangeable.
+pub trait Item: CanonicalSerialize + CanonicalDeserialize {
+    fn label() -> String;
+}
+
+/// A label for `F`: its characteristic, in hex, and its extension degree.
+pub fn field_label<F: Field>() -> String {
+    let hex: String = F::characteristic()
+        .iter()
+        .rev()
+        .map(|limb| format!("{:016x}", limb))
+        .collect();
+    format!("F{}^{}", hex.trim_start_matches('0'), F::extension_degree())
+}
+
+/// A label for `G`: its scalar field, and the size of an element.
+pub fn group_label<G: Group>() -> String {
+    format!(
+        "G{}[{}]",
+        field_label::<G::ScalarField>(),
+        G::zero().uncompressed_size()
+    )
+}
+
+impl Item for bool {
+    fn label() -> String {
+        "bool".to_owned()
+    }
+}
+
+impl<T: Item> Item for Vec<T> {
+    fn label() -> String {
+        format!("[{}]", T::label())
+    }
+}
+
+impl<A: Item, B: Item> Item for (A, B) {
+    fn label() -> String {
+        format!("({},{})", A::label(), B::label())
+    }
+}
+
+impl<A: Item, B: Item, C: Item> Item for (A, B, C) {
+    fn label() -> String {
+        format!("({},{},{})", A::label(), B::label(), C::label())
+    }
+}
+
+#[derive(CanonicalSerialize, CanonicalDeserialize)]
+struct Section {
+    kind: u8,
+    /// The type of the items, from [Item::label].
+    label: String,
+    item_size: u64,
+    count: u64,
+    data: Vec<u8>,
+}
+
+#[derive(CanonicalSerialize, CanonicalDeserialize)]
+struct ConsumptionLog {
+    id: u64,
+    reserved: Vec<u64>,
+}
+
+/// One party's preprocessing material.
+pub struct Store {
+    /// Shared by the stores of all parties from one offline run.
+    id: u64,
+    party: usize,
+    n_parties: usize,
+    sections: Vec<Section>,
+    /// Per section, the number of items handed out (or lost to an earlier run).
+    used: Vec<u64>,
+    /// Per section, the number of items reserved in the consumption log.
+    reserved: Vec<u64>,
+    /// The file backing this store, if any.
+    path: Option<PathBuf>,
+}
+
+fn log_path(path: &Path) -> PathBuf {
+    let mut p = path.as_os_str().to_owned();
+    p.push(".consumed");
+    PathBuf::from(p)
+}
+
+impl Store {
+    /// An empty store.
+    ///
+    /// Every party must call this at the same point in the protocol, so that the stores from one
+    /// offline run share an id.
+    pub fn new() -> Self {
+        let id: u64 = rand::thread_rng().gen();
+        let id = Net::broadcast(&id)[0];
+        Self {
+            id,
+            party: Net::party_id(),
+            n_parties: Net::n_parties(),
+            sections: Vec::new(),
+            used: Vec::new(),
+            reserved: Vec::new(),
+            path: None,
+        }
+    }
+
+    fn find<T: Item>(&self, kind: Kind) -> Option<usize> {
+        let label = T::label();
+        self.sections
+            .iter()
+            .position(|s| s.kind == kind.tag() && s.label == label)
+    }
+
+    /// Append `items` to the section for `kind` and `T`.
+    pub fn push<T: Item>(&mut self, kind: Kind, items: &[T]) {
+        if items.is_empty() {
+            return;
+        }
+        let item_size = items[0].serialized_size() as u64;
+        let i = match self.find::<T>(kind) {
+            Some(i) => i,
+            None => {
+                self.sections.push(Section {
+                    kind: kind.tag(),
+                    label: T::label(),
+                    item_size,
+                    count: 0,
+                    data: Vec::new(),
+                });
+                self.used.push(0);
+                self.reserved.push(0);
+                self.sections.len() - 1
+            }
+        };
+        let s = &mut self.sections[i];
+        assert_eq!(
+            s.item_size, item_size,
+            "Preprocessing items of one type must have one size"
+        );
+        for item in items {
+            item.serialize(&mut s.data).unwrap();
+        }
+        assert_eq!(
+            s.data.len() as u64,
+            (s.count + items.len() as u64) * item_size,
+            "Preprocessing items of one type must have one size"
+        );
+        s.count += items.len() as u64;
+    }
+
+    /// Write this store to `path`, starting a fresh consumption log.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), SerializationError> {
+        let path = path.as_ref();
+        let mut w = BufWriter::new(File::create(path)?);
+        w.write_all(&MAGIC)?;
+        VERSION.serialize(&mut w)?;
+        self.id.serialize(&mut w)?;
+        self.party.serialize(&mut w)?;
+        self.n_parties.serialize(&mut w)?;
+        self.sections.serialize(&mut w)?;
+        w.flush()?;
+        let log = log_path(path);
+        if log.exists() {
+            fs::remove_file(log)?;
+        }
+        Ok(())
+    }
+
+    /// Read a store from `path`, skipping anything its consumption log says was reserved.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SerializationError> {
+        let path = path.as_ref();
+        let mut r = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if magic != MAGIC || u32::deserialize(&mut r)? != VERSION {
+            return Err(SerializationError::InvalidData);
+        }
+        let id = u64::deserialize(&mut r)?;
+        let party = usize::deserialize(&mut r)?;
+        let n_parties = usize::deserialize(&mut r)?;
+        let sections = Vec::<Section>::deserialize(&mut r)?;
+        for s in &sections {
+            if s.data.len() as u64 != s.count * s.item_size {
+                return Err(SerializationError::InvalidData);
+            }
+        }
+        let log = log_path(path);
+        let reserved = if log.exists() {
+            let log = ConsumptionLog::deserialize(BufReader::new(File::open(log)?))?;
+            if log.id != id || log.reserved.len() != sections.len() {
+                return Err(SerializationError::InvalidData);
+            }
+            log.reserved
+        } else {
+            vec![0; sections.len()]
+        };
+        Ok(Self {
+            id,
+            party,
+            n_parties,
+            sections,
+            used: reserved.clone(),
+            reserved,
+            path: Some(path.to_owned()),
+        })
+    }
+
+    fn write_log(&self) -> Result<(), SerializationError> {
+        if let Some(path) = &self.path {
+            let log = log_path(path);
+            let mut tmp = log.clone().into_os_string();
+            tmp.push(".tmp");
+            let mut w = BufWriter::new(File::create(&tmp)?);
+            ConsumptionLog {
+                id: self.id,
+                reserved: self.reserved.clone(),
+            }
+            .serialize(&mut w)?;
+            w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            fs::rename(tmp, log)?;
+        }
+        Ok(())
+    }
+
+    /// How many unused items the section for `kind` and `T` has.
+    pub fn remaining<T: Item>(&self, kind: Kind) -> usize {
+        self.find::<T>(kind)
+            .map(|i| (self.sections[i].count - self.used[i]) as usize)
+            .unwrap_or(0)
+    }
+
+    /// Consume `n` items of type `T`, or `None` if fewer than `n` remain.
+    pub fn take<T: Item>(&mut self, kind: Kind, n: usize) -> Option<Vec<T>> {
+        if self.remaining::<T>(kind) < n {
+            return None;
+        }
+        let i = self.find::<T>(kind)?;
+        let n = n as u64;
+        let s = &self.sections[i];
+        if self.used[i] + n > self.reserved[i] {
+            self.reserved[i] = std::cmp::min(s.count, self.used[i] + n + RESERVE_CHUNK);
+            self.write_log()
+                .expect("Could not record preprocessing consumption");
+        }
+        let s = &self.sections[i];
+        let start = (self.used[i] * s.item_size) as usize;
+        let end = ((self.used[i] + n) * s.item_size) as usize;
+        let mut bytes = &s.data[start..end];
+        let items = (0..n)
+            .map(|_| T::deserialize(&mut bytes).expect("Corrupt preprocessing"))
+            .collect();
+        self.used[i] += n;
+        Some(items)
+    }
+
+    /// The first item of type `T`, without consuming it.
+    pub fn peek<T: Item>(&self, kind: Kind) -> Option<T> {
+        let s = &self.sections[self.find::<T>(kind)?];
+        if s.count == 0 {
+            return None;
+        }
+        Some(T::deserialize(&s.data[..s.item_size as usize]).expect("Corrupt preprocessing"))
+    }
+}
+
+/// The store loaded for the online phase. Kept per [session](mpc_net::Session).
+#[derive(Default)]
+struct Loaded(Option<Store>);
+
+/// Load the store at `path` for the online phase, replacing any loaded store.
+///
+/// Every party must call this at the same point in the protocol: the parties check that they have
+/// loaded stores from the same offline run, and that they have consumed them equally. Call this
+/// before [Reveal::init_protocol](crate::Reveal::init_protocol), so that share types can pick up
+/// key material from the store.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<(), SerializationError> {
+    let store = Store::open(path).and_then(|s| {
+        if s.party == Net::party_id() && s.n_parties == Net::n_parties() {
+            Ok(s)
+        } else {
+            Err(SerializationError::InvalidData)
+        }
+    });
+    let summary = store.as_ref().ok().map(|s| (s.id, s.used.clone()));
+    let all_summaries = Net::broadcast(&summary);
+    let store = store?;
+    if all_summaries.iter().any(|s| s != &summary) {
+        return Err(SerializationError::InvalidData);
+    }
+    Net::with_local(|l: &mut Loaded| l.0 = Some(store));
+    Ok(())
+}
+
+/// Drop the loaded store. Its unused material stays available to the next [load].
+pub fn unload() {
+    Net::with_local(|l: &mut Loaded| l.0 = None);
+}
+
+pub fn is_loaded() -> bool {
+    Net::with_local(|l: &mut Loaded| l.0.is_some())
+}
+
+/// [Store::remaining] for the loaded store. Zero if none is loaded.
+pub fn remaining<T: Item>(kind: Kind) -> usize {
+    Net::with_local(|l: &mut Loaded| l.0.as_ref().map(|s| s.remaining::<T>(kind)).unwrap_or(0))
+}
+
+/// [Store::take] for the loaded store. `None` if none is loaded.
+pub fn take<T: Item>(kind: Kind, n: usize) -> Option<Vec<T>> {
+    Net::with_local(|l: &mut Loaded| l.0.as_mut()?.take(kind, n))
+}
+
+/// The loaded store has a section for some material, but too little of it is left.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Exhausted {
+    pub kind: Kind,
+    pub label: String,
+    pub wanted: usize,
+    pub remaining: usize,
+}
+
+impl Display for Exhausted {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Out of preprocessed {:?} items of {}: wanted {}, {} left",
+            self.kind, self.label, self.wanted, self.remaining
+        )
+    }
+}
+
+impl std::error::Error for Exhausted {}
+
+/// [Store::take] for the loaded store, for material that must not be made online once a store
+/// provides it.
+///
+/// `Ok(None)` if no store is loaded, or if the loaded store has no section for `kind` and `T`. An
+/// error if it has one, but fewer than `n` items remain in it.
+pub fn take_stored<T: Item>(kind: Kind, n: usize) -> Result<Option<Vec<T>>, Exhausted> {
+    Net::with_local(|l: &mut Loaded| {
+        let store = match l.0.as_mut() {
+            Some(store) if store.find::<T>(kind).is_some() => store,
+            _ => return Ok(None),
+        };
+        store.take(kind, n).map(Some).ok_or_else(|| Exhausted {
+            kind,
+            label: T::label(),
+            wanted: n,
+            remaining: store.remaining::<T>(kind),
+        })
+    })
+}
+
+/// [Store::peek] for the loaded store. `None` if none is loaded.
+pub fn peek<T: Item>(kind: Kind) -> Option<T> {
+    Net::with_local(|l: &mut Loaded| l.0.as_ref()?.peek(kind))
+}
+
+#[derive(Derivative)]
+#[derivative(Default(bound = ""), Clone(bound = ""), Copy(bound = ""))]
+/// Serves triples and inverse pairs from the loaded [Store]. Panics when they run out.
+pub struct FileBeaverSource<A, B, C>(PhantomData<(A, B, C)>);
+
+pub type FileFieldTripleSource<F> = FileBeaverSource<F, F, F>;
+pub type FileGroupTripleSource<F, G> = FileBeaverSource<G, F, G>;
+
+impl<A, B, C> BeaverSource<A, B, C> for FileBeaverSource<A, B, C>
+where
+    A: Item,
+    B: Item,
+    C: Item,
+{
+    fn triple(&mut self) -> (A, B, C) {
+        take::<(A, B, C)>(Kind::Triple, 1)
+            .expect("Out of preprocessed triples")
+            .pop()
+            .unwrap()
+    }
+    fn triples(&mut self, n: usize) -> (Vec<A>, Vec<B>, Vec<C>) {
+        let mut xs = Vec::with_capacity(n);
+        let mut ys = Vec::with_capacity(n);
+        let mut zs = Vec::with_capacity(n);
+        for (x, y, z) in take::<(A, B, C)>(Kind::Triple, n).expect("Out of preprocessed triples") {
+            xs.push(x);
+            ys.push(y);
+            zs.push(z);
+        }
+        (xs, ys, zs)
+    }
+    fn inv_pair(&mut self) -> (B, B) {
+        take::<(B, B)>(Kind::InvPair, 1)
+            .expect("Out of preprocessed inverse pairs")
+            .pop()
+            .unwrap()
+    }
+    fn inv_pairs(&mut self, n: usize) -> (Vec<B>, Vec<B>) {
+        take::<(B, B)>(Kind::InvPair, n)
+            .expect("Out of preprocessed inverse pairs")
+            .into_iter()
+            .unzip()
+    }
+}
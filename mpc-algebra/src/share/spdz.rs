@@ -7,7 +7,7 @@ use ark_ff::bytes::{FromBytes, ToBytes};
 use ark_ff::prelude::*;
 use ark_serialize::{
     CanonicalDeserialize, CanonicalDeserializeWithFlags, CanonicalSerialize,
-    CanonicalSerializeWithFlags, Flags, SerializationError,
+    CanonicalSerializeWithFlags, EmptyFlags, Flags, SerializationError,
 };
 
 use std::cmp::Ord;
@@ -15,34 +15,43 @@ use std::fmt::{self, Debug, Display, Formatter};
 use std::hash::Hash;
 use std::io::{self, Read, Write};
 use std::marker::PhantomData;
+use std::sync::Arc;
 
-use mpc_net::{MpcNet, MpcMultiNet as Net};
-use crate::channel::{can_cheat, MpcSerNet};
+use mpc_net::{MpcNet, MpcMultiNet as Net, NetError, Session};
+use crate::channel::{MpcSerNet, SerNet};
+use crate::encoding;
 
 use super::add::{AdditiveFieldShare, AdditiveGroupShare, MulFieldShare};
 use super::field::{DenseOrSparsePolynomial, DensePolynomial, ExtFieldShare, FieldShare};
 use super::group::GroupShare;
 use super::msm::*;
 use super::pairing::{AffProjShare, PairingShare};
+use super::preprocessing::{field_label, group_label, Item};
 use super::{BeaverSource, PanicBeaverSource};
 use crate::Reveal;
 
+pub mod offline;
+pub use offline::{SpdzGroupTripleSource, SpdzTripleSource};
+
 #[inline]
+/// Our share of the MAC key.
+///
+/// Panics unless [offline::init] has sampled the key.
 pub fn mac_share<F: Field>() -> F {
-    if Net::am_king() {
-        F::one()
-    } else {
-        F::zero()
-    }
+    mac_share_in(&Net::session())
 }
 
-#[inline]
-/// A huge cheat. Useful for importing shares.
-pub fn mac<F: Field>() -> F {
-    if can_cheat() {
-        F::one()
-    } else {
-        panic!("Attempted to grab the MAC secret while cheating was not allowed")
+/// Our share of the MAC key, in `session`.
+pub fn mac_share_in<F: Field>(session: &Session) -> F {
+    offline::mac_key_share_in::<F>(session).expect("No SPDZ MAC key. Did you call init_protocol?")
+}
+
+/// A failed MAC check. Some party cheated, but the check cannot say which, so we abort in our own
+/// name.
+fn mac_check_failed(session: &Session) -> NetError {
+    NetError::Abort {
+        party: session.party_id(),
+        reason: "a MAC check failed on opening".to_owned(),
     }
 }
 
@@ -65,46 +74,57 @@ macro_rules! impl_basics_spdz {
             }
         }
         impl<T: $bound> ToBytes for $share<T> {
-            fn write<W: Write>(&self, _writer: W) -> io::Result<()> {
-                unimplemented!("write")
+            fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+                self.serialize(writer).map_err(encoding::to_io_error)
             }
         }
         impl<T: $bound> FromBytes for $share<T> {
-            fn read<R: Read>(_reader: R) -> io::Result<Self> {
-                unimplemented!("read")
+            fn read<R: Read>(reader: R) -> io::Result<Self> {
+                Self::deserialize(reader).map_err(encoding::to_io_error)
             }
         }
         impl<T: $bound> CanonicalSerialize for $share<T> {
-            fn serialize<W: Write>(&self, _writer: W) -> Result<(), SerializationError> {
-                unimplemented!("serialize")
+            fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+                self.serialize_with_flags(writer, EmptyFlags)
             }
             fn serialized_size(&self) -> usize {
-                unimplemented!("serialized_size")
+                self.serialized_size_with_flags::<EmptyFlags>()
             }
         }
         impl<T: $bound> CanonicalSerializeWithFlags for $share<T> {
             fn serialize_with_flags<W: Write, F: Flags>(
                 &self,
-                _writer: W,
-                _flags: F,
+                mut writer: W,
+                flags: F,
             ) -> Result<(), SerializationError> {
-                unimplemented!("serialize_with_flags")
+                encoding::write_header(&mut writer, flags)?;
+                self.sh.val.serialize(&mut writer)?;
+                self.mac.val.serialize(&mut writer)
             }
 
             fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-                unimplemented!("serialized_size_with_flags")
+                1 + self.sh.val.serialized_size() + self.mac.val.serialized_size()
             }
         }
         impl<T: $bound> CanonicalDeserialize for $share<T> {
-            fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-                unimplemented!("deserialize")
+            fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+                Self::deserialize_with_flags::<_, EmptyFlags>(reader).map(|(s, _)| s)
             }
         }
         impl<T: $bound> CanonicalDeserializeWithFlags for $share<T> {
             fn deserialize_with_flags<R: Read, F: Flags>(
-                _reader: R,
+                mut reader: R,
             ) -> Result<(Self, F), SerializationError> {
-                unimplemented!("deserialize_with_flags")
+                let flags = encoding::read_header(&mut reader)?;
+                let sh = T::deserialize(&mut reader)?;
+                let mac = T::deserialize(&mut reader)?;
+                Ok((
+                    Self {
+                        sh: Reveal::from_add_shared(sh),
+                        mac: Reveal::from_add_shared(mac),
+                    },
+                    flags,
+                ))
             }
         }
         impl<T: $bound> UniformRand for $share<T> {
@@ -116,18 +136,23 @@ macro_rules! impl_basics_spdz {
 }
 impl_basics_spdz!(SpdzFieldShare, Field);
 
+impl<F: Field> Item for SpdzFieldShare<F> {
+    fn label() -> String {
+        format!("spdz<{}>", field_label::<F>())
+    }
+}
+
 impl<F: Field> Reveal for SpdzFieldShare<F> {
     type Base = F;
 
     fn reveal(self) -> F {
-        let vals: Vec<F> = Net::broadcast(&self.sh.val);
-        // _Pragmatic MPC_ 6.6.2
-        let x: F = vals.iter().sum();
-        let dx_t: F = mac_share::<F>() * x - self.mac.val;
-        let all_dx_ts: Vec<F> = Net::atomic_broadcast(&dx_t);
-        let sum: F = all_dx_ts.iter().sum();
-        assert!(sum.is_zero());
-        x
+        self.reveal_in(&Net::session())
+    }
+    fn reveal_in(self, session: &Arc<Session>) -> F {
+        Self::try_batch_open_in(Some(self), session)
+            .unwrap_or_else(|e| e.raise())
+            .pop()
+            .unwrap()
     }
     fn from_public(f: F) -> Self {
         Self {
@@ -136,10 +161,7 @@ impl<F: Field> Reveal for SpdzFieldShare<F> {
         }
     }
     fn from_add_shared(f: F) -> Self {
-        Self {
-            sh: Reveal::from_add_shared(f),
-            mac: Reveal::from_add_shared(f * mac::<F>()),
-        }
+        offline::authenticate(&[f]).pop().unwrap()
     }
     fn king_share<R: Rng>(f: Self::Base, rng: &mut R) -> Self {
         let mut r: Vec<F> = (0..(Net::n_parties()-1)).map(|_| F::rand(rng)).collect();
@@ -158,30 +180,47 @@ impl<F: Field> Reveal for SpdzFieldShare<F> {
             f[i] - &rs.iter().map(|r| &r[i]).sum()
         }).collect();
         rs.push(final_shares);
-        Net::recv_from_king(if Net::am_king() { Some(rs) } else {None}).into_iter().map(Self::from_add_shared).collect()
+        let shares: Vec<F> = Net::recv_from_king(if Net::am_king() { Some(rs) } else {None});
+        offline::authenticate(&shares)
+    }
+    fn init_protocol() {
+        offline::init::<F>()
+    }
+    fn deinit_protocol() {
+        offline::deinit::<F>()
     }
 }
 
 impl<F: Field> FieldShare<F> for SpdzFieldShare<F> {
-    fn batch_open(selfs: impl IntoIterator<Item = Self>) -> Vec<F> {
+    type TripleSource = SpdzTripleSource<F>;
+
+    fn try_batch_open_in(
+        selfs: impl IntoIterator<Item = Self>,
+        session: &Arc<Session>,
+    ) -> Result<Vec<F>, NetError> {
+        let _op = mpc_net::op("batch_open");
         let (s_vals, macs): (Vec<F>, Vec<F>) =
             selfs.into_iter().map(|s| (s.sh.val, s.mac.val)).unzip();
         let n = s_vals.len();
-        let all_vals = Net::broadcast(&s_vals);
+        let all_vals = session.try_broadcast(&s_vals)?;
         let vals: Vec<F> =
             (0..n).map(|i| all_vals.iter().map(|v| &v[i]).sum()).collect();
+        let alpha = mac_share_in::<F>(session);
         let dx_ts: Vec<F> =
             macs
             .iter()
             .zip(vals.iter())
-            .map(|(mac, val)| mac_share::<F>() * val - mac)
+            .map(|(mac, val)| alpha * val - mac)
             .collect();
-        let all_dx_ts: Vec<Vec<F>> = Net::atomic_broadcast(&dx_ts);
+        let _check = mpc_net::op("mac_check");
+        let all_dx_ts: Vec<Vec<F>> = session.try_atomic_broadcast(&dx_ts)?;
         for i in 0..n {
             let sum: F = all_dx_ts.iter().map(|dx_ts| &dx_ts[i]).sum();
-            assert!(sum.is_zero());
+            if !sum.is_zero() {
+                return Err(mac_check_failed(session));
+            }
         }
-        vals
+        Ok(vals)
     }
     fn add(&mut self, other: &Self) -> &mut Self {
         self.sh.add(&other.sh);
@@ -256,21 +295,32 @@ pub struct SpdzGroupShare<T, M> {
     mac: AdditiveGroupShare<T, M>,
 }
 
+impl<G: Group, M> Item for SpdzGroupShare<G, M> {
+    fn label() -> String {
+        format!("spdz<{}>", group_label::<G>())
+    }
+}
+
 impl<G: Group, M> Reveal for SpdzGroupShare<G, M> {
     type Base = G;
 
     fn reveal(self) -> G {
-        let vals: Vec<G> = Net::broadcast(&self.sh.val);
-        // _Pragmatic MPC_ 6.6.2
+        self.reveal_in(&Net::session())
+    }
+    fn reveal_in(self, session: &Arc<Session>) -> G {
+        let vals: Vec<G> = session
+            .try_broadcast(&self.sh.val)
+            .unwrap_or_else(|e| e.raise());
         let x: G = vals.iter().sum();
-        let dx_t: G = {
-            let mut t = x.clone();
-            t *= mac_share::<G::ScalarField>();
-            t - self.mac.val
-        };
-        let all_dx_ts: Vec<G> = Net::atomic_broadcast(&dx_t);
-        let sum: G = all_dx_ts.iter().sum();
-        assert!(sum.is_zero());
+        let mut dx_t = x;
+        dx_t *= mac_share_in::<G::ScalarField>(session);
+        dx_t -= self.mac.val;
+        let all_dx_ts: Vec<G> = session
+            .try_atomic_broadcast(&dx_t)
+            .unwrap_or_else(|e| e.raise());
+        if !all_dx_ts.iter().sum::<G>().is_zero() {
+            mac_check_failed(session).raise();
+        }
         x
     }
     fn from_public(f: G) -> Self {
@@ -284,14 +334,7 @@ impl<G: Group, M> Reveal for SpdzGroupShare<G, M> {
         }
     }
     fn from_add_shared(f: G) -> Self {
-        Self {
-            sh: Reveal::from_add_shared(f),
-            mac: Reveal::from_add_shared({
-                let mut t = f;
-                t *= mac::<G::ScalarField>();
-                t
-            }),
-        }
+        offline::authenticate_group(&[f]).pop().unwrap()
     }
     fn king_share<R: Rng>(f: Self::Base, rng: &mut R) -> Self {
         let mut r: Vec<G> = (0..(Net::n_parties()-1)).map(|_| G::rand(rng)).collect();
@@ -310,11 +353,18 @@ impl<G: Group, M> Reveal for SpdzGroupShare<G, M> {
             f[i] - &rs.iter().map(|r| &r[i]).sum()
         }).collect();
         rs.push(final_shares);
-        Net::recv_from_king(if Net::am_king() { Some(rs) } else {None}).into_iter().map(Self::from_add_shared).collect()
+        let shares: Vec<G> = Net::recv_from_king(if Net::am_king() { Some(rs) } else {None});
+        offline::authenticate_group(&shares)
+    }
+    fn init_protocol() {
+        offline::init::<G::ScalarField>()
+    }
+    fn deinit_protocol() {
+        offline::deinit::<G::ScalarField>()
     }
 }
 macro_rules! impl_spdz_basics_2_param {
-    ($share:ident, $bound:ident) => {
+    ($share:ident, $bound:ident $(, $extra:ident)*) => {
         impl<T: $bound, M> Display for $share<T, M> {
             fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
                 write!(f, "{}", self.sh.val)
@@ -326,46 +376,58 @@ macro_rules! impl_spdz_basics_2_param {
             }
         }
         impl<T: $bound, M> ToBytes for $share<T, M> {
-            fn write<W: Write>(&self, _writer: W) -> io::Result<()> {
-                unimplemented!("write")
+            fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+                self.serialize(writer).map_err(encoding::to_io_error)
             }
         }
         impl<T: $bound, M> FromBytes for $share<T, M> {
-            fn read<R: Read>(_reader: R) -> io::Result<Self> {
-                unimplemented!("read")
+            fn read<R: Read>(reader: R) -> io::Result<Self> {
+                Self::deserialize(reader).map_err(encoding::to_io_error)
             }
         }
         impl<T: $bound, M> CanonicalSerialize for $share<T, M> {
-            fn serialize<W: Write>(&self, _writer: W) -> Result<(), SerializationError> {
-                unimplemented!("serialize")
+            fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+                self.serialize_with_flags(writer, EmptyFlags)
             }
             fn serialized_size(&self) -> usize {
-                unimplemented!("serialized_size")
+                self.serialized_size_with_flags::<EmptyFlags>()
             }
         }
         impl<T: $bound, M> CanonicalSerializeWithFlags for $share<T, M> {
             fn serialize_with_flags<W: Write, F: Flags>(
                 &self,
-                _writer: W,
-                _flags: F,
+                mut writer: W,
+                flags: F,
             ) -> Result<(), SerializationError> {
-                unimplemented!("serialize_with_flags")
+                encoding::write_header(&mut writer, flags)?;
+                self.sh.val.serialize(&mut writer)?;
+                self.mac.val.serialize(&mut writer)
             }
 
             fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-                unimplemented!("serialized_size_with_flags")
+                1 + self.sh.val.serialized_size() + self.mac.val.serialized_size()
             }
         }
         impl<T: $bound, M> CanonicalDeserialize for $share<T, M> {
-            fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-                unimplemented!("deserialize")
+            fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+                Self::deserialize_with_flags::<_, EmptyFlags>(reader).map(|(s, _)| s)
             }
         }
         impl<T: $bound, M> CanonicalDeserializeWithFlags for $share<T, M> {
             fn deserialize_with_flags<R: Read, F: Flags>(
-                _reader: R,
+                mut reader: R,
             ) -> Result<(Self, F), SerializationError> {
-                unimplemented!("deserialize_with_flags")
+                let flags = encoding::read_header(&mut reader)?;
+                let sh = T::deserialize(&mut reader)?;
+                let mac = T::deserialize(&mut reader)?;
+                Ok((
+                    Self {
+                        sh: Reveal::from_add_shared(sh),
+                        mac: Reveal::from_add_shared(mac),
+                        $($extra: Default::default(),)*
+                    },
+                    flags,
+                ))
             }
         }
         impl<T: $bound, M> UniformRand for $share<T, M> {
@@ -381,26 +443,35 @@ impl_spdz_basics_2_param!(SpdzGroupShare, Group);
 
 impl<G: Group, M: Msm<G, G::ScalarField>> GroupShare<G> for SpdzGroupShare<G, M> {
     type FieldShare = SpdzFieldShare<G::ScalarField>;
+    type TripleSource = SpdzGroupTripleSource<G, M>;
 
-    fn batch_open(selfs: impl IntoIterator<Item = Self>) -> Vec<G> {
+    fn try_batch_open_in(
+        selfs: impl IntoIterator<Item = Self>,
+        session: &Arc<Session>,
+    ) -> Result<Vec<G>, NetError> {
+        let _op = mpc_net::op("batch_open");
         let (s_vals, macs): (Vec<G>, Vec<G>) =
             selfs.into_iter().map(|s| (s.sh.val, s.mac.val)).unzip();
         let n = s_vals.len();
-        let all_vals = Net::broadcast(&s_vals);
+        let all_vals = session.try_broadcast(&s_vals)?;
         let vals: Vec<G> =
             (0..n).map(|i| all_vals.iter().map(|v| &v[i]).sum()).collect();
+        let alpha = mac_share_in::<G::ScalarField>(session);
         let dx_ts: Vec<G> =
             macs
             .iter()
             .zip(vals.iter())
-            .map(|(mac, val)| val.mul(&mac_share::<G::ScalarField>()) - mac)
+            .map(|(mac, val)| val.mul(&alpha) - mac)
             .collect();
-        let all_dx_ts: Vec<Vec<G>> = Net::atomic_broadcast(&dx_ts);
+        let _check = mpc_net::op("mac_check");
+        let all_dx_ts: Vec<Vec<G>> = session.try_atomic_broadcast(&dx_ts)?;
         for i in 0..n {
             let sum: G = all_dx_ts.iter().map(|dx_ts| &dx_ts[i]).sum();
-            assert!(sum.is_zero());
+            if !sum.is_zero() {
+                return Err(mac_check_failed(session));
+            }
         }
-        vals
+        Ok(vals)
     }
 
     fn add(&mut self, other: &Self) -> &mut Self {
@@ -439,9 +510,18 @@ impl<G: Group, M: Msm<G, G::ScalarField>> GroupShare<G> for SpdzGroupShare<G, M>
 
     fn multi_scale_pub_group(bases: &[G], scalars: &[Self::FieldShare]) -> Self {
         let shares: Vec<G::ScalarField> = scalars.into_iter().map(|s| s.sh.val.clone()).collect();
-        let macs: Vec<G::ScalarField> = scalars.into_iter().map(|s| s.sh.val.clone()).collect();
-        let sh = AdditiveGroupShare::from_add_shared(M::msm(bases, &shares));
-        let mac = AdditiveGroupShare::from_add_shared(M::msm(bases, &macs));
+        let macs: Vec<G::ScalarField> = scalars.into_iter().map(|s| s.mac.val.clone()).collect();
+        let mut msms = M::msm_many(bases, &[&shares, &macs]);
+        let mac = AdditiveGroupShare::from_add_shared(msms.pop().unwrap());
+        let sh = AdditiveGroupShare::from_add_shared(msms.pop().unwrap());
+        Self { sh, mac }
+    }
+
+    fn multi_scale_pub_scalar(bases: &[Self], scalars: &[G::ScalarField]) -> Self {
+        let (shares, macs): (Vec<G>, Vec<G>) =
+            bases.iter().map(|b| (b.sh.val, b.mac.val)).unzip();
+        let sh = AdditiveGroupShare::from_add_shared(M::msm(&shares, scalars));
+        let mac = AdditiveGroupShare::from_add_shared(M::msm(&macs, scalars));
         Self { sh, mac }
     }
 }
@@ -461,20 +541,25 @@ pub struct SpdzMulFieldShare<T, S> {
     mac: MulFieldShare<T>,
     _phants: PhantomData<S>,
 }
-impl_spdz_basics_2_param!(SpdzMulFieldShare, Field);
+impl_spdz_basics_2_param!(SpdzMulFieldShare, Field, _phants);
+
+impl<F: Field, S: PrimeField> Item for SpdzMulFieldShare<F, S> {
+    fn label() -> String {
+        format!("spdz-mul<{},{}>", field_label::<F>(), field_label::<S>())
+    }
+}
 
 impl<F: Field, S: PrimeField> Reveal for SpdzMulFieldShare<F, S> {
     type Base = F;
 
     fn reveal(self) -> F {
-        let vals: Vec<F> = Net::broadcast(&self.sh.val);
-        // _Pragmatic MPC_ 6.6.2
-        let x: F = vals.iter().product();
-        let dx_t: F = x.pow(&mac_share::<S>().into_repr()) / self.mac.val;
-        let all_dx_ts: Vec<F> = Net::atomic_broadcast(&dx_t);
-        let prod: F = all_dx_ts.iter().product();
-        assert!(prod.is_one());
-        x
+        self.reveal_in(&Net::session())
+    }
+    fn reveal_in(self, session: &Arc<Session>) -> F {
+        Self::try_batch_open_in(Some(self), session)
+            .unwrap_or_else(|e| e.raise())
+            .pop()
+            .unwrap()
     }
     fn from_public(f: F) -> Self {
         Self {
@@ -483,25 +568,50 @@ impl<F: Field, S: PrimeField> Reveal for SpdzMulFieldShare<F, S> {
             _phants: PhantomData::default(),
         }
     }
-    fn from_add_shared(f: F) -> Self {
-        Self {
-            sh: Reveal::from_add_shared(f),
-            mac: Reveal::from_add_shared(f.pow(&mac::<S>().into_repr())),
-            _phants: PhantomData::default(),
-        }
+    /// Authenticating a multiplicative share needs every party's MAC key share in the exponent,
+    /// which no party has: make these shares by pairing shared group elements instead.
+    fn from_add_shared(_f: F) -> Self {
+        unimplemented!("from_add_shared for SpdzMulFieldShare")
     }
 }
 
 impl<F: Field, S: PrimeField> FieldShare<F> for SpdzMulFieldShare<F, S> {
+    fn try_batch_open_in(
+        selfs: impl IntoIterator<Item = Self>,
+        session: &Arc<Session>,
+    ) -> Result<Vec<F>, NetError> {
+        let _op = mpc_net::op("batch_open");
+        let (s_vals, macs): (Vec<F>, Vec<F>) =
+            selfs.into_iter().map(|s| (s.sh.val, s.mac.val)).unzip();
+        let all_vals = session.try_broadcast(&s_vals)?;
+        let vals: Vec<F> = (0..s_vals.len())
+            .map(|i| all_vals.iter().map(|v| &v[i]).product())
+            .collect();
+        let alpha = mac_share_in::<S>(session).into_repr();
+        let dx_ts: Vec<F> = macs
+            .iter()
+            .zip(&vals)
+            .map(|(mac, val)| val.pow(alpha) / mac)
+            .collect();
+        let _check = mpc_net::op("mac_check");
+        let all_dx_ts: Vec<Vec<F>> = session.try_atomic_broadcast(&dx_ts)?;
+        for i in 0..vals.len() {
+            let prod: F = all_dx_ts.iter().map(|dx_ts| &dx_ts[i]).product();
+            if !prod.is_one() {
+                return Err(mac_check_failed(session));
+            }
+        }
+        Ok(vals)
+    }
+
     fn add(&mut self, _other: &Self) -> &mut Self {
         unimplemented!("add for SpdzMulFieldShare")
     }
 
     fn scale(&mut self, other: &F) -> &mut Self {
-        if Net::am_king() {
-            self.sh.scale(other);
-        }
-        self.mac.scale(&other.pow(&mac_share::<S>().into_repr()));
+        // Only the king scales its share, but every party scales its MAC share.
+        self.sh.scale(other);
+        self.mac.val *= other.pow(&mac_share::<S>().into_repr());
         self
     }
 
@@ -509,9 +619,9 @@ impl<F: Field, S: PrimeField> FieldShare<F> for SpdzMulFieldShare<F, S> {
         unimplemented!("add for SpdzMulFieldShare")
     }
 
-    fn mul<S2: BeaverSource<Self, Self, Self>>(self, other: Self, _source: &mut S2) -> Self {
-        self.sh.mul(other.sh, &mut PanicBeaverSource::default());
-        self.mac.mul(other.mac, &mut PanicBeaverSource::default());
+    fn mul<S2: BeaverSource<Self, Self, Self>>(mut self, other: Self, _source: &mut S2) -> Self {
+        self.sh = self.sh.mul(other.sh, &mut PanicBeaverSource::default());
+        self.mac = self.mac.mul(other.mac, &mut PanicBeaverSource::default());
         self
     }
 
@@ -521,8 +631,8 @@ impl<F: Field, S: PrimeField> FieldShare<F> for SpdzMulFieldShare<F, S> {
         _source: &mut S2,
     ) -> Vec<Self> {
         for (x, y) in xs.iter_mut().zip(ys.iter()) {
-            x.sh.mul(y.sh, &mut PanicBeaverSource::default());
-            x.mac.mul(y.mac, &mut PanicBeaverSource::default());
+            x.sh = x.sh.mul(y.sh, &mut PanicBeaverSource::default());
+            x.mac = x.mac.mul(y.mac, &mut PanicBeaverSource::default());
         }
         xs
     }
@@ -578,8 +688,8 @@ macro_rules! groups_share {
 
         impl<E: PairingEngine> AffProjShare<E::Fr, E::$affine, E::$proj> for $struct_name<E> {
             type FrShare = SpdzFieldShare<E::Fr>;
-            type AffineShare = SpdzGroupShare<E::$affine, AffineMsm<E::$affine>>;
-            type ProjectiveShare = SpdzGroupShare<E::$proj, ProjectiveMsm<E::$proj>>;
+            type AffineShare = SpdzGroupShare<E::$affine, ParMsm<AffineMsm<E::$affine>>>;
+            type ProjectiveShare = SpdzGroupShare<E::$proj, ParMsm<ProjectiveMsm<E::$proj>>>;
 
             fn sh_aff_to_proj(g: Self::AffineShare) -> Self::ProjectiveShare {
                 SpdzGroupShare {
@@ -640,12 +750,54 @@ impl<E: PairingEngine> PairingShare<E> for SpdzPairingShare<E> {
     type FqeShare = SpdzExtFieldShare<E::Fqe>;
     // Not a typo. We want a multiplicative subgroup.
     type FqkShare = SpdzMulExtFieldShare<E::Fqk, E::Fr>;
-    type G1AffineShare = SpdzGroupShare<E::G1Affine, AffineMsm<E::G1Affine>>;
-    type G2AffineShare = SpdzGroupShare<E::G2Affine, AffineMsm<E::G2Affine>>;
+    type G1AffineShare = SpdzGroupShare<E::G1Affine, ParMsm<AffineMsm<E::G1Affine>>>;
+    type G2AffineShare = SpdzGroupShare<E::G2Affine, ParMsm<AffineMsm<E::G2Affine>>>;
     type G1ProjectiveShare =
-        SpdzGroupShare<E::G1Projective, ProjectiveMsm<E::G1Projective>>;
+        SpdzGroupShare<E::G1Projective, ParMsm<ProjectiveMsm<E::G1Projective>>>;
     type G2ProjectiveShare =
-        SpdzGroupShare<E::G2Projective, ProjectiveMsm<E::G2Projective>>;
+        SpdzGroupShare<E::G2Projective, ParMsm<ProjectiveMsm<E::G2Projective>>>;
     type G1 = SpdzG1Share<E>;
     type G2 = SpdzG2Share<E>;
+
+    // The MAC of `e(a, b)` is `e(a, b)^alpha = e(alpha a, b)`: the loop of the MAC of `a`.
+    fn miller_loop_sh_pub(
+        a: &Self::G1AffineShare,
+        b: &E::G2Prepared,
+    ) -> SpdzMulFieldShare<E::Fqk, E::Fr> {
+        let f = |p: &E::G1Affine| MulFieldShare {
+            val: E::miller_loop(&[((*p).into(), b.clone())]),
+        };
+        SpdzMulFieldShare {
+            sh: f(&a.sh.val),
+            mac: f(&a.mac.val),
+            _phants: PhantomData::default(),
+        }
+    }
+
+    fn miller_loop_pub_sh(
+        a: &E::G1Prepared,
+        b: &Self::G2AffineShare,
+    ) -> SpdzMulFieldShare<E::Fqk, E::Fr> {
+        let f = |q: &E::G2Affine| MulFieldShare {
+            val: E::miller_loop(&[(a.clone(), (*q).into())]),
+        };
+        SpdzMulFieldShare {
+            sh: f(&b.sh.val),
+            mac: f(&b.mac.val),
+            _phants: PhantomData::default(),
+        }
+    }
+
+    fn final_exponentiation(
+        f: SpdzMulFieldShare<E::Fqk, E::Fr>,
+    ) -> SpdzMulFieldShare<E::Fqk, E::Fr> {
+        let f_exp = |s: MulFieldShare<E::Fqk>| MulFieldShare {
+            val: E::final_exponentiation(&s.val).expect("Zero Miller loop share"),
+        };
+        SpdzMulFieldShare {
+            sh: f_exp(f.sh),
+            mac: f_exp(f.mac),
+            _phants: PhantomData::default(),
+        }
+    }
 }
@@ -0,0 +1,499 @@
+//! The SPDZ offline phase.
+//!
+//! Produces authenticated Beaver triples and inverse pairs for [SpdzFieldShare], under a MAC key
+//! that is sampled jointly and never reconstructed. Products of additive shares are computed
+//! pairwise with Gilboa multiplication over OT ([crate::ot]), following MASCOT (Keller, Orsini,
+//! Scholl '16):
+//!
+//! * Every party's MAC key share is the composition of random bits against a public gadget, so it
+//!   can serve as an OT receiver input. Values are authenticated by multiplying them against every
+//!   key share, and the result is checked by opening a masked random combination.
+//! * Triples are produced in pairs, and one triple of each pair is sacrificed to check the other.
+//!
+//! [init] must run for a field before any SPDZ share over it is made: until then there is no MAC
+//! key, and [SpdzTripleSource] refuses to serve triples.
+//!
+//! The offline phase can also be run ahead of time: write the key ([save_key]) and material to a
+//! [Store], and [load](preprocessing::load) it before [init] in the online phase. Loaded material
+//! is used before any is generated.
+use derivative::Derivative;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use ark_ec::group::Group;
+use ark_ff::prelude::*;
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use super::*;
+use crate::ot;
+use crate::share::preprocessing::{self, field_label, Item, Kind, Store};
+
+/// Statistical security parameter, in bits.
+const STAT_SEC: usize = 40;
+
+/// How many triples (or inverse pairs) to generate when the pool runs dry.
+pub const BATCH_SIZE: usize = 256;
+
+/// The most items to generate in one run of the offline phase. Its OTs hold a field element per MAC
+/// key bit per item, so larger requests are generated this many at a time.
+const MAX_CHUNK: usize = 1 << 10;
+
+type Triple<F> = (SpdzFieldShare<F>, SpdzFieldShare<F>, SpdzFieldShare<F>);
+type InvPair<F> = (SpdzFieldShare<F>, SpdzFieldShare<F>);
+type GroupTriple<G, M> = (
+    SpdzGroupShare<G, M>,
+    SpdzFieldShare<<G as Group>::ScalarField>,
+    SpdzGroupShare<G, M>,
+);
+
+/// Our share of the global MAC key for one field.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+struct MacKey<F: Field> {
+    share: F,
+    bits: Vec<bool>,
+}
+
+impl<F: Field> Item for MacKey<F> {
+    fn label() -> String {
+        format!("spdz-mac-key<{}>", field_label::<F>())
+    }
+}
+
+/// Our MAC key shares, by field. Kept per [session](mpc_net::Session).
+#[derive(Default)]
+struct MacKeys(HashMap<TypeId, Box<dyn Any + Send>>);
+
+/// Unused material, by item type, along with the field whose MAC key it is under. Kept per
+/// [session](mpc_net::Session).
+#[derive(Default)]
+struct Pools(HashMap<TypeId, (TypeId, Box<dyn Any + Send>)>);
+
+/// `2^i * e_j` for each base prime field coordinate `j`, with enough `i`s that uniform bits compose
+/// to a statistically uniform element.
+fn gadget<F: Field>() -> Vec<F> {
+    let deg = F::extension_degree() as usize;
+    let l = <F::BasePrimeField as PrimeField>::size_in_bits() + STAT_SEC;
+    let mut out = Vec::with_capacity(deg * l);
+    for j in 0..deg {
+        let mut p = F::BasePrimeField::one();
+        for _ in 0..l {
+            let mut coords = vec![F::BasePrimeField::zero(); deg];
+            coords[j] = p;
+            out.push(F::from_base_prime_field_elems(&coords).unwrap());
+            p.double_in_place();
+        }
+    }
+    out
+}
+
+fn compose<F: Field>(bits: &[bool], gadget: &[F]) -> F {
+    bits.iter()
+        .zip(gadget)
+        .filter(|(b, _)| **b)
+        .map(|(_, g)| *g)
+        .sum()
+}
+
+/// `n` public random field elements, from a jointly chosen seed.
+fn coins<F: Field>(n: usize) -> Vec<F> {
+    let seed: [u8; 32] = rand::thread_rng().gen();
+    let seeds: Vec<[u8; 32]> = Net::atomic_broadcast(&seed.to_vec())
+        .into_iter()
+        .map(|s| {
+            let mut t = [0u8; 32];
+            t.copy_from_slice(&s);
+            t
+        })
+        .collect();
+    let mut joint = [0u8; 32];
+    for s in &seeds {
+        for (j, b) in joint.iter_mut().zip(s) {
+            *j ^= b;
+        }
+    }
+    let rng = &mut StdRng::from_seed(joint);
+    (0..n).map(|_| F::rand(rng)).collect()
+}
+
+/// Whether [init] has run for `F`.
+pub fn is_init<F: Field>() -> bool {
+    mac_key_share::<F>().is_some()
+}
+
+/// Panics unless [init] has run for `F`.
+pub(crate) fn require_init<F: Field>() {
+    assert!(is_init::<F>(), "No SPDZ MAC key. Did you call init_protocol?");
+}
+
+/// Our share of the MAC key for `F`, if one has been sampled.
+pub fn mac_key_share<F: Field>() -> Option<F> {
+    mac_key_share_in(&Net::session())
+}
+
+/// Our share of the MAC key for `F` in `session`, if one has been sampled.
+pub fn mac_key_share_in<F: Field>(session: &Session) -> Option<F> {
+    session.local(|keys: &mut MacKeys| {
+        keys.0
+            .get(&TypeId::of::<F>())
+            .map(|k| k.downcast_ref::<MacKey<F>>().unwrap().share)
+    })
+}
+
+fn mac_key_bits<F: Field>() -> Vec<bool> {
+    Net::with_local(|keys: &mut MacKeys| {
+        keys.0
+            .get(&TypeId::of::<F>())
+            .map(|k| k.downcast_ref::<MacKey<F>>().unwrap().bits.clone())
+    })
+    .expect("No SPDZ MAC key. Did you call init_protocol?")
+}
+
+/// Sample a MAC key for `F` (each party picks its share), setting up OT first if needed.
+///
+/// If a loaded [Store] holds a key for `F`, that key is used instead, so that the store's material
+/// is valid.
+///
+/// Every party must call this at the same point in the protocol. Shares created before this call
+/// are authenticated under the old key, and will fail MAC checks afterwards.
+pub fn init<F: Field>() {
+    ot::init();
+    if is_init::<F>() {
+        return;
+    }
+    let key = preprocessing::peek::<MacKey<F>>(Kind::Key).unwrap_or_else(|| {
+        let g = gadget::<F>();
+        let rng = &mut rand::thread_rng();
+        let bits: Vec<bool> = (0..g.len()).map(|_| rng.gen()).collect();
+        MacKey {
+            share: compose(&bits, &g),
+            bits,
+        }
+    });
+    Net::with_local(|keys: &mut MacKeys| keys.0.insert(TypeId::of::<F>(), Box::new(key)));
+}
+
+/// Forget the MAC key and any unused preprocessing for `F`.
+pub fn deinit<F: Field>() {
+    let no_keys = Net::with_local(|keys: &mut MacKeys| {
+        keys.0.remove(&TypeId::of::<F>());
+        keys.0.is_empty()
+    });
+    Net::with_local(|pools: &mut Pools| {
+        pools.0.retain(|_, (field, _)| *field != TypeId::of::<F>())
+    });
+    if no_keys {
+        ot::deinit();
+    }
+}
+
+/// Add our MAC key share for `F` to `store`, so that [init] can restore it.
+pub fn save_key<F: Field>(store: &mut Store) {
+    let share = mac_key_share::<F>().expect("No SPDZ MAC key. Did you call init_protocol?");
+    let bits = mac_key_bits::<F>();
+    store.push(Kind::Key, &[MacKey { share, bits }]);
+}
+
+/// Attach MACs to additive shares, `xs`. MASCOT `Π_[[·]]`.
+///
+/// Each party's MAC shares come from multiplying its value shares against every party's key share.
+/// A random combination of the results, masked by one extra random value, is then opened with a
+/// MAC check, which catches parties that used inconsistent inputs.
+pub fn authenticate<F: Field>(xs: &[F]) -> Vec<SpdzFieldShare<F>> {
+    let mut vals = xs.to_vec();
+    vals.push(F::rand(&mut rand::thread_rng()));
+    let macs = authenticate_raw::<F, F>(&vals, |x, k| *x * k);
+    let mut shares: Vec<SpdzFieldShare<F>> = vals
+        .into_iter()
+        .zip(macs)
+        .map(|(sh, mac)| SpdzFieldShare {
+            sh: AdditiveFieldShare { val: sh },
+            mac: AdditiveFieldShare { val: mac },
+        })
+        .collect();
+    let mut check = shares.pop().unwrap();
+    for (s, chi) in shares.iter().zip(coins::<F>(shares.len())) {
+        let mut t = *s;
+        check.add(t.scale(&chi));
+    }
+    check.open();
+    shares
+}
+
+/// Like [authenticate], but for group elements, under the MAC key of the scalar field.
+pub fn authenticate_group<G: Group, M>(xs: &[G]) -> Vec<SpdzGroupShare<G, M>> {
+    let mut vals = xs.to_vec();
+    vals.push(G::rand(&mut rand::thread_rng()));
+    let macs = authenticate_raw::<G, G::ScalarField>(&vals, |x, k| x.mul(k));
+    let mut shares: Vec<SpdzGroupShare<G, M>> = vals
+        .into_iter()
+        .zip(macs)
+        .map(|(sh, mac)| SpdzGroupShare {
+            sh: Reveal::from_add_shared(sh),
+            mac: Reveal::from_add_shared(mac),
+        })
+        .collect();
+    let mut check = shares.pop().unwrap();
+    for (s, chi) in shares.iter().zip(coins::<G::ScalarField>(shares.len())) {
+        check.sh.val += s.sh.val.mul(&chi);
+        check.mac.val += s.mac.val.mul(&chi);
+    }
+    check.reveal();
+    shares
+}
+
+/// Our MAC shares for value shares `xs`, where `scale` is the action of the key's field on `T`.
+fn authenticate_raw<T, F: Field>(xs: &[T], scale: impl Fn(&T, &F) -> T) -> Vec<T>
+where
+    T: Copy
+        + Zero
+        + std::ops::Add<Output = T>
+        + std::ops::Sub<Output = T>
+        + UniformRand
+        + CanonicalSerialize
+        + CanonicalDeserialize,
+{
+    let key = mac_key_share::<F>().expect("No SPDZ MAC key. Did you call init_protocol?");
+    let key_bits = mac_key_bits::<F>();
+    let bits: Vec<bool> = xs.iter().flat_map(|_| key_bits.iter().cloned()).collect();
+    let cross = ot::gilboa(xs, &bits, &gadget::<F>(), &scale);
+    xs.iter()
+        .zip(cross)
+        .map(|(x, c)| scale(x, &key) + c)
+        .collect()
+}
+
+/// Generate `n` authenticated triples, checking each against a sacrificed one.
+pub fn triples<F: Field>(n: usize) -> Vec<Triple<F>> {
+    let rng = &mut rand::thread_rng();
+    let g = gadget::<F>();
+    let m = 2 * n;
+    let a: Vec<F> = (0..m).map(|_| F::rand(rng)).collect();
+    let b_bits: Vec<bool> = (0..m * g.len()).map(|_| rng.gen()).collect();
+    let b: Vec<F> = b_bits
+        .chunks(g.len())
+        .map(|bits| compose(bits, &g))
+        .collect();
+    let cross = ot::gilboa(&a, &b_bits, &g, |x, k| *x * k);
+    let c: Vec<F> = a
+        .iter()
+        .zip(&b)
+        .zip(cross)
+        .map(|((a, b), c)| *a * b + c)
+        .collect();
+
+    let mut a = authenticate(&[a, b, c].concat());
+    let mut c = a.split_off(2 * m);
+    let mut b = a.split_off(m);
+    let (f, g, h) = (a.split_off(n), b.split_off(n), c.split_off(n));
+
+    // Sacrifice: with t random, open rho = ta - f and sigma = b - g, then check that
+    // tc - h - sigma f - rho g - sigma rho opens to zero.
+    let t = coins::<F>(1)[0];
+    let rhos = a.iter().zip(&f).map(|(a, f)| *a.clone().scale(&t).sub(f));
+    let sigmas = b.iter().zip(&g).map(|(b, g)| *b.clone().sub(g));
+    let opened = SpdzFieldShare::batch_open(rhos.chain(sigmas).collect::<Vec<_>>());
+    let (rhos, sigmas) = opened.split_at(n);
+    let zs: Vec<SpdzFieldShare<F>> = (0..n)
+        .map(|i| {
+            *c[i]
+                .clone()
+                .scale(&t)
+                .sub(&h[i])
+                .sub(f[i].clone().scale(&sigmas[i]))
+                .sub(g[i].clone().scale(&rhos[i]))
+                .shift(&-(sigmas[i] * rhos[i]))
+        })
+        .collect();
+    for z in SpdzFieldShare::batch_open(zs) {
+        assert!(z.is_zero(), "SPDZ triple sacrifice failed");
+    }
+    a.into_iter()
+        .zip(b)
+        .zip(c)
+        .map(|((a, b), c)| (a, b, c))
+        .collect()
+}
+
+/// Generate `n` authenticated pairs `(r, 1/r)`.
+///
+/// Each comes from a triple `(a, b, ab)`: we open `ab` and output `(a, b / ab)`.
+pub fn inv_pairs<F: Field>(n: usize) -> Vec<InvPair<F>> {
+    let mut out = Vec::with_capacity(n);
+    while out.len() < n {
+        let ts = triples::<F>(n - out.len());
+        let cs = SpdzFieldShare::batch_open(ts.iter().map(|t| t.2).collect::<Vec<_>>());
+        for ((a, mut b, _), c) in ts.into_iter().zip(cs) {
+            if let Some(c_inv) = c.inverse() {
+                b.scale(&c_inv);
+                out.push((a, b));
+            }
+        }
+    }
+    out
+}
+
+/// Generate `n` authenticated triples `(x, y, xy)` for scaling a group element by a scalar.
+///
+/// Each comes from a field triple `(a, b, ab)`, as `(aP, b, abP)` for a fixed public base `P`. So
+/// `x` is uniform, and nobody knows its discrete log.
+pub fn group_triples<G: Group, M: Msm<G, G::ScalarField>>(n: usize) -> Vec<GroupTriple<G, M>> {
+    let base = G::rand(&mut StdRng::from_seed(*b"mpc-algebra spdz group triples\0\0"));
+    triples::<G::ScalarField>(n)
+        .into_iter()
+        .map(|(a, b, c)| {
+            (
+                SpdzGroupShare::scale_pub_group(base, &a),
+                b,
+                SpdzGroupShare::scale_pub_group(base, &c),
+            )
+        })
+        .collect()
+}
+
+fn with_pool<F: Field, T: Send + 'static, O>(f: impl FnOnce(&mut Vec<T>) -> O) -> O {
+    Net::with_local(|pools: &mut Pools| {
+        let (_, pool) = pools
+            .0
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| (TypeId::of::<F>(), Box::new(Vec::<T>::new())));
+        f(pool.downcast_mut().unwrap())
+    })
+}
+
+/// Take `n` items under the MAC key for `F`, refilling the pool first if needed: from the loaded
+/// [Store] if it has enough, and otherwise with `generate`.
+fn take<F: Field, T: Item + Send + 'static>(
+    kind: Kind,
+    n: usize,
+    mut generate: impl FnMut(usize) -> Vec<T>,
+) -> Vec<T> {
+    let have = with_pool::<F, T, _>(|p| p.len());
+    if have < n {
+        let batch = std::cmp::max(BATCH_SIZE, n - have);
+        let stored = preprocessing::remaining::<T>(kind);
+        let new = if stored >= n - have {
+            preprocessing::take::<T>(kind, std::cmp::min(batch, stored)).unwrap()
+        } else {
+            let mut new = Vec::with_capacity(batch);
+            while new.len() < batch {
+                new.extend(generate(std::cmp::min(MAX_CHUNK, batch - new.len())));
+            }
+            new
+        };
+        with_pool::<F, T, _>(|p| p.extend(new));
+    }
+    with_pool::<F, T, _>(|p| {
+        let at = p.len() - n;
+        p.split_off(at)
+    })
+}
+
+fn take_triples<F: Field>(n: usize) -> Vec<Triple<F>> {
+    take::<F, _>(Kind::Triple, n, triples::<F>)
+}
+
+fn take_inv_pairs<F: Field>(n: usize) -> Vec<InvPair<F>> {
+    take::<F, _>(Kind::InvPair, n, inv_pairs::<F>)
+}
+
+fn take_group_triples<G: Group, M: Msm<G, G::ScalarField>>(n: usize) -> Vec<GroupTriple<G, M>> {
+    take::<G::ScalarField, _>(Kind::Triple, n, group_triples::<G, M>)
+}
+
+/// Serves triples and inverse pairs from the offline phase.
+///
+/// Clones share one pool per session, so material is never handed out twice. The pool is refilled
+/// [BATCH_SIZE] at a time, from the loaded [Store] or by running the offline phase; every party
+/// does this at the same point, since all parties consume material identically.
+#[derive(Derivative)]
+#[derivative(Default(bound = ""), Clone(bound = ""), Copy(bound = ""))]
+pub struct SpdzTripleSource<F> {
+    _field: PhantomData<F>,
+}
+
+impl<F: Field> BeaverSource<SpdzFieldShare<F>, SpdzFieldShare<F>, SpdzFieldShare<F>>
+    for SpdzTripleSource<F>
+{
+    fn triple(&mut self) -> Triple<F> {
+        require_init::<F>();
+        take_triples::<F>(1).pop().unwrap()
+    }
+    fn triples(
+        &mut self,
+        n: usize,
+    ) -> (
+        Vec<SpdzFieldShare<F>>,
+        Vec<SpdzFieldShare<F>>,
+        Vec<SpdzFieldShare<F>>,
+    ) {
+        require_init::<F>();
+        let mut xs = Vec::with_capacity(n);
+        let mut ys = Vec::with_capacity(n);
+        let mut zs = Vec::with_capacity(n);
+        for (x, y, z) in take_triples::<F>(n) {
+            xs.push(x);
+            ys.push(y);
+            zs.push(z);
+        }
+        (xs, ys, zs)
+    }
+    fn inv_pair(&mut self) -> InvPair<F> {
+        require_init::<F>();
+        take_inv_pairs::<F>(1).pop().unwrap()
+    }
+    fn inv_pairs(&mut self, n: usize) -> (Vec<SpdzFieldShare<F>>, Vec<SpdzFieldShare<F>>) {
+        require_init::<F>();
+        take_inv_pairs::<F>(n).into_iter().unzip()
+    }
+}
+
+/// Serves triples for scaling shared group elements by shared scalars, like [SpdzTripleSource].
+#[derive(Derivative)]
+#[derivative(Default(bound = ""), Clone(bound = ""), Copy(bound = ""))]
+pub struct SpdzGroupTripleSource<G, M> {
+    _group: PhantomData<(G, M)>,
+}
+
+impl<G: Group, M: Msm<G, G::ScalarField>>
+    BeaverSource<SpdzGroupShare<G, M>, SpdzFieldShare<G::ScalarField>, SpdzGroupShare<G, M>>
+    for SpdzGroupTripleSource<G, M>
+{
+    fn triple(&mut self) -> GroupTriple<G, M> {
+        require_init::<G::ScalarField>();
+        take_group_triples::<G, M>(1).pop().unwrap()
+    }
+    fn triples(
+        &mut self,
+        n: usize,
+    ) -> (
+        Vec<SpdzGroupShare<G, M>>,
+        Vec<SpdzFieldShare<G::ScalarField>>,
+        Vec<SpdzGroupShare<G, M>>,
+    ) {
+        require_init::<G::ScalarField>();
+        let mut xs = Vec::with_capacity(n);
+        let mut ys = Vec::with_capacity(n);
+        let mut zs = Vec::with_capacity(n);
+        for (x, y, z) in take_group_triples::<G, M>(n) {
+            xs.push(x);
+            ys.push(y);
+            zs.push(z);
+        }
+        (xs, ys, zs)
+    }
+    fn inv_pair(&mut self) -> InvPair<G::ScalarField> {
+        SpdzTripleSource::<G::ScalarField>::default().inv_pair()
+    }
+    fn inv_pairs(
+        &mut self,
+        n: usize,
+    ) -> (
+        Vec<SpdzFieldShare<G::ScalarField>>,
+        Vec<SpdzFieldShare<G::ScalarField>>,
+    ) {
+        SpdzTripleSource::<G::ScalarField>::default().inv_pairs(n)
+    }
+}
@@ -0,0 +1,170 @@
+//! Threshold ECDSA and Schnorr signatures.
+//!
+//! The secret key and every nonce are shared. The nonce point `R = g^k` is opened as a whole; no
+//! party ever learns anything about it from its own share. Signatures are ordinary signatures, and
+//! are checked with the plain curve arithmetic.
+use ark_ec::group::Group;
+use ark_ec::{short_weierstrass_jacobian, twisted_edwards_extended, ProjectiveCurve};
+use ark_ec::{ModelParameters, SWModelParameters, TEModelParameters};
+use ark_ff::{to_bytes, Field, FpParameters, PrimeField, UniformRand, Zero};
+use ark_serialize::CanonicalSerialize;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+use crate::share::group::GroupShare;
+use crate::wire::{field::MpcField, group::MpcGroup};
+use crate::Reveal;
+
+/// Affine points with an x-coordinate, as ECDSA needs.
+pub trait XCoordinate {
+    type BaseField: Field;
+    fn x(&self) -> &Self::BaseField;
+}
+
+impl<P: SWModelParameters> XCoordinate for short_weierstrass_jacobian::GroupAffine<P> {
+    type BaseField = <P as ModelParameters>::BaseField;
+    fn x(&self) -> &Self::BaseField {
+        &self.x
+    }
+}
+
+impl<P: TEModelParameters> XCoordinate for twisted_edwards_extended::GroupAffine<P> {
+    type BaseField = <P as ModelParameters>::BaseField;
+    fn x(&self) -> &Self::BaseField {
+        &self.x
+    }
+}
+
+/// The ECDSA `r` of a point: its x-coordinate, read as an integer and reduced mod the group order.
+pub fn x_to_scalar<G: ProjectiveCurve>(p: &G) -> G::ScalarField
+where
+    G::Affine: XCoordinate,
+{
+    let bytes = to_bytes![p.into_affine().x()].unwrap();
+    G::ScalarField::from_le_bytes_mod_order(&bytes)
+}
+
+/// SHA-256 of `msg`, as a scalar: the leftmost bits, as many as the group order has, big-endian.
+pub fn hash_to_scalar<F: PrimeField>(msg: &[u8]) -> F {
+    let mut digest = Sha256::digest(msg).to_vec();
+    let bits = F::Params::MODULUS_BITS as usize;
+    if bits < 8 * digest.len() {
+        let shift = 8 * digest.len() - bits;
+        digest.truncate(digest.len() - shift / 8);
+        if shift % 8 != 0 {
+            for i in (0..digest.len()).rev() {
+                digest[i] >>= shift % 8;
+                if i > 0 {
+                    digest[i] |= digest[i - 1] << (8 - shift % 8);
+                }
+            }
+        }
+    }
+    F::from_be_bytes_mod_order(&digest)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EcdsaSignature<F> {
+    pub r: F,
+    pub s: F,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SchnorrSignature<G: ProjectiveCurve> {
+    pub r: G,
+    pub s: G::ScalarField,
+}
+
+/// A key pair: the secret is shared, the public key is known to all parties.
+#[derive(Clone, Copy, Debug)]
+pub struct ThresholdKey<G: ProjectiveCurve, S: GroupShare<G>> {
+    pub secret: MpcField<G::ScalarField, S::FieldShare>,
+    pub public: G,
+}
+
+impl<G: ProjectiveCurve, S: GroupShare<G>> ThresholdKey<G, S> {
+    /// A fresh key, with a secret that no party chose.
+    pub fn rand<R: Rng>(rng: &mut R) -> Self {
+        Self::from_secret(MpcField::rand(rng))
+    }
+
+    /// The key for an already-shared secret, e.g. one that the prover also uses. Opens `g^x`.
+    pub fn from_secret(secret: MpcField<G::ScalarField, S::FieldShare>) -> Self {
+        let public =
+            (MpcGroup::<G, S>::from_public(G::prime_subgroup_generator()) * secret).reveal();
+        Self { secret, public }
+    }
+
+    /// A shared nonce `k`, and the opened `R = g^k`.
+    fn nonce<R: Rng>(rng: &mut R) -> (MpcField<G::ScalarField, S::FieldShare>, G) {
+        let k = MpcField::rand(rng);
+        let r = (MpcGroup::<G, S>::from_public(G::prime_subgroup_generator()) * k).reveal();
+        (k, r)
+    }
+
+    /// Signs `msg`: `s = k^-1 (e + x r)`, with one shared inverse and one shared product.
+    pub fn ecdsa_sign<R: Rng>(&self, msg: &[u8], rng: &mut R) -> EcdsaSignature<G::ScalarField>
+    where
+        G::Affine: XCoordinate,
+    {
+        let _op = mpc_net::op("ecdsa_sign");
+        let e = MpcField::from_public(hash_to_scalar::<G::ScalarField>(msg));
+        loop {
+            let (k, big_r) = Self::nonce(rng);
+            let r = x_to_scalar(&big_r);
+            if r.is_zero() {
+                continue;
+            }
+            let k_inv = k.inv().expect("zero nonce");
+            let s = (k_inv * (self.secret * MpcField::from_public(r) + e)).reveal();
+            if !s.is_zero() {
+                return EcdsaSignature { r, s };
+            }
+        }
+    }
+
+    /// Signs `msg`: `s = k + e x`, for `e = H(R || X || msg)`. Only the opening of `s` is interactive.
+    pub fn schnorr_sign<R: Rng>(&self, msg: &[u8], rng: &mut R) -> SchnorrSignature<G> {
+        let _op = mpc_net::op("schnorr_sign");
+        let (k, r) = Self::nonce(rng);
+        let e = schnorr_challenge(&r, &self.public, msg);
+        let s = (k + self.secret * MpcField::from_public(e)).reveal();
+        SchnorrSignature { r, s }
+    }
+}
+
+pub fn ecdsa_verify<G: ProjectiveCurve>(
+    public: &G,
+    msg: &[u8],
+    sig: &EcdsaSignature<G::ScalarField>,
+) -> bool
+where
+    G::Affine: XCoordinate,
+{
+    let s_inv = match sig.s.inverse() {
+        Some(s_inv) if !sig.r.is_zero() => s_inv,
+        _ => return false,
+    };
+    let u1 = hash_to_scalar::<G::ScalarField>(msg) * s_inv;
+    let u2 = sig.r * s_inv;
+    let p = Group::mul(&G::prime_subgroup_generator(), &u1) + Group::mul(public, &u2);
+    !p.is_zero() && x_to_scalar(&p) == sig.r
+}
+
+pub fn schnorr_verify<G: ProjectiveCurve>(
+    public: &G,
+    msg: &[u8],
+    sig: &SchnorrSignature<G>,
+) -> bool {
+    let e = schnorr_challenge(&sig.r, public, msg);
+    Group::mul(&G::prime_subgroup_generator(), &sig.s) == sig.r + Group::mul(public, &e)
+}
+
+/// `H(R || X || msg)`, with the points compressed.
+fn schnorr_challenge<G: ProjectiveCurve>(r: &G, public: &G, msg: &[u8]) -> G::ScalarField {
+    let mut bytes = Vec::new();
+    r.into_affine().serialize(&mut bytes).unwrap();
+    public.into_affine().serialize(&mut bytes).unwrap();
+    bytes.extend_from_slice(msg);
+    hash_to_scalar(&bytes)
+}
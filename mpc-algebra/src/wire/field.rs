@@ -5,7 +5,7 @@ use zeroize::Zeroize;
 
 use ark_ff::bytes::{FromBytes, ToBytes};
 use ark_ff::prelude::*;
-use ark_ff::{poly_stub, FftField};
+use ark_ff::{poly_stub, FftField, FftParameters, LegendreSymbol};
 use ark_serialize::{
     CanonicalDeserialize, CanonicalDeserializeWithFlags, CanonicalSerialize,
     CanonicalSerializeWithFlags, Flags, SerializationError,
@@ -17,11 +17,12 @@ use std::io::{self, Read, Write};
 use std::iter::{Product, Sum};
 use std::marker::PhantomData;
 use std::ops::*;
+use std::sync::Arc;
 
 use super::super::share::field::FieldShare;
 use super::super::share::BeaverSource;
 use crate::Reveal;
-use mpc_net::{MpcNet, MpcMultiNet as Net};
+use mpc_net::{MpcMultiNet as Net, MpcNet, Session};
 
 #[derive(Clone, Copy, Hash, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum MpcField<F: Field, S: FieldShare<F>> {
@@ -31,6 +32,42 @@ pub enum MpcField<F: Field, S: FieldShare<F>> {
 
 impl_basics_2!(FieldShare, Field, MpcField);
 
+impl<T: Field, S: FieldShare<T>> CanonicalSerializeWithFlags for MpcField<T, S> {
+    fn serialize_with_flags<W: Write, F: Flags>(
+        &self,
+        mut writer: W,
+        flags: F,
+    ) -> Result<(), SerializationError> {
+        crate::encoding::write_state(&mut writer, self.is_shared())?;
+        match self {
+            Self::Public(v) => v.serialize_with_flags(writer, flags),
+            Self::Shared(s) => s.serialize_with_flags(writer, flags),
+        }
+    }
+
+    fn serialized_size_with_flags<F: Flags>(&self) -> usize {
+        crate::encoding::state_size()
+            + match self {
+                Self::Public(v) => v.serialized_size_with_flags::<F>(),
+                Self::Shared(s) => s.serialized_size_with_flags::<F>(),
+            }
+    }
+}
+
+impl<T: Field, S: FieldShare<T>> CanonicalDeserializeWithFlags for MpcField<T, S> {
+    fn deserialize_with_flags<R: Read, F: Flags>(
+        mut reader: R,
+    ) -> Result<(Self, F), SerializationError> {
+        if crate::encoding::read_state(&mut reader)? {
+            let (s, flags) = S::deserialize_with_flags(reader)?;
+            Ok((Self::Shared(s), flags))
+        } else {
+            let (v, flags) = T::deserialize_with_flags(reader)?;
+            Ok((Self::Public(v), flags))
+        }
+    }
+}
+
 #[derive(Derivative)]
 #[derivative(Default(bound = ""), Clone(bound = ""), Copy(bound = ""))]
 pub struct DummyFieldTripleSource<T, S> {
@@ -82,7 +119,7 @@ impl<T: Field, S: FieldShare<T>> MpcField<T, S> {
         match self {
             Self::Public(x) => x.inverse().map(MpcField::Public),
             Self::Shared(x) => Some(MpcField::Shared(
-                x.inv(&mut DummyFieldTripleSource::default()),
+                x.inv(&mut S::TripleSource::default()),
             )),
         }
     }
@@ -125,7 +162,7 @@ impl<'a, T: Field, S: FieldShare<T>> MulAssign<&'a MpcField<T, S>> for MpcField<
                     x.scale(y);
                 }
                 MpcField::Shared(y) => {
-                    let t = x.mul(*y, &mut DummyFieldTripleSource::default());
+                    let t = x.mul(*y, &mut S::TripleSource::default());
                     *self = MpcField::Shared(t);
                 }
             },
@@ -162,7 +199,7 @@ impl<'a, T: Field, S: FieldShare<T>> DivAssign<&'a MpcField<T, S>> for MpcField<
                     *x /= y;
                 }
                 MpcField::Shared(y) => {
-                    let mut t = y.inv(&mut DummyFieldTripleSource::default());
+                    let mut t = y.inv(&mut S::TripleSource::default());
                     t.scale(&x);
                     *self = MpcField::Shared(t);
                 }
@@ -172,7 +209,7 @@ impl<'a, T: Field, S: FieldShare<T>> DivAssign<&'a MpcField<T, S>> for MpcField<
                     x.scale(&y.inverse().unwrap());
                 }
                 MpcField::Shared(y) => {
-                    let src = &mut DummyFieldTripleSource::default();
+                    let src = &mut S::TripleSource::default();
                     *x = x.div(*y, src);
                 }
             },
@@ -236,6 +273,22 @@ impl<T: Field, S: FieldShare<T>> MpcWire for MpcField<T, S> {
             true
         })
     }
+    fn batch_publicize(selfs: &mut [Self]) {
+        let shares: Vec<S> = selfs
+            .iter()
+            .filter_map(|s| match s {
+                MpcField::Shared(s) => Some(s.clone()),
+                MpcField::Public(_) => None,
+            })
+            .collect();
+        if shares.is_empty() {
+            return;
+        }
+        let mut opened = S::batch_open(shares).into_iter();
+        for s in selfs.iter_mut().filter(|s| s.is_shared()) {
+            *s = MpcField::Public(opened.next().unwrap());
+        }
+    }
     #[inline]
     fn is_shared(&self) -> bool {
         match self {
@@ -257,6 +310,15 @@ impl<T: Field, S: FieldShare<T>> Reveal for MpcField<T, S> {
         result
     }
     #[inline]
+    fn reveal_in(self, session: &Arc<Session>) -> Self::Base {
+        let result = match self {
+            Self::Shared(s) => s.reveal_in(session),
+            Self::Public(s) => s,
+        };
+        super::macros::check_eq_in(result, session);
+        result
+    }
+    #[inline]
     fn from_public(b: Self::Base) -> Self {
         MpcField::Public(b)
     }
@@ -305,6 +367,10 @@ impl<T: PrimeField, S: FieldShare<T>> std::str::FromStr for MpcField<T, S> {
 impl<F: PrimeField, S: FieldShare<F>> Field for MpcField<F, S> {
     type BasePrimeField = Self;
     #[inline]
+    fn characteristic<'a>() -> &'a [u64] {
+        F::characteristic()
+    }
+    #[inline]
     fn extension_degree() -> u64 {
         unimplemented!("extension_degree")
     }
@@ -350,6 +416,20 @@ impl<F: PrimeField, S: FieldShare<F>> Field for MpcField<F, S> {
             self
         })
     }
+    /// For a shared value, `1 / (x + z) - z`, where `z = [x = 0]` comes from the bits of `x`
+    /// ([bits::is_zero](crate::bits::is_zero)). `x + z` is never zero, so inverting it by opening
+    /// a random multiple of it reveals nothing, and neither does anything else: this costs a bit
+    /// decomposition.
+    fn inverse_or_zero(&self) -> Self {
+        match self {
+            Self::Public(x) => Self::Public(x.inverse_or_zero()),
+            Self::Shared(_) => {
+                let _op = mpc_net::op("inverse_or_zero");
+                let z = crate::bits::is_zero(*self);
+                (*self + z).inv().unwrap() - z
+            }
+        }
+    }
     #[inline]
     fn frobenius_map(&mut self, _: usize) {
         unimplemented!("frobenius_map")
@@ -381,12 +461,12 @@ impl<F: PrimeField, S: FieldShare<F>> Field for MpcField<F, S> {
                     Self::Public(_) => unreachable!(),
                 })
                 .collect();
-            let nshares = S::batch_mul(sshares, oshares, &mut DummyFieldTripleSource::default());
+            let nshares = S::batch_mul(sshares, oshares, &mut S::TripleSource::default());
             for (self_, new) in selfs.iter_mut().zip(nshares.into_iter()) {
                 *self_ = Self::Shared(new);
             }
         } else {
-            for (a, b) in ark_std::cfg_iter_mut!(selfs).zip(others.iter()) {
+            for (a, b) in selfs.iter_mut().zip(others.iter()) {
                 *a *= b;
             }
         }
@@ -417,12 +497,12 @@ impl<F: PrimeField, S: FieldShare<F>> Field for MpcField<F, S> {
                     Self::Public(_) => unreachable!(),
                 })
                 .collect();
-            let nshares = S::batch_div(sshares, oshares, &mut DummyFieldTripleSource::default());
+            let nshares = S::batch_div(sshares, oshares, &mut S::TripleSource::default());
             for (self_, new) in selfs.iter_mut().zip(nshares.into_iter()) {
                 *self_ = Self::Shared(new);
             }
         } else {
-            for (a, b) in ark_std::cfg_iter_mut!(selfs).zip(others.iter()) {
+            for (a, b) in selfs.iter_mut().zip(others.iter()) {
                 *a *= b;
             }
         }
@@ -442,7 +522,7 @@ impl<F: PrimeField, S: FieldShare<F>> Field for MpcField<F, S> {
                 })
                 .collect();
             for (self_, new) in selfs.iter_mut().zip(
-                S::partial_products(sshares, &mut DummyFieldTripleSource::default()).into_iter(),
+                S::partial_products(sshares, &mut S::TripleSource::default()).into_iter(),
             ) {
                 *self_ = Self::Shared(new);
             }
@@ -517,30 +597,118 @@ impl<F: PrimeField, S: FieldShare<F>> PrimeField for MpcField<F, S> {
     type Params = F::Params;
     type BigInt = F::BigInt;
     #[inline]
-    fn from_repr(_r: <Self as PrimeField>::BigInt) -> Option<Self> {
-        unimplemented!("No BigInt reprs for shared fields! (from_repr)")
-        //F::from_repr(r).map(|v| Self::from_public(v))
+    fn from_repr(r: <Self as PrimeField>::BigInt) -> Option<Self> {
+        F::from_repr(r).map(Self::from_public)
     }
-    // We're assuming that into_repr is linear
+    /// A shared value has no representation that a party can hold: decompose it into shared bits
+    /// with [bits::to_bits](crate::bits::to_bits), or [PrimeField::to_field_bits_le], instead.
+    /// Asking for it is a bug in the caller, and panics.
     #[inline]
     fn into_repr(&self) -> <Self as PrimeField>::BigInt {
-        unimplemented!("No BigInt reprs for shared fields! (into_repr)")
-        //self.unwrap_as_public().into_repr()
+        match self {
+            Self::Public(x) => x.into_repr(),
+            Self::Shared(_) => panic!(
+                "into_repr of a shared field element: it has no BigInt repr, so decompose it with \
+                 bits::to_bits or to_field_bits_le"
+            ),
+        }
+    }
+    #[inline]
+    fn to_field_bits_le(&self) -> Vec<Self> {
+        crate::bits::to_bits(*self)
+    }
+}
+
+/// `a^((p - 1) / 2)`, as a symbol.
+pub(crate) fn prime_legendre<F: PrimeField>(a: &F) -> LegendreSymbol {
+    let s = a.pow(F::Params::MODULUS_MINUS_ONE_DIV_TWO);
+    if s.is_zero() {
+        LegendreSymbol::Zero
+    } else if s.is_one() {
+        LegendreSymbol::QuadraticResidue
+    } else {
+        LegendreSymbol::QuadraticNonResidue
+    }
+}
+
+/// Tonelli-Shanks, for any prime field: the two-adic root of unity is a non-residue.
+pub(crate) fn prime_sqrt<F: PrimeField>(a: &F) -> Option<F> {
+    if a.is_zero() {
+        return Some(F::zero());
+    }
+    if prime_legendre(a) != LegendreSymbol::QuadraticResidue {
+        return None;
+    }
+    let mut z = F::two_adic_root_of_unity();
+    let mut w = a.pow(F::Params::T_MINUS_ONE_DIV_TWO);
+    let mut x = w * a;
+    let mut b = x * w;
+    let mut v = <F::Params as FftParameters>::TWO_ADICITY as usize;
+    while !b.is_one() {
+        let mut k = 0usize;
+        let mut b2k = b;
+        while !b2k.is_one() {
+            b2k.square_in_place();
+            k += 1;
+        }
+        w = z;
+        for _ in 1..(v - k) {
+            w.square_in_place();
+        }
+        z = w.square();
+        b *= z;
+        x *= w;
+        v = k;
+    }
+    Some(x)
+}
+
+impl<F: PrimeField, S: FieldShare<F>> MpcField<F, S> {
+    /// Opens `x r^2`, for a random shared `r`: it has the symbol of `x`, and otherwise is uniform.
+    /// Also returns `r`.
+    fn open_masked_square(&self, x: S) -> (F, Self) {
+        let r = Self::rand(&mut rand::thread_rng());
+        let masked = Self::Shared(x) * r * r;
+        (masked.reveal(), r)
     }
 }
 
+/// Shared values are masked by a random square `r^2`, which is wrong if `r` is zero, with probability
+/// `1/p`.
 impl<F: PrimeField, S: FieldShare<F>> SquareRootField for MpcField<F, S> {
+    /// For a shared value, opens `x r^2`, for a random `r`. Only the symbol leaks.
     #[inline]
     fn legendre(&self) -> ark_ff::LegendreSymbol {
-        todo!()
+        match self {
+            Self::Public(x) => prime_legendre(x),
+            Self::Shared(x) => {
+                let _op = mpc_net::op("legendre");
+                prime_legendre(&self.open_masked_square(*x).0)
+            }
+        }
     }
+    /// For a shared value, opens `y = x r^2` for a random `r`, and returns `sqrt(y) / r`. Reveals
+    /// only whether `x` is a square (or zero).
     #[inline]
     fn sqrt(&self) -> Option<Self> {
-        todo!()
+        match self {
+            Self::Public(x) => prime_sqrt(x).map(Self::Public),
+            Self::Shared(x) => {
+                let _op = mpc_net::op("sqrt");
+                let (y, r) = self.open_masked_square(*x);
+                if y.is_zero() {
+                    return Some(*self);
+                }
+                prime_sqrt(&y).map(|s| r.inv().unwrap() * Self::Public(s))
+            }
+        }
     }
     #[inline]
     fn sqrt_in_place(&mut self) -> Option<&mut Self> {
-        todo!()
+        self.sqrt().map(|s| {
+            *self = s;
+            self
+        })
     }
 }
 
@@ -553,6 +721,8 @@ mod poly_impl {
     use ark_poly::domain::{EvaluationDomain, GeneralEvaluationDomain};
     use ark_poly::evaluations::univariate::Evaluations;
     use ark_poly::univariate::DensePolynomial;
+    use mpc_net::Session;
+    use std::sync::Arc;
 
     impl<E: PrimeField, S: FieldShare<E>> Reveal for DensePolynomial<MpcField<E, S>> {
         type Base = DensePolynomial<E>;
@@ -569,6 +739,13 @@ mod poly_impl {
             )
         }
 
+        fn reveal_in(self, session: &Arc<Session>) -> Self::Base {
+            Evaluations::from_vec_and_domain(
+                self.evals.reveal_in(session),
+                GeneralEvaluationDomain::new(self.domain.size()).unwrap(),
+            )
+        }
+
         fn from_add_shared(b: Self::Base) -> Self {
             Evaluations::from_vec_and_domain(
                 Reveal::from_add_shared(b.evals),
@@ -17,11 +17,12 @@ use std::io::{self, Read, Write};
 use std::iter::Sum;
 use std::marker::PhantomData;
 use std::ops::*;
+use std::sync::Arc;
 
 use super::super::share::group::GroupShare;
 use super::super::share::BeaverSource;
 use super::field::MpcField;
-use mpc_net::{MpcNet, MpcMultiNet as Net};
+use mpc_net::{MpcNet, MpcMultiNet as Net, Session};
 use crate::Reveal;
 
 #[derive(Clone, Copy, Hash, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -32,6 +33,44 @@ pub enum MpcGroup<G: Group, S: GroupShare<G>> {
 
 impl_basics_2!(GroupShare, Group, MpcGroup);
 
+// NB: CanonicalSerializeWithFlags is unimplemented for Group, so public values take no flags.
+impl<T: Group, S: GroupShare<T>> CanonicalSerializeWithFlags for MpcGroup<T, S> {
+    fn serialize_with_flags<W: Write, F: Flags>(
+        &self,
+        mut writer: W,
+        flags: F,
+    ) -> Result<(), SerializationError> {
+        match self {
+            Self::Public(_) if flags.u8_bitmask() != 0 => Err(SerializationError::UnexpectedFlags),
+            Self::Public(_) => self.serialize(writer),
+            Self::Shared(s) => {
+                crate::encoding::write_state(&mut writer, true)?;
+                s.serialize_with_flags(writer, flags)
+            }
+        }
+    }
+
+    fn serialized_size_with_flags<F: Flags>(&self) -> usize {
+        match self {
+            Self::Public(_) => self.serialized_size(),
+            Self::Shared(s) => crate::encoding::state_size() + s.serialized_size_with_flags::<F>(),
+        }
+    }
+}
+
+impl<T: Group, S: GroupShare<T>> CanonicalDeserializeWithFlags for MpcGroup<T, S> {
+    fn deserialize_with_flags<R: Read, F: Flags>(
+        mut reader: R,
+    ) -> Result<(Self, F), SerializationError> {
+        if crate::encoding::read_state(&mut reader)? {
+            let (s, flags) = S::deserialize_with_flags(reader)?;
+            Ok((Self::Shared(s), flags))
+        } else {
+            Ok((Self::Public(T::deserialize(reader)?), F::default()))
+        }
+    }
+}
+
 #[derive(Derivative)]
 #[derivative(Default(bound = ""), Clone(bound = ""), Copy(bound = ""))]
 pub struct DummyGroupTripleSource<T, S> {
@@ -93,6 +132,22 @@ impl<T: Group, S: GroupShare<T>> MpcWire for MpcGroup<T, S> {
             true
         })
     }
+    fn batch_publicize(selfs: &mut [Self]) {
+        let shares: Vec<S> = selfs
+            .iter()
+            .filter_map(|s| match s {
+                MpcGroup::Shared(s) => Some(s.clone()),
+                MpcGroup::Public(_) => None,
+            })
+            .collect();
+        if shares.is_empty() {
+            return;
+        }
+        let mut opened = S::batch_open(shares).into_iter();
+        for s in selfs.iter_mut().filter(|s| s.is_shared()) {
+            *s = MpcGroup::Public(opened.next().unwrap());
+        }
+    }
     #[inline]
     fn is_shared(&self) -> bool {
         match self {
@@ -114,6 +169,15 @@ impl<T: Group, S: GroupShare<T>> Reveal for MpcGroup<T, S> {
         result
     }
     #[inline]
+    fn reveal_in(self, session: &Arc<Session>) -> Self::Base {
+        let result = match self {
+            Self::Shared(s) => s.reveal_in(session),
+            Self::Public(s) => s,
+        };
+        super::macros::check_eq_in(result, session);
+        result
+    }
+    #[inline]
     fn from_public(b: Self::Base) -> Self {
         Self::Public(b)
     }
@@ -193,7 +257,7 @@ impl<'a, T: Group, S: GroupShare<T>> MulAssign<&'a MpcField<T::ScalarField, S::F
                     x.scale_pub_scalar(y);
                 }
                 MpcField::Shared(y) => {
-                    let t = x.scale(*y, &mut DummyGroupTripleSource::default());
+                    let t = x.scale(*y, &mut S::TripleSource::default());
                     *x = t;
                 }
             },
@@ -228,4 +292,19 @@ impl<T: Group, S: GroupShare<T>> MpcGroup<T, S> {
             Ok(out_a)
         }
     }
+
+    /// `sum_i scalars[i] * bases[i]`. The bases must be all public or all shared, and so must the
+    /// scalars. When only one side is shared, this is a local MSM on the shares.
+    pub fn multi_scalar_mul(
+        bases: &[Self],
+        scalars: &[MpcField<T::ScalarField, S::FieldShare>],
+    ) -> Self {
+        let pub_bases = Self::all_public_or_shared(bases.iter().cloned());
+        match (pub_bases, MpcField::all_public_or_shared(scalars.iter().cloned())) {
+            (Ok(b), Ok(s)) => Self::Public(crate::msm::pippenger(&b, &s)),
+            (Ok(b), Err(s)) => Self::Shared(S::multi_scale_pub_group(&b, &s)),
+            (Err(b), Ok(s)) => Self::Shared(S::multi_scale_pub_scalar(&b, &s)),
+            (Err(_), Err(_)) => bases.iter().zip(scalars).map(|(b, s)| *b * s).sum(),
+        }
+    }
 }
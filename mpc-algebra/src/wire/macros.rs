@@ -2,8 +2,8 @@
 
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 
-use crate::channel::{self, MpcSerNet};
-use mpc_net::MpcNet;
+use crate::channel::{self, MpcSerNet, SerNet};
+use mpc_net::{MpcNet, Session};
 
 use std::fmt::Display;
 
@@ -37,6 +37,31 @@ pub fn check_eq<T: CanonicalSerialize + CanonicalDeserialize + Clone + Eq + Disp
     })
 }
 
+#[track_caller]
+/// [check_eq], among the parties of `session`.
+pub fn check_eq_in<T: CanonicalSerialize + CanonicalDeserialize + Clone + Eq + Display>(
+    t: T,
+    session: &Session,
+) {
+    debug_assert!({
+        log::debug!("Consistency check");
+        let others = session.try_broadcast(&t).unwrap_or_else(|e| e.raise());
+        match others.iter().position(|other_t| &t != other_t) {
+            Some(i) => {
+                println!(
+                    "\nConsistency check failed\nI (party {}) have {}\nvs\n  (party {}) has  {}",
+                    session.party_id(),
+                    t,
+                    i,
+                    others[i]
+                );
+                false
+            }
+            None => true,
+        }
+    })
+}
+
 macro_rules! impl_basics_2 {
     ($share:ident, $bound:ident, $wrap:ident) => {
         impl<T: $bound, S: $share<T>> $wrap<T, S> {
@@ -73,56 +98,47 @@ macro_rules! impl_basics_2 {
             }
         }
         impl<T: $bound, S: $share<T>> ToBytes for $wrap<T, S> {
-            fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+            fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+                crate::encoding::write_state(&mut writer, self.is_shared())
+                    .map_err(crate::encoding::to_io_error)?;
                 match self {
                     Self::Public(v) => v.write(writer),
-                    Self::Shared(_) => unimplemented!("write share: {}", self),
+                    Self::Shared(s) => s.write(writer),
                 }
             }
         }
         impl<T: $bound, S: $share<T>> FromBytes for $wrap<T, S> {
-            fn read<R: Read>(_reader: R) -> io::Result<Self> {
-                unimplemented!("read")
+            fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+                if crate::encoding::read_state(&mut reader).map_err(crate::encoding::to_io_error)? {
+                    Ok(Self::Shared(S::read(reader)?))
+                } else {
+                    Ok(Self::Public(T::read(reader)?))
+                }
             }
         }
         impl<T: $bound, S: $share<T>> CanonicalSerialize for $wrap<T, S> {
-            fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+            fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+                crate::encoding::write_state(&mut writer, self.is_shared())?;
                 match self {
                     Self::Public(v) => v.serialize(writer),
-                    Self::Shared(_) => unimplemented!("serialize share: {}", self),
+                    Self::Shared(s) => s.serialize(writer),
                 }
             }
             fn serialized_size(&self) -> usize {
-                match self {
-                    Self::Public(v) => v.serialized_size(),
-                    Self::Shared(_) => unimplemented!("serialized_size share: {}", self),
-                }
-            }
-        }
-        // NB: CanonicalSerializeWithFlags is unimplemented for Group.
-        impl<T: $bound, S: $share<T>> CanonicalSerializeWithFlags for $wrap<T, S> {
-            fn serialize_with_flags<W: Write, F: Flags>(
-                &self,
-                _writer: W,
-                _flags: F,
-            ) -> Result<(), SerializationError> {
-                unimplemented!("serialize_with_flags")
-            }
-
-            fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-                unimplemented!("serialized_size_with_flags")
+                crate::encoding::state_size()
+                    + match self {
+                        Self::Public(v) => v.serialized_size(),
+                        Self::Shared(s) => s.serialized_size(),
+                    }
             }
         }
         impl<T: $bound, S: $share<T>> CanonicalDeserialize for $wrap<T, S> {
-            fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-                unimplemented!("deserialize")
-            }
-        }
-        impl<T: $bound, S: $share<T>> CanonicalDeserializeWithFlags for $wrap<T, S> {
-            fn deserialize_with_flags<R: Read, F: Flags>(
-                _reader: R,
-            ) -> Result<(Self, F), SerializationError> {
-                unimplemented!("deserialize_with_flags")
+            fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+                if crate::encoding::read_state(&mut reader)? {
+                    Ok(Self::Shared(S::deserialize(reader)?))
+                } else {
+                    Ok(Self::Public(T::deserialize(reader)?))
+                }
             }
         }
         impl<T: $bound, S: $share<T>> UniformRand for $wrap<T, S> {
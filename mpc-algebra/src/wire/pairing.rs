@@ -20,6 +20,7 @@ use std::iter::{Product, Sum};
 use std::marker::PhantomData;
 use zeroize::Zeroize;
 
+use mpc_net::Session;
 use mpc_trait::MpcWire;
 
 use super::super::share::field::ExtFieldShare;
@@ -30,15 +31,20 @@ use super::field::MpcField;
 use super::group::MpcGroup;
 use crate::Reveal;
 
+/// Pairing triples `(X, Y, Z)`, with `Z` the Miller loop of `(X, Y)`, which is `e(X, Y)` up to the
+/// final exponentiation.
+///
+/// Each comes from a field triple `(x, y, xy)` of the [PairingShare]'s own source: `X = x g1`,
+/// `Y = y g2`, and `Z` is the loop of the shared `xy g1` and the public `g2`.
 #[derive(Derivative)]
 #[derivative(Default(bound = ""), Clone(bound = ""), Copy(bound = ""))]
-pub struct DummyPairingTripleSource<E, S> {
+pub struct MpcPairingTripleSource<E, S> {
     _phants: PhantomData<(E, S)>,
 }
 
 impl<E: PairingEngine, S: PairingShare<E>>
     BeaverSource<MpcG1Projective<E, S>, MpcG2Projective<E, S>, MpcExtField<E::Fqk, S::FqkShare>>
-    for DummyPairingTripleSource<E, S>
+    for MpcPairingTripleSource<E, S>
 {
     #[inline]
     fn triple(
@@ -48,12 +54,40 @@ impl<E: PairingEngine, S: PairingShare<E>>
         MpcG2Projective<E, S>,
         MpcExtField<E::Fqk, S::FqkShare>,
     ) {
-        let g1 = E::G1Projective::zero();
-        let g2 = E::G2Projective::zero();
+        let (mut xs, mut ys, mut zs) = self.triples(1);
+        (xs.pop().unwrap(), ys.pop().unwrap(), zs.pop().unwrap())
+    }
+    fn triples(
+        &mut self,
+        n: usize,
+    ) -> (
+        Vec<MpcG1Projective<E, S>>,
+        Vec<MpcG2Projective<E, S>>,
+        Vec<MpcExtField<E::Fqk, S::FqkShare>>,
+    ) {
+        let _op = mpc_net::op("pairing_triples");
+        let rng = &mut rand::thread_rng();
+        let xs: Vec<MpcField<E::Fr, S::FrShare>> = (0..n).map(|_| MpcField::rand(rng)).collect();
+        let ys: Vec<MpcField<E::Fr, S::FrShare>> = (0..n).map(|_| MpcField::rand(rng)).collect();
+        let mut xys = xs.clone();
+        MpcField::batch_product_in_place(&mut xys, &ys);
+        let g1 = MpcG1Projective::<E, S>::prime_subgroup_generator();
+        let g2 = MpcG2Projective::<E, S>::prime_subgroup_generator();
+        let g2_prep = E::G2Prepared::from(E::G2Affine::prime_subgroup_generator());
+        let zs = xys
+            .into_iter()
+            .map(|xy| {
+                let z = match MpcG1Affine::from(g1 * xy).val {
+                    MpcGroup::Shared(s) => S::miller_loop_sh_pub(&s, &g2_prep),
+                    MpcGroup::Public(_) => unreachable!("Public random point"),
+                };
+                MpcExtField::wrap(MpcField::Shared(z))
+            })
+            .collect();
         (
-            MpcG1Projective::from_add_shared(g1.clone()),
-            MpcG2Projective::from_add_shared(g2.clone()),
-            MpcExtField::from_add_shared(E::pairing(g1, g2)),
+            xs.into_iter().map(|x| g1 * x).collect(),
+            ys.into_iter().map(|y| g2 * y).collect(),
+            zs,
         )
     }
     #[inline]
@@ -86,10 +120,24 @@ pub struct MpcG1Projective<E: PairingEngine, PS: PairingShare<E>> {
     pub val: MpcGroup<E::G1Projective, PS::G1ProjectiveShare>,
 }
 
+/// A prepared point: public points are prepared for the Miller loop, shared ones stay shares.
+#[derive(Debug, Derivative)]
+#[derivative(Clone(bound = "P: Clone, S: Clone"))]
+pub enum MpcPrepared<P, S> {
+    Public(P),
+    Shared(S),
+}
+
+impl<P: Default, S> Default for MpcPrepared<P, S> {
+    fn default() -> Self {
+        MpcPrepared::Public(P::default())
+    }
+}
+
 #[derive(Debug, Derivative)]
 #[derivative(Clone(bound = ""), Default(bound = "E::G1Prepared: Default"))]
 pub struct MpcG1Prep<E: PairingEngine, PS: PairingShare<E>> {
-    pub val: E::G1Prepared,
+    pub val: MpcPrepared<E::G1Prepared, PS::G1AffineShare>,
     pub _phants: PhantomData<(E, PS)>,
 }
 
@@ -118,9 +166,9 @@ pub struct MpcG2Projective<E: PairingEngine, PS: PairingShare<E>> {
 }
 
 #[derive(Debug, Derivative)]
-#[derivative(Clone(bound = ""), Default(bound = "E::G1Prepared: Default"))]
+#[derivative(Clone(bound = ""), Default(bound = "E::G2Prepared: Default"))]
 pub struct MpcG2Prep<E: PairingEngine, PS: PairingShare<E>> {
-    pub val: E::G2Prepared,
+    pub val: MpcPrepared<E::G2Prepared, PS::G2AffineShare>,
     pub _phants: PhantomData<(E, PS)>,
 }
 
@@ -165,28 +213,91 @@ impl<E: PairingEngine, PS: PairingShare<E>> PairingEngine for MpcPairingEngine<E
     type G2Prepared = MpcG2Prep<E, PS>;
     type Fqk = MpcExtField<E::Fqk, PS::FqkShare>;
 
-    fn miller_loop<'a, I>(_i: I) -> Self::Fqk
+    /// The Miller loop, with shares of its output that are right up to the final exponentiation.
+    ///
+    /// Public pairs are looped together. A pair with one shared point is looped locally, on
+    /// shares. Pairs with two shared points use pairing triples: with `d = a - X` and `f = b - Y`
+    /// opened, `e(a, b) = e(d, f) e(d, Y) e(X, f) e(X, Y)`.
+    fn miller_loop<'a, I>(i: I) -> Self::Fqk
     where
         I: IntoIterator<Item = &'a (Self::G1Prepared, Self::G2Prepared)>,
     {
-        unimplemented!("miller_loop")
-        // <Bls12_377 as PairingEngine>::miller_loop(i)
+        let _op = mpc_net::op("miller_loop");
+        let mut public = Vec::new();
+        let mut shared = Vec::new();
+        let mut both_shared = Vec::new();
+        for (p, q) in i {
+            match (&p.val, &q.val) {
+                (MpcPrepared::Public(p), MpcPrepared::Public(q)) => {
+                    public.push((p.clone(), q.clone()))
+                }
+                (MpcPrepared::Shared(a), MpcPrepared::Public(q)) => {
+                    shared.push(PS::miller_loop_sh_pub(a, q))
+                }
+                (MpcPrepared::Public(p), MpcPrepared::Shared(b)) => {
+                    shared.push(PS::miller_loop_pub_sh(p, b))
+                }
+                (MpcPrepared::Shared(a), MpcPrepared::Shared(b)) => both_shared.push((a, b)),
+            }
+        }
+        let mut out = Self::Fqk::one();
+        if !both_shared.is_empty() {
+            let (xs, ys, zs) =
+                MpcPairingTripleSource::<E, PS>::default().triples(both_shared.len());
+            let xs: Vec<PS::G1AffineShare> = xs
+                .into_iter()
+                .map(|x| shared_aff(Self::G1Affine::from(x).val))
+                .collect();
+            let ys: Vec<PS::G2AffineShare> = ys
+                .into_iter()
+                .map(|y| shared_aff(Self::G2Affine::from(y).val))
+                .collect();
+            let ds =
+                PS::G1AffineShare::batch_open(both_shared.iter().zip(&xs).map(|((a, _), x)| {
+                    let mut d = (*a).clone();
+                    d.sub(x);
+                    d
+                }));
+            let fs =
+                PS::G2AffineShare::batch_open(both_shared.iter().zip(&ys).map(|((_, b), y)| {
+                    let mut f = (*b).clone();
+                    f.sub(y);
+                    f
+                }));
+            for (((d, f), (x, y)), z) in ds.into_iter().zip(fs).zip(xs.iter().zip(&ys)).zip(zs) {
+                let d = E::G1Prepared::from(d);
+                let f = E::G2Prepared::from(f);
+                shared.push(PS::miller_loop_pub_sh(&d, y));
+                shared.push(PS::miller_loop_sh_pub(x, &f));
+                public.push((d, f));
+                out *= z;
+            }
+        }
+        for s in shared {
+            out *= Self::Fqk::wrap(MpcField::Shared(s));
+        }
+        if !public.is_empty() {
+            out *= Self::Fqk::from_public(E::miller_loop(&public));
+        }
+        out
     }
 
-    fn final_exponentiation(_f: &Self::Fqk) -> Option<Self::Fqk> {
-        unimplemented!("final_exponentiation")
-        // <Bls12_377 as PairingEngine>::final_exponentiation(f)
+    fn final_exponentiation(f: &Self::Fqk) -> Option<Self::Fqk> {
+        match f.val {
+            MpcField::Public(f) => E::final_exponentiation(&f).map(Self::Fqk::from_public),
+            MpcField::Shared(s) => Some(Self::Fqk::wrap(MpcField::Shared(
+                PS::final_exponentiation(s),
+            ))),
+        }
     }
 
     /// Computes a product of pairings.
     #[must_use]
-    fn product_of_pairings<'a, I>(_i: I) -> Self::Fqk
+    fn product_of_pairings<'a, I>(i: I) -> Self::Fqk
     where
         I: IntoIterator<Item = &'a (Self::G1Prepared, Self::G2Prepared)>,
     {
-        // TODO: MPC!
-        // <Bls12_377 as PairingEngine>::product_of_pairings(i)
-        unimplemented!("pairing product")
+        Self::final_exponentiation(&Self::miller_loop(i)).unwrap()
     }
 
     /// Performs multiple pairing operations
@@ -196,36 +307,15 @@ impl<E: PairingEngine, PS: PairingShare<E>> PairingEngine for MpcPairingEngine<E
         G1: Into<Self::G1Affine>,
         G2: Into<Self::G2Affine>,
     {
-        let a: Self::G1Affine = p.into();
-        let b: Self::G2Affine = q.into();
-        let a: Self::G1Projective = a.into();
-        let b: Self::G2Projective = b.into();
-        if a.is_shared() && b.is_shared() {
-            let source = &mut DummyPairingTripleSource::default();
-            // x * y = z
-            let (x, y, z) = source.triple();
-            // x + a
-            let xa = (a + x).reveal();
-            // y + b
-            let yb = (b + y).reveal();
-            let xayb: MpcExtField<E::Fqk, PS::FqkShare> =
-                MpcExtField::wrap(MpcField::Public(E::pairing(xa, yb)));
-            let xay: MpcExtField<E::Fqk, PS::FqkShare> = MpcExtField::wrap(MpcField::Shared(
-                <PS::FqkShare as ExtFieldShare<E::Fqk>>::Ext::from_add_shared(E::pairing(
-                    xa,
-                    y.unwrap_as_public(),
-                )),
-            ));
-            let xyb: MpcExtField<E::Fqk, PS::FqkShare> = MpcExtField::wrap(MpcField::Shared(
-                <PS::FqkShare as ExtFieldShare<E::Fqk>>::Ext::from_add_shared(E::pairing(
-                    x.unwrap_as_public(),
-                    yb,
-                )),
-            ));
-            z / xay / xyb * xayb
-        } else {
-            MpcExtField::wrap(MpcField::Public(E::pairing(a.reveal(), b.reveal())))
-        }
+        Self::product_of_pairings(&[(p.into().into(), q.into().into())])
+    }
+}
+
+/// The share inside a shared point.
+fn shared_aff<G: Group, S: GroupShare<G>>(g: MpcGroup<G, S>) -> S {
+    match g {
+        MpcGroup::Shared(s) => s,
+        MpcGroup::Public(_) => unreachable!("Public triple point"),
     }
 }
 
@@ -242,8 +332,10 @@ macro_rules! impl_pairing_mpc_wrapper {
             }
         }
         impl<E: $bound1, PS: $bound2<E>> FromBytes for $wrap<E, PS> {
-            fn read<R: Read>(_reader: R) -> io::Result<Self> {
-                unimplemented!("read")
+            fn read<R: Read>(reader: R) -> io::Result<Self> {
+                Ok(Self {
+                    val: $wrapped::read(reader)?,
+                })
             }
         }
         impl<E: $bound1, PS: $bound2<E>> CanonicalSerialize for $wrap<E, PS> {
@@ -268,15 +360,18 @@ macro_rules! impl_pairing_mpc_wrapper {
             }
         }
         impl<E: $bound1, PS: $bound2<E>> CanonicalDeserialize for $wrap<E, PS> {
-            fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-                unimplemented!("deserialize")
+            fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+                Ok(Self {
+                    val: $wrapped::deserialize(reader)?,
+                })
             }
         }
         impl<E: $bound1, PS: $bound2<E>> CanonicalDeserializeWithFlags for $wrap<E, PS> {
             fn deserialize_with_flags<R: Read, F: Flags>(
-                _reader: R,
+                reader: R,
             ) -> Result<(Self, F), SerializationError> {
-                unimplemented!("deserialize_with_flags")
+                let (val, flags) = $wrapped::deserialize_with_flags(reader)?;
+                Ok((Self { val }, flags))
             }
         }
         impl<E: $bound1, PS: $bound2<E>> UniformRand for $wrap<E, PS> {
@@ -353,6 +448,13 @@ macro_rules! impl_pairing_mpc_wrapper {
             fn publicize(&mut self) {
                 self.val.publicize();
             }
+            fn batch_publicize(selfs: &mut [Self]) {
+                let mut vals: Vec<_> = selfs.iter().map(|s| s.val.clone()).collect();
+                MpcWire::batch_publicize(&mut vals[..]);
+                for (s, v) in selfs.iter_mut().zip(vals) {
+                    s.val = v;
+                }
+            }
             #[inline]
             fn is_shared(&self) -> bool {
                 self.val.is_shared()
@@ -424,6 +526,10 @@ macro_rules! impl_ext_field_wrapper {
                 self.val.reveal()
             }
             #[inline]
+            fn reveal_in(self, session: &std::sync::Arc<Session>) -> E {
+                self.val.reveal_in(session)
+            }
+            #[inline]
             fn from_public(t: E) -> Self {
                 Self::wrap($wrapped::from_public(t))
             }
@@ -564,6 +670,10 @@ macro_rules! impl_pairing_curve_wrapper {
                 self.val.reveal()
             }
             #[inline]
+            fn reveal_in(self, session: &std::sync::Arc<Session>) -> Self::Base {
+                self.val.reveal_in(session)
+            }
+            #[inline]
             fn from_public(t: Self::Base) -> Self {
                 Self {
                     val: $wrapped::from_public(t),
@@ -688,8 +798,14 @@ macro_rules! impl_aff_proj {
         }
 
         impl<E: PairingEngine, PS: PairingShare<E>> From<$w_aff<E, PS>> for $w_prep<E, PS> {
-            fn from(_o: $w_aff<E, PS>) -> Self {
-                unimplemented!("Prepared curves")
+            fn from(o: $w_aff<E, PS>) -> Self {
+                Self {
+                    val: match o.val {
+                        MpcGroup::Public(g) => MpcPrepared::Public(g.into()),
+                        MpcGroup::Shared(s) => MpcPrepared::Shared(s),
+                    },
+                    _phants: PhantomData::default(),
+                }
             }
         }
 
@@ -703,12 +819,22 @@ macro_rules! impl_aff_proj {
             type Base = E::$prep;
             #[inline]
             fn reveal(self) -> E::$prep {
-                self.val
+                match self.val {
+                    MpcPrepared::Public(g) => g,
+                    MpcPrepared::Shared(s) => s.reveal().into(),
+                }
+            }
+            #[inline]
+            fn reveal_in(self, session: &std::sync::Arc<Session>) -> E::$prep {
+                match self.val {
+                    MpcPrepared::Public(g) => g,
+                    MpcPrepared::Shared(s) => s.reveal_in(session).into(),
+                }
             }
             #[inline]
             fn from_public(g: E::$prep) -> Self {
                 Self {
-                    val: g,
+                    val: MpcPrepared::Public(g),
                     _phants: PhantomData::default(),
                 }
             }
@@ -744,13 +870,35 @@ macro_rules! impl_aff_proj {
                 todo!("AffineCurve::mul_by_cofactor_inv")
             }
             fn multi_scalar_mul(bases: &[Self], scalars: &[Self::ScalarField]) -> Self::Projective {
+                // Witnesses from gadgets mix public and shared values: take each kind separately.
+                if scalars.iter().any(|s| s.is_shared()) && scalars.iter().any(|s| !s.is_shared()) {
+                    let (shared, public): (Vec<_>, Vec<_>) =
+                        bases.iter().zip(scalars).partition(|(_, s)| s.is_shared());
+                    let (shared_bases, shared_scalars): (Vec<Self>, Vec<_>) = shared.into_iter().unzip();
+                    let (public_bases, public_scalars): (Vec<Self>, Vec<_>) = public.into_iter().unzip();
+                    return Self::multi_scalar_mul(&shared_bases, &shared_scalars)
+                        + Self::multi_scalar_mul(&public_bases, &public_scalars);
+                }
                 let b = {
-                    assert!(bases.iter().all(|b| !b.is_shared()));
-                    let scalars_shared = scalars.first().map(|s| s.is_shared()).unwrap_or(true);
-                    assert!(scalars.iter().all(|b| scalars_shared == b.is_shared()));
-                    let bases =
-                        MpcGroup::all_public_or_shared(bases.into_iter().map(|i| i.val.clone()))
-                            .unwrap();
+                    let bases = match MpcGroup::all_public_or_shared(
+                        bases.into_iter().map(|i| i.val.clone()),
+                    ) {
+                        Ok(bases) => bases,
+                        Err(bases) => {
+                            let scalars =
+                                MpcField::all_public_or_shared(scalars.into_iter().cloned())
+                                    .unwrap_or_else(|_| {
+                                        panic!("MSM with shared bases needs public scalars")
+                                    });
+                            return $w_pro {
+                                val: MpcGroup::Shared(PS::$g_name::sh_aff_to_proj(
+                                    <PS::$share_aff as GroupShare<E::$aff>>::multi_scale_pub_scalar(
+                                        &bases, &scalars,
+                                    ),
+                                )),
+                            };
+                        }
+                    };
                     match MpcField::all_public_or_shared(scalars.into_iter().cloned()) {
                         Ok(pub_scalars) => {
                             //let t = start_timer!(|| "MSM inner");
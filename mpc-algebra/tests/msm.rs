@@ -0,0 +1,35 @@
+// The MSMs agree with the naive one.
+use ark_bls12_377::{Fr, G1Projective};
+use ark_ff::UniformRand;
+use mpc_algebra::msm::{ChunkedMsm, Msm, NaiveMsm, PippengerMsm};
+
+#[test]
+fn pippenger_matches_naive() {
+    let rng = &mut ark_std::test_rng();
+    for n in [0, 1, 5, 40, 300] {
+        let bases: Vec<G1Projective> = (0..n).map(|_| G1Projective::rand(rng)).collect();
+        let mut scalars: Vec<Fr> = (0..n).map(|_| Fr::rand(rng)).collect();
+        if n > 2 {
+            scalars[1] = Fr::from(0u64);
+            scalars[2] = -Fr::from(1u64);
+        }
+        let expected = NaiveMsm::msm(&bases, &scalars);
+        assert_eq!(PippengerMsm::msm(&bases, &scalars), expected);
+        assert_eq!(
+            ChunkedMsm::<PippengerMsm<_>>::msm_many(&bases, &[&scalars, &scalars[..n / 2]]),
+            vec![expected, NaiveMsm::msm(&bases, &scalars[..n / 2])]
+        );
+    }
+}
+
+#[test]
+fn chunks_are_summed() {
+    let rng = &mut ark_std::test_rng();
+    let n = 3 * ChunkedMsm::<()>::MIN_CHUNK + 7;
+    let bases: Vec<G1Projective> = (0..n).map(|_| G1Projective::rand(rng)).collect();
+    let scalars: Vec<Fr> = (0..n).map(|_| Fr::rand(rng)).collect();
+    assert_eq!(
+        ChunkedMsm::<PippengerMsm<_>>::msm(&bases, &scalars),
+        NaiveMsm::msm(&bases, &scalars)
+    );
+}
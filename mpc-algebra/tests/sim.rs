@@ -0,0 +1,393 @@
+// Protocols run with every party in this process, over LocalSimNet.
+use ark_bls12_377::{Fr, G1Projective};
+use ark_ec::ProjectiveCurve;
+use ark_ff::{Field, One, PrimeField, UniformRand, Zero};
+use mpc_algebra::msm::{ChunkedMsm, Msm, NaiveMsm, PippengerMsm};
+use mpc_algebra::share::field::FieldShare;
+use mpc_algebra::share::gsz20::field::{self, GszFieldShare};
+use mpc_algebra::share::add::AdditiveGroupShare;
+use mpc_algebra::share::group::GroupShare;
+use mpc_algebra::share::gsz20::group::GszGroupShare;
+use mpc_algebra::Reveal;
+use mpc_net::LocalSimNet;
+
+#[test]
+fn gsz20_mult() {
+    let outs = LocalSimNet::run(4, |_| {
+        let rng = &mut ark_std::test_rng();
+        let a_pub = Fr::rand(rng);
+        let b_pub = Fr::rand(rng);
+        let a = GszFieldShare::from_public(a_pub);
+        let b = GszFieldShare::from_public(b_pub);
+        let c = field::mult(a, &b, true);
+        let cs = field::batch_mult(vec![a, c], &[b, b], true);
+        assert_eq!(field::open(&c), a_pub * b_pub);
+        assert_eq!(
+            GszFieldShare::batch_open(cs),
+            vec![a_pub * b_pub, a_pub * b_pub * b_pub]
+        );
+        field::coin::<Fr>()
+    });
+    assert!(outs.iter().all(|c| *c == outs[0]));
+}
+
+#[test]
+fn gsz20_sessions_are_separate() {
+    // Two computations at once, with different numbers of parties, in the same process.
+    let small = std::thread::spawn(|| {
+        LocalSimNet::run(3, |_| {
+            let r = field::rand::<Fr>();
+            let s = field::mult(r, &GszFieldShare::from_public(Fr::one()), true);
+            (field::open(&r), field::open(&s))
+        })
+    });
+    let big = LocalSimNet::run(4, |_| field::open(&field::rand::<Fr>()));
+    assert!(big.iter().all(|r| *r == big[0]));
+    let small = small.join().unwrap();
+    assert!(small.iter().all(|(r, s)| r == s && *r == small[0].0));
+}
+
+#[test]
+fn gsz20_rounds_are_traced() {
+    let traces = LocalSimNet::run(4, |_| {
+        mpc_net::MpcMultiNet::start_trace();
+        let r = field::rand::<Fr>();
+        let s = field::mult(r, &GszFieldShare::from_public(Fr::one()), true);
+        GszFieldShare::batch_open(vec![r, s]);
+        mpc_net::MpcMultiNet::take_trace().unwrap()
+    });
+    for trace in &traces {
+        let summary = trace.summary();
+        assert_eq!(summary["batch_open"].rounds, 1);
+        assert!(summary.keys().any(|op| op.contains("king_compute")));
+        assert!(trace.spans.iter().any(|s| s.op == "batch_open"));
+    }
+}
+
+/// Both MSMs of shared with public values, against the plain one.
+fn shared_msms_agree<S: GroupShare<G1Projective>>() {
+    use mpc_algebra::wire::{field::MpcField, group::MpcGroup};
+    let rng = &mut ark_std::test_rng();
+    let bases: Vec<G1Projective> = (0..40).map(|_| G1Projective::rand(rng)).collect();
+    let scalars: Vec<Fr> = (0..40).map(|_| Fr::rand(rng)).collect();
+    let expected = NaiveMsm::msm(&bases, &scalars);
+    let pub_bases: Vec<MpcGroup<G1Projective, S>> =
+        bases.iter().map(|b| MpcGroup::from_public(*b)).collect();
+    let sh_scalars: Vec<MpcField<Fr, S::FieldShare>> =
+        scalars.iter().map(|s| MpcField::king_share(*s, rng)).collect();
+    let sh_bases: Vec<MpcGroup<G1Projective, S>> =
+        bases.iter().map(|b| MpcGroup::king_share(*b, rng)).collect();
+    let pub_scalars: Vec<MpcField<Fr, S::FieldShare>> =
+        scalars.iter().map(|s| MpcField::from_public(*s)).collect();
+    assert_eq!(
+        MpcGroup::multi_scalar_mul(&pub_bases, &sh_scalars).reveal(),
+        expected
+    );
+    assert_eq!(
+        MpcGroup::multi_scalar_mul(&sh_bases, &pub_scalars).reveal(),
+        expected
+    );
+}
+
+#[test]
+fn shared_msm() {
+    type Pippenger = PippengerMsm<G1Projective>;
+    LocalSimNet::run(3, |_| {
+        shared_msms_agree::<AdditiveGroupShare<G1Projective, Pippenger>>();
+        shared_msms_agree::<AdditiveGroupShare<G1Projective, ChunkedMsm<Pippenger>>>();
+        shared_msms_agree::<GszGroupShare<G1Projective, Pippenger>>();
+    });
+}
+
+#[test]
+fn packed_fft_and_mult() {
+    use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
+    use mpc_algebra::share::packed;
+    // Enough parties to pack 4 secrets against 3 corruptions.
+    let outs = LocalSimNet::run(16, |_| {
+        let rng = &mut ark_std::test_rng();
+        let domain = Radix2EvaluationDomain::<Fr>::new(16).unwrap();
+        let xs: Vec<Fr> = (0..16).map(|_| Fr::rand(rng)).collect();
+        assert_eq!(packed::t(), 3);
+        let shares = packed::king_share(&xs, 2, packed::degree(2));
+        let ys = packed::fft(&shares, &domain);
+        let back = packed::ifft(&ys, &domain);
+        let squares = packed::mult(&shares, &shares);
+        let repacked = packed::repack(&shares, 4, |v| v);
+        let degrees: Vec<usize> = [&shares, &ys, &squares, &repacked]
+            .iter()
+            .map(|s| s[0].degree)
+            .collect();
+        (
+            packed::open(&ys) == domain.fft(&xs),
+            packed::open(&back) == xs,
+            packed::open(&squares) == xs.iter().map(|x| x.square()).collect::<Vec<_>>(),
+            packed::open(&repacked) == xs,
+            degrees,
+        )
+    });
+    for out in outs {
+        assert_eq!(out, (true, true, true, true, vec![4, 4, 4, 6]));
+    }
+}
+
+/// Both threshold signatures verify under the opened public key, and only for the signed message.
+fn signatures_verify<S: GroupShare<G1Projective>>() {
+    use mpc_algebra::sig::{self, ThresholdKey};
+    use mpc_algebra::wire::field::MpcField;
+    let rng = &mut ark_std::test_rng();
+    let secret = Fr::rand(rng);
+    let key = ThresholdKey::<G1Projective, S>::from_secret(MpcField::king_share(secret, rng));
+    let ecdsa = key.ecdsa_sign(b"message", rng);
+    let schnorr = key.schnorr_sign(b"message", rng);
+    assert_eq!(
+        key.public,
+        G1Projective::prime_subgroup_generator().mul(secret.into_repr())
+    );
+    assert!(sig::ecdsa_verify(&key.public, b"message", &ecdsa));
+    assert!(!sig::ecdsa_verify(&key.public, b"massage", &ecdsa));
+    assert!(sig::schnorr_verify(&key.public, b"message", &schnorr));
+    assert!(!sig::schnorr_verify(&key.public, b"massage", &schnorr));
+}
+
+#[test]
+fn threshold_signatures() {
+    type Pippenger = PippengerMsm<G1Projective>;
+    LocalSimNet::run(4, |_| {
+        signatures_verify::<AdditiveGroupShare<G1Projective, Pippenger>>();
+        signatures_verify::<GszGroupShare<G1Projective, Pippenger>>();
+    });
+}
+
+/// Pairings with every mix of shared and public inputs, alone and as one product, match the plain
+/// ones.
+fn pairings_agree<S: mpc_algebra::PairingShare<ark_bls12_377::Bls12_377>>() {
+    use ark_bls12_377::{Bls12_377 as E, G2Projective};
+    use ark_ec::PairingEngine;
+    use mpc_algebra::wire::field::MpcField;
+    use mpc_algebra::MpcPairingEngine;
+    type ME<S> = MpcPairingEngine<E, S>;
+    let rng = &mut ark_std::test_rng();
+    let (a, b, c, d) = (Fr::rand(rng), Fr::rand(rng), Fr::rand(rng), Fr::rand(rng));
+    let g1 = |x: Fr| G1Projective::prime_subgroup_generator().mul(x.into_repr());
+    let g2 = |x: Fr| G2Projective::prime_subgroup_generator().mul(x.into_repr());
+    let mpc_g1 = |x: Fr, rng: &mut _| {
+        <ME<S> as PairingEngine>::G1Projective::prime_subgroup_generator()
+            * MpcField::king_share(x, rng)
+    };
+    let mpc_g2 = |x: Fr, rng: &mut _| {
+        <ME<S> as PairingEngine>::G2Projective::prime_subgroup_generator()
+            * MpcField::king_share(x, rng)
+    };
+    let (sa, sb, sc, sd) = (
+        mpc_g1(a, rng),
+        mpc_g2(b, rng),
+        mpc_g1(c, rng),
+        mpc_g2(d, rng),
+    );
+    let pa = <ME<S> as PairingEngine>::G1Projective::from_public(g1(a));
+    let pb = <ME<S> as PairingEngine>::G2Projective::from_public(g2(b));
+    let product = ME::<S>::product_of_pairings(&[
+        (sa.into_affine().into(), pb.into_affine().into()),
+        (pa.into_affine().into(), sd.into_affine().into()),
+        (sc.into_affine().into(), sd.into_affine().into()),
+    ]);
+    let expected = E::pairing(g1(a), g2(b));
+    assert_eq!(ME::<S>::pairing(sa, pb).reveal(), expected);
+    assert_eq!(ME::<S>::pairing(pa, sb).reveal(), expected);
+    assert_eq!(ME::<S>::pairing(sa, sb).reveal(), expected);
+    assert_eq!(
+        product.reveal(),
+        E::pairing(g1(a * b + a * d + c * d), g2(Fr::one()))
+    );
+}
+
+#[test]
+fn shared_pairings() {
+    use ark_bls12_377::Bls12_377 as E;
+    use mpc_algebra::share::spdz::{SpdzFieldShare, SpdzPairingShare};
+    use mpc_algebra::share::{add::AdditivePairingShare, gsz20::GszPairingShare};
+    use mpc_algebra::wire::field::MpcField;
+    LocalSimNet::run(4, |_| {
+        pairings_agree::<AdditivePairingShare<E>>();
+        MpcField::<Fr, SpdzFieldShare<Fr>>::init_protocol();
+        pairings_agree::<SpdzPairingShare<E>>();
+        MpcField::<Fr, SpdzFieldShare<Fr>>::deinit_protocol();
+        pairings_agree::<GszPairingShare<E>>();
+    });
+}
+
+/// Square roots, symbols, bits, comparisons and range checks of shared values match the plain
+/// ones.
+fn bits_agree<S: FieldShare<Fr>>() {
+    use ark_ff::{BigInteger, SquareRootField};
+    use mpc_algebra::bits;
+    use mpc_algebra::wire::field::MpcField;
+    let rng = &mut ark_std::test_rng();
+    let (a, b) = (Fr::rand(rng), Fr::rand(rng));
+    let square = a * a;
+    let small = Fr::from(1000u64);
+    let shared = MpcField::<Fr, S>::king_share_batch(vec![a, b, square, small], rng);
+    let root = shared[2].sqrt().unwrap().reveal();
+    let bits = bits::batch_to_bits(&shared[..2]);
+    for (bits, x) in bits.iter().zip(&[a, b]) {
+        let opened: Vec<bool> = bits.iter().map(|b| b.reveal().is_one()).collect();
+        assert_eq!(opened[..], x.into_repr().to_bits_le()[..opened.len()]);
+        assert_eq!(bits::from_bits(bits).reveal(), *x);
+    }
+    let lt = bits::batch_is_less_than(&shared[..2], &[shared[1], shared[0]]);
+    let in_range = bits::batch_is_in_range(&[shared[3], shared[0]], 10);
+    let zeros = bits::batch_is_zero(&[shared[0], shared[3] - shared[3]]);
+    assert!(root == a || root == -a);
+    assert_eq!(shared[2].legendre(), square.legendre());
+    assert_eq!(shared[0].legendre(), a.legendre());
+    assert_eq!(shared[0].sqrt().is_some(), a.sqrt().is_some());
+    assert_eq!(lt[0].reveal().is_one(), a < b);
+    assert_eq!(lt[1].reveal().is_one(), b < a);
+    assert!(in_range[0].reveal().is_one());
+    assert!(!in_range[1].reveal().is_one());
+    assert!(zeros[0].reveal().is_zero());
+    assert!(zeros[1].reveal().is_one());
+}
+
+#[test]
+fn shared_bits() {
+    use mpc_algebra::share::{add::AdditiveFieldShare, spdz::SpdzFieldShare};
+    LocalSimNet::run(4, |_| {
+        bits_agree::<AdditiveFieldShare<Fr>>();
+        SpdzFieldShare::<Fr>::init_protocol();
+        bits_agree::<SpdzFieldShare<Fr>>();
+        SpdzFieldShare::<Fr>::deinit_protocol();
+        bits_agree::<GszFieldShare<Fr>>();
+    });
+}
+
+/// Shared inverses, of zero too, match the plain ones.
+fn inverses_agree<S: FieldShare<Fr>>() {
+    use mpc_algebra::wire::field::MpcField;
+    let rng = &mut ark_std::test_rng();
+    let a = Fr::rand(rng);
+    let shared = MpcField::<Fr, S>::king_share_batch(vec![a, Fr::from(0u64)], rng);
+    let inverses: Vec<_> = shared.iter().map(|x| x.inverse_or_zero()).collect();
+    assert_eq!(inverses[0].reveal(), a.inverse().unwrap());
+    assert_eq!(inverses[1].reveal(), Fr::from(0u64));
+}
+
+#[test]
+fn shared_inverses() {
+    use mpc_algebra::share::{add::AdditiveFieldShare, spdz::SpdzFieldShare};
+    LocalSimNet::run(4, |_| {
+        inverses_agree::<AdditiveFieldShare<Fr>>();
+        SpdzFieldShare::<Fr>::init_protocol();
+        inverses_agree::<SpdzFieldShare<Fr>>();
+        SpdzFieldShare::<Fr>::deinit_protocol();
+        inverses_agree::<GszFieldShare<Fr>>();
+    });
+}
+
+#[test]
+fn shared_reprs_are_refused() {
+    use mpc_algebra::wire::field::MpcField;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    type MFr = MpcField<Fr, GszFieldShare<Fr>>;
+    let outs = LocalSimNet::run(3, |_| {
+        let five = Fr::from(5u64);
+        let shared = MFr::king_share(five, &mut ark_std::test_rng());
+        assert_eq!(MFr::from_public(five).into_repr(), five.into_repr());
+        catch_unwind(AssertUnwindSafe(|| shared.into_repr()))
+    });
+    for out in outs {
+        let payload = out.unwrap_err();
+        let msg = payload.downcast_ref::<&str>().unwrap();
+        assert!(msg.starts_with("into_repr of a shared field element"));
+    }
+}
+
+#[test]
+fn open_errors_are_returned() {
+    use mpc_algebra::share::add::AdditiveFieldShare;
+    use mpc_net::{MpcMultiNet as Net, MpcNet, NetError};
+    let share = || AdditiveFieldShare::<Fr>::from_add_shared(Fr::one());
+    let outs = LocalSimNet::run(3, move |session| {
+        let id = session.party_id();
+        if id == 2 {
+            // Not a vector of field elements.
+            Net::broadcast_bytes(&[0xff; 3]);
+            return None;
+        }
+        Some(AdditiveFieldShare::try_batch_open(vec![share()]))
+    });
+    for out in &outs[..2] {
+        assert!(matches!(out, Some(Err(NetError::Deserialize { party: 2, .. }))));
+    }
+    let outs = LocalSimNet::run(3, move |session| {
+        let id = session.party_id();
+        if id == 2 {
+            Net::abort("bad share");
+            return None;
+        }
+        Some(share().try_open())
+    });
+    for out in &outs[..2] {
+        assert_eq!(
+            out,
+            &Some(Err(NetError::Abort {
+                party: 2,
+                reason: "bad share".into()
+            }))
+        );
+    }
+}
+
+#[test]
+fn ot_gilboa_products() {
+    // Each party is the sender with x_i and the receiver with the bits of y_i; with the local
+    // products, the cross terms sum to (sum x_i)(sum y_i).
+    let outs = LocalSimNet::run(3, |session| {
+        let id = session.party_id();
+        let gadget: Vec<Fr> = (0..8).map(|j| Fr::from(1u64 << j)).collect();
+        mpc_algebra::ot::init();
+        let x = Fr::from(id as u64 + 2);
+        let y = id as u64 * 40 + 5;
+        let bits: Vec<bool> = (0..8).map(|j| (y >> j) & 1 == 1).collect();
+        let cross = mpc_algebra::ot::gilboa(&[x], &bits, &gadget, |x, k| *x * k);
+        mpc_algebra::ot::deinit();
+        (x, Fr::from(y), cross[0] + x * Fr::from(y))
+    });
+    let x: Fr = outs.iter().map(|o| o.0).sum();
+    let y: Fr = outs.iter().map(|o| o.1).sum();
+    let z: Fr = outs.iter().map(|o| o.2).sum();
+    assert_eq!(z, x * y);
+}
+
+#[test]
+fn spdz_opens_in_sessions() {
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use mpc_algebra::share::spdz::SpdzFieldShare;
+    use mpc_algebra::wire::field::MpcField;
+    use mpc_net::NetError;
+    type F = MpcField<Fr, SpdzFieldShare<Fr>>;
+    let outs = LocalSimNet::run(3, |session| {
+        F::init_protocol();
+        let rng = &mut ark_std::test_rng();
+        let a_pub = Fr::rand(rng);
+        let b_pub = Fr::rand(rng);
+        let c = F::king_share(a_pub, rng) * F::king_share(b_pub, rng);
+        assert_eq!(c.reveal_in(session), a_pub * b_pub);
+        let mut c = match c {
+            MpcField::Shared(c) => c,
+            MpcField::Public(_) => unreachable!(),
+        };
+        if session.party_id() == 1 {
+            // Change our share of the value, but not of its MAC.
+            let mut bytes = Vec::new();
+            c.serialize(&mut bytes).unwrap();
+            bytes[1] ^= 1;
+            c = SpdzFieldShare::deserialize(&bytes[..]).unwrap();
+        }
+        SpdzFieldShare::try_batch_open_in(vec![c], session)
+    });
+    for (id, out) in outs.into_iter().enumerate() {
+        assert!(matches!(out, Err(NetError::Abort { party, .. }) if party == id));
+    }
+}
@@ -1,6 +1,5 @@
 use log::debug;
-use mpc_net::multi as net;
-use mpc_algebra::share::gs20;
+use mpc_net::{Identity, MpcMultiNet as Net, MpcNet, Tag};
 
 use std::path::PathBuf;
 use structopt::StructOpt;
@@ -14,6 +13,14 @@ struct Opt {
     /// Input file
     #[structopt(parse(from_os_str))]
     input: PathBuf,
+
+    /// Our certificate, for TLS (see the keygen example)
+    #[structopt(long, parse(from_os_str), requires = "key")]
+    cert: Option<PathBuf>,
+
+    /// Our private key, for TLS
+    #[structopt(long, parse(from_os_str), requires = "cert")]
+    key: Option<PathBuf>,
 }
 
 fn main() {
@@ -21,11 +28,48 @@ fn main() {
     debug!("Start");
     let opt = Opt::from_args();
     println!("{:?}", opt);
-    multi::init_from_path(opt.input.to_str().unwrap(), opt.id);
-    let all = multi::broadcast(&[opt.id as u8]);
+    match (&opt.cert, &opt.key) {
+        (Some(cert), Some(key)) => {
+            let identity = Identity::read(cert, key).expect("Could not read identity");
+            Net::init_with_identity(opt.input.to_str().unwrap(), opt.id, &identity)
+                .unwrap_or_else(|e| e.raise());
+        }
+        _ => Net::init_from_file(opt.input.to_str().unwrap(), opt.id),
+    }
+    let id = opt.id as u8;
+    let n = Net::n_parties();
+    let all = Net::broadcast_bytes(&[id]);
     println!("{:?}", all);
-    let r = multi::send_to_king(&[opt.id as u8]);
-    let all = multi::recv_from_king(r);
+    assert_eq!(all, (0..n as u8).map(|i| vec![i]).collect::<Vec<_>>());
+    let r = Net::send_bytes_to_king(&[id]);
+    let all = Net::recv_bytes_from_king(r.map(|v| v.into_iter().rev().collect()));
     println!("{:?}", all);
-    multi::uninit();
+    assert_eq!(all, vec![(n - 1 - opt.id) as u8]);
+
+    // Two sessions at once, each from its own thread. Their frames interleave on the same streams,
+    // in a different order at each party, and tags keep them apart.
+    let session = |s: u64| {
+        move || {
+            (0..10u64)
+                .map(|round| {
+                    let all = Net::broadcast_bytes_tagged(Tag::new(s, round), &[id, s as u8]);
+                    assert!(all.iter().enumerate().all(|(i, b)| b == &[i as u8, s as u8]));
+                    all.len()
+                })
+                .sum::<usize>()
+        }
+    };
+    let (a, b) = if opt.id % 2 == 0 {
+        let b = std::thread::spawn(session(2));
+        (session(1)(), b.join().unwrap())
+    } else {
+        let a = std::thread::spawn(session(1));
+        (a.join().unwrap(), session(2)())
+    };
+    assert_eq!(a + b, 20 * n);
+    // Untagged messages still go in call order, alongside the tagged ones.
+    let all = Net::broadcast_bytes(&[id]);
+    assert_eq!(all.len(), n);
+    println!("Stats: {:#?}", Net::stats());
+    Net::deinit();
 }
@@ -0,0 +1,27 @@
+use mpc_net::Identity;
+
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "keygen", about = "Generate a party's TLS identity.")]
+struct Opt {
+    /// Where to write the certificate
+    #[structopt(parse(from_os_str))]
+    cert: PathBuf,
+
+    /// Where to write the private key
+    #[structopt(parse(from_os_str))]
+    key: PathBuf,
+}
+
+/// Writes a fresh certificate and key, and prints the fingerprint that goes after this party's
+/// address in everyone's hosts file.
+fn main() {
+    let opt = Opt::from_args();
+    let identity = Identity::generate();
+    identity
+        .write(&opt.cert, &opt.key)
+        .expect("Could not write identity");
+    println!("{}", identity.fingerprint());
+}
@@ -0,0 +1,114 @@
+//! What can go wrong on the network.
+//!
+//! The `try_` methods of [MpcNet](crate::MpcNet) return a [NetError]. The other methods raise it
+//! instead: they unwind with the error as the payload, which [catch] turns back into a `Result`.
+//! This lets code deep in a protocol, which cannot return a `Result`, fail in a way that the caller
+//! at the top can report. Raising needs unwinding: under `panic = "abort"`, use the `try_` methods.
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use super::Tag;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NetError {
+    /// We could not connect to `party` during setup.
+    Unreachable { party: usize, reason: String },
+    /// `party`'s connection closed while we waited for its message under `tag`.
+    Disconnected { party: usize, tag: Tag },
+    /// We heard nothing from `party`, not even heartbeats, for the read timeout, while we waited
+    /// for its message under `tag`.
+    TimedOut { party: usize, tag: Tag },
+    /// The message from `party` under `tag` was `len` bytes, which is not the length of what we
+    /// expected.
+    LengthMismatch { party: usize, tag: Tag, len: usize },
+    /// The message from `party` under `tag` did not deserialize.
+    Deserialize {
+        party: usize,
+        tag: Tag,
+        reason: String,
+    },
+    /// The protocol was aborted by `party`, or because of it.
+    Abort { party: usize, reason: String },
+    /// The hosts file of `party` (us) is missing or malformed.
+    Config { party: usize, reason: String },
+}
+
+impl NetError {
+    /// The party at fault, as far as we can tell.
+    pub fn party(&self) -> usize {
+        match self {
+            NetError::Unreachable { party, .. }
+            | NetError::Disconnected { party, .. }
+            | NetError::TimedOut { party, .. }
+            | NetError::LengthMismatch { party, .. }
+            | NetError::Deserialize { party, .. }
+            | NetError::Abort { party, .. }
+            | NetError::Config { party, .. } => *party,
+        }
+    }
+
+    /// The tag of the message we were waiting for, if any.
+    pub fn tag(&self) -> Option<Tag> {
+        match self {
+            NetError::Disconnected { tag, .. }
+            | NetError::TimedOut { tag, .. }
+            | NetError::LengthMismatch { tag, .. }
+            | NetError::Deserialize { tag, .. } => Some(*tag),
+            NetError::Unreachable { .. } | NetError::Abort { .. } | NetError::Config { .. } => None,
+        }
+    }
+
+    /// Unwind with this error, for [catch] to find. Nothing is printed, so run protocols that may
+    /// raise inside [catch].
+    pub fn raise(self) -> ! {
+        panic::resume_unwind(Box::new(self))
+    }
+
+    /// Report which party failed, and where, and exit the process.
+    pub fn exit(self) -> ! {
+        eprintln!("MPC failed: {}", self);
+        std::process::exit(1)
+    }
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetError::Unreachable { party, reason } => {
+                write!(f, "could not connect to party {}: {}", party, reason)
+            }
+            NetError::Disconnected { party, tag } => {
+                write!(f, "party {} disconnected in {}", party, tag)
+            }
+            NetError::TimedOut { party, tag } => write!(f, "party {} timed out in {}", party, tag),
+            NetError::LengthMismatch { party, tag, len } => write!(
+                f,
+                "party {} sent a message of unexpected length {} in {}",
+                party, len, tag
+            ),
+            NetError::Deserialize { party, tag, reason } => write!(
+                f,
+                "party {} sent a message that does not deserialize in {}: {}",
+                party, tag, reason
+            ),
+            NetError::Abort { party, reason } => {
+                write!(f, "aborted by party {}: {}", party, reason)
+            }
+            NetError::Config { party, reason } => {
+                write!(f, "bad hosts file for party {}: {}", party, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NetError {}
+
+/// Run `f`, and return the [NetError] it raises, if any. Other panics pass through.
+pub fn catch<R>(f: impl FnOnce() -> R) -> Result<R, NetError> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        match payload.downcast::<NetError>() {
+            Ok(e) => *e,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    })
+}
@@ -1,30 +1,66 @@
+pub mod error;
 pub mod multi;
+pub mod mux;
+pub mod session;
+pub mod sim;
+pub mod tls;
+pub mod trace;
 pub mod two;
 
+pub use error::{catch, NetError};
+pub use mux::Tag;
+pub use session::Session;
+pub use sim::LocalSimNet;
+pub use tls::{Fingerprint, Identity};
+pub use trace::{op, Trace};
+
 pub use two::MpcTwoNet;
 pub use multi::MpcMultiNet;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Stats {
     pub bytes_sent: usize,
     pub bytes_recv: usize,
     pub broadcasts: usize,
     pub to_king: usize,
     pub from_king: usize,
+    pub all_to_all: usize,
+}
+
+/// Timeouts for the network.
+#[derive(Clone, Debug)]
+pub struct NetConfig {
+    /// How long to keep trying to reach each party during setup.
+    pub connect_timeout: std::time::Duration,
+    /// How long a party may be silent, while we wait for its message, before we give up on it.
+    /// `None` waits forever.
+    pub read_timeout: Option<std::time::Duration>,
+    /// How often to tell peers that we are alive, so that long computations between our messages
+    /// do not look like silence. `None` sends no heartbeats.
+    pub heartbeat: Option<std::time::Duration>,
+    /// The longest message we accept from a party. A longer one aborts the protocol.
+    pub max_frame: usize,
+    /// How many bytes of messages from one party, under all tags together, may wait for us to
+    /// receive them. More aborts the protocol.
+    pub max_queued: usize,
 }
 
-impl std::default::Default for Stats {
+impl std::default::Default for NetConfig {
     fn default() -> Self {
         Self {
-            bytes_sent: 0,
-            bytes_recv: 0,
-            broadcasts: 0,
-            to_king: 0,
-            from_king: 0,
+            connect_timeout: std::time::Duration::from_secs(30),
+            read_timeout: Some(std::time::Duration::from_secs(60)),
+            heartbeat: Some(std::time::Duration::from_secs(5)),
+            max_frame: 1 << 30,
+            max_queued: 2 << 30,
         }
     }
 }
 
+/// The network between the parties.
+///
+/// The `try_` methods return a [NetError] on failure. The others raise it ([NetError::raise]); use
+/// [catch] to get it back.
 pub trait MpcNet {
     /// Am I the first party?
     #[inline]
@@ -37,10 +73,16 @@ pub trait MpcNet {
     fn party_id() -> usize;
     /// Initialize the network layer from a file.
     /// The file should contain one HOST:PORT setting per line, corresponding to the addresses of
-    /// the parties in increasing order.
+    /// the parties in increasing order. A line may also list the party's certificate fingerprint,
+    /// for TLS ([MpcMultiNet::init_with_identity]); this needs an identity, so it is an error here.
     ///
     /// Parties are zero-indexed.
-    fn init_from_file(path: &str, party_id: usize);
+    #[inline]
+    fn init_from_file(path: &str, party_id: usize) {
+        Self::try_init_from_file(path, party_id).unwrap_or_else(|e| e.raise())
+    }
+    /// [init_from_file](Self::init_from_file), failing if some party cannot be reached.
+    fn try_init_from_file(path: &str, party_id: usize) -> Result<(), NetError>;
     /// Is the network layer initalized?
     fn is_init() -> bool;
     /// Uninitialize the network layer, closing all connections.
@@ -49,13 +91,101 @@ pub trait MpcNet {
     fn reset_stats();
     /// Get statistics.
     fn stats() -> Stats;
+    /// Abort the protocol: every other party's pending and future receives fail with
+    /// [NetError::Abort], giving `reason`.
+    fn abort(reason: &str);
+    /// The tag of the next untagged message.
+    ///
+    /// Untagged messages are matched by the order of the calls that send them, so every party must
+    /// make those calls in the same order.
+    fn next_tag() -> Tag;
     /// All parties send bytes to each other.
-    fn broadcast_bytes(bytes: &[u8]) -> Vec<Vec<u8>>;
+    #[inline]
+    fn broadcast_bytes(bytes: &[u8]) -> Vec<Vec<u8>> {
+        Self::broadcast_bytes_tagged(Self::next_tag(), bytes)
+    }
     /// All parties send bytes to the king.
-    fn send_bytes_to_king(bytes: &[u8]) -> Option<Vec<Vec<u8>>>;
+    #[inline]
+    fn send_bytes_to_king(bytes: &[u8]) -> Option<Vec<Vec<u8>>> {
+        Self::send_bytes_to_king_tagged(Self::next_tag(), bytes)
+    }
     /// All parties recv bytes from the king.
     /// Provide bytes iff you're the king!
-    fn recv_bytes_from_king(bytes: Option<Vec<Vec<u8>>>) -> Vec<u8>;
+    #[inline]
+    fn recv_bytes_from_king(bytes: Option<Vec<Vec<u8>>>) -> Vec<u8> {
+        Self::recv_bytes_from_king_tagged(Self::next_tag(), bytes)
+    }
+    /// All parties send (different) bytes to each other: `bytes[i]` goes to party `i`.
+    /// Returns the bytes received from each party, in party order.
+    #[inline]
+    fn all_to_all_bytes(bytes: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+        Self::all_to_all_bytes_tagged(Self::next_tag(), bytes)
+    }
+
+    /// [broadcast_bytes](Self::broadcast_bytes), or the error.
+    #[inline]
+    fn try_broadcast_bytes(bytes: &[u8]) -> Result<Vec<Vec<u8>>, NetError> {
+        Self::try_broadcast_bytes_tagged(Self::next_tag(), bytes)
+    }
+    /// [send_bytes_to_king](Self::send_bytes_to_king), or the error.
+    #[inline]
+    fn try_send_bytes_to_king(bytes: &[u8]) -> Result<Option<Vec<Vec<u8>>>, NetError> {
+        Self::try_send_bytes_to_king_tagged(Self::next_tag(), bytes)
+    }
+    /// [recv_bytes_from_king](Self::recv_bytes_from_king), or the error.
+    #[inline]
+    fn try_recv_bytes_from_king(bytes: Option<Vec<Vec<u8>>>) -> Result<Vec<u8>, NetError> {
+        Self::try_recv_bytes_from_king_tagged(Self::next_tag(), bytes)
+    }
+    /// [all_to_all_bytes](Self::all_to_all_bytes), or the error.
+    #[inline]
+    fn try_all_to_all_bytes(bytes: Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>, NetError> {
+        Self::try_all_to_all_bytes_tagged(Self::next_tag(), bytes)
+    }
+
+    // Tagged messages are matched by their tag, not by call order. Calls with distinct tags may
+    // run concurrently, from different threads, and parties may make them in different orders.
+    // Each tag must be used for one call per party. Sessions [Tag::UNTAGGED] and [Tag::CONTROL]
+    // are reserved.
+
+    /// [broadcast_bytes](Self::broadcast_bytes), under `tag`.
+    #[inline]
+    fn broadcast_bytes_tagged(tag: Tag, bytes: &[u8]) -> Vec<Vec<u8>> {
+        Self::try_broadcast_bytes_tagged(tag, bytes).unwrap_or_else(|e| e.raise())
+    }
+    /// [send_bytes_to_king](Self::send_bytes_to_king), under `tag`.
+    #[inline]
+    fn send_bytes_to_king_tagged(tag: Tag, bytes: &[u8]) -> Option<Vec<Vec<u8>>> {
+        Self::try_send_bytes_to_king_tagged(tag, bytes).unwrap_or_else(|e| e.raise())
+    }
+    /// [recv_bytes_from_king](Self::recv_bytes_from_king), under `tag`.
+    #[inline]
+    fn recv_bytes_from_king_tagged(tag: Tag, bytes: Option<Vec<Vec<u8>>>) -> Vec<u8> {
+        Self::try_recv_bytes_from_king_tagged(tag, bytes).unwrap_or_else(|e| e.raise())
+    }
+    /// [all_to_all_bytes](Self::all_to_all_bytes), under `tag`.
+    #[inline]
+    fn all_to_all_bytes_tagged(tag: Tag, bytes: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+        Self::try_all_to_all_bytes_tagged(tag, bytes).unwrap_or_else(|e| e.raise())
+    }
+
+    /// [broadcast_bytes_tagged](Self::broadcast_bytes_tagged), or the error.
+    fn try_broadcast_bytes_tagged(tag: Tag, bytes: &[u8]) -> Result<Vec<Vec<u8>>, NetError>;
+    /// [send_bytes_to_king_tagged](Self::send_bytes_to_king_tagged), or the error.
+    fn try_send_bytes_to_king_tagged(
+        tag: Tag,
+        bytes: &[u8],
+    ) -> Result<Option<Vec<Vec<u8>>>, NetError>;
+    /// [recv_bytes_from_king_tagged](Self::recv_bytes_from_king_tagged), or the error.
+    fn try_recv_bytes_from_king_tagged(
+        tag: Tag,
+        bytes: Option<Vec<Vec<u8>>>,
+    ) -> Result<Vec<u8>, NetError>;
+    /// [all_to_all_bytes_tagged](Self::all_to_all_bytes_tagged), or the error.
+    fn try_all_to_all_bytes_tagged(
+        tag: Tag,
+        bytes: Vec<Vec<u8>>,
+    ) -> Result<Vec<Vec<u8>>, NetError>;
 
     /// Everyone sends bytes to the king, who recieves those bytes, runs a computation on them, and
     /// redistributes the resulting bytes.
@@ -67,4 +197,15 @@ pub trait MpcNet {
         let king_response = Self::send_bytes_to_king(bytes).map(f);
         Self::recv_bytes_from_king(king_response)
     }
+
+    /// [king_compute](Self::king_compute), under `tag` and then `tag.next()`.
+    #[inline]
+    fn king_compute_tagged(
+        tag: Tag,
+        bytes: &[u8],
+        f: impl Fn(Vec<Vec<u8>>) -> Vec<Vec<u8>>,
+    ) -> Vec<u8> {
+        let king_response = Self::send_bytes_to_king_tagged(tag, bytes).map(f);
+        Self::recv_bytes_from_king_tagged(tag.next(), king_response)
+    }
 }
@@ -1,24 +1,28 @@
 use lazy_static::lazy_static;
 use log::debug;
-use rayon::prelude::*;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Read, Write};
 use std::net::{SocketAddr, TcpListener, TcpStream};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 
-use ark_std::{end_timer, start_timer};
+use super::mux::{Mux, Stream, Tag};
+use super::session::Session;
+use super::tls::{self, Fingerprint, Identity};
+use super::trace::Trace;
+use super::{MpcNet, NetConfig, NetError};
 
-use super::{MpcNet, Stats};
-
-#[macro_use]
 lazy_static! {
     static ref CONNECTIONS: Mutex<Connections> = Mutex::new(Connections::default());
 }
 
 /// Macro for locking the FieldChannel singleton in the current scope.
+///
+/// Nothing panics while [Connections] is half updated, so a lock poisoned by a panicking holder is
+/// still good.
 macro_rules! get_ch {
     () => {
-        CONNECTIONS.lock().expect("Poisoned FieldChannel")
+        CONNECTIONS.lock().unwrap_or_else(PoisonError::into_inner)
     };
 }
 
@@ -26,14 +30,19 @@ macro_rules! get_ch {
 struct Peer {
     id: usize,
     addr: SocketAddr,
-    stream: Option<TcpStream>,
+    /// Their certificate, if links are over TLS.
+    fingerprint: Option<Fingerprint>,
+    /// Only used while connecting; afterwards, the stream belongs to the [Mux].
+    stream: Option<Stream>,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default)]
 struct Connections {
     id: usize,
     peers: Vec<Peer>,
-    stats: Stats,
+    /// Empty if uninitialized
+    session: Option<Arc<Session>>,
+    config: NetConfig,
 }
 
 impl std::default::Default for Peer {
@@ -41,6 +50,7 @@ impl std::default::Default for Peer {
         Self {
             id: 0,
             addr: "127.0.0.1:8000".parse().unwrap(),
+            fingerprint: None,
             stream: None,
         }
     }
@@ -48,234 +58,254 @@ impl std::default::Default for Peer {
 
 impl Connections {
     /// Given a path and the `id` of oneself, initialize the structure
-    fn init_from_path(&mut self, path: &str, id: usize) {
-        let f = BufReader::new(File::open(path).expect("host configuration path"));
+    fn init_from_path(&mut self, path: &str, id: usize) -> Result<(), NetError> {
+        let config = |reason: String| NetError::Config { party: id, reason };
+        let f = File::open(path).map_err(|e| config(format!("cannot open {}: {}", path, e)))?;
+        self.peers.clear();
         let mut peer_id = 0;
-        for line in f.lines() {
-            let line = line.unwrap();
-            let trimmed = line.trim();
-            if trimmed.len() > 0 {
-                let addr: SocketAddr = trimmed
+        for line in BufReader::new(f).lines() {
+            let line = line.map_err(|e| config(format!("cannot read {}: {}", path, e)))?;
+            let mut words = line.split_whitespace();
+            if let Some(addr) = words.next() {
+                let addr: SocketAddr = addr
                     .parse()
-                    .unwrap_or_else(|e| panic!("bad socket address: {}:\n{}", trimmed, e));
+                    .map_err(|e| config(format!("bad socket address {}: {}", addr, e)))?;
+                let fingerprint =
+                    words.next().map(|f| f.parse()).transpose().map_err(|e| {
+                        config(format!("bad fingerprint for party {}: {}", peer_id, e))
+                    })?;
                 let peer = Peer {
                     id: peer_id,
                     addr,
+                    fingerprint,
                     stream: None,
                 };
                 self.peers.push(peer);
                 peer_id += 1;
             }
         }
-        assert!(id < self.peers.len());
+        if id >= self.peers.len() {
+            return Err(config(format!(
+                "{} lists {} parties, so there is no party {}",
+                path,
+                self.peers.len(),
+                id
+            )));
+        }
         self.id = id;
+        Ok(())
     }
-    fn connect_to_all(&mut self) {
+    /// Connect to every peer. If the hosts file lists fingerprints, the links are TLS, as
+    /// `identity`.
+    fn connect_to_all(&mut self, identity: Option<&Identity>) -> Result<(), NetError> {
         //let timer = start_timer!(|| "Connecting");
         let n = self.peers.len();
+        let pinned = self
+            .peers
+            .iter()
+            .filter(|p| p.fingerprint.is_some())
+            .count();
+        let config = |reason: &str| {
+            Err(NetError::Config {
+                party: self.id,
+                reason: reason.to_owned(),
+            })
+        };
+        if pinned != 0 && pinned != n {
+            return config("it must list a fingerprint for every party, or for none");
+        }
+        match identity {
+            Some(identity) if self.peers[self.id].fingerprint != Some(identity.fingerprint()) => {
+                return config("it does not list our certificate as ours");
+            }
+            None if pinned != 0 => return config("it lists fingerprints, so we need an identity"),
+            _ => {}
+        }
+        let timeout = self.config.connect_timeout;
+        let listener =
+            TcpListener::bind(self.peers[self.id].addr).map_err(|e| NetError::Unreachable {
+                party: self.id,
+                reason: format!("we cannot listen on {}: {}", self.peers[self.id].addr, e),
+            })?;
         for from_id in 0..n {
             for to_id in (from_id + 1)..n {
                 debug!("{} to {}", from_id, to_id);
                 if self.id == from_id {
-                    let to_addr = self.peers[to_id].addr;
                     debug!("Contacting {}", to_id);
-                    let stream = loop {
-                        let mut ms_waited = 0;
-                        match TcpStream::connect(to_addr) {
-                            Ok(s) => break s,
-                            Err(e) => match e.kind() {
-                                std::io::ErrorKind::ConnectionRefused
-                                | std::io::ErrorKind::ConnectionReset => {
-                                    ms_waited += 10;
-                                    std::thread::sleep(std::time::Duration::from_millis(10));
-                                    if ms_waited % 3_000 == 0 {
-                                        debug!("Still waiting");
-                                    } else if ms_waited > 30_000 {
-                                        panic!("Could not find peer in 30s");
-                                    }
-                                }
-                                _ => {
-                                    panic!("Error during FieldChannel::new: {}", e);
-                                }
-                            },
-                        }
-                    };
-                    stream.set_nodelay(true).unwrap();
-                    self.peers[to_id].stream = Some(stream);
+                    let stream = dial(self.peers[to_id].addr, to_id, timeout)?;
+                    self.peers[to_id].stream = Some(self.secure(stream, to_id, identity, true)?);
                 } else if self.id == to_id {
                     debug!("Awaiting {}", from_id);
-                    let listener = TcpListener::bind(self.peers[self.id].addr).unwrap();
-                    let (stream, _addr) = listener.accept().unwrap();
-                    stream.set_nodelay(true).unwrap();
-                    self.peers[from_id].stream = Some(stream);
+                    let stream = accept(&listener, from_id, timeout)?;
+                    self.peers[from_id].stream =
+                        Some(self.secure(stream, from_id, identity, false)?);
                 }
             }
             // Sender for next round waits for note from this sender to prevent race on receipt.
             if from_id + 1 < n {
                 if self.id == from_id {
-                    self.peers[self.id + 1]
-                        .stream
-                        .as_mut()
-                        .unwrap()
+                    let stream = self.peers[self.id + 1].stream.as_mut().unwrap();
+                    stream
+                        .writer
                         .write_all(&[0u8])
-                        .unwrap();
+                        .and_then(|()| stream.writer.flush())
+                        .map_err(|e| unreachable(self.id + 1, e))?;
                 } else if self.id == from_id + 1 {
                     self.peers[self.id - 1]
                         .stream
                         .as_mut()
                         .unwrap()
+                        .reader
                         .read_exact(&mut [0u8])
-                        .unwrap();
+                        .map_err(|e| unreachable(self.id - 1, e))?;
                 }
             }
         }
-        // Do a round with the king, to be sure everyone is ready
-        let from_all = self.send_to_king(&[self.id as u8]);
-        self.recv_from_king(from_all);
-        for id in 0..n {
-            if id != self.id {
-                assert!(self.peers[id].stream.is_some());
+        let mut streams = Vec::new();
+        for peer in &mut self.peers {
+            let stream = peer.stream.take();
+            if let Some(stream) = &stream {
+                // From now on, the multiplexer watches for silence.
+                stream
+                    .socket
+                    .set_read_timeout(None)
+                    .map_err(|e| unreachable(peer.id, e))?;
             }
+            streams.push(stream);
         }
+        let session = Session::new(Mux::new(self.id, streams, &self.config));
+        // Do a round with the king, to be sure everyone is ready
+        let from_all = session.send_bytes_to_king(session.next_tag(), &[self.id as u8])?;
+        session.recv_bytes_from_king(session.next_tag(), from_all)?;
+        session.reset_stats();
+        self.session = Some(Arc::new(session));
         //end_timer!(timer);
+        Ok(())
     }
-    fn am_king(&self) -> bool {
-        self.id == 0
-    }
-    fn broadcast(&mut self, bytes_out: &[u8]) -> Vec<Vec<u8>> {
-        //let timer = start_timer!(|| format!("Broadcast {}", bytes_out.len()));
-        let m = bytes_out.len();
-        let own_id = self.id;
-        self.stats.bytes_sent += (self.peers.len() - 1) * m;
-        self.stats.bytes_recv += (self.peers.len() - 1) * m;
-        self.stats.broadcasts += 1;
-        let r = self
-            .peers
-            .par_iter_mut()
-            .enumerate()
-            .map(|(id, peer)| {
-                let mut bytes_in = vec![0u8; m];
-                if id < own_id {
-                    let stream = peer.stream.as_mut().unwrap();
-                    stream.read_exact(&mut bytes_in[..]).unwrap();
-                    stream.write_all(bytes_out).unwrap();
-                } else if id == own_id {
-                    bytes_in.copy_from_slice(bytes_out);
-                } else {
-                    let stream = peer.stream.as_mut().unwrap();
-                    stream.write_all(bytes_out).unwrap();
-                    stream.read_exact(&mut bytes_in[..]).unwrap();
-                };
-                bytes_in
-            })
-            .collect();
-        //end_timer!(timer);
-        r
-    }
-    fn send_to_king(&mut self, bytes_out: &[u8]) -> Option<Vec<Vec<u8>>> {
-        //let timer = start_timer!(|| format!("To king {}", bytes_out.len()));
-        let m = bytes_out.len();
-        let own_id = self.id;
-        self.stats.to_king += 1;
-        let r = if self.am_king() {
-            self.stats.bytes_recv += (self.peers.len() - 1) * m;
-            Some(
-                self.peers
-                    .par_iter_mut()
-                    .enumerate()
-                    .map(|(id, peer)| {
-                        let mut bytes_in = vec![0u8; m];
-                        if id == own_id {
-                            bytes_in.copy_from_slice(bytes_out);
-                        } else {
-                            let stream = peer.stream.as_mut().unwrap();
-                            stream.read_exact(&mut bytes_in[..]).unwrap();
-                        };
-                        bytes_in
-                    })
-                    .collect(),
-            )
-        } else {
-            self.stats.bytes_sent += m;
-            self.peers[0]
-                .stream
-                .as_mut()
-                .unwrap()
-                .write_all(bytes_out)
-                .unwrap();
-            None
-        };
-        //end_timer!(timer);
-        r
-    }
-    fn recv_from_king(&mut self, bytes_out: Option<Vec<Vec<u8>>>) -> Vec<u8> {
-        let own_id = self.id;
-        self.stats.from_king += 1;
-        if self.am_king() {
-            let bytes_out = bytes_out.unwrap();
-            let m = bytes_out[0].len();
-            //let timer = start_timer!(|| format!("From king {}", m));
-            let bytes_size = (m as u64).to_le_bytes();
-            self.stats.bytes_sent += (self.peers.len() - 1) * (m + 8);
-            self.peers
-                .par_iter_mut()
-                .enumerate()
-                .filter(|p| p.0 != own_id)
-                .for_each(|(id, peer)| {
-                    let stream = peer.stream.as_mut().unwrap();
-                    assert_eq!(bytes_out[id].len(), m);
-                    stream.write_all(&bytes_size).unwrap();
-                    stream.write_all(&bytes_out[id]).unwrap();
-                });
-            //end_timer!(timer);
-            bytes_out[own_id].clone()
-        } else {
-            let stream = self.peers[0].stream.as_mut().unwrap();
-            let mut bytes_size = [0u8; 8];
-            stream.read_exact(&mut bytes_size).unwrap();
-            let m = u64::from_le_bytes(bytes_size) as usize;
-            self.stats.bytes_recv += m;
-            let mut bytes_in = vec![0u8; m];
-            stream.read_exact(&mut bytes_in).unwrap();
-            bytes_in
+    /// Wrap a new connection to `peer` in TLS, if we use it. `client` is whether we connected.
+    fn secure(
+        &self,
+        socket: TcpStream,
+        peer: usize,
+        identity: Option<&Identity>,
+        client: bool,
+    ) -> Result<Stream, NetError> {
+        match (identity, self.peers[peer].fingerprint) {
+            (Some(identity), Some(fingerprint)) => {
+                tls::handshake(socket, identity, fingerprint, client).map_err(|e| {
+                    NetError::Unreachable {
+                        party: peer,
+                        reason: format!("TLS handshake failed: {}", e),
+                    }
+                })
+            }
+            _ => Ok(socket.into()),
         }
     }
     fn uninit(&mut self) {
-        for p in &mut self.peers {
-            p.stream = None;
-        }
+        self.session = None;
+        self.peers.clear();
     }
 }
 
+/// The multi-party network.
+///
+/// Calls go to the [Session] that the calling thread has [entered](Session::enter), or else to the
+/// process-wide session set up by [init_from_file](MpcNet::init_from_file).
 pub struct MpcMultiNet;
 
+impl MpcMultiNet {
+    /// The session that calls from this thread go to. Cloned out, so that no lock is held while
+    /// waiting.
+    #[inline]
+    pub fn session() -> Arc<Session> {
+        Self::try_session().expect("Uninitialized MpcMultiNet. Did you forget init_from_file(..)?")
+    }
+
+    #[inline]
+    pub(crate) fn try_session() -> Option<Arc<Session>> {
+        Session::current().or_else(|| get_ch!().session.clone())
+    }
+
+    /// Connect to the parties in the hosts file at `path`, as party `party_id`, and return the
+    /// session, without making it process-wide.
+    ///
+    /// Hosts file lines may list a certificate fingerprint after the address (`HOST:PORT
+    /// FINGERPRINT`). Then links are mutually authenticated TLS, we are `identity`, and each peer
+    /// must present the certificate listed for it.
+    pub fn connect(
+        path: &str,
+        party_id: usize,
+        identity: Option<&Identity>,
+        config: &NetConfig,
+    ) -> Result<Arc<Session>, NetError> {
+        let mut ch = Connections {
+            config: config.clone(),
+            ..Connections::default()
+        };
+        ch.init_from_path(path, party_id)?;
+        ch.connect_to_all(identity)?;
+        Ok(ch.session.unwrap())
+    }
+
+    /// Like [try_init_from_file](MpcNet::try_init_from_file), but over TLS, as `identity`. The
+    /// hosts file must list every party's fingerprint ([MpcMultiNet::connect]).
+    pub fn init_with_identity(
+        path: &str,
+        party_id: usize,
+        identity: &Identity,
+    ) -> Result<(), NetError> {
+        let mut ch = get_ch!();
+        ch.init_from_path(path, party_id)?;
+        ch.connect_to_all(Some(identity))
+    }
+
+    /// Set the timeouts for the process-wide session. This takes effect at the next init.
+    pub fn set_config(config: NetConfig) {
+        get_ch!().config = config;
+    }
+
+    /// [Session::start_trace], in the current session.
+    pub fn start_trace() {
+        Self::session().start_trace()
+    }
+
+    /// [Session::take_trace], in the current session.
+    pub fn take_trace() -> Option<Trace> {
+        Self::session().take_trace()
+    }
+
+    /// [Session::local], in the current session.
+    #[inline]
+    pub fn with_local<T: std::any::Any + Send + Default, R>(f: impl FnOnce(&mut T) -> R) -> R {
+        Self::session().local(f)
+    }
+}
+
 impl MpcNet for MpcMultiNet {
     #[inline]
     fn party_id() -> usize {
-        get_ch!().id
+        Self::session().party_id()
     }
 
     #[inline]
     fn n_parties() -> usize {
-        get_ch!().peers.len()
+        Self::session().n_parties()
     }
 
     #[inline]
-    fn init_from_file(path: &str, party_id: usize) {
+    fn try_init_from_file(path: &str, party_id: usize) -> Result<(), NetError> {
         let mut ch = get_ch!();
-        ch.init_from_path(path, party_id);
-        ch.connect_to_all();
+        ch.init_from_path(path, party_id)?;
+        ch.connect_to_all(None)
     }
 
     #[inline]
     fn is_init() -> bool {
-        get_ch!()
-            .peers
-            .first()
-            .map(|p| p.stream.is_some())
-            .unwrap_or(false)
+        Self::try_session().is_some()
     }
 
+    /// Closes the process-wide session. Entered sessions close when their last handle drops.
     #[inline]
     fn deinit() {
         get_ch!().uninit()
@@ -283,26 +313,256 @@ impl MpcNet for MpcMultiNet {
 
     #[inline]
     fn reset_stats() {
-        get_ch!().stats = Stats::default();
+        Self::session().reset_stats();
     }
 
     #[inline]
     fn stats() -> crate::Stats {
-        get_ch!().stats.clone()
+        Self::try_session().map_or_else(Default::default, |s| s.stats())
+    }
+
+    #[inline]
+    fn abort(reason: &str) {
+        Self::session().abort(reason)
+    }
+
+    #[inline]
+    fn next_tag() -> Tag {
+        Self::session().next_tag()
+    }
+
+    #[inline]
+    fn try_broadcast_bytes_tagged(tag: Tag, bytes: &[u8]) -> Result<Vec<Vec<u8>>, NetError> {
+        Self::session().broadcast_bytes(tag, bytes)
     }
 
     #[inline]
-    fn broadcast_bytes(bytes: &[u8]) -> Vec<Vec<u8>> {
-        get_ch!().broadcast(bytes)
+    fn try_send_bytes_to_king_tagged(
+        tag: Tag,
+        bytes: &[u8],
+    ) -> Result<Option<Vec<Vec<u8>>>, NetError> {
+        Self::session().send_bytes_to_king(tag, bytes)
     }
 
     #[inline]
-    fn send_bytes_to_king(bytes: &[u8]) -> Option<Vec<Vec<u8>>> {
-        get_ch!().send_to_king(bytes)
+    fn try_recv_bytes_from_king_tagged(
+        tag: Tag,
+        bytes: Option<Vec<Vec<u8>>>,
+    ) -> Result<Vec<u8>, NetError> {
+        Self::session().recv_bytes_from_king(tag, bytes)
     }
 
     #[inline]
-    fn recv_bytes_from_king(bytes: Option<Vec<Vec<u8>>>) -> Vec<u8> {
-        get_ch!().recv_from_king(bytes)
+    fn try_all_to_all_bytes_tagged(
+        tag: Tag,
+        bytes: Vec<Vec<u8>>,
+    ) -> Result<Vec<Vec<u8>>, NetError> {
+        Self::session().all_to_all_bytes(tag, bytes)
+    }
+}
+
+/// Connect to `party` at `addr`, retrying until it listens or `timeout` passes.
+pub(crate) fn dial(
+    addr: SocketAddr,
+    party: usize,
+    timeout: Duration,
+) -> Result<TcpStream, NetError> {
+    let start = Instant::now();
+    let mut reported = start;
+    let stream = loop {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(s) => break s,
+            Err(e) => match e.kind() {
+                std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::ConnectionReset => {
+                    if start.elapsed() > timeout {
+                        return Err(NetError::Unreachable {
+                            party,
+                            reason: format!("nobody listened at {} for {:?}", addr, timeout),
+                        });
+                    }
+                    if reported.elapsed() > Duration::from_secs(3) {
+                        debug!("Still waiting");
+                        reported = Instant::now();
+                    }
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                _ => return Err(unreachable(party, e)),
+            },
+        }
+    };
+    setup(stream, party, timeout)
+}
+
+/// Accept a connection from `party`, waiting up to `timeout`.
+pub(crate) fn accept(
+    listener: &TcpListener,
+    party: usize,
+    timeout: Duration,
+) -> Result<TcpStream, NetError> {
+    let start = Instant::now();
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| unreachable(party, e))?;
+    let stream = loop {
+        match listener.accept() {
+            Ok((s, _addr)) => break s,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                if start.elapsed() > timeout {
+                    return Err(NetError::Unreachable {
+                        party,
+                        reason: format!("it did not connect to us in {:?}", timeout),
+                    });
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Err(e) => return Err(unreachable(party, e)),
+        }
+    };
+    stream
+        .set_nonblocking(false)
+        .map_err(|e| unreachable(party, e))?;
+    setup(stream, party, timeout)
+}
+
+/// Configure a new connection to `party`: no Nagle, and reads that give up after `timeout` while
+/// we set up.
+fn setup(stream: TcpStream, party: usize, timeout: Duration) -> Result<TcpStream, NetError> {
+    stream
+        .set_nodelay(true)
+        .and_then(|()| stream.set_read_timeout(Some(timeout)))
+        .map_err(|e| unreachable(party, e))?;
+    Ok(stream)
+}
+
+fn unreachable(party: usize, e: std::io::Error) -> NetError {
+    NetError::Unreachable {
+        party,
+        reason: e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A hosts file for `n` parties on loopback ports from `port`.
+    fn hosts(name: &str, port: u16, n: u16) -> String {
+        let path = std::env::temp_dir().join(format!("mpc-net-{}-{}", name, std::process::id()));
+        let lines: Vec<String> = (port..port + n)
+            .map(|p| format!("127.0.0.1:{}", p))
+            .collect();
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    fn config(read_timeout: u64, heartbeat: Option<u64>) -> NetConfig {
+        NetConfig {
+            connect_timeout: Duration::from_millis(300),
+            read_timeout: Some(Duration::from_millis(read_timeout)),
+            heartbeat: heartbeat.map(Duration::from_millis),
+            ..NetConfig::default()
+        }
+    }
+
+    /// Run two parties over loopback; party 1 sleeps `delay` ms before it broadcasts.
+    fn delayed_broadcast(port: u16, config: NetConfig, delay: u64) -> Result<(), NetError> {
+        let path = hosts("delayed", port, 2);
+        let late = {
+            let (path, config) = (path.clone(), config.clone());
+            std::thread::spawn(move || {
+                let session = MpcMultiNet::connect(&path, 1, None, &config).unwrap();
+                std::thread::sleep(Duration::from_millis(delay));
+                // Party 0 may have given up by now.
+                let _ = session.broadcast_bytes(session.next_tag(), &[1]);
+            })
+        };
+        let session = MpcMultiNet::connect(&path, 0, None, &config)?;
+        let r = session
+            .broadcast_bytes(session.next_tag(), &[0])
+            .map(|_| ());
+        drop(session);
+        late.join().unwrap();
+        r
+    }
+
+    #[test]
+    fn missing_party_is_unreachable() {
+        let start = Instant::now();
+        let r = MpcMultiNet::connect(&hosts("missing", 27330, 2), 0, None, &config(300, None));
+        assert!(matches!(r, Err(NetError::Unreachable { party: 1, .. })));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn bad_hosts_file_is_an_error() {
+        let missing = std::env::temp_dir().join("mpc-net-no-such-hosts-file");
+        let r = MpcMultiNet::connect(missing.to_str().unwrap(), 0, None, &config(300, None));
+        assert!(matches!(r, Err(NetError::Config { party: 0, .. })));
+        let path = std::env::temp_dir().join(format!("mpc-net-bad-{}", std::process::id()));
+        std::fs::write(&path, "127.0.0.1:27360\nnot-an-address\n").unwrap();
+        let r = MpcMultiNet::connect(path.to_str().unwrap(), 0, None, &config(300, None));
+        assert!(matches!(r, Err(NetError::Config { party: 0, .. })));
+        let r = MpcMultiNet::connect(&hosts("short", 27360, 2), 2, None, &config(300, None));
+        assert!(matches!(r, Err(NetError::Config { party: 2, .. })));
+    }
+
+    /// Run two parties over loopback; party 1 sends `frames` to party 0, which waits for another.
+    fn flood(port: u16, config: NetConfig, frames: Vec<Vec<u8>>) -> Result<(), NetError> {
+        let path = hosts("flood", port, 2);
+        let sender = {
+            let (path, config) = (path.clone(), config.clone());
+            std::thread::spawn(move || {
+                let session = MpcMultiNet::connect(&path, 1, None, &config).unwrap();
+                for f in frames {
+                    let _ = session.send_bytes_to_king(session.next_tag(), &f);
+                }
+            })
+        };
+        let session = MpcMultiNet::connect(&path, 0, None, &config)?;
+        sender.join().unwrap();
+        session.send_bytes_to_king(Tag::new(1, 0), &[]).map(|_| ())
+    }
+
+    #[test]
+    fn long_frame_aborts() {
+        let config = NetConfig {
+            max_frame: 64,
+            ..config(1_000, None)
+        };
+        assert!(matches!(
+            flood(27370, config, vec![vec![0; 100]]),
+            Err(NetError::Abort { party: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn full_inbox_aborts() {
+        let config = NetConfig {
+            max_queued: 100,
+            ..config(1_000, None)
+        };
+        assert!(matches!(
+            flood(27380, config, vec![vec![0; 40]; 3]),
+            Err(NetError::Abort { party: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn silent_party_times_out() {
+        assert_eq!(
+            delayed_broadcast(27340, config(200, None), 1_000),
+            Err(NetError::TimedOut {
+                party: 1,
+                tag: Tag::new(Tag::UNTAGGED, 3)
+            })
+        );
+    }
+
+    #[test]
+    fn heartbeats_keep_slow_party_alive() {
+        assert_eq!(
+            delayed_broadcast(27350, config(200, Some(20)), 1_000),
+            Ok(())
+        );
     }
 }
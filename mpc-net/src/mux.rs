@@ -0,0 +1,526 @@
+//! Tagged frames, multiplexed over one stream per peer.
+//!
+//! Every message travels as a frame: its [Tag], its length, and its bytes. A background thread per
+//! peer reads frames as they arrive and files them by `(peer, tag)`, and receivers wait for the
+//! frame they want. So messages are matched by tag rather than by arrival order, and operations
+//! with different tags can run concurrently, from different threads, without deadlocking. A peer
+//! that sends a frame longer than [NetConfig::max_frame], or gets more than [NetConfig::max_queued]
+//! bytes ahead of us, aborts the protocol.
+//!
+//! A stream is a TCP connection, possibly wrapped in TLS ([crate::tls]). Peers in the same process
+//! ([Mux::local]) skip the streams, and file frames directly.
+//!
+//! Control frames, in session [Tag::CONTROL], are not filed: heartbeats show that a peer is alive
+//! while it computes, and an abort ends the protocol for everyone.
+use log::debug;
+use serde::Serialize;
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::{NetConfig, NetError};
+
+/// Names a logical message: which (sub-)protocol session it belongs to, and which round of it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct Tag {
+    pub session: u64,
+    pub round: u64,
+}
+
+impl Tag {
+    /// Session 0 is used by the untagged network methods, in call order.
+    pub const UNTAGGED: u64 = 0;
+    /// The last session is for the network's own frames.
+    pub const CONTROL: u64 = u64::MAX;
+
+    pub fn new(session: u64, round: u64) -> Self {
+        Self { session, round }
+    }
+
+    /// The tag of the following round in the same session.
+    pub fn next(self) -> Self {
+        Self::new(self.session, self.round + 1)
+    }
+
+    const HEARTBEAT: Tag = Tag {
+        session: Tag::CONTROL,
+        round: 0,
+    };
+    const ABORT: Tag = Tag {
+        session: Tag::CONTROL,
+        round: 1,
+    };
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.session == Tag::UNTAGGED {
+            write!(f, "round {}", self.round)
+        } else {
+            write!(f, "round {} of session {}", self.round, self.session)
+        }
+    }
+}
+
+/// Frame header: session, round, payload length.
+const HEADER_LEN: usize = 24;
+
+struct InboxState {
+    frames: HashMap<(usize, Tag), VecDeque<Vec<u8>>>,
+    /// How many bytes of frames from each peer are filed.
+    queued: Vec<usize>,
+    closed: Vec<bool>,
+    /// When we last heard from each peer.
+    heard: Vec<Instant>,
+    /// The first abort we heard of: who, and why.
+    aborted: Option<(usize, String)>,
+}
+
+struct Inbox {
+    state: Mutex<InboxState>,
+    arrived: Condvar,
+    /// The most bytes of frames from one peer that we file.
+    max_queued: usize,
+}
+
+impl Inbox {
+    fn new(n: usize, max_queued: usize) -> Self {
+        Self {
+            state: Mutex::new(InboxState {
+                frames: HashMap::new(),
+                queued: vec![0; n],
+                closed: vec![false; n],
+                heard: vec![Instant::now(); n],
+                aborted: None,
+            }),
+            arrived: Condvar::new(),
+            max_queued,
+        }
+    }
+
+    /// File a frame from `peer`, or act on it if it is a control frame, and wake any receivers.
+    ///
+    /// Returns false, having aborted, if filing it would queue too much from `peer`.
+    fn deliver(&self, peer: usize, tag: Tag, bytes: Vec<u8>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.heard[peer] = Instant::now();
+        match tag {
+            Tag::HEARTBEAT => return true,
+            Tag::ABORT => {
+                if state.aborted.is_none() {
+                    state.aborted = Some((peer, String::from_utf8_lossy(&bytes).into_owned()));
+                }
+            }
+            _ if state.queued[peer] + bytes.len() > self.max_queued => {
+                drop(state);
+                self.fault(
+                    peer,
+                    format!("sent more than {} bytes ahead of us", self.max_queued),
+                );
+                return false;
+            }
+            _ => {
+                state.queued[peer] += bytes.len();
+                state
+                    .frames
+                    .entry((peer, tag))
+                    .or_default()
+                    .push_back(bytes);
+            }
+        }
+        self.arrived.notify_all();
+        true
+    }
+
+    /// Abort because `peer` broke the framing rules, unless something aborted already.
+    fn fault(&self, peer: usize, reason: String) {
+        debug!("Party {} misbehaved: {}", peer, reason);
+        let mut state = self.state.lock().unwrap();
+        if state.aborted.is_none() {
+            state.aborted = Some((peer, reason));
+        }
+        self.arrived.notify_all();
+    }
+
+    /// Note that `peer` will send nothing more.
+    fn close(&self, peer: usize) {
+        self.state.lock().unwrap().closed[peer] = true;
+        self.arrived.notify_all();
+    }
+}
+
+/// A connection to one peer: its two directions, and the socket under them.
+pub struct Stream {
+    pub reader: Box<dyn Read + Send>,
+    pub writer: Box<dyn Write + Send>,
+    /// Shut down when the [Mux] drops, which ends the reader.
+    pub socket: TcpStream,
+}
+
+impl fmt::Debug for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stream")
+            .field("socket", &self.socket)
+            .finish()
+    }
+}
+
+/// A plaintext stream.
+impl From<TcpStream> for Stream {
+    fn from(socket: TcpStream) -> Self {
+        Self {
+            reader: Box::new(socket.try_clone().expect("Could not clone peer stream")),
+            writer: Box::new(socket.try_clone().expect("Could not clone peer stream")),
+            socket,
+        }
+    }
+}
+
+/// How we reach one peer.
+enum Link {
+    /// A stream. A background thread reads it.
+    Stream {
+        writer: Mutex<Box<dyn Write + Send>>,
+        socket: TcpStream,
+    },
+    /// The peer's inbox, in this process.
+    Local(Arc<Inbox>),
+}
+
+impl Link {
+    /// Send a frame from party `from`.
+    fn send(&self, from: usize, tag: Tag, bytes: &[u8]) -> io::Result<()> {
+        match self {
+            Link::Stream { writer, .. } => {
+                let mut header = [0u8; HEADER_LEN];
+                header[..8].copy_from_slice(&tag.session.to_le_bytes());
+                header[8..16].copy_from_slice(&tag.round.to_le_bytes());
+                header[16..].copy_from_slice(&(bytes.len() as u64).to_le_bytes());
+                let mut writer = writer.lock().unwrap();
+                writer.write_all(&header)?;
+                writer.write_all(bytes)?;
+                writer.flush()
+            }
+            Link::Local(inbox) => {
+                inbox.deliver(from, tag, bytes.to_vec());
+                Ok(())
+            }
+        }
+    }
+}
+
+pub struct Mux {
+    id: usize,
+    links: Arc<Vec<Option<Link>>>,
+    inbox: Arc<Inbox>,
+    readers: Vec<JoinHandle<()>>,
+    /// Dropped to stop the heartbeats.
+    heartbeat: Option<(mpsc::Sender<()>, JoinHandle<()>)>,
+    read_timeout: Option<Duration>,
+    bytes_sent: AtomicUsize,
+    /// Counted as frames are received, not as they arrive, so that a reset is not undone by
+    /// frames that peers sent early.
+    bytes_recv: AtomicUsize,
+}
+
+impl Mux {
+    /// Start multiplexing over `streams`, which holds a stream for each peer, and `None` at `id`.
+    pub fn new(id: usize, streams: Vec<Option<Stream>>, config: &NetConfig) -> Self {
+        let inbox = Arc::new(Inbox::new(streams.len(), config.max_queued));
+        let max_frame = config.max_frame;
+        let mut links = Vec::new();
+        let mut readers = Vec::new();
+        for (peer, stream) in streams.into_iter().enumerate() {
+            match stream {
+                Some(Stream {
+                    reader,
+                    writer,
+                    socket,
+                }) => {
+                    let inbox = inbox.clone();
+                    readers.push(std::thread::spawn(move || {
+                        read_frames(peer, reader, &inbox, max_frame)
+                    }));
+                    links.push(Some(Link::Stream {
+                        writer: Mutex::new(writer),
+                        socket,
+                    }));
+                }
+                None => {
+                    assert_eq!(peer, id, "Missing stream for peer {}", peer);
+                    links.push(None);
+                }
+            }
+        }
+        let links = Arc::new(links);
+        let heartbeat = config.heartbeat.map(|interval| {
+            let (stop, stopped) = mpsc::channel();
+            let links = links.clone();
+            let beat = std::thread::spawn(move || {
+                while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    for link in links.iter().flatten() {
+                        // A failed link shows up at its reader.
+                        let _ = link.send(id, Tag::HEARTBEAT, &[]);
+                    }
+                }
+            });
+            (stop, beat)
+        });
+        Self {
+            id,
+            links,
+            inbox,
+            readers,
+            heartbeat,
+            read_timeout: config.read_timeout,
+            bytes_sent: AtomicUsize::new(0),
+            bytes_recv: AtomicUsize::new(0),
+        }
+    }
+
+    /// Multiplexers for `n` parties in this process, connected to each other in memory. Entry `i`
+    /// is party `i`'s. They wait on each other without timeouts.
+    pub fn local(n: usize) -> Vec<Self> {
+        let inboxes: Vec<Arc<Inbox>> = (0..n)
+            .map(|_| Arc::new(Inbox::new(n, usize::MAX)))
+            .collect();
+        (0..n)
+            .map(|id| Self {
+                id,
+                links: Arc::new(
+                    (0..n)
+                        .map(|p| {
+                            if p == id {
+                                None
+                            } else {
+                                Some(Link::Local(inboxes[p].clone()))
+                            }
+                        })
+                        .collect(),
+                ),
+                inbox: inboxes[id].clone(),
+                readers: Vec::new(),
+                heartbeat: None,
+                read_timeout: None,
+                bytes_sent: AtomicUsize::new(0),
+                bytes_recv: AtomicUsize::new(0),
+            })
+            .collect()
+    }
+
+    pub fn party_id(&self) -> usize {
+        self.id
+    }
+
+    pub fn n_parties(&self) -> usize {
+        self.links.len()
+    }
+
+    pub fn bytes_sent(&self) -> usize {
+        self.bytes_sent.load(Ordering::SeqCst)
+    }
+
+    pub fn bytes_recv(&self) -> usize {
+        self.bytes_recv.load(Ordering::SeqCst)
+    }
+
+    pub fn reset_stats(&self) {
+        self.bytes_sent.store(0, Ordering::SeqCst);
+        self.bytes_recv.store(0, Ordering::SeqCst);
+    }
+
+    /// Send `bytes` to party `to`, under `tag`.
+    pub fn send(&self, to: usize, tag: Tag, bytes: &[u8]) -> Result<(), NetError> {
+        self.links[to]
+            .as_ref()
+            .expect("Cannot send to oneself")
+            .send(self.id, tag, bytes)
+            .map_err(|e| {
+                debug!("Sending to party {} failed: {}", to, e);
+                NetError::Disconnected { party: to, tag }
+            })?;
+        self.bytes_sent
+            .fetch_add(HEADER_LEN + bytes.len(), Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Receive the next message from party `from` under `tag`, waiting for it if needed.
+    ///
+    /// Fails if any party aborts, if `from` disconnects, or if we hear nothing at all from `from`
+    /// for the read timeout.
+    pub fn recv(&self, from: usize, tag: Tag) -> Result<Vec<u8>, NetError> {
+        assert_ne!(from, self.id, "Cannot receive from oneself");
+        let start = Instant::now();
+        let mut state = self.inbox.state.lock().unwrap();
+        loop {
+            if let Some((party, reason)) = &state.aborted {
+                return Err(NetError::Abort {
+                    party: *party,
+                    reason: reason.clone(),
+                });
+            }
+            if let Some(bytes) = state
+                .frames
+                .get_mut(&(from, tag))
+                .and_then(|q| q.pop_front())
+            {
+                if state.frames[&(from, tag)].is_empty() {
+                    state.frames.remove(&(from, tag));
+                }
+                state.queued[from] -= bytes.len();
+                self.bytes_recv
+                    .fetch_add(HEADER_LEN + bytes.len(), Ordering::SeqCst);
+                return Ok(bytes);
+            }
+            if state.closed[from] {
+                return Err(NetError::Disconnected { party: from, tag });
+            }
+            state = match self.read_timeout {
+                Some(timeout) => {
+                    // Silence counts from when we started waiting, or last heard, if later.
+                    let deadline = std::cmp::max(start, state.heard[from]) + timeout;
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(NetError::TimedOut { party: from, tag });
+                    }
+                    self.inbox
+                        .arrived
+                        .wait_timeout(state, deadline - now)
+                        .unwrap()
+                        .0
+                }
+                None => self.inbox.arrived.wait(state).unwrap(),
+            };
+        }
+    }
+
+    /// Tell every peer that we abort the protocol, and why. Their pending and future receives
+    /// fail.
+    pub fn abort(&self, reason: &str) {
+        for link in self.links.iter().flatten() {
+            let _ = link.send(self.id, Tag::ABORT, reason.as_bytes());
+        }
+    }
+
+    fn others(&self) -> impl Iterator<Item = usize> + '_ {
+        let id = self.id;
+        (0..self.n_parties()).filter(move |p| *p != id)
+    }
+
+    /// All parties send bytes to each other.
+    pub fn broadcast(&self, tag: Tag, bytes: &[u8]) -> Result<Vec<Vec<u8>>, NetError> {
+        for p in self.others() {
+            self.send(p, tag, bytes)?;
+        }
+        (0..self.n_parties())
+            .map(|p| {
+                if p == self.id {
+                    Ok(bytes.to_vec())
+                } else {
+                    self.recv(p, tag)
+                }
+            })
+            .collect()
+    }
+
+    /// All parties send bytes to the king.
+    pub fn send_to_king(&self, tag: Tag, bytes: &[u8]) -> Result<Option<Vec<Vec<u8>>>, NetError> {
+        if self.id == 0 {
+            (0..self.n_parties())
+                .map(|p| {
+                    if p == self.id {
+                        Ok(bytes.to_vec())
+                    } else {
+                        self.recv(p, tag)
+                    }
+                })
+                .collect::<Result<_, _>>()
+                .map(Some)
+        } else {
+            self.send(0, tag, bytes)?;
+            Ok(None)
+        }
+    }
+
+    /// All parties recv bytes from the king. Provide bytes iff you're the king!
+    pub fn recv_from_king(
+        &self,
+        tag: Tag,
+        bytes: Option<Vec<Vec<u8>>>,
+    ) -> Result<Vec<u8>, NetError> {
+        if self.id == 0 {
+            let mut bytes = bytes.expect("king needs bytes");
+            assert_eq!(bytes.len(), self.n_parties());
+            for p in self.others() {
+                self.send(p, tag, &bytes[p])?;
+            }
+            Ok(bytes.swap_remove(self.id))
+        } else {
+            self.recv(0, tag)
+        }
+    }
+
+    /// All parties send (different) bytes to each other: `bytes[i]` goes to party `i`.
+    pub fn all_to_all(&self, tag: Tag, mut bytes: Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>, NetError> {
+        assert_eq!(bytes.len(), self.n_parties());
+        for p in self.others() {
+            self.send(p, tag, &bytes[p])?;
+        }
+        for p in self.others() {
+            bytes[p] = self.recv(p, tag)?;
+        }
+        Ok(bytes)
+    }
+}
+
+impl Drop for Mux {
+    fn drop(&mut self) {
+        if let Some((stop, beat)) = self.heartbeat.take() {
+            drop(stop);
+            let _ = beat.join();
+        }
+        for link in self.links.iter().flatten() {
+            match link {
+                Link::Stream { socket, .. } => {
+                    let _ = socket.shutdown(Shutdown::Both);
+                }
+                Link::Local(inbox) => inbox.close(self.id),
+            }
+        }
+        for r in self.readers.drain(..) {
+            let _ = r.join();
+        }
+    }
+}
+
+/// File frames from `peer` into `inbox`, until the stream closes, or `peer` sends a frame longer
+/// than `max_frame` or more than the inbox holds.
+fn read_frames(peer: usize, mut stream: impl Read, inbox: &Inbox, max_frame: usize) {
+    let mut header = [0u8; HEADER_LEN];
+    while stream.read_exact(&mut header).is_ok() {
+        let mut word = [0u8; 8];
+        let mut field = |i: usize| {
+            word.copy_from_slice(&header[i * 8..(i + 1) * 8]);
+            u64::from_le_bytes(word)
+        };
+        let tag = Tag::new(field(0), field(1));
+        let len = field(2);
+        if len > max_frame as u64 {
+            inbox.fault(peer, format!("sent a frame of {} bytes", len));
+            break;
+        }
+        let mut bytes = vec![0u8; len as usize];
+        if stream.read_exact(&mut bytes).is_err() || !inbox.deliver(peer, tag, bytes) {
+            break;
+        }
+    }
+    debug!("Stream from party {} closed", peer);
+    inbox.close(peer);
+}
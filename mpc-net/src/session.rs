@@ -0,0 +1,245 @@
+//! One party's state in one MPC session.
+//!
+//! A [Session] owns the connections to the other parties, the counter for untagged messages, the
+//! statistics, and any per-session state that higher layers keep ([Session::local]). Sessions are
+//! values, so one process can hold several: for different computations, or for different parties
+//! of the same computation ([crate::LocalSimNet]).
+//!
+//! A session can also record a [Trace] of its rounds ([Session::start_trace]).
+//!
+//! The static [MpcNet](crate::MpcNet) methods of [MpcMultiNet](crate::MpcMultiNet) act on the
+//! session that the calling thread has [entered](Session::enter), if any.
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use super::mux::{Mux, Tag};
+use super::trace::{Recorder, Trace, TRACING};
+use super::{NetError, Stats};
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Session>>> = const { RefCell::new(None) };
+}
+
+type Local = Arc<Mutex<Box<dyn Any + Send>>>;
+
+pub struct Session {
+    mux: Mux,
+    /// The round of the last untagged message
+    round: AtomicU64,
+    stats: Mutex<Stats>,
+    trace: Mutex<Option<Recorder>>,
+    locals: Mutex<HashMap<TypeId, Local>>,
+}
+
+impl Session {
+    pub fn new(mux: Mux) -> Self {
+        Self {
+            mux,
+            round: AtomicU64::new(0),
+            stats: Mutex::new(Stats::default()),
+            trace: Mutex::new(None),
+            locals: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn party_id(&self) -> usize {
+        self.mux.party_id()
+    }
+
+    pub fn n_parties(&self) -> usize {
+        self.mux.n_parties()
+    }
+
+    pub fn am_king(&self) -> bool {
+        self.party_id() == 0
+    }
+
+    /// The tag of the next untagged message.
+    pub fn next_tag(&self) -> Tag {
+        Tag::new(Tag::UNTAGGED, self.round.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+
+    pub fn stats(&self) -> Stats {
+        let mut stats = self.stats.lock().unwrap().clone();
+        stats.bytes_sent = self.mux.bytes_sent();
+        stats.bytes_recv = self.mux.bytes_recv();
+        stats
+    }
+
+    pub fn reset_stats(&self) {
+        *self.stats.lock().unwrap() = Stats::default();
+        self.mux.reset_stats();
+    }
+
+    /// All parties send bytes to each other.
+    pub fn broadcast_bytes(&self, tag: Tag, bytes: &[u8]) -> Result<Vec<Vec<u8>>, NetError> {
+        self.stats.lock().unwrap().broadcasts += 1;
+        let sent = bytes.len() * (self.n_parties() - 1);
+        self.traced(
+            "broadcast",
+            tag,
+            sent,
+            || self.mux.broadcast(tag, bytes),
+            |r| self.len_from_others(r),
+        )
+    }
+
+    /// All parties send bytes to the king.
+    pub fn send_bytes_to_king(
+        &self,
+        tag: Tag,
+        bytes: &[u8],
+    ) -> Result<Option<Vec<Vec<u8>>>, NetError> {
+        self.stats.lock().unwrap().to_king += 1;
+        let sent = if self.am_king() { 0 } else { bytes.len() };
+        self.traced(
+            "to_king",
+            tag,
+            sent,
+            || self.mux.send_to_king(tag, bytes),
+            |r| r.as_ref().map_or(0, |r| self.len_from_others(r)),
+        )
+    }
+
+    /// All parties recv bytes from the king. Provide bytes iff you're the king!
+    pub fn recv_bytes_from_king(
+        &self,
+        tag: Tag,
+        bytes: Option<Vec<Vec<u8>>>,
+    ) -> Result<Vec<u8>, NetError> {
+        self.stats.lock().unwrap().from_king += 1;
+        let sent = bytes.as_ref().map_or(0, |b| self.len_from_others(b));
+        let king = self.am_king();
+        self.traced(
+            "from_king",
+            tag,
+            sent,
+            || self.mux.recv_from_king(tag, bytes),
+            |r| if king { 0 } else { r.len() },
+        )
+    }
+
+    /// All parties send (different) bytes to each other: `bytes[i]` goes to party `i`.
+    pub fn all_to_all_bytes(
+        &self,
+        tag: Tag,
+        bytes: Vec<Vec<u8>>,
+    ) -> Result<Vec<Vec<u8>>, NetError> {
+        self.stats.lock().unwrap().all_to_all += 1;
+        let sent = self.len_from_others(&bytes);
+        self.traced(
+            "all_to_all",
+            tag,
+            sent,
+            || self.mux.all_to_all(tag, bytes),
+            |r| self.len_from_others(r),
+        )
+    }
+
+    /// The total length of the entries of `per_party` that are not ours.
+    fn len_from_others(&self, per_party: &[Vec<u8>]) -> usize {
+        let id = self.party_id();
+        per_party
+            .iter()
+            .enumerate()
+            .filter(|(p, _)| *p != id)
+            .map(|(_, b)| b.len())
+            .sum()
+    }
+
+    /// Run the round `f`, and record it if we are tracing. `recv` measures what it received.
+    fn traced<R>(
+        &self,
+        kind: &'static str,
+        tag: Tag,
+        sent: usize,
+        f: impl FnOnce() -> Result<R, NetError>,
+        recv: impl FnOnce(&R) -> usize,
+    ) -> Result<R, NetError> {
+        if TRACING.load(Ordering::Relaxed) == 0 || self.trace.lock().unwrap().is_none() {
+            return f();
+        }
+        let start = Instant::now();
+        let r = f()?;
+        if let Some(recorder) = self.trace.lock().unwrap().as_mut() {
+            recorder.round(kind, tag, (sent, recv(&r)), start);
+        }
+        Ok(r)
+    }
+
+    /// Start recording a [Trace] of this session's rounds, and of the ops that run in it.
+    pub fn start_trace(&self) {
+        let mut trace = self.trace.lock().unwrap();
+        if trace.is_none() {
+            *trace = Some(Recorder::new(self.party_id()));
+            TRACING.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Stop tracing, and return the trace, if we were.
+    pub fn take_trace(&self) -> Option<Trace> {
+        let recorder = self.trace.lock().unwrap().take()?;
+        TRACING.fetch_sub(1, Ordering::SeqCst);
+        Some(recorder.finish())
+    }
+
+    pub(crate) fn record_span(&self, op: String, start: Instant) {
+        if let Some(recorder) = self.trace.lock().unwrap().as_mut() {
+            recorder.span(op, start);
+        }
+    }
+
+    /// Tell the other parties that we abort, and why.
+    pub fn abort(&self, reason: &str) {
+        self.mux.abort(reason)
+    }
+
+    /// Run `f` on this session's `T`, which starts out as `T::default()`.
+    ///
+    /// This is for state that belongs to one party in one session, such as key material or pools
+    /// of preprocessing. Each `T` has its own lock, held while `f` runs, so `f` may use the state
+    /// of other types, but not its own.
+    pub fn local<T: Any + Send + Default, R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let local = self
+            .locals
+            .lock()
+            .unwrap()
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Arc::new(Mutex::new(Box::new(T::default()))))
+            .clone();
+        let mut local = local.lock().unwrap();
+        f(local.downcast_mut::<T>().unwrap())
+    }
+
+    /// Run `f` in this session: while it runs, the calling thread's
+    /// [MpcMultiNet](crate::MpcMultiNet) calls go to this session.
+    ///
+    /// Threads do not inherit the session. Threads spawned by `f` that communicate should enter it
+    /// themselves.
+    pub fn enter<R>(self: &Arc<Self>, f: impl FnOnce() -> R) -> R {
+        struct Restore(Option<Arc<Session>>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let previous = self.0.take();
+                CURRENT.with(|c| *c.borrow_mut() = previous);
+            }
+        }
+        let _restore = Restore(CURRENT.with(|c| c.borrow_mut().replace(self.clone())));
+        f()
+    }
+
+    /// The session the calling thread has entered, if any.
+    pub fn current() -> Option<Arc<Session>> {
+        CURRENT.with(|c| c.borrow().clone())
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.take_trace();
+    }
+}
@@ -0,0 +1,211 @@
+//! Many parties in one process.
+use std::sync::Arc;
+
+use super::mux::Mux;
+use super::session::Session;
+
+/// Runs N parties as threads of this process, connected by in-memory channels.
+///
+/// Each party runs in its own [Session], so code written against
+/// [MpcMultiNet](crate::MpcMultiNet) works unchanged. This is for tests.
+pub struct LocalSimNet;
+
+impl LocalSimNet {
+    /// Sessions for `n` parties, connected to each other in memory. Entry `i` is party `i`'s.
+    pub fn sessions(n: usize) -> Vec<Arc<Session>> {
+        Mux::local(n)
+            .into_iter()
+            .map(|mux| Arc::new(Session::new(mux)))
+            .collect()
+    }
+
+    /// Run `f` as each of `n` parties, each in its own thread and session. `f` gets the party's
+    /// session, which it also runs inside (see [Session::enter]). Returns the parties' outputs, in
+    /// party order.
+    ///
+    /// If a party panics, so does this, and the other parties fail when they next wait on it.
+    pub fn run<T, F>(n: usize, f: F) -> Vec<T>
+    where
+        T: Send + 'static,
+        F: Fn(&Arc<Session>) -> T + Send + Sync + 'static,
+    {
+        let f = Arc::new(f);
+        let parties: Vec<_> = Self::sessions(n)
+            .into_iter()
+            .enumerate()
+            .map(|(id, session)| {
+                let f = f.clone();
+                std::thread::Builder::new()
+                    .name(format!("party {}", id))
+                    .spawn(move || session.enter(|| f(&session)))
+                    .expect("Could not spawn party thread")
+            })
+            .collect();
+        parties
+            .into_iter()
+            .map(|p| p.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MpcMultiNet as Net, MpcNet, NetError, Tag};
+
+    #[test]
+    fn broadcast_and_king() {
+        let outs = LocalSimNet::run(4, |session| {
+            let id = session.party_id();
+            assert_eq!(Net::party_id(), id);
+            let all = Net::broadcast_bytes(&[id as u8]);
+            assert_eq!(all, vec![vec![0], vec![1], vec![2], vec![3]]);
+            let sum = Net::king_compute(&[id as u8], |xs| {
+                let sum: u8 = xs.iter().map(|x| x[0]).sum();
+                vec![vec![sum]; xs.len()]
+            });
+            let spread = Net::all_to_all_bytes((0..4).map(|to| vec![id as u8, to]).collect());
+            assert!(spread
+                .iter()
+                .enumerate()
+                .all(|(from, b)| b == &[from as u8, id as u8]));
+            (sum[0], Net::stats().broadcasts)
+        });
+        assert_eq!(outs, vec![(6, 1); 4]);
+    }
+
+    #[test]
+    fn tagged_sessions_interleave() {
+        LocalSimNet::run(3, |session| {
+            let id = session.party_id();
+            let run = |s: u64| {
+                let session = session.clone();
+                move || {
+                    for round in 0..20 {
+                        let all = session
+                            .broadcast_bytes(Tag::new(s, round), &[s as u8])
+                            .unwrap();
+                        assert!(all.iter().all(|b| b == &[s as u8]));
+                    }
+                }
+            };
+            // Each party starts the two sessions in a different order.
+            let (first, second) = if id % 2 == 0 { (1, 2) } else { (2, 1) };
+            let t = std::thread::spawn(run(first));
+            run(second)();
+            t.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn sessions_are_separate() {
+        let outs = LocalSimNet::run(2, |session| {
+            let id = session.party_id();
+            Net::with_local(|x: &mut usize| *x += id + 1);
+            Net::with_local(|x: &mut usize| *x)
+        });
+        assert_eq!(outs, vec![1, 2]);
+    }
+
+    #[test]
+    fn abort_reaches_everyone() {
+        let outs = LocalSimNet::run(3, |session| {
+            let id = session.party_id();
+            if id == 2 {
+                Net::abort("bad share");
+            }
+            Net::try_broadcast_bytes(&[id as u8])
+        });
+        for out in &outs[..2] {
+            assert_eq!(
+                out,
+                &Err(NetError::Abort {
+                    party: 2,
+                    reason: "bad share".into()
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn errors_are_caught() {
+        let outs = LocalSimNet::run(2, |session| {
+            let id = session.party_id();
+            if id == 1 {
+                return Ok(());
+            }
+            crate::catch(|| {
+                Net::broadcast_bytes(&[0]);
+            })
+        });
+        assert_eq!(
+            outs[0],
+            Err(NetError::Disconnected {
+                party: 1,
+                tag: Tag::new(Tag::UNTAGGED, 1)
+            })
+        );
+    }
+
+    #[test]
+    fn rounds_are_traced_by_op() {
+        let traces = LocalSimNet::run(3, |session| {
+            let id = session.party_id();
+            Net::start_trace();
+            {
+                let _op = crate::op("open");
+                Net::broadcast_bytes(&[id as u8; 4]);
+                let _inner = crate::op("check");
+                Net::king_compute(&[id as u8], |xs| xs);
+            }
+            Net::broadcast_bytes(&[]);
+            Net::take_trace().unwrap()
+        });
+        for (id, trace) in traces.iter().enumerate() {
+            assert_eq!(trace.party, id);
+            let ops: Vec<_> = trace
+                .rounds
+                .iter()
+                .map(|r| (r.op.as_str(), r.kind))
+                .collect();
+            assert_eq!(
+                ops,
+                vec![
+                    ("open", "broadcast"),
+                    ("open/check", "to_king"),
+                    ("open/check", "from_king"),
+                    ("", "broadcast"),
+                ]
+            );
+            let summary = trace.summary();
+            assert_eq!(summary["open"].rounds, 1);
+            assert_eq!(summary["open"].bytes_sent, 8);
+            assert_eq!(summary["open"].bytes_recv, 8);
+            assert_eq!(summary["open/check"].rounds, 2);
+            let spans: Vec<_> = trace.spans.iter().map(|s| s.op.as_str()).collect();
+            assert_eq!(spans, vec!["open/check", "open"]);
+            let json: serde_json::Value = serde_json::from_str(&trace.to_json()).unwrap();
+            assert_eq!(json["party"], id);
+            assert_eq!(json["rounds"][1]["op"], "open/check");
+            assert_eq!(json["rounds"][1]["session"], Tag::UNTAGGED);
+            assert!(json["rounds"][1]["wait_us"].is_u64());
+        }
+        let chrome = crate::trace::chrome_trace(&traces);
+        let chrome: serde_json::Value = serde_json::from_str(&chrome).unwrap();
+        let events = chrome["traceEvents"].as_array().unwrap();
+        assert_eq!(events.iter().filter(|e| e["cat"] == "round").count(), 12);
+        assert_eq!(events.iter().filter(|e| e["cat"] == "op").count(), 6);
+    }
+
+    #[test]
+    #[should_panic]
+    fn panics_propagate() {
+        LocalSimNet::run(3, |session| {
+            let id = session.party_id();
+            if id == 1 {
+                panic!("party 1 fails");
+            }
+            Net::broadcast_bytes(&[id as u8]);
+        });
+    }
+}
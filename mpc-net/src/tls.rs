@@ -0,0 +1,389 @@
+//! Mutually authenticated TLS links between parties.
+//!
+//! Each party has a static [Identity]: a self-signed certificate and its private key. The hosts
+//! file lists the [Fingerprint] of each party's certificate next to its address, and each end of a
+//! link accepts only the certificate it expects for the party at the other end. There are no
+//! certificate authorities and no host names: the fingerprints are the trust.
+use rustls::client::{ServerCertVerified, ServerCertVerifier, ServerName};
+use rustls::server::{ClientCertVerified, ClientCertVerifier};
+use rustls::{Certificate, ClientConfig, ClientConnection, Connection, PrivateKey, ServerConfig};
+use rustls::{DistinguishedNames, ServerConnection};
+use sha2::{Digest, Sha256};
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use super::mux::Stream;
+
+/// The name in every party's certificate. Peers are checked by fingerprint, not by name.
+const NAME: &str = "mpc-party";
+
+/// A party's long-term key pair, with a self-signed certificate for its public key.
+#[derive(Clone)]
+pub struct Identity {
+    /// DER
+    cert: Vec<u8>,
+    /// PKCS#8 DER
+    key: Vec<u8>,
+}
+
+impl Identity {
+    /// A fresh identity.
+    pub fn generate() -> Self {
+        let cert = rcgen::generate_simple_self_signed(vec![NAME.to_owned()])
+            .expect("Could not generate a certificate");
+        Self {
+            cert: cert
+                .serialize_der()
+                .expect("Could not serialize a certificate"),
+            key: cert.serialize_private_key_der(),
+        }
+    }
+
+    /// Read an identity written by [Identity::write].
+    pub fn read(cert: impl AsRef<Path>, key: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            cert: std::fs::read(cert)?,
+            key: std::fs::read(key)?,
+        })
+    }
+
+    /// Write the certificate and the private key, as DER, to two files.
+    pub fn write(&self, cert: impl AsRef<Path>, key: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(cert, &self.cert)?;
+        std::fs::write(key, &self.key)
+    }
+
+    /// What the other parties put in their hosts files for us.
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprint::of(&self.cert)
+    }
+
+    fn cert_chain(&self) -> Vec<Certificate> {
+        vec![Certificate(self.cert.clone())]
+    }
+
+    fn private_key(&self) -> PrivateKey {
+        PrivateKey(self.key.clone())
+    }
+}
+
+/// The SHA-256 hash of a certificate. Written as 64 hex digits.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint(pub [u8; 32]);
+
+impl Fingerprint {
+    fn of(cert: &[u8]) -> Self {
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&Sha256::digest(cert));
+        Self(hash)
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in &self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Fingerprint({})", self)
+    }
+}
+
+impl FromStr for Fingerprint {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(format!("expected 64 hex digits, got {:?}", s));
+        }
+        let mut hash = [0u8; 32];
+        for (i, b) in hash.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&s[2 * i..2 * i + 2], 16)
+                .map_err(|e| format!("bad fingerprint {:?}: {}", s, e))?;
+        }
+        Ok(Self(hash))
+    }
+}
+
+/// Accepts exactly one certificate, as either end of a link. The handshake still checks that the
+/// peer holds its private key.
+struct Pinned(Fingerprint);
+
+impl Pinned {
+    fn check(&self, cert: &Certificate) -> Result<(), rustls::Error> {
+        let got = Fingerprint::of(&cert.0);
+        if got == self.0 {
+            Ok(())
+        } else {
+            Err(rustls::Error::InvalidCertificateData(format!(
+                "expected certificate {}, got {}",
+                self.0, got
+            )))
+        }
+    }
+}
+
+impl ServerCertVerifier for Pinned {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.check(end_entity)
+            .map(|()| ServerCertVerified::assertion())
+    }
+}
+
+impl ClientCertVerifier for Pinned {
+    fn client_auth_root_subjects(&self) -> Option<DistinguishedNames> {
+        Some(DistinguishedNames::new())
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _now: SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        self.check(end_entity)
+            .map(|()| ClientCertVerified::assertion())
+    }
+}
+
+/// Run the TLS handshake over `socket`, as us (`identity`), with a peer that must present the
+/// certificate `peer`. The party that connected is the client.
+pub fn handshake(
+    socket: TcpStream,
+    identity: &Identity,
+    peer: Fingerprint,
+    client: bool,
+) -> io::Result<Stream> {
+    let bad_key = |e: rustls::Error| io::Error::new(io::ErrorKind::InvalidInput, e);
+    let verifier = Arc::new(Pinned(peer));
+    let mut conn: Connection = if client {
+        let config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(verifier)
+            .with_single_cert(identity.cert_chain(), identity.private_key())
+            .map_err(bad_key)?;
+        let name = ServerName::try_from(NAME).unwrap();
+        ClientConnection::new(Arc::new(config), name)
+            .map_err(bad_key)?
+            .into()
+    } else {
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(identity.cert_chain(), identity.private_key())
+            .map_err(bad_key)?;
+        ServerConnection::new(Arc::new(config))
+            .map_err(bad_key)?
+            .into()
+    };
+    let mut tcp = socket.try_clone()?;
+    while conn.is_handshaking() {
+        conn.complete_io(&mut tcp)?;
+    }
+    // The client's last flight may still be buffered.
+    while conn.wants_write() {
+        conn.write_tls(&mut tcp)?;
+    }
+    let conn = Arc::new(Mutex::new(conn));
+    Ok(Stream {
+        reader: Box::new(TlsReader {
+            conn: conn.clone(),
+            socket: socket.try_clone()?,
+            buf: vec![0u8; 1 << 16],
+            pending: 0..0,
+        }),
+        writer: Box::new(TlsWriter {
+            conn,
+            socket: socket.try_clone()?,
+        }),
+        socket,
+    })
+}
+
+/// Decrypts what arrives on the socket.
+///
+/// The reader and the writer share the connection state, but neither holds its lock while blocked
+/// on the socket. The reader never writes to the socket: anything the connection has to send in
+/// response goes out with the next write.
+struct TlsReader {
+    conn: Arc<Mutex<Connection>>,
+    socket: TcpStream,
+    /// Ciphertext read from the socket; `pending` is the part not yet given to `conn`.
+    buf: Vec<u8>,
+    pending: std::ops::Range<usize>,
+}
+
+impl Read for TlsReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        loop {
+            {
+                let mut conn = self.conn.lock().unwrap();
+                match conn.reader().read(out) {
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    r => return r,
+                }
+                // No plaintext is buffered, so the connection has room for more records.
+                if !self.pending.is_empty() {
+                    let mut data = &self.buf[self.pending.clone()];
+                    let n = conn.read_tls(&mut data)?;
+                    self.pending.start += n;
+                    conn.process_new_packets()
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    continue;
+                }
+            }
+            let n = self.socket.read(&mut self.buf)?;
+            if n == 0 {
+                // Let the connection see the end of the stream.
+                self.conn.lock().unwrap().read_tls(&mut io::empty())?;
+            }
+            self.pending = 0..n;
+        }
+    }
+}
+
+/// Encrypts on [Write::write], and sends on [Write::flush], or when the encrypted records fill the
+/// connection's buffer.
+struct TlsWriter {
+    conn: Arc<Mutex<Connection>>,
+    socket: TcpStream,
+}
+
+impl Write for TlsWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        loop {
+            let n = self.conn.lock().unwrap().writer().write(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            // The connection's buffer is full.
+            self.flush()?;
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut records = Vec::new();
+        {
+            let mut conn = self.conn.lock().unwrap();
+            while conn.wants_write() {
+                conn.write_tls(&mut records)?;
+            }
+        }
+        self.socket.write_all(&records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MpcMultiNet as Net, MpcNet, NetConfig, NetError};
+
+    /// Write a hosts file for parties on loopback ports from `port`, with these fingerprints.
+    fn hosts(name: &str, port: u16, fingerprints: &[Fingerprint]) -> String {
+        let path = std::env::temp_dir().join(format!("mpc-net-{}-{}", name, std::process::id()));
+        let lines: Vec<String> = fingerprints
+            .iter()
+            .enumerate()
+            .map(|(i, f)| format!("127.0.0.1:{} {}", port + i as u16, f))
+            .collect();
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn fingerprint_roundtrip() {
+        let f = Identity::generate().fingerprint();
+        assert_eq!(f.to_string().parse::<Fingerprint>(), Ok(f));
+        assert!("00ff".parse::<Fingerprint>().is_err());
+    }
+
+    #[test]
+    fn loopback() {
+        let ids: Vec<Identity> = (0..3).map(|_| Identity::generate()).collect();
+        let path = hosts(
+            "loopback",
+            27310,
+            &ids.iter().map(Identity::fingerprint).collect::<Vec<_>>(),
+        );
+        let parties: Vec<_> = ids
+            .into_iter()
+            .enumerate()
+            .map(|(id, identity)| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let session =
+                        Net::connect(&path, id, Some(&identity), &NetConfig::default()).unwrap();
+                    session.enter(|| {
+                        let big = vec![id as u8; 100_000];
+                        let all = Net::broadcast_bytes(&big);
+                        assert!(all
+                            .iter()
+                            .enumerate()
+                            .all(|(i, b)| b == &vec![i as u8; 100_000]));
+                        Net::stats()
+                    })
+                })
+            })
+            .collect();
+        for p in parties {
+            let stats = p.join().unwrap();
+            assert_eq!(stats.broadcasts, 1);
+            assert_eq!(stats.bytes_sent, stats.bytes_recv);
+            assert!(stats.bytes_sent > 200_000);
+        }
+    }
+
+    #[test]
+    fn unexpected_peer_is_rejected() {
+        let ids: Vec<Identity> = (0..2).map(|_| Identity::generate()).collect();
+        let honest: Vec<_> = ids.iter().map(Identity::fingerprint).collect();
+        // Party 1 expects someone else as party 0.
+        let mut wrong = honest.clone();
+        wrong[0] = Identity::generate().fingerprint();
+        let paths = [
+            hosts("honest", 27320, &honest),
+            hosts("wrong", 27320, &wrong),
+        ];
+        let parties: Vec<_> = ids
+            .into_iter()
+            .enumerate()
+            .map(|(id, identity)| {
+                let path = paths[id].clone();
+                std::thread::spawn(move || {
+                    Net::connect(&path, id, Some(&identity), &NetConfig::default()).map(|_| ())
+                })
+            })
+            .collect();
+        // Party 1 rejects party 0's certificate. Party 0 may finish its side of the handshake
+        // first, but then finds party 1 gone.
+        let results: Vec<_> = parties.into_iter().map(|p| p.join().unwrap()).collect();
+        assert!(matches!(
+            results[1],
+            Err(NetError::Unreachable { party: 0, .. })
+        ));
+        assert!(matches!(
+            results[0],
+            Err(NetError::Unreachable { party: 1, .. })
+                | Err(NetError::Disconnected { party: 1, .. })
+        ));
+    }
+}
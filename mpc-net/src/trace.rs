@@ -0,0 +1,235 @@
+//! Where the time and the bytes go: a trace of every network round.
+//!
+//! Code names what it is doing with [op]; ops nest, per thread. While a session is tracing
+//! ([Session::start_trace]), each of its rounds is recorded with the op that triggered it, its
+//! payload sizes, how long the party waited in it, and how long the party computed since its
+//! previous round. Ops are recorded as spans too.
+//!
+//! A [Trace] exports as JSON, or as a Chrome trace (for `chrome://tracing` or Perfetto), and
+//! [Trace::summary] totals it by op.
+use serde::{Serialize, Serializer};
+use serde_json::json;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use super::multi::MpcMultiNet;
+use super::Tag;
+
+thread_local! {
+    static OPS: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+/// How many sessions are tracing, so that ops cost nothing when none are.
+pub(crate) static TRACING: AtomicUsize = AtomicUsize::new(0);
+
+/// The calling thread is in op `name` until the guard drops.
+pub fn op(name: &'static str) -> Op {
+    OPS.with(|ops| ops.borrow_mut().push(name));
+    Op {
+        start: if TRACING.load(Ordering::Relaxed) > 0 {
+            Some(Instant::now())
+        } else {
+            None
+        },
+    }
+}
+
+/// The calling thread's ops, outermost first, joined by `/`.
+pub fn current_op() -> String {
+    OPS.with(|ops| ops.borrow().join("/"))
+}
+
+/// See [op].
+#[must_use = "the op ends when this drops"]
+pub struct Op {
+    start: Option<Instant>,
+}
+
+impl Drop for Op {
+    fn drop(&mut self) {
+        let name = current_op();
+        OPS.with(|ops| ops.borrow_mut().pop());
+        if let Some(start) = self.start {
+            if let Some(session) = MpcMultiNet::try_session() {
+                session.record_span(name, start);
+            }
+        }
+    }
+}
+
+/// One network round, as one party saw it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Round {
+    /// The op that made the round.
+    pub op: String,
+    /// `broadcast`, `to_king`, `from_king`, or `all_to_all`.
+    pub kind: &'static str,
+    #[serde(flatten)]
+    pub tag: Tag,
+    /// Payload bytes, without framing.
+    pub bytes_sent: usize,
+    pub bytes_recv: usize,
+    /// When the round started, since the trace started.
+    #[serde(rename = "start_us", serialize_with = "micros")]
+    pub start: Duration,
+    /// How long the round took, mostly waiting for peers.
+    #[serde(rename = "wait_us", serialize_with = "micros")]
+    pub wait: Duration,
+    /// How long since the previous round ended: local computation.
+    #[serde(rename = "compute_us", serialize_with = "micros")]
+    pub compute: Duration,
+}
+
+/// An op, as one party ran it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Span {
+    pub op: String,
+    #[serde(rename = "start_us", serialize_with = "micros")]
+    pub start: Duration,
+    #[serde(rename = "duration_us", serialize_with = "micros")]
+    pub duration: Duration,
+}
+
+fn micros<S: Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_u128(d.as_micros())
+}
+
+/// Everything one party traced in one session.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Trace {
+    pub party: usize,
+    pub rounds: Vec<Round>,
+    pub spans: Vec<Span>,
+}
+
+/// The totals for one op, from [Trace::summary].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OpCost {
+    pub rounds: usize,
+    pub bytes_sent: usize,
+    pub bytes_recv: usize,
+    pub wait: Duration,
+    pub compute: Duration,
+}
+
+impl Trace {
+    /// The rounds' costs, totalled by op.
+    pub fn summary(&self) -> BTreeMap<String, OpCost> {
+        let mut costs = BTreeMap::<String, OpCost>::new();
+        for r in &self.rounds {
+            let cost = costs.entry(r.op.clone()).or_default();
+            cost.rounds += 1;
+            cost.bytes_sent += r.bytes_sent;
+            cost.bytes_recv += r.bytes_recv;
+            cost.wait += r.wait;
+            cost.compute += r.compute;
+        }
+        costs
+    }
+
+    /// As a JSON object: `{"party": .., "rounds": [..], "spans": [..]}`. Times are in
+    /// microseconds.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// In the Chrome trace event format. See [chrome_trace].
+    pub fn to_chrome_trace(&self) -> String {
+        chrome_trace(std::slice::from_ref(self))
+    }
+}
+
+/// Several parties' traces as one Chrome trace: a process per party, with its ops on one track
+/// and its rounds on another. The parties' clocks are not synchronized.
+pub fn chrome_trace(traces: &[Trace]) -> String {
+    let mut events = Vec::new();
+    for t in traces {
+        for s in &t.spans {
+            events.push(json!({
+                "name": s.op.rsplit('/').next().unwrap(),
+                "cat": "op",
+                "ph": "X",
+                "ts": s.start.as_micros() as u64,
+                "dur": s.duration.as_micros() as u64,
+                "pid": t.party,
+                "tid": 0,
+            }));
+        }
+        for r in &t.rounds {
+            events.push(json!({
+                "name": r.kind,
+                "cat": "round",
+                "ph": "X",
+                "ts": r.start.as_micros() as u64,
+                "dur": r.wait.as_micros() as u64,
+                "pid": t.party,
+                "tid": 1,
+                "args": {
+                    "op": r.op,
+                    "tag": r.tag.to_string(),
+                    "bytes_sent": r.bytes_sent,
+                    "bytes_recv": r.bytes_recv,
+                    "compute_us": r.compute.as_micros() as u64,
+                },
+            }));
+        }
+    }
+    json!({ "traceEvents": events }).to_string()
+}
+
+/// A session's trace in progress.
+pub(crate) struct Recorder {
+    start: Instant,
+    /// When the last round ended.
+    idle_since: Instant,
+    trace: Trace,
+}
+
+impl Recorder {
+    pub(crate) fn new(party: usize) -> Self {
+        let now = Instant::now();
+        Self {
+            start: now,
+            idle_since: now,
+            trace: Trace {
+                party,
+                ..Trace::default()
+            },
+        }
+    }
+
+    pub(crate) fn round(
+        &mut self,
+        kind: &'static str,
+        tag: Tag,
+        bytes: (usize, usize),
+        start: Instant,
+    ) {
+        let end = Instant::now();
+        self.trace.rounds.push(Round {
+            op: current_op(),
+            kind,
+            tag,
+            bytes_sent: bytes.0,
+            bytes_recv: bytes.1,
+            start: start.saturating_duration_since(self.start),
+            wait: end - start,
+            compute: start.saturating_duration_since(self.idle_since),
+        });
+        self.idle_since = end;
+    }
+
+    pub(crate) fn span(&mut self, op: String, start: Instant) {
+        self.trace.spans.push(Span {
+            op,
+            start: start.saturating_duration_since(self.start),
+            duration: start.elapsed(),
+        });
+    }
+
+    pub(crate) fn finish(self) -> Trace {
+        self.trace
+    }
+}
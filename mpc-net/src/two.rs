@@ -1,15 +1,14 @@
 use lazy_static::lazy_static;
 use log::debug;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Write};
-use std::net::{SocketAddr, TcpListener, TcpStream};
-use std::sync::Mutex;
+use std::io::{BufRead, BufReader};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
 
-use ark_std::{end_timer, start_timer};
+use super::multi::{accept, dial};
+use super::mux::{Mux, Tag};
+use super::{MpcNet, NetConfig, NetError, Stats};
 
-use super::{MpcNet, Stats};
-
-#[macro_use]
 lazy_static! {
     pub static ref CH: Mutex<FieldChannel> = Mutex::new(FieldChannel::default());
 }
@@ -23,22 +22,25 @@ macro_rules! get_ch {
 
 pub struct FieldChannel {
     /// Empty if unitialized
-    pub stream: Option<TcpStream>,
+    pub mux: Option<Arc<Mux>>,
     pub self_addr: SocketAddr,
     pub other_addr: SocketAddr,
     pub stats: Stats,
     pub talk_first: bool,
+    /// The round of the last untagged message
+    pub round: u64,
 }
 
 impl std::default::Default for FieldChannel {
     #[inline]
     fn default() -> Self {
         Self {
-            stream: None,
+            mux: None,
             self_addr: "127.0.0.1:8000".parse().unwrap(),
             other_addr: "127.0.0.1:8000".parse().unwrap(),
             stats: Stats::default(),
             talk_first: false,
+            round: 0,
         }
     }
 }
@@ -50,7 +52,7 @@ impl FieldChannel {
         for line in f.lines() {
             let line = line.unwrap();
             let trimmed = line.trim();
-            if trimmed.len() > 0 {
+            if !trimmed.is_empty() {
                 let addr: SocketAddr = trimmed
                     .parse()
                     .unwrap_or_else(|e| panic!("bad socket address: {}:\n{}", trimmed, e));
@@ -65,124 +67,79 @@ impl FieldChannel {
     }
 
     #[inline]
-    pub fn connect(&mut self) {
+    pub fn connect(&mut self) -> Result<(), NetError> {
         debug!("I am {}, connecting to {}", self.self_addr, self.other_addr);
-        self.stream = Some(if self.talk_first {
+        let id = self.party_id();
+        let config = NetConfig::default();
+        let stream = if self.talk_first {
             debug!("Attempting to contact peer");
-            loop {
-                let mut ms_waited = 0;
-                match TcpStream::connect(self.other_addr) {
-                    Ok(s) => break s,
-                    Err(e) => {
-                        if e.kind() == std::io::ErrorKind::ConnectionRefused {
-                            ms_waited += 100;
-                            std::thread::sleep(std::time::Duration::from_millis(100));
-                            if ms_waited % 3_000 == 0 {
-                                debug!("Still waiting");
-                            } else if ms_waited > 30_000 {
-                                panic!("Could not find peer in 30s");
-                            }
-                        } else {
-                            panic!("Error during FieldChannel::new: {}", e);
-                        }
-                    }
-                }
-            }
+            dial(self.other_addr, 1 - id, config.connect_timeout)?
         } else {
-            let listener = TcpListener::bind(self.self_addr).unwrap();
+            let listener =
+                TcpListener::bind(self.self_addr).map_err(|e| NetError::Unreachable {
+                    party: id,
+                    reason: format!("we cannot listen on {}: {}", self.self_addr, e),
+                })?;
             debug!("Waiting for peer to contact us");
-            let (stream, _addr) = listener.accept().unwrap();
-            stream
-        });
-        // disable nagle's alg
-        self.stream.as_mut().unwrap().set_nodelay(true).unwrap();
-        self.stream.as_mut().unwrap().set_nonblocking(true).unwrap();
-    }
-    #[inline]
-    pub fn stream(&mut self) -> &mut TcpStream {
-        self.stream
-            .as_mut()
-            .expect("Unitialized FieldChannel. Did you forget init(..)?")
+            accept(&listener, 1 - id, config.connect_timeout)?
+        };
+        stream
+            .set_read_timeout(None)
+            .map_err(|e| NetError::Unreachable {
+                party: 1 - id,
+                reason: e.to_string(),
+            })?;
+        let mut streams = vec![None, None];
+        streams[1 - id] = Some(stream.into());
+        self.mux = Some(Arc::new(Mux::new(id, streams, &config)));
+        Ok(())
     }
 
     #[inline]
-    pub fn send_slice(&mut self, v: &[u8]) {
-        let s = self.stream();
-        s.set_nonblocking(false).unwrap();
-        let bytes = (v.len() as u64).to_ne_bytes();
-        s.write_all(&bytes[..]).unwrap();
-        s.write_all(v).unwrap();
-        s.set_nonblocking(true).unwrap();
-        self.stats.bytes_sent += bytes.len() + v.len();
+    pub fn party_id(&self) -> usize {
+        if self.talk_first {
+            0
+        } else {
+            1
+        }
     }
 
+    /// The multiplexer over the peer stream. Cloned out, so that no lock is held while waiting.
     #[inline]
-    pub fn recv_vec(&mut self) -> Vec<u8> {
-        let s = self.stream();
-        let mut len = [0u8; 8];
-        s.set_nonblocking(false).unwrap();
-        s.read_exact(&mut len[..]).unwrap();
-        let mut bytes = vec![0u8; u64::from_ne_bytes(len) as usize];
-        s.read_exact(&mut bytes[..]).unwrap();
-        s.set_nonblocking(true).unwrap();
-        self.stats.bytes_recv += bytes.len() + len.len();
-        bytes
+    pub fn mux(&self) -> Arc<Mux> {
+        self.mux
+            .clone()
+            .expect("Unitialized FieldChannel. Did you forget init(..)?")
     }
 
     #[inline]
-    pub fn exchange_bytes(&mut self, bytes_out: &[u8]) -> std::io::Result<Vec<u8>> {
-        let timer = start_timer!(|| format!("Exchanging {}", bytes_out.len()));
-        let s = self.stream();
-        let n = bytes_out.len();
-        let mut bytes_in = vec![0u8; n];
-        let mut bytes_in_offset = 0;
-        let mut bytes_out_offset = 0;
-        while bytes_out_offset < n || bytes_in_offset < n {
-            if bytes_out_offset < n {
-                match s.write(&bytes_out[bytes_out_offset..]) {
-                    Ok(written) => {
-                        bytes_out_offset += written;
-                        let _e = s.flush();
-                    }
-                    Err(e) => {
-                        if e.kind() == std::io::ErrorKind::WouldBlock {
-                        } else if e.kind() == std::io::ErrorKind::Interrupted {
-                        } else {
-                            return Err(e);
-                        }
-                    }
-                }
-            }
-            if bytes_in_offset < n {
-                match s.read(&mut bytes_in[bytes_in_offset..]) {
-                    Ok(read) => {
-                        bytes_in_offset += read;
-                    }
-                    Err(e) => {
-                        if e.kind() == std::io::ErrorKind::WouldBlock {
-                        } else if e.kind() == std::io::ErrorKind::Interrupted {
-                        } else {
-                            return Err(e);
-                        }
-                    }
-                }
-            }
-        }
-        self.stats.broadcasts += 1;
-        self.stats.bytes_sent += n;
-        self.stats.bytes_recv += n;
-        end_timer!(timer);
-        Ok(bytes_in)
+    pub fn next_tag(&mut self) -> Tag {
+        self.round += 1;
+        Tag::new(Tag::UNTAGGED, self.round)
     }
 
     #[inline]
     pub fn stats(&self) -> Stats {
-        self.stats.clone()
+        let mut stats = self.stats.clone();
+        if let Some(mux) = &self.mux {
+            stats.bytes_sent = mux.bytes_sent();
+            stats.bytes_recv = mux.bytes_recv();
+        }
+        stats
     }
 
     #[inline]
     pub fn reset_stats(&mut self) {
         self.stats = Stats::default();
+        if let Some(mux) = &self.mux {
+            mux.reset_stats();
+        }
+    }
+
+    #[inline]
+    pub fn uninit(&mut self) {
+        self.mux = None;
+        self.round = 0;
     }
 }
 
@@ -191,29 +148,29 @@ impl FieldChannel {
 pub fn init_from_path(path: &str, id: usize) {
     let mut ch = get_ch!();
     assert!(
-        ch.stream.is_none(),
+        ch.mux.is_none(),
         "FieldChannel should no be re-intialized. Did you call init(..) twice?"
     );
     ch.init_from_path(path, id);
-    ch.connect();
+    ch.connect().unwrap_or_else(|e| e.raise());
     debug!("Connected");
 }
 
 #[inline]
 pub fn deinit() {
-    CH.lock().expect("Poisoned FieldChannel").stream = None;
+    get_ch!().uninit();
 }
 
+/// Send bytes to the other party, and receive theirs.
 #[inline]
 pub fn exchange_bytes(bytes_out: &[u8]) -> std::io::Result<Vec<u8>> {
-    CH.lock()
-        .expect("Poisoned FieldChannel")
-        .exchange_bytes(bytes_out)
+    let mut all = MpcTwoNet::try_broadcast_bytes(bytes_out).map_err(std::io::Error::other)?;
+    Ok(all.swap_remove(1 - MpcTwoNet::party_id()))
 }
 
 #[inline]
 pub fn is_init() -> bool {
-    get_ch!().stream.is_some()
+    get_ch!().mux.is_some()
 }
 
 #[inline]
@@ -232,7 +189,7 @@ pub fn reset_stats() {
 #[inline]
 pub fn am_first() -> bool {
     let ch = get_ch!();
-    assert!(ch.stream.is_some(), "uninit channel");
+    assert!(ch.mux.is_some(), "uninit channel");
     ch.talk_first
 }
 
@@ -241,12 +198,7 @@ pub struct MpcTwoNet;
 impl MpcNet for MpcTwoNet {
     #[inline]
     fn party_id() -> usize {
-        let first = get_ch!().talk_first;
-        if first {
-            0
-        } else {
-            1
-        }
+        get_ch!().party_id()
     }
 
     #[inline]
@@ -255,65 +207,87 @@ impl MpcNet for MpcTwoNet {
     }
 
     #[inline]
-    fn init_from_file(path: &str, party_id: usize) {
+    fn try_init_from_file(path: &str, party_id: usize) -> Result<(), NetError> {
         get_ch!().init_from_path(path, party_id);
+        Ok(())
     }
 
     #[inline]
     fn is_init() -> bool {
-        get_ch!().stream.is_some()
+        get_ch!().mux.is_some()
     }
 
     #[inline]
     fn deinit() {
-        get_ch!().stream = None;
+        get_ch!().uninit();
     }
 
     #[inline]
     fn reset_stats() {
-        get_ch!().stats = Stats::default();
+        get_ch!().reset_stats();
     }
 
     #[inline]
     fn stats() -> crate::Stats {
-        get_ch!().stats.clone()
+        get_ch!().stats()
     }
 
     #[inline]
-    fn broadcast_bytes(bytes: &[u8]) -> Vec<Vec<u8>> {
-        let other = get_ch!().exchange_bytes(bytes).unwrap();
-        if Self::am_king() {
-            vec![bytes.to_vec(), other]
-        } else {
-            vec![other, bytes.to_vec()]
-        }
+    fn abort(reason: &str) {
+        get_ch!().mux().abort(reason)
     }
 
     #[inline]
-    fn send_bytes_to_king(bytes: &[u8]) -> Option<Vec<Vec<u8>>> {
-        let mut ch = get_ch!();
-        ch.stats.to_king += 1;
-        if ch.talk_first {
-            let other = ch.recv_vec();
-            debug_assert_eq!(bytes.len(), other.len());
-            Some(vec![bytes.to_vec(), other])
-        } else {
-            ch.send_slice(bytes);
-            None
-        }
+    fn next_tag() -> Tag {
+        get_ch!().next_tag()
     }
 
     #[inline]
-    fn recv_bytes_from_king(bytes: Option<Vec<Vec<u8>>>) -> Vec<u8> {
-        let mut ch = get_ch!();
-        ch.stats.from_king += 1;
-        if ch.talk_first {
-            let mut bytes = bytes.expect("king needs bytes");
-            assert_eq!(bytes.len(), 2);
-            ch.send_slice(&bytes.pop().unwrap());
-            bytes.pop().unwrap()
-        } else {
-            ch.recv_vec()
-        }
+    fn try_broadcast_bytes_tagged(tag: Tag, bytes: &[u8]) -> Result<Vec<Vec<u8>>, NetError> {
+        let mux = {
+            let mut ch = get_ch!();
+            ch.stats.broadcasts += 1;
+            ch.mux()
+        };
+        mux.broadcast(tag, bytes)
+    }
+
+    #[inline]
+    fn try_send_bytes_to_king_tagged(
+        tag: Tag,
+        bytes: &[u8],
+    ) -> Result<Option<Vec<Vec<u8>>>, NetError> {
+        let mux = {
+            let mut ch = get_ch!();
+            ch.stats.to_king += 1;
+            ch.mux()
+        };
+        mux.send_to_king(tag, bytes)
+    }
+
+    #[inline]
+    fn try_recv_bytes_from_king_tagged(
+        tag: Tag,
+        bytes: Option<Vec<Vec<u8>>>,
+    ) -> Result<Vec<u8>, NetError> {
+        let mux = {
+            let mut ch = get_ch!();
+            ch.stats.from_king += 1;
+            ch.mux()
+        };
+        mux.recv_from_king(tag, bytes)
+    }
+
+    #[inline]
+    fn try_all_to_all_bytes_tagged(
+        tag: Tag,
+        bytes: Vec<Vec<u8>>,
+    ) -> Result<Vec<Vec<u8>>, NetError> {
+        let mux = {
+            let mut ch = get_ch!();
+            ch.stats.all_to_all += 1;
+            ch.mux()
+        };
+        mux.all_to_all(tag, bytes)
     }
 }
@@ -4,153 +4,375 @@
 use ark_ff::Field;
 use ark_poly::univariate::DensePolynomial;
 use ark_poly_commit::{LabeledCommitment, LabeledPolynomial, PCCommitment};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
 use mpc_trait::{struct_mpc_wire_impl, MpcWire};
 
-/// Check that S(X)*(P(X) + P(wX)) + (1-S(X))*P(X)*P(WX) - P(WWX) = Q(X)*Z(X)
+/// Check that qL(X)*P(X) + qR(X)*P(wX) + qM(X)*P(X)*P(wX) + qC(X) - qO(X)*P(wwX) = Q(X)*Z(X)
 /// where Z vanishes on the gate domain, and Q is existential
-#[derive(Clone)]
-pub struct GateProof<C, O> {
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct GateProof<F, C> {
     /// Q commitment
     pub q_cmt: C,
-    /// S(x) proof
-    pub s_open: O,
-    /// Q(x) proof
-    pub q_open: O,
-    /// P(x) proof
-    pub p_open: O,
-    /// P(w*x) proof
-    pub p_w_open: O,
-    /// P(w*w*x) proof
-    pub p_w2_open: O,
+    /// Q(x)
+    pub q_x: F,
+    /// Selectors, at x
+    pub sel_x: Selectors<F>,
+}
+
+/// One of each gate selector: a gate holds when qL*l + qR*r + qM*l*r + qC = qO*o
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Selectors<T> {
+    /// Left wire coefficient
+    pub q_l: T,
+    /// Right wire coefficient
+    pub q_r: T,
+    /// Output wire coefficient
+    pub q_o: T,
+    /// Product coefficient
+    pub q_m: T,
+    /// Constant
+    pub q_c: T,
+}
+
+impl<T> Selectors<T> {
+    pub fn as_ref(&self) -> Selectors<&T> {
+        Selectors {
+            q_l: &self.q_l,
+            q_r: &self.q_r,
+            q_o: &self.q_o,
+            q_m: &self.q_m,
+            q_c: &self.q_c,
+        }
+    }
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> Selectors<U> {
+        Selectors {
+            q_l: f(self.q_l),
+            q_r: f(self.q_r),
+            q_o: f(self.q_o),
+            q_m: f(self.q_m),
+            q_c: f(self.q_c),
+        }
+    }
+    pub fn zip<U>(self, other: Selectors<U>) -> Selectors<(T, U)> {
+        Selectors {
+            q_l: (self.q_l, other.q_l),
+            q_r: (self.q_r, other.q_r),
+            q_o: (self.q_o, other.q_o),
+            q_m: (self.q_m, other.q_m),
+            q_c: (self.q_c, other.q_c),
+        }
+    }
+    /// The selectors, in the order `map` visits them
+    pub fn into_vec(self) -> Vec<T> {
+        vec![self.q_l, self.q_r, self.q_o, self.q_m, self.q_c]
+    }
+    /// Like `map`, with each selector's name
+    pub fn map_labeled<U>(self, mut f: impl FnMut(&'static str, T) -> U) -> Selectors<U> {
+        Selectors {
+            q_l: f("q_l", self.q_l),
+            q_r: f("q_r", self.q_r),
+            q_o: f("q_o", self.q_o),
+            q_m: f("q_m", self.q_m),
+            q_c: f("q_c", self.q_c),
+        }
+    }
+}
+
+/// A lookup table's columns: one for each gate wire, and the table id
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct TableColumns<T> {
+    /// Left wire column
+    pub t_l: T,
+    /// Right wire column
+    pub t_r: T,
+    /// Output wire column
+    pub t_o: T,
+    /// Table id (from one)
+    pub t_id: T,
+}
+
+impl<T> TableColumns<T> {
+    pub fn as_ref(&self) -> TableColumns<&T> {
+        TableColumns {
+            t_l: &self.t_l,
+            t_r: &self.t_r,
+            t_o: &self.t_o,
+            t_id: &self.t_id,
+        }
+    }
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> TableColumns<U> {
+        TableColumns {
+            t_l: f(self.t_l),
+            t_r: f(self.t_r),
+            t_o: f(self.t_o),
+            t_id: f(self.t_id),
+        }
+    }
+    pub fn zip<U>(self, other: TableColumns<U>) -> TableColumns<(T, U)> {
+        TableColumns {
+            t_l: (self.t_l, other.t_l),
+            t_r: (self.t_r, other.t_r),
+            t_o: (self.t_o, other.t_o),
+            t_id: (self.t_id, other.t_id),
+        }
+    }
+    /// The columns, in the order `map` visits them
+    pub fn into_vec(self) -> Vec<T> {
+        vec![self.t_l, self.t_r, self.t_o, self.t_id]
+    }
+    /// Like `map`, with each column's name
+    pub fn map_labeled<U>(self, mut f: impl FnMut(&'static str, T) -> U) -> TableColumns<U> {
+        TableColumns {
+            t_l: f("t_l", self.t_l),
+            t_r: f("t_r", self.t_r),
+            t_o: f("t_o", self.t_o),
+            t_id: f("t_id", self.t_id),
+        }
+    }
+}
+
+impl<F: Field> TableColumns<F> {
+    /// Combine a row into one value: `t_l + z*t_r + z^2*t_o + z^3*t_id`
+    pub fn compress(&self, z: F) -> F {
+        self.t_l + z * (self.t_r + z * (self.t_o + z * self.t_id))
+    }
+}
+
+/// A circuit's lookup selector and tables
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct LookupTables<T> {
+    /// The table id (from one) at lookup gates; zero elsewhere
+    pub q_k: T,
+    /// The rows of every table, one after another
+    pub t: TableColumns<T>,
+}
+
+impl<T> LookupTables<T> {
+    pub fn as_ref(&self) -> LookupTables<&T> {
+        LookupTables {
+            q_k: &self.q_k,
+            t: self.t.as_ref(),
+        }
+    }
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> LookupTables<U> {
+        LookupTables {
+            q_k: f(self.q_k),
+            t: self.t.map(f),
+        }
+    }
+    pub fn zip<U>(self, other: LookupTables<U>) -> LookupTables<(T, U)> {
+        LookupTables {
+            q_k: (self.q_k, other.q_k),
+            t: self.t.zip(other.t),
+        }
+    }
+    /// The polynomials, in the order `map` visits them
+    pub fn into_vec(self) -> Vec<T> {
+        let mut v = vec![self.q_k];
+        v.extend(self.t.into_vec());
+        v
+    }
+    /// Like `map`, with each polynomial's name
+    pub fn map_labeled<U>(self, mut f: impl FnMut(&'static str, T) -> U) -> LookupTables<U> {
+        LookupTables {
+            q_k: f("q_k", self.q_k),
+            t: self.t.map_labeled(f),
+        }
+    }
+}
+
+/// Check that the wires of each lookup gate are a row of its table (plookup).
+///
+/// With rows compressed by a challenge z, f(X) holds the lookup gates' wires, t(X) the tables,
+/// and h1(X), h2(X) the even and odd entries of the tables and f, sorted by table row.
+/// Then L(X) = num(X)/den(X) multiplies to 1 over the gates, where
+/// num(X) = (1+b)(c+f(X))(c(1+b)+t(X)+b*t(gX)) and
+/// den(X) = (c(1+b)+h1(X)+b*h2(X))(c(1+b)+h2(X)+b*h1(gX)).
+/// We check that, and
+/// L(X)den(X) - num(X) + a*qK(X)(P(X) + z*P(wX) + z^2*P(wwX) + z^3*qK(X) - f(X)) = Q(X)*Z(X)
+/// where Z vanishes on the gate domain.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct LookupProof<F, C> {
+    /// f commitment
+    pub f_cmt: C,
+    /// h1 commitment
+    pub h1_cmt: C,
+    /// h2 commitment
+    pub h2_cmt: C,
+    /// L commitment
+    pub l_cmt: C,
+    /// proof that L multiplies to 1 over the gate domain
+    pub l_prod_pf: ProductProof<F, C>,
+    /// Q commitment
+    pub q_cmt: C,
+    /// f(x)
+    pub f_x: F,
+    /// h1(x)
+    pub h1_x: F,
+    /// h1(g*x)
+    pub h1_gx: F,
+    /// h2(x)
+    pub h2_x: F,
+    /// L(x)
+    pub l_x: F,
+    /// Q(x)
+    pub q_x: F,
+    /// qK(x)
+    pub q_k_x: F,
+    /// Tables, at x
+    pub t_x: TableColumns<F>,
+    /// Tables, at g*x
+    pub t_gx: TableColumns<F>,
 }
 
 /// Check that P(X) agree with v(X) for the public wires
 /// via P(X) - v(X) = Q(X)*Z(X)
 /// where Z vanishes on the public wires
-#[derive(Clone)]
-pub struct PublicProof<C, O> {
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct PublicProof<F, C> {
     /// Q commitment
     pub q_cmt: C,
-    /// Q(x) proof
-    pub q_open: O,
-    /// P(x) proof
-    pub p_open: O,
+    /// Q(x)
+    pub q_x: F,
 }
 
-/// Proof that some polynomial f has a product pi over a domain
-#[derive(Clone)]
-pub struct ProductProof<C, O> {
+/// Proof that some polynomial f multiplies to 1 over a domain with generator w and size k
+/// via t(wX) - t(X)f(wX) = q(X)*Z(X) and t(w^{k-1}) = 1
+/// where t holds the partial products, and Z vanishes on the domain
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct ProductProof<F, C> {
     /// t (partial products) commitment
     pub t_cmt: C,
     /// quotient commitment
     pub q_cmt: C,
-    /// t(w^{k-1}) opening
-    pub t_wk_open: O,
-    /// t(r) opening
-    pub t_r_open: O,
-    /// t(w*r) opening
-    pub t_wr_open: O,
-    /// f(w*r) opening
-    pub f_wr_open: O,
-    /// q(r) opening
-    pub q_r_open: O,
+    /// t(w^{k-1})
+    pub t_wk: F,
+    /// t(x)
+    pub t_x: F,
+    /// t(w*x)
+    pub t_wx: F,
+    /// f(w*x)
+    pub f_wx: F,
+    /// q(x)
+    pub q_x: F,
 }
 
 /// Check that P(X) = P(W(X)) on the wires
 /// via P(X) - v(X) = Q(X)*Z(X)
 /// where Z vanishes on the public wires
-#[derive(Clone)]
-pub struct WiringProof<C, O> {
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct WiringProof<F, C> {
     /// commitment to L_1
     pub l1_cmt: C,
     /// proof that L_1 multiplies to 1
     /// over the wire wire domain
-    pub l1_prod_pf: ProductProof<C, O>,
+    pub l1_prod_pf: ProductProof<F, C>,
     /// commitment to L_2's quotient over the wire domain
     pub l2_q_cmt: C,
-    /// p(x) openning
-    pub p_x_open: O,
-    /// w(x) openning
-    pub w_x_open: O,
-    /// L_1(x) openning
-    pub l1_x_open: O,
-    /// L_2(x) openning
-    pub l2_q_x_open: O,
+    /// w(x)
+    pub w_x: F,
+    /// L_1(x)
+    pub l1_x: F,
+    /// L_2's quotient at x
+    pub l2_q_x: F,
 }
 
 /// Plonk proof
-#[derive(Clone)]
-pub struct Proof<F, C, O> {
+///
+/// Every polynomial is opened at one challenge x, or at its shifts, by a single batched proof.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Proof<F, C, B> {
     /// Commitment to P
     pub p_cmt: C,
+    /// P(x)
+    pub p_x: F,
+    /// P(w*x)
+    pub p_wx: F,
+    /// P(w*w*x)
+    pub p_w2x: F,
     /// Proof of wiring
-    pub wiring: WiringProof<C, (F, O)>,
-    /// Proof of gates
-    pub gates: GateProof<C, (F, O)>,
+    pub wiring: WiringProof<F, C>,
     /// Proof of gates
-    pub public: PublicProof<C, (F, O)>,
+    pub gates: GateProof<F, C>,
+    /// Proof of public inputs
+    pub public: PublicProof<F, C>,
+    /// Proof of lookups, if the circuit has any
+    pub lookup: Option<LookupProof<F, C>>,
+    /// Batched proof of the evaluations
+    pub pc_proof: B,
 }
 
-#[derive(Clone)]
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
 pub struct ProverKey<F: Field, C: PCCommitment, PcCk> {
     pub w: LabeledPolynomial<F, DensePolynomial<F>>,
     pub w_cmt: LabeledCommitment<C>,
-    pub s: LabeledPolynomial<F, DensePolynomial<F>>,
-    pub s_cmt: LabeledCommitment<C>,
+    pub q: Selectors<LabeledPolynomial<F, DensePolynomial<F>>>,
+    pub q_cmt: Selectors<LabeledCommitment<C>>,
+    pub lookup: Option<LookupTables<LabeledPolynomial<F, DensePolynomial<F>>>>,
+    pub lookup_cmt: Option<LookupTables<LabeledCommitment<C>>>,
     pub pc_ck: PcCk,
 }
 
-#[derive(Clone)]
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
 pub struct VerifierKey<C: PCCommitment, PcVk> {
     pub w_cmt: LabeledCommitment<C>,
-    pub s_cmt: LabeledCommitment<C>,
+    pub q_cmt: Selectors<LabeledCommitment<C>>,
+    pub lookup_cmt: Option<LookupTables<LabeledCommitment<C>>>,
     pub pc_vk: PcVk,
 }
 
-impl<C: MpcWire, O: MpcWire> MpcWire for GateProof<C, O> {
-    struct_mpc_wire_impl!(GateProof<C, O>;
-        (C, q_cmt), (O, s_open), (O, q_open), (O, p_open), (O, p_w_open), (O, p_w2_open));
+impl<T: MpcWire> MpcWire for Selectors<T> {
+    struct_mpc_wire_impl!(Selectors<T>;
+        (T, q_l), (T, q_r), (T, q_o), (T, q_m), (T, q_c));
 }
 
-impl<C: MpcWire, O: MpcWire> MpcWire for PublicProof<C, O> {
-    struct_mpc_wire_impl!(PublicProof<C, O>;
-        (C, q_cmt), (O, q_open), (O, p_open));
+impl<T: MpcWire> MpcWire for TableColumns<T> {
+    struct_mpc_wire_impl!(TableColumns<T>;
+        (T, t_l), (T, t_r), (T, t_o), (T, t_id));
 }
-impl<C: MpcWire, O: MpcWire> MpcWire for ProductProof<C, O> {
-    struct_mpc_wire_impl!(ProductProof<C, O>;
-        (C, q_cmt), (C, t_cmt), (O, t_wk_open), (O, t_r_open), (O, t_wr_open), (O, f_wr_open), (O, q_r_open));
+
+impl<T: MpcWire> MpcWire for LookupTables<T> {
+    struct_mpc_wire_impl!(LookupTables<T>;
+        (T, q_k), (TableColumns<T>, t));
 }
 
-impl<C: MpcWire, O: MpcWire> MpcWire for WiringProof<C, O> {
-    struct_mpc_wire_impl!(WiringProof<C, O>;
-        (C, l1_cmt), (ProductProof<C, O>, l1_prod_pf), (C, l2_q_cmt), (O, p_x_open), (O, w_x_open), (O, l1_x_open), (O, l2_q_x_open));
+impl<F: MpcWire, C: MpcWire> MpcWire for LookupProof<F, C> {
+    struct_mpc_wire_impl!(LookupProof<F, C>;
+        (C, f_cmt), (C, h1_cmt), (C, h2_cmt), (C, l_cmt), (ProductProof<F, C>, l_prod_pf),
+        (C, q_cmt), (F, f_x), (F, h1_x), (F, h1_gx), (F, h2_x), (F, l_x), (F, q_x), (F, q_k_x),
+        (TableColumns<F>, t_x), (TableColumns<F>, t_gx));
 }
 
-impl<F: MpcWire, C: MpcWire, O: MpcWire> MpcWire for Proof<F, C, O> {
-    struct_mpc_wire_impl!(Proof<F, PC>;
-        (C, p_cmt),
-        (WiringProof<C, (F, O)>, wiring),
-        (GateProof<C, (F, O)>, gates),
-        (PublicProof<C, (F, O)>, public)
-    );
+impl<F: MpcWire, C: MpcWire> MpcWire for GateProof<F, C> {
+    struct_mpc_wire_impl!(GateProof<F, C>;
+        (C, q_cmt), (F, q_x), (Selectors<F>, sel_x));
 }
 
-// impl<F: MpcWire, C: MpcWire, PcCk: MpcWire> MpcWire for ProverKey<F, C, PcCk> {
-//     struct_mpc_wire_impl!(ProverKey<F, C, PcCk>;
-//         (C, p_cmt),
-//         (WiringProof<C, (F, O)>, wiring),
-//         (GateProof<C, (F, O)>, gates),
-//         (PublicProof<C, (F, O)>, public)
-//     );
-// }
-// 
-// impl<F: Reveal, C: Reveal, O: Reveal> Reveal for Proof<F, C, O> {
-//     type Base = Proof<F::Base, C::Base, O::Base>;
-//     struct_reveal_impl!(Proof<F, PC>, Proof;
-//         (C, p_cmt),
-//         (WiringProof<C, (F, O)>, wiring),
-//         (GateProof<C, (F, O)>, gates),
-//         (PublicProof<C, (F, O)>, public)
-//     );
-// }
+impl<F: MpcWire, C: MpcWire> MpcWire for PublicProof<F, C> {
+    struct_mpc_wire_impl!(PublicProof<F, C>;
+        (C, q_cmt), (F, q_x));
+}
+impl<F: MpcWire, C: MpcWire> MpcWire for ProductProof<F, C> {
+    struct_mpc_wire_impl!(ProductProof<F, C>;
+        (C, t_cmt), (C, q_cmt), (F, t_wk), (F, t_x), (F, t_wx), (F, f_wx), (F, q_x));
+}
+
+impl<F: MpcWire, C: MpcWire> MpcWire for WiringProof<F, C> {
+    struct_mpc_wire_impl!(WiringProof<F, C>;
+        (C, l1_cmt), (ProductProof<F, C>, l1_prod_pf), (C, l2_q_cmt), (F, w_x), (F, l1_x),
+        (F, l2_q_x));
+}
 
+impl<F: MpcWire, C: MpcWire, B: MpcWire> MpcWire for Proof<F, C, B> {
+    struct_mpc_wire_impl!(Proof<F, C, B>;
+        (C, p_cmt),
+        (F, p_x),
+        (F, p_wx),
+        (F, p_w2x),
+        (WiringProof<F, C>, wiring),
+        (GateProof<F, C>, gates),
+        (PublicProof<F, C>, public),
+        (Option<LookupProof<F, C>>, lookup),
+        (B, pc_proof)
+    );
+}
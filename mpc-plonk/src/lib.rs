@@ -12,6 +12,8 @@ pub use data_structures::*;
 pub mod relations;
 pub use relations::*;
 pub mod reveal;
+pub mod transcript;
+pub use transcript::Transcript;
 mod util;
 
 use log::debug;
@@ -20,10 +22,14 @@ use blake2::Blake2s;
 
 use ark_ff::{FftField, Field};
 
-use ark_poly_commit::{LabeledCommitment, LabeledPolynomial, PCRandomness, PolynomialCommitment};
+use ark_poly_commit::{
+    LabeledCommitment, LabeledPolynomial, PCCommitment, PCRandomness, PolynomialCommitment,
+    QuerySet,
+};
 
 use ark_poly::{
-    domain::EvaluationDomain,
+    domain::{EvaluationDomain, Radix2EvaluationDomain},
+    evaluations::univariate::Evaluations,
     univariate::{DenseOrSparsePolynomial, DensePolynomial},
     Polynomial, UVPolynomial,
 };
@@ -36,8 +42,8 @@ use std::iter::once;
 use std::marker::PhantomData;
 use thiserror::Error;
 
+pub use ark_marlin::rng::FiatShamirRng;
 use mpc_trait::MpcWire;
-pub use util::FiatShamirRng;
 
 pub fn setup<'r, F: FftField, PC: PolynomialCommitment<F, DensePolynomial<F>>>(
     srs: &PC::UniversalParams,
@@ -49,42 +55,121 @@ pub fn setup<'r, F: FftField, PC: PolynomialCommitment<F, DensePolynomial<F>>>(
     let (ck, vk) = PC::trim(
         &srs,
         circ.degree_bound(),
-        0,
-        Some(&[circ.domains.wires.size() - 1]),
+        relations::flat::P_QUERIES,
+        Some(&[circ.p_degree_bound()]),
     )
     .unwrap();
     let w = LabeledPolynomial::new("w".into(), circ.w.clone(), None, None);
-    let (mut cs, rs) = PC::commit(&ck, once(&w), None).unwrap();
-    assert_eq!(cs.len(), 1);
-    assert_eq!(rs.len(), 1);
-    let w_cmt = cs.pop().unwrap();
-    let s = LabeledPolynomial::new("s".into(), circ.s.clone(), None, None);
-    let (mut cs, rs) = PC::commit(&ck, once(&s), None).unwrap();
-    assert_eq!(cs.len(), 1);
-    assert_eq!(rs.len(), 1);
-    let s_cmt = cs.pop().unwrap();
+    let q = circ
+        .q
+        .clone()
+        .map_labeled(|label, q| LabeledPolynomial::new(label.into(), q, None, None));
+    let lookup = circ
+        .lookup
+        .clone()
+        .map(|l| l.map_labeled(|label, t| LabeledPolynomial::new(label.into(), t, None, None)));
+    // Commit to w, the selectors, and the lookup tables, in that order
+    let polys = once(&w).chain(q.as_ref().into_vec()).chain(
+        lookup
+            .as_ref()
+            .into_iter()
+            .flat_map(|l| l.as_ref().into_vec()),
+    );
+    let (cs, rs) = PC::commit(&ck, polys, None).unwrap();
+    assert_eq!(cs.len(), rs.len());
+    let mut cs = cs.into_iter();
+    let w_cmt = cs.next().unwrap();
+    let q_cmt = q.as_ref().map(|_| cs.next().unwrap());
+    let lookup_cmt = lookup
+        .as_ref()
+        .map(|l| l.as_ref().map(|_| cs.next().unwrap()));
+    assert!(cs.next().is_none());
     (
         ProverKey {
             pc_ck: ck,
-            s_cmt: s_cmt.clone(),
+            q_cmt: q_cmt.clone(),
             w_cmt: w_cmt.clone(),
-            s,
+            lookup_cmt: lookup_cmt.clone(),
+            q,
             w,
+            lookup,
         },
         VerifierKey {
             pc_vk: vk,
-            s_cmt,
+            q_cmt,
             w_cmt,
+            lookup_cmt,
         },
     )
 }
 
+/// Domain separator for the transcript
+const PROTOCOL: &str = "mpc-plonk";
+
+/// Absorb the statement: the circuit's size, its public inputs (sorted by name, as
+/// (name, wire index, value) triples), and the circuit key's commitments.
+fn absorb_statement<F: FftField, C: PCCommitment>(
+    transcript: &mut Transcript,
+    circ: &relations::flat::CircuitLayout<F>,
+    public: &[(&String, usize, F)],
+    w_cmt: &LabeledCommitment<C>,
+    q_cmt: &Selectors<LabeledCommitment<C>>,
+    lookup_cmt: &Option<LookupTables<LabeledCommitment<C>>>,
+) {
+    transcript.append("n_gates", &(circ.domains.gates.size() as u64));
+    transcript.append("n_public", &(public.len() as u64));
+    for (name, i, v) in public {
+        transcript.append_bytes("public_name", name.as_bytes());
+        transcript.append("public_index", &(*i as u64));
+        transcript.append("public_value", v);
+    }
+    transcript.append(w_cmt.label(), w_cmt);
+    for c in q_cmt.as_ref().into_vec() {
+        transcript.append(c.label(), c);
+    }
+    for c in lookup_cmt.iter().flat_map(|l| l.as_ref().into_vec()) {
+        transcript.append(c.label(), c);
+    }
+}
+
 #[allow(dead_code)]
 pub struct Prover<'r, F: FftField, PC: PolynomialCommitment<F, DensePolynomial<F>>> {
     _pc: PhantomData<PC>,
     pk: &'r ProverKey<F, PC::Commitment, PC::CommitterKey>,
     zk_rng: RefCell<&'r mut dyn RngCore>,
-    fs_rng: RefCell<FiatShamirRng<Blake2s>>,
+    transcript: RefCell<Transcript>,
+}
+
+/// A committed polynomial: (commitment, labeled polynomial, randomness)
+type Committed<F, PC> = (
+    LabeledCommitment<<PC as PolynomialCommitment<F, DensePolynomial<F>>>::Commitment>,
+    LabeledPolynomial<F, DensePolynomial<F>>,
+    <PC as PolynomialCommitment<F, DensePolynomial<F>>>::Randomness,
+);
+
+/// An opening query: (polynomial, randomness, commitment, point label, point)
+type Query<'a, F, PC> = (
+    &'a LabeledPolynomial<F, DensePolynomial<F>>,
+    &'a <PC as PolynomialCommitment<F, DensePolynomial<F>>>::Randomness,
+    &'a LabeledCommitment<<PC as PolynomialCommitment<F, DensePolynomial<F>>>::Commitment>,
+    &'static str,
+    F,
+);
+
+/// The lookups' compressed rows over the gates: the tables, f, and the sorted witness as h1 and
+/// h2
+struct LookupEvals<F> {
+    /// The challenge that compresses the rows
+    zeta: F,
+    t: Vec<F>,
+    f: Vec<F>,
+    h1: Vec<F>,
+    h2: Vec<F>,
+}
+
+/// Interpolate evaluations over the gates
+fn interpolate<F: FftField>(evals: Vec<F>, dom: Radix2EvaluationDomain<F>) -> DensePolynomial<F> {
+    Evaluations::<F, Radix2EvaluationDomain<F>>::from_vec_and_domain(evals, dom).interpolate()
 }
 
 impl<'r, F: FftField, PC: PolynomialCommitment<F, DensePolynomial<F>>> Prover<'r, F, PC> {
@@ -96,7 +181,7 @@ impl<'r, F: FftField, PC: PolynomialCommitment<F, DensePolynomial<F>>> Prover<'r
             _pc: PhantomData::default(),
             pk,
             zk_rng: RefCell::new(zk_rng),
-            fs_rng: RefCell::new(FiatShamirRng::from_seed(&0u64)),
+            transcript: RefCell::new(Transcript::new(PROTOCOL)),
         }
     }
 }
@@ -107,164 +192,139 @@ where
     PC::Commitment: mpc_trait::MpcWire,
     PC::Error: 'static,
 {
-    fn prove_unit_product<D: EvaluationDomain<F>>(
+    fn challenge(&self, label: &str) -> F {
+        self.transcript.borrow_mut().challenge::<F>(label)
+    }
+
+    /// A query of a committed polynomial at `x`, labeled `point_label`.
+    fn query<'a>(c: &'a Committed<F, PC>, point_label: &'static str, x: F) -> Query<'a, F, PC> {
+        (&c.1, &c.2, &c.0, point_label, x)
+    }
+
+    /// The partial products t of f over the domain, and the quotient q of t(wX) - t(X)f(wX) by
+    /// the domain's vanishing polynomial, labeled after f.
+    ///
+    /// Neither needs a challenge, so they are committed in f's round.
+    fn unit_product_polys<D: EvaluationDomain<F>>(
         &self,
         f: &LabeledPolynomial<F, DensePolynomial<F>>,
-        f_cmt: &LabeledCommitment<PC::Commitment>,
-        f_rand: &PC::Randomness,
         domain: D,
-    ) -> ProductProof<PC::Commitment, (F, PC::Proof)> {
-        let timer = start_timer!(|| "prove_unit_product");
+    ) -> Vec<LabeledPolynomial<F, DensePolynomial<F>>> {
+        let timer = start_timer!(|| "unit_product_polys");
+        let _op = mpc_net::op("unit_product_polys");
         let t_evals = {
-            let mut t = f.evaluate_over_domain_by_ref(domain);
+            let mut t = util::evaluate_over_domain(f.polynomial(), domain);
             F::partial_products_in_place(&mut t.evals);
             t
         };
-        // debug_assert!(t_evals.evals[f.coeffs.len() - 1], F::one());
-        //        debug_assert_eq!(
-        //            t_evals.evals[f.coeffs.len() - 1] * t_evals.evals[0],
-        //            t_evals[0]
-        //        );
-        let t = t_evals.interpolate();
-        let (t_cmt, t, t_rand) = self.commit("t", t.clone(), None, None).unwrap();
+        // t is opened at x and wx, off the domain, and at wk
+        let t = self.blind(t_evals.interpolate(), domain, 2);
         let w = domain.element(1);
-        // let q = {
-        //     let d = &shift(t.clone(), w) - &t.naive_mul(&shift(f.clone(), w));
-        //     let (q,r) = d.divide_by_vanishing_poly(domain).unwrap();
-        //     assert!(r.is_zero());
-        //     q
-        // };
-        let q_timer = start_timer!(|| "q");
+        // Blinding takes t and f past the domain's size, so we divide in coefficient form
         let q = {
-            // get f(wX) over coset
-            let mut f_evals = f.coeffs.clone();
-            D::distribute_powers(&mut f_evals, w);
-            domain.coset_fft_in_place(&mut f_evals);
-            // get t(X) over coset
-            let mut t_evals = t.coeffs.clone();
-            domain.coset_fft_in_place(&mut t_evals);
-            // get f(wX)t(X) over coset
-            let fwt_evals = domain.mul_polynomials_in_evaluation_domain(&f_evals, &t_evals);
-            // get t(wX) over coset
-            let mut tw_evals = t.coeffs.clone();
-            D::distribute_powers(&mut tw_evals, w);
-            domain.coset_fft_in_place(&mut tw_evals);
-            // get t(wX) - f(wX)t(X) over coset
-            ark_std::cfg_iter_mut!(tw_evals)
-                .zip(fwt_evals)
-                .for_each(|(a, b)| *a -= b);
-            domain.divide_by_vanishing_poly_on_coset_in_place(&mut tw_evals);
-            domain.coset_ifft_in_place(&mut tw_evals);
-            DensePolynomial::from_coefficients_vec(tw_evals)
+            let d = &util::shift(t.clone(), w) - &(&t * &util::shift(f.polynomial().clone(), w));
+            let (q, _r) = d.divide_by_vanishing_poly(domain).unwrap();
+            q
         };
-        end_timer!(q_timer);
-        // assert_eq!(q, qq);
-        let (q_cmt, q, q_rand) = self.commit("q", q.clone(), None, None).unwrap();
-        let k = domain.size();
-        //        debug_assert_eq!(t.evaluate(&domain.element(k - 1)), F::one());
-        //        for i in 0..k {
-        //            let r = domain.element(i);
-        //            debug_assert_eq!(t.evaluate(&(w * r)), t.evaluate(&r) * f.evaluate(&(w * r)));
-        //        }
-        let r = self.fs_rng.borrow_mut().gen::<F>();
-        //        debug_assert_eq!(
-        //            t.evaluate(&(w * r)) - t.evaluate(&r) * f.evaluate(&(w * r)),
-        //            domain.evaluate_vanishing_polynomial(r) * q.evaluate(&r)
-        //        );
-        let t_wr_open = self.eval(&t, &t_rand, &t_cmt, w * r).unwrap();
-        let t_r_open = self.eval(&t, &t_rand, &t_cmt, r).unwrap();
-        let t_wk_open = self
-            .eval(&t, &t_rand, &t_cmt, domain.element(k - 1))
-            .unwrap();
-        let f_wr_open = self.eval(&f, &f_rand, &f_cmt, w * r).unwrap();
-        let q_r_open = self.eval(&q, &q_rand, &q_cmt, r).unwrap();
         end_timer!(timer);
-        //        debug_assert_eq!(
-        //            t_wr_open.0 - t_r_open.0 * f_wr_open.0,
-        //            domain.evaluate_vanishing_polynomial(r) * q_r_open.0
-        //        );
-        //        debug_assert_eq!(t_wk_open.0, F::one());
+        vec![
+            LabeledPolynomial::new(format!("{}_t", f.label()), t, None, Some(3)),
+            LabeledPolynomial::new(format!("{}_q", f.label()), q, None, Some(1)),
+        ]
+    }
+
+    /// The queries of the product argument for f: t at the domain's last element, at x and at
+    /// wx; f at wx; and q at x. `labels` name the last element and wx.
+    fn unit_product_queries<'a, D: EvaluationDomain<F>>(
+        f: &'a Committed<F, PC>,
+        t: &'a Committed<F, PC>,
+        q: &'a Committed<F, PC>,
+        domain: D,
+        labels: (&'static str, &'static str),
+        x: F,
+    ) -> Vec<Query<'a, F, PC>> {
+        let (last, shifted) = labels;
+        let wx = domain.element(1) * x;
+        vec![
+            Self::query(t, last, domain.element(domain.size() - 1)),
+            Self::query(t, "x", x),
+            Self::query(t, shifted, wx),
+            Self::query(f, shifted, wx),
+            Self::query(q, "x", x),
+        ]
+    }
+
+    /// The product argument for f, from its commitments and, via `next`, its evaluations in
+    /// query order.
+    fn unit_product_proof(
+        t: &Committed<F, PC>,
+        q: &Committed<F, PC>,
+        next: &mut impl FnMut() -> F,
+    ) -> ProductProof<F, PC::Commitment> {
         ProductProof {
-            t_cmt: t_cmt.commitment,
-            q_cmt: q_cmt.commitment,
-            t_wk_open,
-            t_r_open,
-            t_wr_open,
-            f_wr_open,
-            q_r_open,
+            t_cmt: t.0.commitment().clone(),
+            q_cmt: q.0.commitment().clone(),
+            t_wk: next(),
+            t_x: next(),
+            t_wx: next(),
+            f_wx: next(),
+            q_x: next(),
         }
     }
 
-    /// Prove that p(X) = p(w(X)) on the domain.
-    fn prove_wiring<D: EvaluationDomain<F>>(
+    /// The wiring accumulator L1 = (p + y*w + z)/(p + yX + z) over the domain, blinded.
+    ///
+    /// It multiplies to 1 over the domain iff p(X) = p(w(X)) there.
+    fn wiring_accumulator<D: EvaluationDomain<F>>(
         &self,
         p: &LabeledPolynomial<F, DensePolynomial<F>>,
-        p_cmt: &LabeledCommitment<PC::Commitment>,
-        p_rand: &PC::Randomness,
+        y: F,
+        z: F,
         dom: D,
-    ) -> WiringProof<PC::Commitment, (F, PC::Proof)> {
-        let timer = start_timer!(|| "prove_wiring");
-        let y = self.fs_rng.borrow_mut().gen::<F>();
-        let z = self.fs_rng.borrow_mut().gen::<F>();
-        let p_evals = p.evaluate_over_domain_by_ref(dom);
+    ) -> LabeledPolynomial<F, DensePolynomial<F>> {
+        let timer = start_timer!(|| "wiring_accumulator");
+        let _op = mpc_net::op("wiring_accumulator");
+        let p_evals = util::evaluate_over_domain(p.polynomial(), dom);
         let w_evals = self.pk.w.evaluate_over_domain_by_ref(dom);
         let yx_z_evals =
             DensePolynomial::from_coefficients_vec(vec![z, y]).evaluate_over_domain_by_ref(dom);
         let num_evals = &(&p_evals + &(&w_evals * &y)) + &z;
         let den_evals = &(&p_evals + &yx_z_evals);
-        //TODO: batch!
         let l1_evals = &num_evals / &den_evals;
-        let l1 = l1_evals.clone().interpolate();
-        let (l1_cmt, l1, l1_rand) = self.commit("l1", l1, None, None).unwrap();
-        let l1_prod_pf = self.prove_unit_product(&l1, &l1_cmt, &l1_rand, dom);
-        let l2_q_coeffs = {
-            let mut l1_v = l1.coeffs.clone();
-            let mut num_v = num_evals.interpolate().coeffs;
-            let mut den_v = den_evals.clone().interpolate().coeffs;
-            dom.coset_fft_in_place(&mut l1_v);
-            dom.coset_fft_in_place(&mut num_v);
-            dom.coset_fft_in_place(&mut den_v);
-            let mut l1_den_v = dom.mul_polynomials_in_evaluation_domain(&l1_v, &den_v);
-            ark_std::cfg_iter_mut!(l1_den_v)
-                .zip(num_v)
-                .for_each(|(a, b)| *a -= b);
-            dom.divide_by_vanishing_poly_on_coset_in_place(&mut l1_den_v);
-            dom.coset_ifft_in_place(&mut l1_den_v);
-            l1_den_v
-        };
-        let l2_q = DensePolynomial::from_coefficients_vec(l2_q_coeffs);
-        let (l2_q_cmt, l2_q, l2_q_rand) = self.commit("l2_q", l2_q, None, None).unwrap();
-        let x = self.fs_rng.borrow_mut().gen::<F>();
-        let l2_q_x_open = self.eval(&l2_q, &l2_q_rand, &l2_q_cmt, x).unwrap();
-        let w_x_open = self
-            .eval(&self.pk.w, &PC::Randomness::empty(), &self.pk.w_cmt, x)
-            .unwrap();
-        let l1_x_open = self.eval(&l1, &l1_rand, &l1_cmt, x).unwrap();
-        let p_x_open = self.eval(&p, &p_rand, &p_cmt, x).unwrap();
-        //        debug_assert_eq!(
-        //            (p_x_open.0 + y * x + z) * l1_x_open.0 - (p_x_open.0 + y * w_x_open.0 + z),
-        //            l2_q_x_open.0 * dom.evaluate_vanishing_polynomial(x)
-        //        );
+        // l1 is opened at x, and at wx by its product argument
+        let l1 = self.blind(l1_evals.interpolate(), dom, 2);
         end_timer!(timer);
-        WiringProof {
-            l1_prod_pf,
-            l2_q_x_open,
-            l1_x_open,
-            p_x_open,
-            w_x_open,
-            l1_cmt: l1_cmt.commitment,
-            l2_q_cmt: l2_q_cmt.commitment,
-        }
+        LabeledPolynomial::new("l1".into(), l1, None, Some(2))
     }
 
-    fn prove_public(
+    /// The quotient of L1(X)(p(X) + yX + z) - (p(X) + y*w(X) + z) by the domain's vanishing
+    /// polynomial.
+    fn wiring_quotient<D: EvaluationDomain<F>>(
+        &self,
+        p: &LabeledPolynomial<F, DensePolynomial<F>>,
+        l1: &LabeledPolynomial<F, DensePolynomial<F>>,
+        y: F,
+        z: F,
+        dom: D,
+    ) -> DensePolynomial<F> {
+        // num and den as polynomials, rather than their interpolations over the domain: p is
+        // blinded
+        let num = &(p.polynomial() + &(self.pk.w.polynomial() * &y)) + &z;
+        let den = p.polynomial() + &DensePolynomial::from_coefficients_vec(vec![z, y]);
+        let d = &(l1.polynomial() * &den) - &num;
+        let (q, _r) = d.divide_by_vanishing_poly(dom).unwrap();
+        q
+    }
+
+    /// The quotient of p(X) - v(X) by the vanishing polynomial of the public wires, where v
+    /// interpolates p there.
+    fn public_quotient(
         &self,
         p: &LabeledPolynomial<F, DensePolynomial<F>>,
-        p_cmt: &LabeledCommitment<PC::Commitment>,
-        p_rand: &PC::Randomness,
         circ: &relations::flat::CircuitLayout<F>,
-    ) -> PublicProof<PC::Commitment, (F, PC::Proof)> {
-        let timer = start_timer!(|| "prove_public");
+    ) -> DensePolynomial<F> {
+        let timer = start_timer!(|| "public_quotient");
+        let _op = mpc_net::op("public_quotient");
         let points: Vec<(F, F)> = circ
             .public_indices
             .iter()
@@ -279,90 +339,262 @@ where
         let (q, _r) = DenseOrSparsePolynomial::DPolynomial(Cow::Owned(p.polynomial() - &v))
             .divide_with_q_and_r(&DenseOrSparsePolynomial::DPolynomial(Cow::Borrowed(&z)))
             .unwrap();
-        let (q_cmt, q, q_rand) = self.commit("pub_q", q, None, None).unwrap();
-        let x = self.fs_rng.borrow_mut().gen::<F>();
-        let q_open = self.eval(&q, &q_rand, &q_cmt, x).unwrap();
-        let p_open = self.eval(&p, &p_rand, &p_cmt, x).unwrap();
-        //debug_assert!( p_open.0 - v.evaluate(&x), q_open.0 * z.evaluate(&x));
         end_timer!(timer);
-        PublicProof {
-            q_open,
-            q_cmt: q_cmt.commitment,
-            p_open,
-        }
+        q
     }
 
-    fn prove_gates(
+    /// The quotient of qL(X)*P(X) + qR(X)*P(wX) + qM(X)*P(X)*P(wX) + qC(X) - qO(X)*P(wwX) by the
+    /// gates' vanishing polynomial.
+    fn gates_quotient(
         &self,
         p: &LabeledPolynomial<F, DensePolynomial<F>>,
-        p_cmt: &LabeledCommitment<PC::Commitment>,
-        p_rand: &PC::Randomness,
         circ: &relations::flat::CircuitLayout<F>,
-    ) -> GateProof<PC::Commitment, (F, PC::Proof)> {
-        let timer = start_timer!(|| "prove_gates");
+    ) -> DensePolynomial<F> {
+        let timer = start_timer!(|| "gates_quotient");
+        let _op = mpc_net::op("gates_quotient");
         let w = circ.domains.wires.group_gen;
         let pw = util::shift(p.polynomial().clone(), w);
         let pww = util::shift(p.polynomial().clone(), w * w);
-        let d = &(&circ.s * &(p.polynomial() + &pw)
-            + &(&(&circ.s * &-F::one()) + &F::one()) * &(p.polynomial() * &pw))
-            - &pww;
+        let sel = &circ.q;
+        let d = &(&(&(&(&sel.q_l * p.polynomial()) + &(&sel.q_r * &pw))
+            + &(&sel.q_m * &(p.polynomial() * &pw)))
+            + &sel.q_c)
+            - &(&sel.q_o * &pww);
         let (q, _r) = DenseOrSparsePolynomial::DPolynomial(Cow::Owned(d))
             .divide_with_q_and_r(&DenseOrSparsePolynomial::SPolynomial(Cow::Owned(
                 circ.domains.gates.vanishing_polynomial(),
             )))
             .unwrap();
-        // debug_assert!(r.is_zero());
-        let (q_cmt, q, q_rand) = self.commit("gates_q", q, None, None).unwrap();
-        let x = self.fs_rng.borrow_mut().gen::<F>();
-        let s_open = self
-            .eval(&self.pk.s, &PC::Randomness::empty(), &self.pk.s_cmt, x)
+        end_timer!(timer);
+        q
+    }
+
+    /// The tables, f, and the sorted witness (as h1 and h2) over the gates, with rows
+    /// compressed by `zeta`.
+    fn lookup_evals(
+        &self,
+        p: &LabeledPolynomial<F, DensePolynomial<F>>,
+        circ: &relations::flat::CircuitLayout<F>,
+        zeta: F,
+    ) -> LookupEvals<F> {
+        let timer = start_timer!(|| "lookup_evals");
+        let _op = mpc_net::op("lookup_evals");
+        let tables = self.pk.lookup.as_ref().expect("lookup tables");
+        let sorted = circ.lookup_witness.as_ref().expect("lookup witness");
+        let dom = circ.domains.gates;
+        let n = dom.size();
+        let w = circ.domains.wires.group_gen;
+        let pw = util::shift(p.polynomial().clone(), w);
+        let pww = util::shift(p.polynomial().clone(), w * w);
+        let compress = |c: TableColumns<&Vec<F>>, i: usize| {
+            TableColumns {
+                t_l: c.t_l[i],
+                t_r: c.t_r[i],
+                t_o: c.t_o[i],
+                t_id: c.t_id[i],
+            }
+            .compress(zeta)
+        };
+        // Tables, compressed
+        let t: Vec<F> = {
+            let t = tables
+                .t
+                .as_ref()
+                .map(|t| t.evaluate_over_domain_by_ref(dom).evals);
+            (0..n).map(|i| compress(t.as_ref(), i)).collect()
+        };
+        // f: the lookup gates' wires, compressed; the first table row elsewhere.
+        // Written as a sum, so that shared wires make every entry shared.
+        let f: Vec<F> = {
+            let q_k = tables.q_k.evaluate_over_domain_by_ref(dom).evals;
+            let p_evals = circ.evaluate_over_gates(p.polynomial()).evals;
+            let pw_evals = circ.evaluate_over_gates(&pw).evals;
+            let pww_evals = circ.evaluate_over_gates(&pww).evals;
+            (0..n)
+                .map(|i| {
+                    let is_lookup = if q_k[i].is_zero() {
+                        F::zero()
+                    } else {
+                        F::one()
+                    };
+                    let f = TableColumns {
+                        t_l: p_evals[i],
+                        t_r: pw_evals[i],
+                        t_o: pww_evals[i],
+                        t_id: q_k[i],
+                    }
+                    .compress(zeta);
+                    is_lookup * f + (F::one() - is_lookup) * t[0]
+                })
+                .collect()
+        };
+        // h1 and h2: the even and odd entries of the sorted witness, compressed
+        let (h1, h2): (Vec<F>, Vec<F>) = (0..n)
+            .map(|i| {
+                (
+                    compress(sorted.as_ref(), 2 * i),
+                    compress(sorted.as_ref(), 2 * i + 1),
+                )
+            })
+            .unzip();
+        end_timer!(timer);
+        LookupEvals { zeta, t, f, h1, h2 }
+    }
+
+    /// f, h1 and h2, blinded.
+    fn lookup_sorted_polys(
+        &self,
+        e: &LookupEvals<F>,
+        dom: Radix2EvaluationDomain<F>,
+    ) -> Vec<LabeledPolynomial<F, DensePolynomial<F>>> {
+        // f and h2 are opened at x; h1 at x and gx
+        let blind = |evals: &Vec<F>, k: usize| self.blind(interpolate(evals.clone(), dom), dom, k);
+        vec![
+            LabeledPolynomial::new("f".into(), blind(&e.f, 1), None, Some(1)),
+            LabeledPolynomial::new("h1".into(), blind(&e.h1, 2), None, Some(2)),
+            LabeledPolynomial::new("h2".into(), blind(&e.h2, 1), None, Some(1)),
+        ]
+    }
+
+    /// The lookup accumulator L = num/den over the gates, blinded.
+    fn lookup_accumulator(
+        &self,
+        e: &LookupEvals<F>,
+        beta: F,
+        gamma: F,
+        dom: Radix2EvaluationDomain<F>,
+    ) -> LabeledPolynomial<F, DensePolynomial<F>> {
+        let timer = start_timer!(|| "lookup_accumulator");
+        let _op = mpc_net::op("lookup_accumulator");
+        let n = dom.size();
+        let b1 = F::one() + beta;
+        let gb1 = gamma * b1;
+        let num_evals: Vec<F> = (0..n)
+            .map(|i| b1 * (gamma + e.f[i]) * (gb1 + e.t[i] + beta * e.t[(i + 1) % n]))
+            .collect();
+        let mut den_evals: Vec<F> = (0..n).map(|i| gb1 + e.h1[i] + beta * e.h2[i]).collect();
+        let den2_evals: Vec<F> = (0..n)
+            .map(|i| gb1 + e.h2[i] + beta * e.h1[(i + 1) % n])
+            .collect();
+        F::batch_product_in_place(&mut den_evals, &den2_evals);
+        let mut l_evals = num_evals;
+        F::batch_division_in_place(&mut l_evals, &den_evals);
+        // l is opened at x, and at gx by its product argument
+        let l = self.blind(interpolate(l_evals, dom), dom, 2);
+        end_timer!(timer);
+        LabeledPolynomial::new("l".into(), l, None, Some(2))
+    }
+
+    /// The quotient of L(X)den(X) - num(X) + a*qK(X)(P(X) + z*P(wX) + z^2*P(wwX) + z^3*qK(X) -
+    /// f(X)) by the gates' vanishing polynomial.
+    #[allow(clippy::too_many_arguments)]
+    fn lookup_quotient(
+        &self,
+        p: &LabeledPolynomial<F, DensePolynomial<F>>,
+        circ: &relations::flat::CircuitLayout<F>,
+        e: &LookupEvals<F>,
+        sorted: &[Committed<F, PC>],
+        l: &LabeledPolynomial<F, DensePolynomial<F>>,
+        beta: F,
+        gamma: F,
+        alpha: F,
+    ) -> DensePolynomial<F> {
+        let timer = start_timer!(|| "lookup_quotient");
+        let _op = mpc_net::op("lookup_quotient");
+        let tables = self.pk.lookup.as_ref().expect("lookup tables");
+        let dom = circ.domains.gates;
+        let w = circ.domains.wires.group_gen;
+        let g = dom.group_gen;
+        let zeta = e.zeta;
+        let (f, h1, h2) = (&sorted[0].1, &sorted[1].1, &sorted[2].1);
+        let pw = util::shift(p.polynomial().clone(), w);
+        let pww = util::shift(p.polynomial().clone(), w * w);
+        let b1 = F::one() + beta;
+        let gb1 = gamma * b1;
+        let t = interpolate(e.t.clone(), dom);
+        let tg = util::shift(t.clone(), g);
+        let h1g = util::shift(h1.polynomial().clone(), g);
+        let num = &(&(f.polynomial() + &gamma) * &b1) * &(&(&t + &gb1) + &(&tg * &beta));
+        let den = &(&(h1.polynomial() + &gb1) + &(h2.polynomial() * &beta))
+            * &(&(h2.polynomial() + &gb1) + &(&h1g * &beta));
+        let q_k = &tables.q_k;
+        let reads = &(&(&(p.polynomial() + &(&pw * &zeta)) + &(&pww * &(zeta * zeta)))
+            + &(q_k.polynomial() * &(zeta * zeta * zeta)))
+            - f.polynomial();
+        let d = &(&(l.polynomial() * &den) - &num) + &(&(q_k.polynomial() * &reads) * &alpha);
+        let (q, _r) = DenseOrSparsePolynomial::DPolynomial(Cow::Owned(d))
+            .divide_with_q_and_r(&DenseOrSparsePolynomial::SPolynomial(Cow::Owned(
+                dom.vanishing_polynomial(),
+            )))
             .unwrap();
-        let p_open = self.eval(p, p_rand, p_cmt, x).unwrap();
-        let q_open = self.eval(&q, &q_rand, &q_cmt, x).unwrap();
-        let p_w_open = self.eval(p, p_rand, p_cmt, w * x).unwrap();
-        let p_w2_open = self.eval(p, p_rand, p_cmt, w * w * x).unwrap();
-        //        debug_assert_eq!(
-        //            s_open.0 * (p_open.0 + p_w_open.0) + (F::one() - s_open.0) * p_open.0 * p_w_open.0
-        //                - p_w2_open.0,
-        //            q_open.0 * circ.domains.gates.evaluate_vanishing_polynomial(x)
-        //        );
         end_timer!(timer);
-        GateProof {
-            q_cmt: q_cmt.commitment,
-            s_open,
-            p_open,
-            q_open,
-            p_w_open,
-            p_w2_open,
-        }
+        q
     }
 
-    /// Evaluate polynomial `p` at `x`, producing a proof of the evaluation as well.
+    /// Evaluate each queried polynomial at its point, and prove all the evaluations at once.
     ///
-    /// With respect to a commitment `p_c` under randomness `p_r`.
-    fn eval(
+    /// A query is (polynomial, randomness, commitment, point label, point); queries at the same
+    /// point must use the same label. The evaluations are published, in query order.
+    fn open(
         &self,
-        p: &LabeledPolynomial<F, DensePolynomial<F>>,
-        p_r: &PC::Randomness,
-        p_c: &LabeledCommitment<PC::Commitment>,
-        x: F,
-    ) -> Result<(F, PC::Proof), Error<PC::Error>> {
-        let timer = start_timer!(|| format!("open: {}", p.label()));
-        let pf_p = PC::open(
+        queries: &[Query<'_, F, PC>],
+    ) -> Result<(Vec<F>, PC::BatchProof), Error<PC::Error>> {
+        let timer = start_timer!(|| format!("open: {} queries", queries.len()));
+        let _op = mpc_net::op("open");
+        let mut ys: Vec<F> = queries
+            .iter()
+            .map(|(p, _, _, _, x)| p.polynomial().evaluate(x))
+            .collect();
+        let p_timer = start_timer!(|| "publicize");
+        ys.publicize();
+        end_timer!(p_timer);
+        let opening_challenge = {
+            let mut transcript = self.transcript.borrow_mut();
+            transcript.append("evals", &ys);
+            transcript.challenge::<F>("opening")
+        };
+        let query_set: QuerySet<F> = queries
+            .iter()
+            .map(|(p, _, _, point_label, x)| (p.label().clone(), (point_label.to_string(), *x)))
+            .collect();
+        let pf = PC::batch_open(
             &self.pk.pc_ck,
-            once(p),
-            once(p_c),
-            &x,
-            F::one(), // acceptable b/c this is just one commitment.
-            once(p_r),
+            queries.iter().map(|q| q.0),
+            queries.iter().map(|q| q.2),
+            &query_set,
+            opening_challenge,
+            queries.iter().map(|q| q.1),
             Some(&mut *self.zk_rng.borrow_mut()),
         )?;
-        let mut y = p.polynomial().evaluate(&x);
-        let p_timer = start_timer!(|| "publicize");
-        y.publicize();
-        end_timer!(p_timer);
         end_timer!(timer);
-        Ok((y, pf_p))
+        Ok((ys, pf))
+    }
+
+    /// Commit to polynomials `ps`, all at once.
+    ///
+    /// Produces a (commitment, labeled_poly, randomness) triple for each.
+    fn commit_many(
+        &self,
+        ps: Vec<LabeledPolynomial<F, DensePolynomial<F>>>,
+    ) -> Result<Vec<Committed<F, PC>>, Error<PC::Error>> {
+        let labels: Vec<&String> = ps.iter().map(|p| p.label()).collect();
+        debug!("commit: {:?}", labels);
+        let timer = start_timer!(|| format!("commit: {:?}", labels));
+        let _op = mpc_net::op("commit");
+        let (mut cs, rs) = PC::commit(&self.pk.pc_ck, &ps, Some(&mut *self.zk_rng.borrow_mut()))?;
+        assert_eq!(cs.len(), ps.len());
+        assert_eq!(rs.len(), ps.len());
+        cs.publicize();
+        for c in &cs {
+            self.transcript.borrow_mut().append(c.label(), c);
+        }
+        end_timer!(timer);
+        Ok(cs
+            .into_iter()
+            .zip(ps)
+            .zip(rs)
+            .map(|((c, p), r)| (c, p, r))
+            .collect())
     }
 
     /// Commit to a polynomial `p`.
@@ -374,56 +606,262 @@ where
         p: DensePolynomial<F>,
         degree: Option<usize>,
         hiding_bound: Option<usize>,
-    ) -> Result<
-        (
-            LabeledCommitment<PC::Commitment>,
-            LabeledPolynomial<F, DensePolynomial<F>>,
-            PC::Randomness,
-        ),
-        Error<PC::Error>,
-    > {
-        debug!("commit: {}", label);
-        let timer = start_timer!(|| format!("commit: {}", label));
+    ) -> Result<Committed<F, PC>, Error<PC::Error>> {
         let label_p = LabeledPolynomial::new(format!("{}", label), p, degree, hiding_bound);
-        let (mut cs, mut rs) = PC::commit(
-            &self.pk.pc_ck,
-            once(&label_p),
-            Some(&mut *self.zk_rng.borrow_mut()),
-        )?;
-        assert_eq!(cs.len(), 1);
-        assert_eq!(rs.len(), 1);
-        let mut c = cs.pop().unwrap();
-        c.commitment.publicize();
-        self.fs_rng
-            .borrow_mut()
-            .absorb(&ark_ff::to_bytes![c].expect("failed serialization"));
-        end_timer!(timer);
-        Ok((c, label_p, rs.pop().unwrap()))
+        Ok(self.commit_many(vec![label_p])?.pop().unwrap())
+    }
+
+    /// Blind `p`, which only matters over `domain`, for `k` evaluations off the domain.
+    ///
+    /// Adds a random multiple of the domain's vanishing polynomial, of degree `k - 1`, so that any
+    /// `k` evaluations off the domain are uniformly random.
+    fn blind<D: EvaluationDomain<F>>(
+        &self,
+        p: DensePolynomial<F>,
+        domain: D,
+        k: usize,
+    ) -> DensePolynomial<F> {
+        let b: Vec<F> = {
+            let rng = &mut *self.zk_rng.borrow_mut();
+            (0..k).map(|_| F::rand(rng)).collect()
+        };
+        util::add_vanishing_multiple(p, &b, domain)
+    }
+
+    /// The public inputs, sorted by name, as (name, wire index, value) triples.
+    ///
+    /// Their values are publicized: they are part of the statement.
+    fn public_inputs<'c>(
+        &self,
+        circ: &'c relations::flat::CircuitLayout<F>,
+    ) -> Vec<(&'c String, usize, F)> {
+        let p = circ.p.as_ref().expect("wire values");
+        let mut public: Vec<(&String, usize)> =
+            circ.public_indices.iter().map(|(n, i)| (n, *i)).collect();
+        public.sort();
+        let mut values: Vec<F> = public
+            .iter()
+            .map(|(_, i)| p.evaluate(&circ.domains.wires.element(*i)))
+            .collect();
+        values.publicize();
+        public
+            .into_iter()
+            .zip(values)
+            .map(|((n, i), v)| (n, i, v))
+            .collect()
     }
 
     pub fn prove(
         &self,
         circ: &relations::flat::CircuitLayout<F>,
-    ) -> Proof<F, PC::Commitment, PC::Proof> {
+    ) -> Proof<F, PC::Commitment, PC::BatchProof> {
         assert!(circ.p.is_some());
-        let n_gates = circ.domains.gates.size();
-        let n_wires = n_gates * 3;
-        let (p_cmt, p, p_rand) = self
+        let public = self.public_inputs(circ);
+        absorb_statement(
+            &mut self.transcript.borrow_mut(),
+            circ,
+            &public,
+            &self.pk.w_cmt,
+            &self.pk.q_cmt,
+            &self.pk.lookup_cmt,
+        );
+        let (wires, gates) = (circ.domains.wires, circ.domains.gates);
+
+        // Round 1: the wire values
+        let p = self.blind(circ.p.clone().unwrap(), wires, relations::flat::P_QUERIES);
+        let p = self
             .commit(
                 "p".to_owned(),
-                circ.p.clone().unwrap(),
-                Some(n_wires - 1),
-                None,
+                p,
+                Some(circ.p_degree_bound()),
+                Some(relations::flat::P_QUERIES),
             )
             .unwrap();
-        let public = self.prove_public(&p, &p_cmt, &p_rand, circ);
-        let gates = self.prove_gates(&p, &p_cmt, &p_rand, circ);
-        let wiring = self.prove_wiring(&p, &p_cmt, &p_rand, circ.domains.wires);
+
+        // Round 2: the wiring accumulator with its product argument, and the lookups' f, h1, h2
+        let y = self.challenge("y");
+        let z = self.challenge("z");
+        let lookup = self.pk.lookup.as_ref().map(|_| {
+            let zeta = self.challenge("zeta");
+            self.lookup_evals(&p.1, circ, zeta)
+        });
+        let l1 = self.wiring_accumulator(&p.1, y, z, wires);
+        let mut polys = self.unit_product_polys(&l1, wires);
+        polys.insert(0, l1);
+        if let Some(e) = &lookup {
+            polys.extend(self.lookup_sorted_polys(e, gates));
+        }
+        let round2 = self.commit_many(polys).unwrap();
+
+        // Round 3: the lookup accumulator with its product argument
+        let lookup = lookup.map(|e| {
+            let beta = self.challenge("beta");
+            let gamma = self.challenge("gamma");
+            let l = self.lookup_accumulator(&e, beta, gamma, gates);
+            let mut polys = self.unit_product_polys(&l, gates);
+            polys.insert(0, l);
+            (e, beta, gamma, self.commit_many(polys).unwrap())
+        });
+
+        // Round 4: the quotients
+        let mut quotients = vec![
+            ("pub_q", self.public_quotient(&p.1, circ)),
+            ("gates_q", self.gates_quotient(&p.1, circ)),
+            (
+                "l2_q",
+                self.wiring_quotient(&p.1, &round2[0].1, y, z, wires),
+            ),
+        ];
+        if let Some((e, beta, gamma, round3)) = &lookup {
+            let alpha = self.challenge("alpha");
+            let q = self.lookup_quotient(
+                &p.1,
+                circ,
+                e,
+                &round2[3..],
+                &round3[0].1,
+                *beta,
+                *gamma,
+                alpha,
+            );
+            quotients.push(("lookup_q", q));
+        }
+        let round4 = self
+            .commit_many(
+                quotients
+                    .into_iter()
+                    .map(|(label, q)| LabeledPolynomial::new(label.into(), q, None, Some(1)))
+                    .collect(),
+            )
+            .unwrap();
+
+        // Open everything at x, or at its shifts, at once
+        let x = self.challenge("x");
+        let w = wires.group_gen;
+        let g = gates.group_gen;
+        let empty = PC::Randomness::empty();
+        let mut queries = vec![
+            Self::query(&p, "x", x),
+            Self::query(&p, "wx", w * x),
+            Self::query(&p, "w2x", w * w * x),
+            Self::query(&round4[0], "x", x),
+            Self::query(&round4[1], "x", x),
+        ];
+        queries.extend(
+            self.pk
+                .q
+                .as_ref()
+                .zip(self.pk.q_cmt.as_ref())
+                .into_vec()
+                .into_iter()
+                .map(|(q, q_cmt)| (q, &empty, q_cmt, "x", x)),
+        );
+        queries.push((&self.pk.w, &empty, &self.pk.w_cmt, "x", x));
+        queries.push(Self::query(&round2[0], "x", x));
+        queries.push(Self::query(&round4[2], "x", x));
+        queries.extend(Self::unit_product_queries(
+            &round2[0],
+            &round2[1],
+            &round2[2],
+            wires,
+            ("wk", "wx"),
+            x,
+        ));
+        if let Some((_, _, _, round3)) = &lookup {
+            let tables = self.pk.lookup.as_ref().unwrap();
+            let tables_cmt = self
+                .pk
+                .lookup_cmt
+                .as_ref()
+                .expect("lookup table commitments");
+            queries.extend(vec![
+                Self::query(&round2[3], "x", x),
+                Self::query(&round2[4], "x", x),
+                Self::query(&round2[4], "gx", g * x),
+                Self::query(&round2[5], "x", x),
+                Self::query(&round3[0], "x", x),
+                Self::query(&round4[3], "x", x),
+                (&tables.q_k, &empty, &tables_cmt.q_k, "x", x),
+            ]);
+            let t = tables.t.as_ref().zip(tables_cmt.t.as_ref()).into_vec();
+            queries.extend(t.iter().map(|(t, t_cmt)| (*t, &empty, *t_cmt, "x", x)));
+            queries.extend(t.iter().map(|(t, t_cmt)| (*t, &empty, *t_cmt, "gx", g * x)));
+            queries.extend(Self::unit_product_queries(
+                &round3[0],
+                &round3[1],
+                &round3[2],
+                gates,
+                ("gk", "gx"),
+                x,
+            ));
+        }
+        let (ys, pc_proof) = self.open(&queries).unwrap();
+
+        // The evaluations come back in query order
+        let mut ys = ys.into_iter();
+        let mut next = || ys.next().unwrap();
+        let cmt = |c: &Committed<F, PC>| c.0.commitment().clone();
+        let (p_x, p_wx, p_w2x) = (next(), next(), next());
+        let public = PublicProof {
+            q_cmt: cmt(&round4[0]),
+            q_x: next(),
+        };
+        let gates = GateProof {
+            q_cmt: cmt(&round4[1]),
+            q_x: next(),
+            sel_x: Selectors {
+                q_l: next(),
+                q_r: next(),
+                q_o: next(),
+                q_m: next(),
+                q_c: next(),
+            },
+        };
+        let wiring = WiringProof {
+            l1_cmt: cmt(&round2[0]),
+            w_x: next(),
+            l1_x: next(),
+            l2_q_x: next(),
+            l1_prod_pf: Self::unit_product_proof(&round2[1], &round2[2], &mut next),
+            l2_q_cmt: cmt(&round4[2]),
+        };
+        let lookup = lookup.map(|(_, _, _, round3)| LookupProof {
+            f_cmt: cmt(&round2[3]),
+            h1_cmt: cmt(&round2[4]),
+            h2_cmt: cmt(&round2[5]),
+            l_cmt: cmt(&round3[0]),
+            q_cmt: cmt(&round4[3]),
+            f_x: next(),
+            h1_x: next(),
+            h1_gx: next(),
+            h2_x: next(),
+            l_x: next(),
+            q_x: next(),
+            q_k_x: next(),
+            t_x: TableColumns {
+                t_l: next(),
+                t_r: next(),
+                t_o: next(),
+                t_id: next(),
+            },
+            t_gx: TableColumns {
+                t_l: next(),
+                t_r: next(),
+                t_o: next(),
+                t_id: next(),
+            },
+            l_prod_pf: Self::unit_product_proof(&round3[1], &round3[2], &mut next),
+        });
         Proof {
-            p_cmt: p_cmt.commitment,
+            p_cmt: cmt(&p),
+            p_x,
+            p_wx,
+            p_w2x,
             wiring,
             gates,
             public,
+            lookup,
+            pc_proof,
         }
     }
 }
@@ -432,7 +870,7 @@ pub struct Verifier<'r, F: FftField, PC: PolynomialCommitment<F, DensePolynomial
     _field: PhantomData<F>,
     _pc: PhantomData<PC>,
     vk: &'r VerifierKey<PC::Commitment, PC::VerifierKey>,
-    fs_rng: RefCell<FiatShamirRng<Blake2s>>,
+    transcript: RefCell<Transcript>,
 }
 #[allow(dead_code)]
 impl<'r, F: FftField, PC: PolynomialCommitment<F, DensePolynomial<F>>> Verifier<'r, F, PC>
@@ -445,32 +883,53 @@ where
             _field: PhantomData::default(),
             _pc: PhantomData::default(),
             vk,
-            fs_rng: RefCell::new(FiatShamirRng::from_seed(&0u64)),
+            transcript: RefCell::new(Transcript::new(PROTOCOL)),
         }
     }
-    fn verify_unit_product<D: EvaluationDomain<F>>(
+    fn challenge(&self, label: &str) -> F {
+        self.transcript.borrow_mut().challenge::<F>(label)
+    }
+
+    /// The queries of the product argument for f: t at the domain's last element, at x and at
+    /// wx; f at wx; and q at x. `labels` name the last element and wx.
+    fn unit_product_queries<'a, D: EvaluationDomain<F>>(
+        f_cmt: &'a LabeledCommitment<PC::Commitment>,
+        t_cmt: &'a LabeledCommitment<PC::Commitment>,
+        q_cmt: &'a LabeledCommitment<PC::Commitment>,
+        pf: &ProductProof<F, PC::Commitment>,
+        domain: D,
+        labels: (&'static str, &'static str),
+        x: F,
+    ) -> Vec<(&'a LabeledCommitment<PC::Commitment>, &'static str, F, F)> {
+        let (last, shifted) = labels;
+        let wx = domain.element(1) * x;
+        vec![
+            (t_cmt, last, domain.element(domain.size() - 1), pf.t_wk),
+            (t_cmt, "x", x, pf.t_x),
+            (t_cmt, shifted, wx, pf.t_wx),
+            (f_cmt, shifted, wx, pf.f_wx),
+            (q_cmt, "x", x, pf.q_x),
+        ]
+    }
+
+    /// Check that f multiplies to 1 over the domain, given checked evaluations at x.
+    ///
+    /// Returns whether the product argument holds; the caller decides what a failure means.
+    fn check_unit_product<D: EvaluationDomain<F>>(
         &self,
-        f_cmt: &LabeledCommitment<PC::Commitment>,
-        pf: ProductProof<PC::Commitment, (F, PC::Proof)>,
+        pf: &ProductProof<F, PC::Commitment>,
         domain: D,
-    ) {
-        let k = domain.size();
-        let w = domain.element(1);
-        let t_cmt = self.recv_commit("t", pf.t_cmt, None);
-        let q_cmt = self.recv_commit("q", pf.q_cmt, None);
-        let r = self.fs_rng.borrow_mut().gen::<F>();
-        // Check commitments
-        let f_wr = self.check(f_cmt, w * r, &pf.f_wr_open);
-        let q_r = self.check(&q_cmt, r, &pf.q_r_open);
-        let t_r = self.check(&t_cmt, r, &pf.t_r_open);
-        let t_wr = self.check(&t_cmt, w * r, &pf.t_wr_open);
-        let t_wk = self.check(&t_cmt, domain.element(k - 1), &pf.t_wk_open);
+        x: F,
+    ) -> bool {
         // Check partial product
-        let l = t_wr - t_r * f_wr;
-        let r = domain.evaluate_vanishing_polynomial(r) * q_r;
-        assert_eq!(l, r, "Partial product failure: \n{}\nnot equal to\n{}", l, r);
+        let l = pf.t_wx - pf.t_x * pf.f_wx;
+        let r = domain.evaluate_vanishing_polynomial(x) * pf.q_x;
+        if l != r {
+            debug!("Partial product failure: \n{}\nnot equal to\n{}", l, r);
+            return false;
+        }
         // Check total product is 1
-        assert_eq!(t_wk, F::one());
+        pf.t_wk == F::one()
     }
     /// Receive a commitment
     ///
@@ -482,103 +941,347 @@ where
         degree: Option<usize>,
     ) -> LabeledCommitment<PC::Commitment> {
         let label_c = LabeledCommitment::new(format!("{}", label), c, degree);
-        self.fs_rng
+        self.transcript
             .borrow_mut()
-            .absorb(&ark_ff::to_bytes![label_c].expect("failed serialization"));
+            .append(label_c.label(), &label_c);
         label_c
     }
 
-    #[track_caller]
-    fn check(&self, cmt: &LabeledCommitment<PC::Commitment>, x: F, open: &(F, PC::Proof)) -> F {
-        assert!(
-            PC::check(
-                &self.vk.pc_vk,
-                once(cmt),
-                &x,
-                once(open.0),
-                &open.1,
-                F::one(), // Okay b/c a single commit
-                None,
-            )
-            .unwrap(),
-            "Verification failed: {} at {}",
-            cmt.label(),
-            x
+    /// Check claimed evaluations of committed polynomials against one batch proof.
+    ///
+    /// A query is (commitment, point label, point, value), in the order the prover opened them.
+    fn check(
+        &self,
+        queries: &[(&LabeledCommitment<PC::Commitment>, &'static str, F, F)],
+        pf: &PC::BatchProof,
+    ) -> Result<(), PlonkError> {
+        let describe = || {
+            queries
+                .iter()
+                .map(|(c, point_label, _, _)| format!("{} at {}", c.label(), point_label))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let ys: Vec<F> = queries.iter().map(|q| q.3).collect();
+        let opening_challenge = {
+            let mut transcript = self.transcript.borrow_mut();
+            transcript.append("evals", &ys);
+            transcript.challenge::<F>("opening")
+        };
+        let query_set: QuerySet<F> = queries
+            .iter()
+            .map(|(c, point_label, x, _)| (c.label().clone(), (point_label.to_string(), *x)))
+            .collect();
+        let evaluations: ark_poly_commit::Evaluations<F, F> = queries
+            .iter()
+            .map(|(c, _, x, y)| ((c.label().clone(), *x), *y))
+            .collect();
+        let proofs: Vec<PC::Proof> = pf.clone().into();
+        let n_points = queries
+            .iter()
+            .map(|q| q.1)
+            .collect::<std::collections::BTreeSet<_>>()
+            .len();
+        if proofs.len() != n_points {
+            return Err(PlonkError::MalformedProof(format!(
+                "{} opening proofs for {} points",
+                proofs.len(),
+                n_points
+            )));
+        }
+        let mut check_rng = FiatShamirRng::<Blake2s>::from_seed(
+            &ark_ff::to_bytes![opening_challenge, proofs].expect("failed serialization"),
         );
-        open.0
+        let valid = PC::batch_check(
+            &self.vk.pc_vk,
+            queries.iter().map(|q| q.0),
+            &query_set,
+            &evaluations,
+            pf,
+            opening_challenge,
+            &mut check_rng,
+        )
+        .map_err(|e| PlonkError::PcCheckFailed(format!("{}: {}", describe(), e)))?;
+        if valid {
+            Ok(())
+        } else {
+            Err(PlonkError::PcCheckFailed(describe()))
+        }
+    }
+
+    /// Check that the key's commitments carry the labels that openings refer to, and that the
+    /// key has lookup tables iff the circuit does.
+    fn check_key(&self, circ: &relations::flat::CircuitLayout<F>) -> Result<(), PlonkError> {
+        let mut cmts = vec![("w", &self.vk.w_cmt)];
+        cmts.extend(self.vk.q_cmt.as_ref().map_labeled(|l, c| (l, c)).into_vec());
+        if let Some(tables_cmt) = &self.vk.lookup_cmt {
+            cmts.extend(tables_cmt.as_ref().map_labeled(|l, c| (l, c)).into_vec());
+        }
+        if let Some((l, c)) = cmts.into_iter().find(|(l, c)| c.label() != l) {
+            return Err(PlonkError::MalformedKey(format!(
+                "commitment to {} is labeled {}",
+                l,
+                c.label()
+            )));
+        }
+        if self.vk.lookup_cmt.is_some() != circ.lookup.is_some() {
+            return Err(PlonkError::MalformedKey(
+                "lookup tables do not match the circuit".into(),
+            ));
+        }
+        Ok(())
     }
 
+    /// Verify a proof that the circuit is satisfied with the given public inputs.
+    ///
+    /// Returns `Ok(true)` if the proof is valid, and otherwise an error saying which check it
+    /// failed.
     pub fn verify(
         &self,
         circ: &relations::flat::CircuitLayout<F>,
-        pf: Proof<F, PC::Commitment, PC::Proof>,
+        pf: Proof<F, PC::Commitment, PC::BatchProof>,
         public: &HashMap<String, F>,
-    ) {
-        assert!(circ.p.is_none());
-        let n_gates = circ.domains.gates.size();
-        let n_wires = n_gates * 3;
-        let p = self.recv_commit("p", pf.p_cmt, Some(n_wires - 1));
-        self.verify_public(&circ, &p, pf.public, public);
-        self.verify_gates(&p, &circ, pf.gates);
-        self.verify_wiring(&p, circ.domains.wires, pf.wiring);
+    ) -> Result<bool, PlonkError> {
+        self.check_key(circ)?;
+        if public.len() != circ.public_indices.len() {
+            return Err(PlonkError::PublicInputMismatch);
+        }
+        let mut public_inputs = Vec::new();
+        for (name, i) in &circ.public_indices {
+            let v = public.get(name).ok_or(PlonkError::PublicInputMismatch)?;
+            public_inputs.push((name, *i, *v));
+        }
+        public_inputs.sort_by(|a, b| a.0.cmp(b.0));
+        let lookup = match (&self.vk.lookup_cmt, &pf.lookup) {
+            (Some(tables_cmt), Some(lookup)) => Some((tables_cmt, lookup)),
+            (None, None) => None,
+            (Some(_), None) => {
+                return Err(PlonkError::MalformedProof("missing lookup proof".into()))
+            }
+            (None, Some(_)) => {
+                return Err(PlonkError::MalformedProof("unexpected lookup proof".into()))
+            }
+        };
+        absorb_statement(
+            &mut self.transcript.borrow_mut(),
+            circ,
+            &public_inputs,
+            &self.vk.w_cmt,
+            &self.vk.q_cmt,
+            &self.vk.lookup_cmt,
+        );
+        let (wires, gates) = (circ.domains.wires, circ.domains.gates);
+        let recv = |label: &str, c: &PC::Commitment| self.recv_commit(label, c.clone(), None);
+
+        // Round 1: the wire values
+        let p = self.recv_commit("p", pf.p_cmt.clone(), Some(circ.p_degree_bound()));
+
+        // Round 2: the wiring accumulator with its product argument, and the lookups' f, h1, h2
+        let y = self.challenge("y");
+        let z = self.challenge("z");
+        let zeta = lookup.map(|_| self.challenge("zeta"));
+        let mut round2 = vec![
+            recv("l1", &pf.wiring.l1_cmt),
+            recv("l1_t", &pf.wiring.l1_prod_pf.t_cmt),
+            recv("l1_q", &pf.wiring.l1_prod_pf.q_cmt),
+        ];
+        if let Some((_, l)) = lookup {
+            round2.push(recv("f", &l.f_cmt));
+            round2.push(recv("h1", &l.h1_cmt));
+            round2.push(recv("h2", &l.h2_cmt));
+        }
+
+        // Round 3: the lookup accumulator with its product argument
+        let lookup = lookup.map(|(tables_cmt, l)| {
+            let beta = self.challenge("beta");
+            let gamma = self.challenge("gamma");
+            let round3 = vec![
+                recv("l", &l.l_cmt),
+                recv("l_t", &l.l_prod_pf.t_cmt),
+                recv("l_q", &l.l_prod_pf.q_cmt),
+            ];
+            (tables_cmt, l, beta, gamma, round3)
+        });
+
+        // Round 4: the quotients
+        let alpha = lookup.as_ref().map(|_| self.challenge("alpha"));
+        let mut round4 = vec![
+            recv("pub_q", &pf.public.q_cmt),
+            recv("gates_q", &pf.gates.q_cmt),
+            recv("l2_q", &pf.wiring.l2_q_cmt),
+        ];
+        if let Some((_, l, _, _, _)) = &lookup {
+            round4.push(recv("lookup_q", &l.q_cmt));
+        }
+
+        // Check every evaluation, at x or at its shifts, at once
+        let x = self.challenge("x");
+        let w = wires.group_gen;
+        let g = gates.group_gen;
+        let mut queries = vec![
+            (&p, "x", x, pf.p_x),
+            (&p, "wx", w * x, pf.p_wx),
+            (&p, "w2x", w * w * x, pf.p_w2x),
+            (&round4[0], "x", x, pf.public.q_x),
+            (&round4[1], "x", x, pf.gates.q_x),
+        ];
+        queries.extend(
+            self.vk
+                .q_cmt
+                .as_ref()
+                .zip(pf.gates.sel_x.clone())
+                .into_vec()
+                .into_iter()
+                .map(|(cmt, y)| (cmt, "x", x, y)),
+        );
+        queries.push((&self.vk.w_cmt, "x", x, pf.wiring.w_x));
+        queries.push((&round2[0], "x", x, pf.wiring.l1_x));
+        queries.push((&round4[2], "x", x, pf.wiring.l2_q_x));
+        queries.extend(Self::unit_product_queries(
+            &round2[0],
+            &round2[1],
+            &round2[2],
+            &pf.wiring.l1_prod_pf,
+            wires,
+            ("wk", "wx"),
+            x,
+        ));
+        if let Some((tables_cmt, l, _, _, round3)) = &lookup {
+            queries.extend(vec![
+                (&round2[3], "x", x, l.f_x),
+                (&round2[4], "x", x, l.h1_x),
+                (&round2[4], "gx", g * x, l.h1_gx),
+                (&round2[5], "x", x, l.h2_x),
+                (&round3[0], "x", x, l.l_x),
+                (&round4[3], "x", x, l.q_x),
+                (&tables_cmt.q_k, "x", x, l.q_k_x),
+            ]);
+            let t_cmt = tables_cmt.t.as_ref();
+            queries.extend(
+                t_cmt
+                    .clone()
+                    .zip(l.t_x.clone())
+                    .into_vec()
+                    .into_iter()
+                    .map(|(cmt, y)| (cmt, "x", x, y)),
+            );
+            queries.extend(
+                t_cmt
+                    .zip(l.t_gx.clone())
+                    .into_vec()
+                    .into_iter()
+                    .map(|(cmt, y)| (cmt, "gx", g * x, y)),
+            );
+            queries.extend(Self::unit_product_queries(
+                &round3[0],
+                &round3[1],
+                &round3[2],
+                &l.l_prod_pf,
+                gates,
+                ("gk", "gx"),
+                x,
+            ));
+        }
+        self.check(&queries, &pf.pc_proof)?;
+
+        self.check_public(circ, &pf, public, x)?;
+        self.check_gates(circ, &pf, x)?;
+        self.check_wiring(wires, &pf, y, z, x)?;
+        if let (Some((_, l, beta, gamma, _)), Some(zeta), Some(alpha)) = (&lookup, zeta, alpha) {
+            self.check_lookups(&pf, l, circ, [zeta, *beta, *gamma, alpha], x)?;
+        }
+        Ok(true)
     }
 
-    fn verify_public(
+    fn check_public(
         &self,
         circ: &relations::flat::CircuitLayout<F>,
-        p_cmt: &LabeledCommitment<PC::Commitment>,
-        pf: PublicProof<PC::Commitment, (F, PC::Proof)>,
+        pf: &Proof<F, PC::Commitment, PC::BatchProof>,
         public: &HashMap<String, F>,
-    ) {
-        let q_cmt = self.recv_commit("pub_q", pf.q_cmt, None);
-        let x = self.fs_rng.borrow_mut().gen::<F>();
-        let p_val = self.check(p_cmt, x, &pf.p_open);
-        let q_val = self.check(&q_cmt, x, &pf.q_open);
+        x: F,
+    ) -> Result<(), PlonkError> {
         let z = circ.vanishing_poly_on_inputs();
-        let v = circ.inputs_poly(public);
-        assert_eq!(p_val - v.evaluate(&x), q_val * z.evaluate(&x));
+        let v_x = if public.is_empty() {
+            F::zero()
+        } else {
+            circ.inputs_poly(public).evaluate(&x)
+        };
+        if pf.p_x - v_x != pf.public.q_x * z.evaluate(&x) {
+            return Err(PlonkError::PublicInputMismatch);
+        }
+        Ok(())
     }
 
-    fn verify_gates(
+    fn check_gates(
         &self,
-        p_cmt: &LabeledCommitment<PC::Commitment>,
         circ: &relations::flat::CircuitLayout<F>,
-        pf: GateProof<PC::Commitment, (F, PC::Proof)>,
-    ) {
-        let q_cmt = self.recv_commit("gates_q", pf.q_cmt, None);
-        let x = self.fs_rng.borrow_mut().gen::<F>();
-        let w = circ.domains.wires.group_gen;
-        let s = self.check(&self.vk.s_cmt, x, &pf.s_open);
-        let q = self.check(&q_cmt, x, &pf.q_open);
-        let p = self.check(p_cmt, x, &pf.p_open);
-        let pw = self.check(p_cmt, x * w, &pf.p_w_open);
-        let pww = self.check(p_cmt, x * w * w, &pf.p_w2_open);
-        assert_eq!(
-            s * (p + pw) + (F::one() - s) * p * pw - pww,
-            q * circ.domains.gates.evaluate_vanishing_polynomial(x)
-        );
+        pf: &Proof<F, PC::Commitment, PC::BatchProof>,
+        x: F,
+    ) -> Result<(), PlonkError> {
+        let (sel, p, pw, pww) = (&pf.gates.sel_x, pf.p_x, pf.p_wx, pf.p_w2x);
+        if sel.q_l * p + sel.q_r * pw + sel.q_m * p * pw + sel.q_c - sel.q_o * pww
+            != pf.gates.q_x * circ.domains.gates.evaluate_vanishing_polynomial(x)
+        {
+            return Err(PlonkError::GateCheckFailed);
+        }
+        Ok(())
     }
-    fn verify_wiring<D: EvaluationDomain<F>>(
+
+    /// Check the lookup identity, with the challenges zeta, beta, gamma and alpha.
+    fn check_lookups(
+        &self,
+        pf: &Proof<F, PC::Commitment, PC::BatchProof>,
+        l: &LookupProof<F, PC::Commitment>,
+        circ: &relations::flat::CircuitLayout<F>,
+        challenges: [F; 4],
+        x: F,
+    ) -> Result<(), PlonkError> {
+        let [zeta, beta, gamma, alpha] = challenges;
+        let dom = circ.domains.gates;
+        if !self.check_unit_product(&l.l_prod_pf, dom, x) {
+            return Err(PlonkError::LookupCheckFailed);
+        }
+        let t = l.t_x.compress(zeta);
+        let tg = l.t_gx.compress(zeta);
+        let (q_k, f, h1, h1g, h2, acc, q) = (l.q_k_x, l.f_x, l.h1_x, l.h1_gx, l.h2_x, l.l_x, l.q_x);
+        let (p, pw, pww) = (pf.p_x, pf.p_wx, pf.p_w2x);
+        let b1 = F::one() + beta;
+        let gb1 = gamma * b1;
+        let num = b1 * (gamma + f) * (gb1 + t + beta * tg);
+        let den = (gb1 + h1 + beta * h2) * (gb1 + h2 + beta * h1g);
+        let reads = TableColumns {
+            t_l: p,
+            t_r: pw,
+            t_o: pww,
+            t_id: q_k,
+        }
+        .compress(zeta)
+            - f;
+        if acc * den - num + alpha * q_k * reads != q * dom.evaluate_vanishing_polynomial(x) {
+            return Err(PlonkError::LookupCheckFailed);
+        }
+        Ok(())
+    }
+
+    fn check_wiring<D: EvaluationDomain<F>>(
         &self,
-        p_cmt: &LabeledCommitment<PC::Commitment>,
         dom: D,
-        pf: WiringProof<PC::Commitment, (F, PC::Proof)>,
-    ) {
-        let y = self.fs_rng.borrow_mut().gen::<F>();
-        let z = self.fs_rng.borrow_mut().gen::<F>();
-        let l1 = self.recv_commit("l1", pf.l1_cmt, None);
-        self.verify_unit_product(&l1, pf.l1_prod_pf, dom);
-        let l2_q = self.recv_commit("l2_q", pf.l2_q_cmt, None);
-        let x = self.fs_rng.borrow_mut().gen::<F>();
-
-        let l2_q_x = self.check(&l2_q, x, &pf.l2_q_x_open);
-        let w_x = self.check(&self.vk.w_cmt, x, &pf.w_x_open);
-        let l1_x = self.check(&l1, x, &pf.l1_x_open);
-        let p_x = self.check(&p_cmt, x, &pf.p_x_open);
-        assert_eq!(
-            (p_x + y * x + z) * l1_x - (p_x + y * w_x + z),
-            l2_q_x * dom.evaluate_vanishing_polynomial(x)
-        );
+        pf: &Proof<F, PC::Commitment, PC::BatchProof>,
+        y: F,
+        z: F,
+        x: F,
+    ) -> Result<(), PlonkError> {
+        let wiring = &pf.wiring;
+        if !self.check_unit_product(&wiring.l1_prod_pf, dom, x) {
+            return Err(PlonkError::WiringCheckFailed);
+        }
+        let (p_x, w_x, l1_x, l2_q_x) = (pf.p_x, wiring.w_x, wiring.l1_x, wiring.l2_q_x);
+        if (p_x + y * x + z) * l1_x - (p_x + y * w_x + z)
+            != l2_q_x * dom.evaluate_vanishing_polynomial(x)
+        {
+            return Err(PlonkError::WiringCheckFailed);
+        }
+        Ok(())
     }
 }
 
@@ -594,6 +1297,25 @@ pub enum Error<PCE: 'static + std::error::Error> {
     DomainCheckFailed,
 }
 
+/// Why the verifier rejected a proof
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PlonkError {
+    #[error("Polynomial commitment check failed: {0}")]
+    PcCheckFailed(String),
+    #[error("Gate identity failed")]
+    GateCheckFailed,
+    #[error("Wiring (permutation) check failed")]
+    WiringCheckFailed,
+    #[error("Lookup check failed")]
+    LookupCheckFailed,
+    #[error("Public inputs do not match the proof")]
+    PublicInputMismatch,
+    #[error("Malformed key: {0}")]
+    MalformedKey(String),
+    #[error("Malformed proof: {0}")]
+    MalformedProof(String),
+}
+
 pub type Result<T, PCE> = std::result::Result<T, PCE>;
 
 pub struct Plonk<F: Field, PC: PolynomialCommitment<F, DensePolynomial<F>>>(PhantomData<(F, PC)>);
@@ -604,7 +1326,8 @@ where
     PC::Error: 'static,
 {
     pub fn universal_setup<R: RngCore>(n_gates: usize, setup_rng: &mut R) -> PC::UniversalParams {
-        PC::setup(n_gates * 6 - 1, Some(1), setup_rng).unwrap()
+        let max_degree = relations::flat::CircuitLayout::<F>::degree_bound_for(n_gates);
+        PC::setup(max_degree, Some(1), setup_rng).unwrap()
     }
     pub fn circuit_setup(
         srs: &PC::UniversalParams,
@@ -619,16 +1342,16 @@ where
         pk: &ProverKey<F, PC::Commitment, PC::CommitterKey>,
         circ: &relations::flat::CircuitLayout<F>,
         zk_rng: &mut dyn RngCore,
-    ) -> Proof<F, PC::Commitment, PC::Proof> {
+    ) -> Proof<F, PC::Commitment, PC::BatchProof> {
         let prv = Prover::<F, PC>::new(pk, zk_rng);
         prv.prove(circ)
     }
     pub fn verify(
         vk: &VerifierKey<PC::Commitment, PC::VerifierKey>,
         circ: &relations::flat::CircuitLayout<F>,
-        pf: Proof<F, PC::Commitment, PC::Proof>,
+        pf: Proof<F, PC::Commitment, PC::BatchProof>,
         public: &HashMap<String, F>,
-    ) {
+    ) -> Result<bool, PlonkError> {
         let ver = Verifier::<F, PC>::new(vk);
         ver.verify(circ, pf, public)
     }
@@ -643,30 +1366,211 @@ mod tests {
     type P = DensePolynomial<F>;
     type PC = ark_poly_commit::marlin::marlin_pc::MarlinKZG10<E, P>;
     type Pl = Plonk<F, PC>;
+    type Pf = Proof<
+        F,
+        <PC as PolynomialCommitment<F, P>>::Commitment,
+        <PC as PolynomialCommitment<F, P>>::BatchProof,
+    >;
 
-    #[test]
-    fn plonk_test() {
-        use relations::{flat::*, structured::*};
-        use std::collections::HashMap;
+    fn prove_and_verify(c: &relations::structured::PlonkCircuit<F>, public: &HashMap<String, F>) {
+        assert!(prove_then_verify(c, public, public, |_| {}).unwrap());
+    }
+
+    /// Prove with `public`, let `tamper` modify the proof, then verify with `v_public`.
+    fn prove_then_verify(
+        c: &relations::structured::PlonkCircuit<F>,
+        public: &HashMap<String, F>,
+        v_public: &HashMap<String, F>,
+        tamper: impl FnOnce(&mut Pf),
+    ) -> Result<bool, PlonkError> {
+        use relations::flat::*;
+        let circ = CircuitLayout::from_circuit(c);
+        circ.check(public);
+
+        let setup_rng = &mut ark_std::test_rng();
+        let zk_rng = &mut ark_std::test_rng();
+
+        let v_circ = {
+            let mut t = circ.clone();
+            t.p = None;
+            t
+        };
+
+        let srs = Pl::universal_setup(c.n_gates(), setup_rng);
+        let (pk, vk) = Pl::circuit_setup(&srs, &v_circ);
+        let mut pf = Pl::prove(&pk, &circ, zk_rng);
+        tamper(&mut pf);
+        Pl::verify(&vk, &v_circ, pf, v_public)
+    }
+
+    fn squaring_circuit() -> (relations::structured::PlonkCircuit<F>, HashMap<String, F>) {
+        use relations::structured::*;
         let steps = 4;
         let start = F::from(2u64);
         let c = PlonkCircuit::<F>::new_squaring_circuit(steps, Some(start));
         let res = (0..steps).fold(start, |a, _| a * a);
         let public: HashMap<String, F> = vec![("out".to_owned(), res)].into_iter().collect();
-        let circ = CircuitLayout::from_circuit(&c);
+        (c, public)
+    }
 
-        let setup_rng = &mut ark_std::test_rng();
-        let zk_rng = &mut ark_std::test_rng();
+    #[test]
+    fn plonk_test() {
+        let (c, public) = squaring_circuit();
+        prove_and_verify(&c, &public);
+    }
 
+    #[test]
+    fn plonk_wrong_public_test() {
+        let (c, public) = squaring_circuit();
+        let wrong: HashMap<String, F> = vec![("out".to_owned(), F::from(7u64))]
+            .into_iter()
+            .collect();
+        // The public inputs are in the transcript, so the openings are checked at other points
+        let res = prove_then_verify(&c, &public, &wrong, |_| {});
+        assert!(matches!(res, Err(PlonkError::PcCheckFailed(_))));
+        let missing = HashMap::new();
+        let res = prove_then_verify(&c, &public, &missing, |_| {});
+        assert_eq!(res, Err(PlonkError::PublicInputMismatch));
+    }
+
+    #[test]
+    fn plonk_tampered_proof_test() {
+        let (c, public) = squaring_circuit();
+        let res = prove_then_verify(&c, &public, &public, |pf| pf.gates.q_x += F::from(1u64));
+        assert!(matches!(res, Err(PlonkError::PcCheckFailed(_))));
+        let res = prove_then_verify(&c, &public, &public, |pf| {
+            pf.pc_proof.pop();
+        });
+        assert!(matches!(res, Err(PlonkError::MalformedProof(_))));
+    }
+
+    #[test]
+    fn plonk_blinding_test() {
+        use ark_std::rand::{rngs::StdRng, SeedableRng};
+        let (c, public) = squaring_circuit();
+        let circ = relations::flat::CircuitLayout::from_circuit(&c);
         let v_circ = {
             let mut t = circ.clone();
             t.p = None;
             t
         };
+        let srs = Pl::universal_setup(c.n_gates(), &mut ark_std::test_rng());
+        let (pk, vk) = Pl::circuit_setup(&srs, &v_circ);
+        let pf1 = Pl::prove(&pk, &circ, &mut StdRng::seed_from_u64(1));
+        let pf2 = Pl::prove(&pk, &circ, &mut StdRng::seed_from_u64(2));
+        // Same witness, different blinding: nothing p reveals is the same
+        assert!(pf1.p_cmt != pf2.p_cmt);
+        assert_ne!(pf1.p_x, pf2.p_x);
+        assert!(Pl::verify(&vk, &v_circ, pf1, &public).unwrap());
+        assert!(Pl::verify(&vk, &v_circ, pf2, &public).unwrap());
+    }
 
-        let srs = Pl::universal_setup(steps, setup_rng);
+    #[test]
+    fn plonk_gates_test() {
+        use ark_ff::{One, Zero};
+        use relations::structured::*;
+        let (x, y) = (F::from(3u64), F::from(4u64));
+        // 2x + 5y + 7xy + 11, then scaled, shifted, and multiplied by a constant
+        let z = F::from(2u64) * x + F::from(5u64) * y + F::from(7u64) * x * y + F::from(11u64);
+        let out = (F::from(3u64) * z + F::from(1u64)) * F::from(13u64) - x;
+        let mut c = PlonkCircuit::<F>::new(true);
+        let xv = c.new_var(|| x);
+        let yv = c.new_var(|| y);
+        let zv = c.new_gate(
+            xv,
+            yv,
+            F::from(2u64),
+            F::from(5u64),
+            F::from(7u64),
+            F::from(11u64),
+        );
+        let zv = c.new_scale(zv, F::from(3u64));
+        let zv = c.new_add_const(zv, F::one());
+        let k = c.new_const(F::from(13u64));
+        let zv = c.new_prod(zv, k);
+        let ov = c.new_lc(zv, F::one(), xv, -F::one());
+        // A custom gate on existing wires: x * y - 12 = 0
+        c.enforce_gate(Gate {
+            l: xv,
+            r: yv,
+            o: xv,
+            q: Selectors {
+                q_l: F::zero(),
+                q_r: F::zero(),
+                q_o: F::zero(),
+                q_m: F::one(),
+                q_c: -F::from(12u64),
+            },
+        });
+        c.pad_to_power_of_2();
+        c.publicize_var(ov, "out".to_owned());
+        let public: HashMap<String, F> = vec![("out".to_owned(), out)].into_iter().collect();
+        prove_and_verify(&c, &public);
+    }
+
+    fn lookup_circuit(x: u64, y: u64, x_xor_y: u64) -> relations::structured::PlonkCircuit<F> {
+        use relations::structured::*;
+        let mut c = PlonkCircuit::<F>::new(true);
+        let range = c.new_range_table(4);
+        let xor = c.new_xor_table(3);
+        let xv = c.new_var(|| F::from(x));
+        let yv = c.new_var(|| F::from(y));
+        c.lookup(range, xv, xv, xv);
+        c.lookup(range, yv, yv, yv);
+        let zv = c.new_lookup(xor, xv, yv, || F::from(x_xor_y));
+        let ov = c.new_sum(zv, xv);
+        c.pad_to_power_of_2();
+        c.publicize_var(ov, "out".to_owned());
+        c
+    }
+
+    #[test]
+    fn plonk_lookup_test() {
+        let c = lookup_circuit(5, 3, 6);
+        let public: HashMap<String, F> = vec![("out".to_owned(), F::from(11u64))]
+            .into_iter()
+            .collect();
+        prove_and_verify(&c, &public);
+    }
+
+    #[test]
+    fn plonk_serialization_test() {
+        use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+        use relations::flat::*;
+        let c = lookup_circuit(5, 3, 6);
+        let public: HashMap<String, F> = vec![("out".to_owned(), F::from(11u64))]
+            .into_iter()
+            .collect();
+        let circ = CircuitLayout::from_circuit(&c);
+        let v_circ = {
+            let mut t = circ.clone();
+            t.p = None;
+            t
+        };
+        let srs = Pl::universal_setup(c.n_gates(), &mut ark_std::test_rng());
         let (pk, vk) = Pl::circuit_setup(&srs, &v_circ);
-        let pf = Pl::prove(&pk, &circ, zk_rng);
-        Pl::verify(&vk, &v_circ, pf, &public);
+
+        let mut pk_bytes = Vec::new();
+        pk.serialize(&mut pk_bytes).unwrap();
+        assert_eq!(pk_bytes.len(), pk.serialized_size());
+        let pk = ProverKey::deserialize(&pk_bytes[..]).unwrap();
+        let pf = Pl::prove(&pk, &circ, &mut ark_std::test_rng());
+
+        let mut bytes = Vec::new();
+        pf.serialize(&mut bytes).unwrap();
+        vk.serialize(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), pf.serialized_size() + vk.serialized_size());
+        let mut reader = &bytes[..];
+        let pf = Pf::deserialize(&mut reader).unwrap();
+        let vk = VerifierKey::deserialize(&mut reader).unwrap();
+        assert!(reader.is_empty());
+        assert!(Pl::verify(&vk, &v_circ, pf, &public).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "missing from table")]
+    fn plonk_lookup_missing_row_test() {
+        let c = lookup_circuit(5, 3, 7);
+        relations::flat::CircuitLayout::from_circuit(&c);
     }
 }
@@ -1,5 +1,5 @@
 use ark_ff::{FftField, FftParameters};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use ark_poly::{
     domain::{EvaluationDomain, MixedRadixEvaluationDomain, Radix2EvaluationDomain},
@@ -11,22 +11,33 @@ use ark_poly::{
 
 use std::iter;
 
-use crate::util::shift;
+use crate::data_structures::{LookupTables, Selectors, TableColumns};
+use crate::util::{evaluate_over_domain, shift};
 
 use super::structured::PlonkCircuit;
 
+/// The points off the wires domain at which the prover opens the wire polynomial p: x, wx and
+/// w^2x.
+///
+/// p is blinded so that this many evaluations of it reveal nothing.
+pub const P_QUERIES: usize = 3;
+
 #[derive(Clone)]
 pub struct CircuitLayout<F: FftField> {
     /// Wiring permutation polynomial
     pub w: DensePolynomial<F>,
-    /// Gate selection polynomial
-    pub s: DensePolynomial<F>,
+    /// Gate selector polynomials
+    pub q: Selectors<DensePolynomial<F>>,
+    /// Lookup selector and table polynomials, if the circuit has lookup tables
+    pub lookup: Option<LookupTables<DensePolynomial<F>>>,
     /// Map from variables to indices in the layout
     pub vars_to_indices: HashMap<u32, Vec<usize>>,
     /// Public variables
     pub public_indices: HashMap<String, usize>,
     /// Wire value polynomial
     pub p: Option<DensePolynomial<F>>,
+    /// Sorted lookup witness: evaluations over twice the gates
+    pub lookup_witness: Option<TableColumns<Vec<F>>>,
     /// Domains over which the polynomials have meaning
     pub domains: Domains<F>,
 }
@@ -34,16 +45,52 @@ pub struct CircuitLayout<F: FftField> {
 impl<F: FftField> CircuitLayout<F> {
     pub fn from_circuit(c: &PlonkCircuit<F>) -> Self {
         let domains = Domains::from_circuit(c);
-        // Our layout is products followed by sums
+        // Our layout is the gates, in order
 
-        // Start with gate selector polynomial
-        let gate_selector_evals = Evaluations::<F, Radix2EvaluationDomain<F>>::from_vec_and_domain(
-            iter::repeat(F::zero())
-                .take(c.prods.len())
-                .chain(iter::repeat(F::one()).take(c.sums.len()))
-                .collect(),
-            domains.gates.clone(),
-        );
+        // Start with the gate selector polynomials
+        let selector_evals = |sel: fn(&Selectors<F>) -> F| {
+            Evaluations::<F, Radix2EvaluationDomain<F>>::from_vec_and_domain(
+                c.gates.iter().map(|g| sel(&g.q)).collect(),
+                domains.gates.clone(),
+            )
+            .interpolate()
+        };
+        let q = Selectors {
+            q_l: selector_evals(|q| q.q_l),
+            q_r: selector_evals(|q| q.q_r),
+            q_o: selector_evals(|q| q.q_o),
+            q_m: selector_evals(|q| q.q_m),
+            q_c: selector_evals(|q| q.q_c),
+        };
+
+        // Then the lookup selector and tables
+        let lookup = if c.tables.is_empty() {
+            None
+        } else {
+            let gate_poly = |evals: Vec<F>| {
+                Evaluations::<F, Radix2EvaluationDomain<F>>::from_vec_and_domain(
+                    evals,
+                    domains.gates.clone(),
+                )
+                .interpolate()
+            };
+            let table_id = |t: usize| F::from(t as u64 + 1);
+            let rows = c.table_rows();
+            Some(LookupTables {
+                q_k: gate_poly(
+                    (0..c.n_gates())
+                        .map(|i| c.lookups.get(&i).map_or(F::zero(), |t| table_id(*t)))
+                        .collect(),
+                ),
+                t: TableColumns {
+                    t_l: gate_poly(rows.iter().map(|(_, r)| r[0]).collect()),
+                    t_r: gate_poly(rows.iter().map(|(_, r)| r[1]).collect()),
+                    t_o: gate_poly(rows.iter().map(|(_, r)| r[2]).collect()),
+                    t_id: gate_poly(rows.iter().map(|(t, _)| table_id(*t)).collect()),
+                },
+            })
+        };
+        let lookup_witness = lookup.as_ref().and_then(|_| c.sorted_lookup_columns());
 
         // Get powers of w for wire permuation poly
         let n_wires = c.n_gates() * 3;
@@ -52,12 +99,7 @@ impl<F: FftField> CircuitLayout<F> {
             .take(n_wires)
             .collect();
         // Manifest layout
-        let var_layout: Vec<u32> = c
-            .prods
-            .iter()
-            .chain(c.sums.iter())
-            .flat_map(|(in0, in1, out)| vec![*in0, *in1, *out])
-            .collect();
+        let var_layout: Vec<u32> = c.gates.iter().flat_map(|g| vec![g.l, g.r, g.o]).collect();
         // Assemble cycles
         let vars_to_indices = {
             let mut vars_to_indices: HashMap<u32, Vec<usize>> =
@@ -120,9 +162,11 @@ impl<F: FftField> CircuitLayout<F> {
             }
         CircuitLayout {
             w,
-            s: gate_selector_evals.interpolate(),
+            q,
+            lookup,
             domains: domains.clone(),
             p,
+            lookup_witness,
             public_indices: c
                 .pub_vars
                 .iter()
@@ -136,8 +180,21 @@ impl<F: FftField> CircuitLayout<F> {
         }
     }
 
+    /// Bound on the degree of the (blinded) wire polynomial p
+    pub fn p_degree_bound(&self) -> usize {
+        self.domains.wires.size() + P_QUERIES - 1
+    }
+
+    /// Bound on the degree of any polynomial the prover commits to
     pub fn degree_bound(&self) -> usize {
-        self.domains.wires.size() * 2 - 1
+        Self::degree_bound_for(self.domains.gates.size())
+    }
+
+    /// Bound on the degree of any polynomial the prover commits to, for `n_gates` gates
+    ///
+    /// The largest is the gate quotient, which is quadratic in the blinded p.
+    pub fn degree_bound_for(n_gates: usize) -> usize {
+        2 * (n_gates * 3 + P_QUERIES) - 1
     }
 
     /// Check that no wire is in more than `d` connections
@@ -176,9 +233,9 @@ impl<F: FftField> CircuitLayout<F> {
         let cs = p.coeffs.len();
         if cs <= self.domains.gates.size() {
             p.evaluate_over_domain_by_ref(self.domains.gates)
-        } else if cs <= self.domains.wires.size() {
+        } else {
             Evaluations::from_vec_and_domain(
-                p.evaluate_over_domain_by_ref(self.domains.wires)
+                evaluate_over_domain(p, self.domains.wires)
                     .evals
                     .into_iter()
                     .enumerate()
@@ -187,11 +244,6 @@ impl<F: FftField> CircuitLayout<F> {
                     .collect(),
                 self.domains.gates,
             )
-        } else {
-            panic!(
-                "Cannot evaluate polynomial with {} coefficients over gates domain",
-                cs
-            )
         }
     }
 
@@ -214,14 +266,40 @@ impl<F: FftField> CircuitLayout<F> {
             let p_x_evals = self.evaluate_over_gates(p);
             let p_wx_evals = self.evaluate_over_gates(&shift(p.clone(), wire_g));
             let p_wwx_evals = self.evaluate_over_gates(&shift(p.clone(), wire_g * wire_g));
-            let s_evals = self.evaluate_over_gates(&self.s);
-            let a = &s_evals * &(&p_x_evals + &p_wx_evals);
-            let b = &(&(&s_evals * &-F::one()) + &F::one()) * &(&p_x_evals * &p_wx_evals);
-            let c = &a + &b;
+            let q = self.q.as_ref().map(|q| self.evaluate_over_gates(q));
+            let c = &(&(&(&q.q_l * &p_x_evals) + &(&q.q_r * &p_wx_evals))
+                + &(&q.q_m * &(&p_x_evals * &p_wx_evals)))
+                + &q.q_c;
+            let p_wwx_evals = &q.q_o * &p_wwx_evals;
             assert_eq!(c, p_wwx_evals);
         }
     }
 
+    fn check_lookups(&self) {
+        let wire_g = self.domains.wires.group_gen;
+        if let (Some(p), Some(lookup)) = (&self.p, &self.lookup) {
+            let t = lookup.t.as_ref().map(|t| self.evaluate_over_gates(t));
+            let rows: HashSet<[F; 4]> = (0..self.domains.gates.size())
+                .map(|i| [t.t_l[i], t.t_r[i], t.t_o[i], t.t_id[i]])
+                .collect();
+            let q_k = self.evaluate_over_gates(&lookup.q_k);
+            let p_x_evals = self.evaluate_over_gates(p);
+            let p_wx_evals = self.evaluate_over_gates(&shift(p.clone(), wire_g));
+            let p_wwx_evals = self.evaluate_over_gates(&shift(p.clone(), wire_g * wire_g));
+            for i in 0..self.domains.gates.size() {
+                if !q_k[i].is_zero() {
+                    let row = [p_x_evals[i], p_wx_evals[i], p_wwx_evals[i], q_k[i]];
+                    assert!(
+                        rows.contains(&row),
+                        "Gate {} reads {:?}, not in its table",
+                        i,
+                        row
+                    );
+                }
+            }
+        }
+    }
+
     fn check_wiring(&self) {
         let n_wires = self.domains.wires.size();
         let wire_g = self.domains.wires.group_gen;
@@ -272,6 +350,7 @@ impl<F: FftField> CircuitLayout<F> {
     pub fn check(&self, public_wires: &HashMap<String, F>) {
         self.check_gates();
         self.check_wiring();
+        self.check_lookups();
         self.check_inputs(public_wires);
     }
 }
@@ -333,8 +412,7 @@ mod tests {
     fn circuit_polys() {
         for steps in &[1, 3] {
             let c = PlonkCircuit::<F>::new_squaring_circuit(*steps, None);
-            let d = Domains::from_circuit(&c);
-            let polys = CircuitLayout::from_circuit(&c, &d);
+            let polys = CircuitLayout::from_circuit(&c);
             polys.check_connection_degree(3);
         }
     }
@@ -345,8 +423,7 @@ mod tests {
             let c = PlonkCircuit::<F>::new_squaring_circuit(*steps, Some(start));
             let res = (0..*steps).fold(start, |a, _| a * a);
             let public: HashMap<String, F> = vec![("out".to_owned(), res)].into_iter().collect();
-            let d = Domains::from_circuit(&c);
-            let polys = CircuitLayout::from_circuit(&c, &d);
+            let polys = CircuitLayout::from_circuit(&c);
             polys.check_connection_degree(3);
             polys.check(&public);
         }
@@ -1,24 +1,101 @@
 use ark_ff::Field;
 use std::collections::HashMap;
 
+use crate::data_structures::{Selectors, TableColumns};
+
 pub struct PlonkCircuit<F: Field> {
     pub n_vars: u32,
     pub pub_vars: HashMap<Var, String>,
-    pub prods: Vec<(Var, Var, Var)>,
-    pub sums: Vec<(Var, Var, Var)>,
+    pub gates: Vec<Gate<F>>,
     pub values: Option<Vec<F>>,
+    /// Lookup tables, as rows of (left, right, output) wire values
+    pub tables: Vec<Vec<[F; 3]>>,
+    /// Map from lookup gates to the table their wires are a row of
+    pub lookups: HashMap<usize, usize>,
 }
 
 type Var = u32;
 
+/// The `c` in the key `l + c r + c^2 o` by which shared lookups are sorted. Any value works unless
+/// two rows of one table share a key.
+const LOOKUP_KEY_BASE: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// A gate on wires `l`, `r` and `o`, which holds when
+/// `q_l * l + q_r * r + q_m * l * r + q_c = q_o * o`.
+#[derive(Clone, Debug)]
+pub struct Gate<F: Field> {
+    pub l: Var,
+    pub r: Var,
+    pub o: Var,
+    pub q: Selectors<F>,
+}
+
+impl<F: Field> Gate<F> {
+    /// `l + r = o`
+    pub fn sum(l: Var, r: Var, o: Var) -> Self {
+        Self::lc(l, F::one(), r, F::one(), o)
+    }
+    /// `l * r = o`
+    pub fn prod(l: Var, r: Var, o: Var) -> Self {
+        Self {
+            l,
+            r,
+            o,
+            q: Selectors {
+                q_l: F::zero(),
+                q_r: F::zero(),
+                q_o: F::one(),
+                q_m: F::one(),
+                q_c: F::zero(),
+            },
+        }
+    }
+    /// `a * l + b * r = o`
+    pub fn lc(l: Var, a: F, r: Var, b: F, o: Var) -> Self {
+        Self {
+            l,
+            r,
+            o,
+            q: Selectors {
+                q_l: a,
+                q_r: b,
+                q_o: F::one(),
+                q_m: F::zero(),
+                q_c: F::zero(),
+            },
+        }
+    }
+    /// A gate on `v` that always holds: all its selectors are zero.
+    pub fn empty(v: Var) -> Self {
+        Self::lookup(v, v, v)
+    }
+    /// A gate on `l`, `r` and `o` with all selectors zero: a lookup gate constrains its wires
+    /// only through its table.
+    pub fn lookup(l: Var, r: Var, o: Var) -> Self {
+        Self {
+            l,
+            r,
+            o,
+            q: Selectors {
+                q_l: F::zero(),
+                q_r: F::zero(),
+                q_o: F::zero(),
+                q_m: F::zero(),
+                q_c: F::zero(),
+            },
+        }
+    }
+}
+
 impl<F: Field> PlonkCircuit<F> {
     pub fn new(values: bool) -> Self {
         Self {
             n_vars: 0,
             pub_vars: HashMap::new(),
-            prods: Vec::new(),
-            sums: Vec::new(),
+            gates: Vec::new(),
             values: if values { Some(Vec::new()) } else { None },
+            tables: Vec::new(),
+            lookups: HashMap::new(),
         }
     }
     pub fn new_var(&mut self, value: impl FnOnce() -> F) -> Var {
@@ -34,23 +111,306 @@ impl<F: Field> PlonkCircuit<F> {
             );
         }
     }
-    pub fn new_sum(&mut self, a: Var, b: Var) -> Var {
+    /// A new variable `q_l * a + q_r * b + q_m * a * b + q_c`, from one gate.
+    pub fn new_gate(&mut self, a: Var, b: Var, q_l: F, q_r: F, q_m: F, q_c: F) -> Var {
         self.values.as_mut().map(|v| {
-            let o = v[a as usize] + v[b as usize];
+            let (x, y) = (v[a as usize], v[b as usize]);
+            let mut o = q_l * x + q_r * y + q_c;
+            // Skipped when zero: for shared values, the product costs a round.
+            if !q_m.is_zero() {
+                o += q_m * x * y;
+            }
             v.push(o);
         });
-        self.sums.push((a, b, self.n_vars));
+        let q = Selectors {
+            q_l,
+            q_r,
+            q_o: F::one(),
+            q_m,
+            q_c,
+        };
+        self.gates.push(Gate {
+            l: a,
+            r: b,
+            o: self.n_vars,
+            q,
+        });
         self.n_vars += 1;
         self.n_vars - 1
     }
+    pub fn new_sum(&mut self, a: Var, b: Var) -> Var {
+        self.new_gate(a, b, F::one(), F::one(), F::zero(), F::zero())
+    }
     pub fn new_prod(&mut self, a: Var, b: Var) -> Var {
-        self.values.as_mut().map(|v| {
-            let o = v[a as usize] * v[b as usize];
-            v.push(o);
+        self.new_gate(a, b, F::zero(), F::zero(), F::one(), F::zero())
+    }
+    /// `c_a * a + c_b * b`
+    pub fn new_lc(&mut self, a: Var, c_a: F, b: Var, c_b: F) -> Var {
+        self.new_gate(a, b, c_a, c_b, F::zero(), F::zero())
+    }
+    /// `c * a`
+    pub fn new_scale(&mut self, a: Var, c: F) -> Var {
+        self.new_gate(a, a, c, F::zero(), F::zero(), F::zero())
+    }
+    /// `a + c`
+    pub fn new_add_const(&mut self, a: Var, c: F) -> Var {
+        self.new_gate(a, a, F::one(), F::zero(), F::zero(), c)
+    }
+    /// A variable fixed to the constant `c`.
+    pub fn new_const(&mut self, c: F) -> Var {
+        let v = self.new_var(|| c);
+        self.enforce_gate(Gate {
+            l: v,
+            r: v,
+            o: v,
+            q: Selectors {
+                q_l: F::zero(),
+                q_r: F::zero(),
+                q_o: F::one(),
+                q_m: F::zero(),
+                q_c: c,
+            },
         });
-        self.prods.push((a, b, self.n_vars));
-        self.n_vars += 1;
-        self.n_vars - 1
+        v
+    }
+    /// Constrain existing variables with a custom gate. Its output wire is not computed: the
+    /// caller must make the gate hold.
+    pub fn enforce_gate(&mut self, gate: Gate<F>) {
+        for v in &[gate.l, gate.r, gate.o] {
+            assert!(*v < self.n_vars, "Gate on unknown variable {}", v);
+        }
+        self.gates.push(gate);
+    }
+    /// A new lookup table with the given rows. Returns its index.
+    pub fn new_table(&mut self, rows: Vec<[F; 3]>) -> usize {
+        assert!(!rows.is_empty(), "Empty lookup table");
+        self.tables.push(rows);
+        self.tables.len() - 1
+    }
+    /// A table of `[k, k, k]` for `k < 2^bits`: look up `[v, v, v]` to range-check `v`.
+    pub fn new_range_table(&mut self, bits: u32) -> usize {
+        self.new_table(
+            (0..1u64 << bits)
+                .map(|k| [F::from(k), F::from(k), F::from(k)])
+                .collect(),
+        )
+    }
+    /// A table of `[a, b, op(a, b)]` for `a, b < 2^bits`.
+    pub fn new_binary_table(&mut self, bits: u32, op: impl Fn(u64, u64) -> u64) -> usize {
+        let n = 1u64 << bits;
+        self.new_table(
+            (0..n)
+                .flat_map(|a| (0..n).map(move |b| (a, b)))
+                .map(|(a, b)| [F::from(a), F::from(b), F::from(op(a, b))])
+                .collect(),
+        )
+    }
+    pub fn new_xor_table(&mut self, bits: u32) -> usize {
+        self.new_binary_table(bits, |a, b| a ^ b)
+    }
+    pub fn new_and_table(&mut self, bits: u32) -> usize {
+        self.new_binary_table(bits, |a, b| a & b)
+    }
+    /// Constrain `[a, b, c]` to be a row of `table`, with one lookup gate.
+    pub fn lookup(&mut self, table: usize, a: Var, b: Var, c: Var) {
+        assert!(table < self.tables.len(), "Unknown table {}", table);
+        self.lookups.insert(self.gates.len(), table);
+        self.enforce_gate(Gate::lookup(a, b, c));
+    }
+    /// A new variable `c` with `[a, b, c]` a row of `table`. Its value is not computed: the
+    /// caller supplies it.
+    pub fn new_lookup(&mut self, table: usize, a: Var, b: Var, value: impl FnOnce() -> F) -> Var {
+        let c = self.new_var(value);
+        self.lookup(table, a, b, c);
+        c
+    }
+    /// The rows of every table, one after another, with their table's index, and the last row
+    /// repeated to fill the gates.
+    pub fn table_rows(&self) -> Vec<(usize, [F; 3])> {
+        let mut rows: Vec<(usize, [F; 3])> = self
+            .tables
+            .iter()
+            .enumerate()
+            .flat_map(|(i, t)| t.iter().map(move |r| (i, *r)))
+            .collect();
+        assert!(
+            rows.len() <= self.n_gates(),
+            "{} table rows, but only {} gates",
+            rows.len(),
+            self.n_gates()
+        );
+        let last = *rows.last().expect("No lookup tables");
+        rows.resize(self.n_gates(), last);
+        rows
+    }
+    /// The table rows, each followed by copies of itself for every gate that reads it. Lookup
+    /// gates read their wires; other gates read the first row.
+    ///
+    /// If a lookup gate has a shared wire, the row it reads is secret, so the rows are placed
+    /// without looking at the values, by [oblivious_sort_lookups](Self::oblivious_sort_lookups).
+    pub fn sort_lookups(&self) -> Vec<[F; 3]> {
+        let vals = self.values.as_ref().expect("Sorting lookups needs values");
+        let rows = self.table_rows();
+        let mut row_indices: HashMap<(usize, [F; 3]), usize> = HashMap::new();
+        for (i, r) in rows.iter().enumerate() {
+            row_indices.entry(*r).or_insert(i);
+        }
+        let mut counts = vec![1; rows.len()];
+        let mut shared_reads = Vec::new();
+        for (i, g) in self.gates.iter().enumerate() {
+            let i_row = match self.lookups.get(&i) {
+                Some(t) => {
+                    let row = [vals[g.l as usize], vals[g.r as usize], vals[g.o as usize]];
+                    if row.iter().any(|v| v.is_shared()) {
+                        shared_reads.push((*t, row));
+                        continue;
+                    }
+                    *row_indices.get(&(*t, row)).unwrap_or_else(|| {
+                        panic!("Gate {} reads a row missing from table {}", i, t)
+                    })
+                }
+                None => 0,
+            };
+            counts[i_row] += 1;
+        }
+        if !shared_reads.is_empty() {
+            return Self::oblivious_sort_lookups(&rows, &row_indices, counts, &shared_reads);
+        }
+        rows.iter()
+            .zip(counts)
+            .flat_map(|((_, r), c)| std::iter::repeat(*r).take(c))
+            .collect()
+    }
+    /// [sort_lookups](Self::sort_lookups), given the counts of the public reads and the shared
+    /// reads themselves, with nothing opened.
+    ///
+    /// A read is keyed by `l + c r + c^2 o`, for a fixed `c`, and so are its table's distinct
+    /// rows. The Lagrange basis polynomial of row `j` over those keys is one at a read of row `j`
+    /// and zero at a read of another, so summing it over the reads counts the shared reads of row
+    /// `j`: that is linear in the reads' power sums. Row `j` then starts at `b_j + u_j`, where
+    /// `b_j` counts the public reads and the shared reads of earlier tables, and `u_j`, the shared
+    /// reads of earlier rows of its table, is at most that table's `k` shared reads. So entry `p`
+    /// of the result is the first row, plus, for each later row, its difference from the one
+    /// before it times `[u_j <= p - b_j]`: a sum of Lagrange basis polynomials in `u_j` over
+    /// `0..=k`.
+    ///
+    /// A read of a row missing from its table makes the result garbage, and the proof fail.
+    fn oblivious_sort_lookups(
+        rows: &[(usize, [F; 3])],
+        row_indices: &HashMap<(usize, [F; 3]), usize>,
+        public_counts: Vec<usize>,
+        shared_reads: &[(usize, [F; 3])],
+    ) -> Vec<[F; 3]> {
+        let _op = mpc_net::op("sort_lookups");
+        let n = rows.len();
+        let c = F::from(LOOKUP_KEY_BASE);
+        let key = |r: &[F; 3]| r[0] + c * r[1] + c * c * r[2];
+        // Adding this to a value keeps it shared, as batch products need: here, and in the
+        // prover's products of the result.
+        #[allow(clippy::eq_op)]
+        let zero = {
+            let k = key(&shared_reads[0].1);
+            k - k
+        };
+        let n_tables = rows[n - 1].0 + 1;
+        let mut n_reads = vec![0; n_tables];
+        for (t, _) in shared_reads {
+            n_reads[*t] += 1;
+        }
+        // The shared reads of each row
+        let mut counts = vec![F::zero(); n];
+        for t in (0..n_tables).filter(|t| n_reads[*t] > 0) {
+            // The distinct rows of table t, by first index
+            let firsts: Vec<usize> = (0..n)
+                .filter(|i| rows[*i].0 == t && row_indices[&rows[*i]] == *i)
+                .collect();
+            let keys: Vec<F> = firsts.iter().map(|i| key(&rows[*i].1)).collect();
+            let reads: Vec<F> = shared_reads
+                .iter()
+                .filter(|(u, _)| *u == t)
+                .map(|(_, r)| key(r))
+                .collect();
+            let mut sums = vec![F::zero(); keys.len()];
+            for pows in powers(&reads, keys.len()) {
+                sums.iter_mut().zip(pows).for_each(|(s, p)| *s += p);
+            }
+            for (i, b) in firsts.iter().zip(lagrange_basis(&keys)) {
+                counts[*i] = b.iter().zip(&sums).map(|(b, s)| *b * s).sum();
+            }
+        }
+        // For each row that differs from the one before it: its table, b_j, and u_j
+        let mut steps = Vec::new();
+        let (mut b, mut u) = (0, zero);
+        for j in 1..n {
+            b += public_counts[j - 1];
+            if rows[j].0 == rows[j - 1].0 {
+                u += counts[j - 1];
+            } else {
+                b += n_reads[rows[j - 1].0];
+                u = zero;
+            }
+            if rows[j].1 != rows[j - 1].1 {
+                steps.push((j, b, u));
+            }
+        }
+        let first = rows[0].1;
+        let mut out = vec![[first[0] + zero, first[1] + zero, first[2] + zero]; 2 * n];
+        for (t, k) in n_reads.iter().copied().enumerate() {
+            let table_steps: Vec<_> = steps.iter().filter(|(j, _, _)| rows[*j].0 == t).collect();
+            let us: Vec<F> = table_steps.iter().map(|(_, _, u)| *u).collect();
+            let basis = lagrange_basis(&(0..=k as u64).map(F::from).collect::<Vec<_>>());
+            for ((j, b, _), pows) in table_steps.into_iter().zip(powers(&us, k + 1)) {
+                let delta: Vec<F> = (0..3).map(|i| rows[*j].1[i] - rows[*j - 1].1[i]).collect();
+                // The sum of the indicators of u_j = 0..=p - b_j
+                let mut at_most = F::zero();
+                for p in *b..2 * n {
+                    if p - b <= k {
+                        at_most += basis[p - b]
+                            .iter()
+                            .zip(&pows)
+                            .map(|(c, x)| *c * x)
+                            .sum::<F>();
+                    }
+                    for (o, d) in out[p].iter_mut().zip(&delta) {
+                        *o += *d * at_most;
+                    }
+                }
+            }
+        }
+        out
+    }
+    /// The table of each entry of the sorted lookup witness. Which gates read which table is
+    /// public, so this is too.
+    fn sorted_table_indices(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.tables.len()];
+        for (t, _) in self.table_rows() {
+            counts[t] += 1;
+        }
+        for i in 0..self.n_gates() {
+            counts[self.lookups.get(&i).copied().unwrap_or(0)] += 1;
+        }
+        counts
+            .into_iter()
+            .enumerate()
+            .flat_map(|(t, c)| std::iter::repeat(t).take(c))
+            .collect()
+    }
+    /// The columns of the sorted lookup witness, with table ids, if there are values.
+    pub fn sorted_lookup_columns(&self) -> Option<TableColumns<Vec<F>>> {
+        self.values.as_ref()?;
+        let witness = self.sort_lookups();
+        let ids = self.sorted_table_indices();
+        assert_eq!(
+            witness.len(),
+            ids.len(),
+            "The lookup witness should have two entries per gate"
+        );
+        Some(TableColumns {
+            t_l: witness.iter().map(|r| r[0]).collect(),
+            t_r: witness.iter().map(|r| r[1]).collect(),
+            t_o: witness.iter().map(|r| r[2]).collect(),
+            t_id: ids.into_iter().map(|t| F::from(t as u64 + 1)).collect(),
+        })
     }
     pub fn new_pub_var(&mut self, value: impl FnOnce() -> F, name: String) -> Var {
         let v = self.new_var(value);
@@ -58,14 +418,16 @@ impl<F: Field> PlonkCircuit<F> {
         v
     }
     pub fn n_gates(&self) -> usize {
-        self.prods.len() + self.sums.len()
+        self.gates.len()
     }
+    /// Pad with empty gates to a power of two, with room for every table row.
     pub fn pad_to_power_of_2(&mut self) {
-        let n = self.n_gates().next_power_of_two();
+        let n_rows: usize = self.tables.iter().map(|t| t.len()).sum();
+        let n = self.n_gates().max(n_rows).next_power_of_two();
         assert!(self.n_vars > 0, "Cannot pad an empty circuit!");
         for _ in self.n_gates()..n {
-            let v = self.n_vars - 1;
-            self.new_sum(v, v);
+            let v = self.new_var(F::zero);
+            self.gates.push(Gate::empty(v));
         }
         assert!(self.n_gates().is_power_of_two());
     }
@@ -80,3 +442,56 @@ impl<F: Field> PlonkCircuit<F> {
         self_
     }
 }
+
+/// The coefficients of the Lagrange basis polynomials over distinct `points`, lowest first.
+fn lagrange_basis<F: Field>(points: &[F]) -> Vec<Vec<F>> {
+    // The product of (X - x) over the points, and its quotient by each factor
+    let mut all = vec![F::one()];
+    for x in points {
+        all.insert(0, F::zero());
+        for i in 0..all.len() - 1 {
+            let next = all[i + 1];
+            all[i] -= *x * next;
+        }
+    }
+    points
+        .iter()
+        .map(|x| {
+            let mut quotient = vec![F::zero(); points.len()];
+            let mut carry = F::zero();
+            for i in (0..points.len()).rev() {
+                carry = all[i + 1] + *x * carry;
+                quotient[i] = carry;
+            }
+            let at_x = quotient.iter().rev().fold(F::zero(), |acc, q| acc * x + q);
+            let scale = at_x.inverse().expect("Lookup keys collide");
+            quotient.iter().map(|q| *q * scale).collect()
+        })
+        .collect()
+}
+
+/// `[1, x, ..., x^(n-1)]` for each of `xs`, which are all shared or all public. Each round of
+/// batch products nearly doubles the powers.
+fn powers<F: Field>(xs: &[F], n: usize) -> Vec<Vec<F>> {
+    let mut pows: Vec<Vec<F>> = xs
+        .iter()
+        .map(|x| vec![F::one(), *x].into_iter().take(n).collect())
+        .collect();
+    while !xs.is_empty() && pows[0].len() < n {
+        let have = pows[0].len();
+        let want = (2 * have - 1).min(n);
+        let mut lo: Vec<F> = pows
+            .iter()
+            .flat_map(|p| p[1..want - have + 1].to_vec())
+            .collect();
+        let hi: Vec<F> = pows
+            .iter()
+            .flat_map(|p| vec![p[have - 1]; want - have])
+            .collect();
+        F::batch_product_in_place(&mut lo, &hi);
+        for (p, new) in pows.iter_mut().zip(lo.chunks(want - have)) {
+            p.extend_from_slice(new);
+        }
+    }
+    pows
+}
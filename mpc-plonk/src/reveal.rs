@@ -4,37 +4,66 @@ use mpc_algebra::*;
 
 use crate::*;
 
-impl<C: Reveal, O: Reveal> Reveal for GateProof<C, O> {
-    type Base = GateProof<C::Base, O::Base>;
-    struct_reveal_impl!(GateProof<C, O>, GateProof;
-        (C, q_cmt), (O, s_open), (O, q_open), (O, p_open), (O, p_w_open), (O, p_w2_open));
+impl<T: Reveal> Reveal for Selectors<T> {
+    type Base = Selectors<T::Base>;
+    struct_reveal_simp_impl!(Selectors; q_l, q_r, q_o, q_m, q_c);
 }
 
-impl<C: Reveal, O: Reveal> Reveal for PublicProof<C, O> {
-    type Base = PublicProof<C::Base, O::Base>;
-    struct_reveal_impl!(PublicProof<C, O>, PublicProof;
-        (C, q_cmt), (O, q_open), (O, p_open));
+impl<T: Reveal> Reveal for TableColumns<T> {
+    type Base = TableColumns<T::Base>;
+    struct_reveal_simp_impl!(TableColumns; t_l, t_r, t_o, t_id);
 }
 
-impl<C: Reveal, O: Reveal> Reveal for ProductProof<C, O> {
-    type Base = ProductProof<C::Base, O::Base>;
-    struct_reveal_impl!(ProductProof<C, O>, ProductProof;
-        (C, q_cmt), (C, t_cmt), (O, t_wk_open), (O, t_r_open), (O, t_wr_open), (O, f_wr_open), (O, q_r_open));
+impl<T: Reveal> Reveal for LookupTables<T> {
+    type Base = LookupTables<T::Base>;
+    struct_reveal_simp_impl!(LookupTables; q_k, t);
 }
 
-impl<C: Reveal, O: Reveal> Reveal for WiringProof<C, O> {
-    type Base = WiringProof<C::Base, O::Base>;
-    struct_reveal_impl!(WiringProof<C, O>, WiringProof;
-        (C, l1_cmt), (ProductProof<C, O>, l1_prod_pf), (C, l2_q_cmt), (O, p_x_open), (O, w_x_open), (O, l1_x_open), (O, l2_q_x_open));
+impl<F: Reveal, C: Reveal> Reveal for LookupProof<F, C> {
+    type Base = LookupProof<F::Base, C::Base>;
+    struct_reveal_impl!(LookupProof<F, C>, LookupProof;
+        (C, f_cmt), (C, h1_cmt), (C, h2_cmt), (C, l_cmt), (ProductProof<F, C>, l_prod_pf),
+        (C, q_cmt), (F, f_x), (F, h1_x), (F, h1_gx), (F, h2_x), (F, l_x), (F, q_x), (F, q_k_x),
+        (TableColumns<F>, t_x), (TableColumns<F>, t_gx));
 }
 
-impl<F: Reveal, C: Reveal, O: Reveal> Reveal for Proof<F, C, O> {
-    type Base = Proof<F::Base, C::Base, O::Base>;
-    struct_reveal_impl!(Proof<F, PC>, Proof;
+impl<F: Reveal, C: Reveal> Reveal for GateProof<F, C> {
+    type Base = GateProof<F::Base, C::Base>;
+    struct_reveal_impl!(GateProof<F, C>, GateProof;
+        (C, q_cmt), (F, q_x), (Selectors<F>, sel_x));
+}
+
+impl<F: Reveal, C: Reveal> Reveal for PublicProof<F, C> {
+    type Base = PublicProof<F::Base, C::Base>;
+    struct_reveal_impl!(PublicProof<F, C>, PublicProof;
+        (C, q_cmt), (F, q_x));
+}
+
+impl<F: Reveal, C: Reveal> Reveal for ProductProof<F, C> {
+    type Base = ProductProof<F::Base, C::Base>;
+    struct_reveal_impl!(ProductProof<F, C>, ProductProof;
+        (C, t_cmt), (C, q_cmt), (F, t_wk), (F, t_x), (F, t_wx), (F, f_wx), (F, q_x));
+}
+
+impl<F: Reveal, C: Reveal> Reveal for WiringProof<F, C> {
+    type Base = WiringProof<F::Base, C::Base>;
+    struct_reveal_impl!(WiringProof<F, C>, WiringProof;
+        (C, l1_cmt), (ProductProof<F, C>, l1_prod_pf), (C, l2_q_cmt), (F, w_x), (F, l1_x),
+        (F, l2_q_x));
+}
+
+impl<F: Reveal, C: Reveal, B: Reveal> Reveal for Proof<F, C, B> {
+    type Base = Proof<F::Base, C::Base, B::Base>;
+    struct_reveal_impl!(Proof<F, C, B>, Proof;
         (C, p_cmt),
-        (WiringProof<C, (F, O)>, wiring),
-        (GateProof<C, (F, O)>, gates),
-        (PublicProof<C, (F, O)>, public)
+        (F, p_x),
+        (F, p_wx),
+        (F, p_w2x),
+        (WiringProof<F, C>, wiring),
+        (GateProof<F, C>, gates),
+        (PublicProof<F, C>, public),
+        (Option<LookupProof<F, C>>, lookup),
+        (B, pc_proof)
     );
 }
 
@@ -50,5 +79,15 @@ impl<E: PairingEngine, S: PairingShare<E>> Reveal
         marlin_pc::Commitment<E>,
         marlin_pc::CommitterKey<E>,
     >;
-    struct_reveal_simp_impl!(ProverKey; w, s, w_cmt, s_cmt, pc_ck);
+    struct_reveal_simp_impl!(ProverKey; w, q, lookup, w_cmt, q_cmt, lookup_cmt, pc_ck);
+}
+
+impl<E: PairingEngine, S: PairingShare<E>> Reveal
+    for VerifierKey<
+        marlin_pc::Commitment<MpcPairingEngine<E, S>>,
+        marlin_pc::VerifierKey<MpcPairingEngine<E, S>>,
+    >
+{
+    type Base = VerifierKey<marlin_pc::Commitment<E>, marlin_pc::VerifierKey<E>>;
+    struct_reveal_simp_impl!(VerifierKey; w_cmt, q_cmt, lookup_cmt, pc_vk);
 }
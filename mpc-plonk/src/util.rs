@@ -1,11 +1,7 @@
 ///! Extra algebra utils
-use ark_ff::{FftField, FromBytes, ToBytes, PubUniformRand};
+use ark_ff::FftField;
 use ark_poly::univariate::DensePolynomial;
-use ark_poly::UVPolynomial;
-use ark_std::marker::PhantomData;
-use ark_std::rand::{RngCore, SeedableRng};
-use digest::{generic_array::GenericArray, Digest};
-use rand_chacha::ChaChaRng;
+use ark_poly::{EvaluationDomain, Evaluations, Polynomial, UVPolynomial};
 
 /// Computes f(a*X) from a and f(X)
 pub fn shift<F: FftField>(mut f: DensePolynomial<F>, a: F) -> DensePolynomial<F> {
@@ -17,6 +13,47 @@ pub fn shift<F: FftField>(mut f: DensePolynomial<F>, a: F) -> DensePolynomial<F>
     f
 }
 
+/// Computes f(X) + b(X)Z(X), where Z is the vanishing polynomial of `domain`, from f and the
+/// coefficients of b
+///
+/// The result agrees with f over `domain`.
+pub fn add_vanishing_multiple<F: FftField, D: EvaluationDomain<F>>(
+    mut f: DensePolynomial<F>,
+    b: &[F],
+    domain: D,
+) -> DensePolynomial<F> {
+    let z = domain.vanishing_polynomial();
+    let len = z.degree() + b.len();
+    if f.coeffs.len() < len {
+        f.coeffs.resize(len, F::zero());
+    }
+    for (j, z_j) in z.coeffs.iter() {
+        for (i, b_i) in b.iter().enumerate() {
+            f.coeffs[i + j] += *b_i * z_j;
+        }
+    }
+    f
+}
+
+/// Evaluate f over `domain`, whatever its degree
+///
+/// The domain must be a subgroup, so that its vanishing polynomial is X^n - 1: then f agrees
+/// over it with f mod X^n - 1, which we get by folding f's coefficients.
+pub fn evaluate_over_domain<F: FftField, D: EvaluationDomain<F>>(
+    f: &DensePolynomial<F>,
+    domain: D,
+) -> Evaluations<F, D> {
+    let n = domain.size();
+    if f.coeffs.len() <= n {
+        return f.evaluate_over_domain_by_ref(domain);
+    }
+    let mut folded = f.coeffs[..n].to_vec();
+    for (i, c) in f.coeffs.iter().enumerate().skip(n) {
+        folded[i % n] += c;
+    }
+    Evaluations::from_vec_and_domain(domain.fft(&folded), domain)
+}
+
 pub fn interpolate<F: FftField>(points: &[(F, F)]) -> DensePolynomial<F> {
     let k = points.len();
     let mut scaled_lagrange_basis: Vec<DensePolynomial<F>> = (0..k)
@@ -41,72 +78,6 @@ pub fn interpolate<F: FftField>(points: &[(F, F)]) -> DensePolynomial<F> {
     scaled_lagrange_basis.into_iter().fold(p, |a, b| a + b)
 }
 
-/// A `SeedableRng` that refreshes its seed by hashing together the previous seed
-/// and the new seed material.
-// TODO: later: re-evaluate decision about ChaChaRng
-pub struct FiatShamirRng<D: Digest> {
-    r: ChaChaRng,
-    seed: GenericArray<u8, D::OutputSize>,
-    #[doc(hidden)]
-    digest: PhantomData<D>,
-}
-
-impl<D: Digest> RngCore for FiatShamirRng<D> {
-    #[inline]
-    fn next_u32(&mut self) -> u32 {
-        self.r.next_u32()
-    }
-
-    #[inline]
-    fn next_u64(&mut self) -> u64 {
-        self.r.next_u64()
-    }
-
-    #[inline]
-    fn fill_bytes(&mut self, dest: &mut [u8]) {
-        self.r.fill_bytes(dest);
-    }
-
-    #[inline]
-    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), ark_std::rand::Error> {
-        Ok(self.r.fill_bytes(dest))
-    }
-}
-
-impl<D: Digest> FiatShamirRng<D> {
-    /// Create a new `Self` by initializing with a fresh seed.
-    /// `self.seed = H(self.seed || new_seed)`.
-    #[inline]
-    pub fn from_seed<'a, T: 'a + ToBytes>(seed: &'a T) -> Self {
-        let mut bytes = Vec::new();
-        seed.write(&mut bytes).expect("failed to convert to bytes");
-        let seed = D::digest(&bytes);
-        let r_seed: [u8; 32] = FromBytes::read(seed.as_ref()).expect("failed to get [u32; 8]");
-        let r = ChaChaRng::from_seed(r_seed);
-        Self {
-            r,
-            seed,
-            digest: PhantomData,
-        }
-    }
-
-    /// Refresh `self.seed` with new material. Achieved by setting
-    /// `self.seed = H(self.seed || new_seed)`.
-    #[inline]
-    pub fn absorb<'a, T: 'a + ToBytes>(&mut self, seed: &'a T) {
-        let mut bytes = Vec::new();
-        seed.write(&mut bytes).expect("failed to convert to bytes");
-        bytes.extend_from_slice(&self.seed);
-        self.seed = D::digest(&bytes);
-        let seed: [u8; 32] = FromBytes::read(self.seed.as_ref()).expect("failed to get [u32; 8]");
-        self.r = ChaChaRng::from_seed(seed);
-    }
-
-    pub fn gen<T: PubUniformRand>(&mut self) -> T {
-        T::pub_rand(self)
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -136,6 +107,23 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn vanishing_multiple_test() {
+        use ark_poly::GeneralEvaluationDomain;
+        let rng = &mut ark_std::test_rng();
+        let domain = GeneralEvaluationDomain::<F>::new(8).unwrap();
+        let f = DensePolynomial::<F>::rand(7, rng);
+        let b: Vec<F> = (0..3).map(|_| F::rand(rng)).collect();
+        let g = add_vanishing_multiple(f.clone(), &b, domain);
+        assert_eq!(g.degree(), 10);
+        assert_eq!(
+            evaluate_over_domain(&g, domain).evals,
+            f.evaluate_over_domain_by_ref(domain).evals
+        );
+        let x = F::rand(rng);
+        assert_ne!(g.evaluate(&x), f.evaluate(&x));
+    }
+
     #[test]
     fn interp_test_5() {
         let rng = &mut ark_std::test_rng();
@@ -1,34 +1,30 @@
-#![feature(trait_alias)]
 //! Mostly just for testing
-use ark_std::{start_timer, end_timer, test_rng, UniformRand};
+//!
+//! Each computation runs on shares of random inputs, and is checked against the same computation
+//! on the inputs in the clear.
+use ark_std::{end_timer, start_timer, test_rng, UniformRand};
 use log::debug;
 
-use ark_bls12_377::{Fr, FqParameters};
-use ark_ec::{group::Group, AffineCurve, PairingEngine, ProjectiveCurve};
-use ark_ff::{Field, PrimeField, Fp256, BigInteger256, BigInteger};
+use ark_bls12_377::Fr;
+use ark_ec::{group::Group, msm::VariableBaseMSM, AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{Field, One, PrimeField, Zero};
 use ark_poly::domain::radix2::Radix2EvaluationDomain;
-use ark_poly::univariate::DensePolynomial;
+use ark_poly::univariate::{DenseOrSparsePolynomial, DensePolynomial};
 use ark_poly::{EvaluationDomain, Polynomial, UVPolynomial};
+use ark_poly_commit::kzg10::{self, KZG10};
 use ark_poly_commit::marlin_pc;
-use ark_poly_commit::PolynomialCommitment;
-use ark_serialize::CanonicalSerialize;
-use ark_std::rand::SeedableRng;
-use rand::{Rng, random};
-use std::borrow::Cow;
-use std::path::PathBuf;
-use std::vec;
-use ark_sponge::{ CryptographicSponge, FieldBasedCryptographicSponge, poseidon::PoseidonSponge, poseidon::PoseidonParameters};
-use ark_sponge::poseidon_parameters_for_test;
+use ark_poly_commit::{LabeledPolynomial, PolynomialCommitment, QuerySet};
 use mpc_algebra::com::ComField;
-use mpc_algebra::honest_but_curious as hbc;
-use mpc_algebra::malicious_majority as mm;
-use mpc_algebra::honest_majority as hm;
+use mpc_algebra::share::add::AdditivePairingShare;
+use mpc_algebra::share::gsz20::GszPairingShare;
+use mpc_algebra::share::spdz::SpdzPairingShare;
 use mpc_algebra::*;
-use mpc_trait::MpcWire;
-use mpc_net::{MpcNet, MpcMultiNet};
+use mpc_net::{MpcMultiNet, MpcNet};
+use rand::Rng;
+use std::borrow::Cow;
+use std::path::PathBuf;
 
 use clap::arg_enum;
-use merlin::Transcript;
 use structopt::StructOpt;
 
 mod groth;
@@ -36,11 +32,6 @@ mod marlin;
 mod plonk;
 mod silly;
 
-pub type PoseidonParam<F> = PoseidonParameters<F>;
-pub type SPNGFunction<F> = PoseidonSponge<F>;
-pub type SPNGOutput<F> = Vec<F>;
-pub type SPNGParam<F> = <SPNGFunction<F> as CryptographicSponge>::Parameters;
-
 arg_enum! {
     #[derive(PartialEq, Debug)]
     pub enum Computation {
@@ -69,20 +60,19 @@ arg_enum! {
         KzgZkBatch,
         PcTwoCom,
         Plonk,
+        PlonkLookups,
         PolyDiv,
         Ecdsa
     }
 }
 
-#[derive(PartialEq, Debug)]
-enum ComputationDomain {
-    G1,
-    G2,
-    Group,
-    Field,
-    Pairing,
-    BlsPairing,
-    PolyField,
+arg_enum! {
+    #[derive(PartialEq, Debug, Clone, Copy)]
+    pub enum MpcAlg {
+        Spdz,
+        Hbc,
+        Gsz,
+    }
 }
 
 #[derive(Debug, StructOpt)]
@@ -105,100 +95,41 @@ struct Opt {
     #[structopt()]
     computation: Computation,
 
-    /// Computation to perform
+    /// Do Diffie-Hellman in G2, not G1
     #[structopt(long)]
     use_g2: bool,
 
-    /// Computation to perform
-    #[structopt(long)]
-    spdz: bool,
+    /// Which sharing to use
+    #[structopt(long, default_value = "gsz")]
+    alg: MpcAlg,
 
-    /// Input a
+    /// Input size: how many random inputs to share
     #[structopt()]
     args: u64,
 }
 
-impl Opt {
-    fn domain(&self) -> ComputationDomain {
-        match &self.computation {
-            Computation::Dh => {
-                if self.use_g2 {
-                    ComputationDomain::G2
-                } else {
-                    ComputationDomain::G1
-                }
-            }
-            Computation::NaiveMsm | Computation::GroupOps => {
-                ComputationDomain::Group
-            }
-            Computation::PairingDh | Computation::PairingProd | Computation::PairingDiv => {
-                ComputationDomain::Pairing
-            }
-            Computation::Marlin
-            | Computation::Groth16
-            | Computation::Plonk
-            | Computation::Kzg
-            | Computation::KzgCommit
-            | Computation::KzgZk
-            | Computation::Msm
-            | Computation::KzgZkBatch
-            | Computation::MarlinPc
-            | Computation::Ecdsa
-            | Computation::MarlinPcBatch => ComputationDomain::BlsPairing,
-            Computation::PolyEval => ComputationDomain::PolyField,
-            _ => ComputationDomain::Field,
-        }
-    }
-}
-
-fn ecdsa_gsz (
-    x: MFrg, k: MFrg, p: <MEg as PairingEngine>::G1Projective, m: &[u8], params: SPNGParam<Fr>
-) -> MFrg {
-    let R = p.scalar_mul(&k);
-
-    let (x_coord, y_coord) = match R.val{
-        MpcGroup::Public(cp) => (cp.x, cp.y),
-        MpcGroup::Shared(cp) => (cp.val.x, cp.val.y),
-    };
-    let x_coord_bytes = x_coord.into_repr().to_bytes_le();
-    let r = MFrg::from_le_bytes_mod_order(&x_coord_bytes);
-    let mut sponge = PoseidonSponge::< >::new(&params);
-    sponge.absorb(&m);
-    let e: Fr = sponge.squeeze_native_field_elements(1)[0];
-    let rng = &mut test_rng();
-    let e_share = MFrg::Public(e);
-    let xr = x * r;
-    let mut s = e_share + xr;
-    s = s * k.inv().unwrap();
-    s
-}
-
-fn ecdsa_spdz (
-    x: MFrs, k: MFrs, p: <MEs as PairingEngine>::G1Projective, m: &[u8], params: SPNGParam<Fr>
-) -> MFrs {
-    let R = p.scalar_mul(&k);
-
-    let (x_coord, y_coord) = match R.val{
-        MpcGroup::Public(cp) => (cp.x, cp.y),
-        MpcGroup::Shared(cp) => (cp.sh.val.x, cp.sh.val.y),
-    };
-    let x_coord_bytes = x_coord.into_repr().to_bytes_le();
-    let r = MFrs::from_le_bytes_mod_order(&x_coord_bytes);
-    let mut sponge = PoseidonSponge::< >::new(&params);
-    sponge.absorb(&m);
-    let e: Fr = sponge.squeeze_native_field_elements(1)[0];
-    let xr = x * r;
-    let rng = &mut test_rng();
-    let e_share = MFrs::Public(e);
-    let mut s = e_share + xr;
-    s = s * k.inv().unwrap();
-    s
+type E = ark_bls12_377::Bls12_377;
+type P = DensePolynomial<Fr>;
+type ME<S> = MpcPairingEngine<E, S>;
+type MFr<S> = <ME<S> as PairingEngine>::Fr;
+type MP<S> = DensePolynomial<MFr<S>>;
+type Kzg = KZG10<E, P>;
+type MpcKzg<S> = KZG10<ME<S>, MP<S>>;
+type MarlinPc = marlin_pc::MarlinKZG10<E, P>;
+type MpcMarlinPc<S> = marlin_pc::MarlinKZG10<ME<S>, MP<S>>;
+
+/// Signs `m` with the shared key `x`, and checks the signature.
+fn ecdsa<S: PairingShare<E>>(x: MFr<S>, m: &[u8]) {
+    let key =
+        sig::ThresholdKey::<<E as PairingEngine>::G1Projective, S::G1ProjectiveShare>::from_secret(
+            x,
+        );
+    let sig = key.ecdsa_sign(m, &mut rand::thread_rng());
+    assert!(sig::ecdsa_verify(&key.public, m, &sig));
 }
 
-fn powers_to_mpc<'a, P: PairingShare<ark_bls12_377::Bls12_377>>(
-    p: ark_poly_commit::kzg10::Powers<'a, ark_bls12_377::Bls12_377>,
-) -> ark_poly_commit::kzg10::Powers<'a, MpcPairingEngine<ark_bls12_377::Bls12_377, P>>{
-    ark_poly_commit::kzg10::Powers {
+fn powers_to_mpc<'a, S: PairingShare<E>>(p: kzg10::Powers<'a, E>) -> kzg10::Powers<'a, ME<S>> {
+    kzg10::Powers {
         powers_of_g: Cow::Owned(
             p.powers_of_g
                 .iter()
@@ -216,186 +147,423 @@ fn powers_to_mpc<'a, P: PairingShare<ark_bls12_377::Bls12_377>>(
     }
 }
 
+/// KZG parameters for polynomials of degree less than `n`: the powers, and the verifier key.
+fn kzg_setup(n: usize) -> (kzg10::Powers<'static, E>, kzg10::VerifierKey<E>) {
+    let rng = &mut test_rng();
+    let pp = Kzg::setup(std::cmp::max(n, 2) - 1, true, rng).unwrap();
+    let powers = kzg10::Powers {
+        powers_of_g: Cow::Owned(pp.powers_of_g[..n].to_vec()),
+        powers_of_gamma_g: Cow::Owned((0..n).map(|i| pp.powers_of_gamma_g[&i]).collect()),
+    };
+    let vk = kzg10::VerifierKey {
+        g: pp.powers_of_g[0],
+        gamma_g: pp.powers_of_gamma_g[&0],
+        h: pp.h,
+        beta_h: pp.beta_h,
+        prepared_h: pp.prepared_h.clone(),
+        prepared_beta_h: pp.prepared_beta_h.clone(),
+    };
+    (powers, vk)
+}
+
+/// One FRI folding round: `f` on the points `xs` and their negations (in the second half) becomes
+/// `f'(x^2) = (f(x) + f(-x))/2 + alpha (f(x) - f(-x))/2x`.
+fn fri_fold<F: Field>(f: &[F], alpha: F, xs_inv: &[F]) -> Vec<F> {
+    let half = f.len() / 2;
+    let two_inv = F::from(2u64).inverse().unwrap();
+    (0..half)
+        .map(|i| {
+            let (a, b) = (f[i], f[i + half]);
+            ((a + b) + alpha * (a - b) * xs_inv[i]) * two_inv
+        })
+        .collect()
+}
 
-// fn commit_from_mpc<'a>(
-//     p: ark_poly_commit::kzg10::Commitment<hbc::MpcPairingEngine<ark_bls12_377::Bls12_377>>,
-// ) -> ark_poly_commit::kzg10::Commitment<ark_bls12_377::Bls12_377> {
-//     ark_poly_commit::kzg10::Commitment(p.0.reveal())
-// }
-// fn commit_from_mpc_spdz<'a>(
-//     p: ark_poly_commit::kzg10::Commitment<mm::MpcPairingEngine<ark_bls12_377::Bls12_377>>,
-// ) -> ark_poly_commit::kzg10::Commitment<ark_bls12_377::Bls12_377> {
-//     ark_poly_commit::kzg10::Commitment(p.0.reveal())
-// }
-fn pf_from_mpc<'a>(
-    pf: ark_poly_commit::kzg10::Proof<hbc::MpcPairingEngine<ark_bls12_377::Bls12_377>>,
-) -> ark_poly_commit::kzg10::Proof<ark_bls12_377::Bls12_377> {
-    ark_poly_commit::kzg10::Proof {
-        w: pf.w.reveal(),
-        random_v: pf.random_v.map(MpcField::reveal),
+/// `(g^a)^b` for each pair of inputs, with both exponents shared.
+fn dh<G: ProjectiveCurve<ScalarField = Fr>, S: GroupShare<G>>(
+    shares: &[MpcField<Fr, S::FieldShare>],
+    inputs: &[Fr],
+) {
+    let g = G::prime_subgroup_generator();
+    for (s, i) in shares.chunks_exact(2).zip(inputs.chunks_exact(2)) {
+        let ga = MpcGroup::<G, S>::from_public(g) * s[0];
+        let gab = ga * s[1];
+        assert_eq!(gab.reveal(), Group::mul(&g, &(i[0] * i[1])));
     }
 }
 
+/// `e(g1^a, g2^b)`, for shared `a` and `b`.
+fn mpc_pairing<S: PairingShare<E>>(a: MFr<S>, b: MFr<S>) -> <ME<S> as PairingEngine>::Fqk {
+    let g1 = <ME<S> as PairingEngine>::G1Projective::prime_subgroup_generator();
+    let g2 = <ME<S> as PairingEngine>::G2Projective::prime_subgroup_generator();
+    ME::<S>::pairing(Group::mul(&g1, &a), Group::mul(&g2, &b))
+}
+
+/// `e(g1, g2)^(ab)`
+fn pairing_pow(ab: Fr) -> <E as PairingEngine>::Fqk {
+    E::pairing(
+        <E as PairingEngine>::G1Affine::prime_subgroup_generator(),
+        <E as PairingEngine>::G2Affine::prime_subgroup_generator(),
+    )
+    .pow(ab.into_repr())
+}
+
 impl Computation {
-    fn run_gsz(&self, inputs: Vec<MFrg>) -> Vec<MFrg> {
-        let outputs: Vec<MFrg> = match self {
-            Computation::KzgCommit => {
-                //let timer = start_timer!(|| "KZG Commitment");
-                //let ipt_reveal: Vec<Fr> = inputs.iter().map(|x| x.reveal()).collect();
-                //println!("REVEALED VALUES{:?}", ipt_reveal);
-                let poly = MPg::from_coefficients_slice(&inputs);
-                let rng = &mut ark_std::test_rng();
-                let pp = ark_poly_commit::kzg10::KZG10::<
-                    ark_bls12_377::Bls12_377,
-                    ark_poly::univariate::DensePolynomial<ark_bls12_377::Fr>,
-                >::setup(inputs.len() - 1, true, rng)
-                .unwrap();
-                let powers_of_gamma_g = (0..inputs.len())
-                    .map(|i| pp.powers_of_gamma_g[&i])
-                    .collect::<Vec<_>>();
-                let powers = ark_poly_commit::kzg10::Powers::<ark_bls12_377::Bls12_377> {
-                    powers_of_g: Cow::Borrowed(&pp.powers_of_g),
-                    powers_of_gamma_g: Cow::Owned(powers_of_gamma_g),
+    /// Runs this computation on `S`-shares of `inputs`, and checks it against the plain one.
+    fn run<S: PairingShare<E>>(&self, inputs: &[Fr], use_g2: bool) {
+        MFr::<S>::init_protocol();
+        let rng = &mut test_rng();
+        let n = inputs.len();
+        let shares: Vec<MFr<S>> = MFr::<S>::king_share_batch(inputs.to_vec(), rng);
+        let timer = start_timer!(|| format!("{:?}", self));
+        match self {
+            Computation::Fft => {
+                let domain = Radix2EvaluationDomain::<Fr>::new(n).unwrap();
+                assert_eq!(domain.size(), n, "FFT sizes are powers of two");
+                let mpc_domain = Radix2EvaluationDomain::<MFr<S>>::new(n).unwrap();
+                assert_eq!(mpc_domain.fft(&shares).reveal(), domain.fft(inputs));
+            }
+            Computation::Sum => {
+                let sum = shares.iter().fold(MFr::<S>::zero(), |a, b| a + b);
+                assert_eq!(sum.reveal(), inputs.iter().sum());
+            }
+            Computation::Product => {
+                let product = shares.iter().fold(MFr::<S>::one(), |a, b| a * b);
+                assert_eq!(product.reveal(), inputs.iter().product());
+            }
+            Computation::PProduct => {
+                let mut products = shares.clone();
+                MFr::<S>::partial_products_in_place(&mut products);
+                let mut expected = inputs.to_vec();
+                Fr::partial_products_in_place(&mut expected);
+                assert_eq!(products.reveal(), expected);
+            }
+            Computation::Commit => {
+                let (tree, root) = MFr::<S>::commit(&shares);
+                let (v, pf) = MFr::<S>::open_at(&shares, &tree, 0);
+                assert!(MFr::<S>::check_opening(&root, pf, 0, v));
+                assert_eq!(v.reveal(), inputs[0]);
+            }
+            Computation::Merkle => {
+                let (tree, root) = MFr::<S>::commit(&shares);
+                for i in 0..n {
+                    let (v, pf) = MFr::<S>::open_at(&shares, &tree, i);
+                    assert!(MFr::<S>::check_opening(&root, pf, i, v));
+                    assert_eq!(v.reveal(), inputs[i]);
+                }
+            }
+            Computation::Fri => {
+                let domain = Radix2EvaluationDomain::<Fr>::new(n).unwrap();
+                assert!(n >= 2 && domain.size() == n, "FRI sizes are powers of two");
+                let xs_inv: Vec<Fr> = domain
+                    .elements()
+                    .take(n / 2)
+                    .map(|x| x.inverse().unwrap())
+                    .collect();
+                let (tree, root) = MFr::<S>::commit(&shares);
+                let alpha = Fr::rand(rng);
+                let folded = fri_fold(
+                    &shares,
+                    MFr::<S>::from_public(alpha),
+                    &xs_inv
+                        .iter()
+                        .cloned()
+                        .map(MFr::<S>::from_public)
+                        .collect::<Vec<_>>(),
+                );
+                let (folded_tree, folded_root) = MFr::<S>::commit(&folded);
+                // Query a random point: f(x), f(-x), and f'(x^2) must agree.
+                let q = rng.gen_range(0, n / 2);
+                let (fx, pf) = MFr::<S>::open_at(&shares, &tree, q);
+                assert!(MFr::<S>::check_opening(&root, pf, q, fx));
+                let (fnx, pf) = MFr::<S>::open_at(&shares, &tree, q + n / 2);
+                assert!(MFr::<S>::check_opening(&root, pf, q + n / 2, fnx));
+                let (fx2, pf) = MFr::<S>::open_at(&folded, &folded_tree, q);
+                assert!(MFr::<S>::check_opening(&folded_root, pf, q, fx2));
+                let query = [fx.reveal(), fnx.reveal()];
+                assert_eq!(
+                    fri_fold(&query, alpha, &xs_inv[q..q + 1]),
+                    vec![fx2.reveal()]
+                );
+                assert_eq!(folded.reveal(), fri_fold(inputs, alpha, &xs_inv));
+            }
+            Computation::Dh => {
+                if use_g2 {
+                    dh::<<E as PairingEngine>::G2Projective, S::G2ProjectiveShare>(&shares, inputs)
+                } else {
+                    dh::<<E as PairingEngine>::G1Projective, S::G1ProjectiveShare>(&shares, inputs)
+                }
+            }
+            Computation::NaiveMsm | Computation::Msm => {
+                let g = <E as PairingEngine>::G1Projective::prime_subgroup_generator();
+                let bases: Vec<<E as PairingEngine>::G1Affine> = (0..n)
+                    .map(|_| Group::mul(&g, &Fr::rand(rng)).into_affine())
+                    .collect();
+                let repr: Vec<_> = inputs.iter().map(|i| i.into_repr()).collect();
+                let expected = VariableBaseMSM::multi_scalar_mul(&bases, &repr);
+                let mpc_bases: Vec<<ME<S> as PairingEngine>::G1Affine> = bases
+                    .iter()
+                    .cloned()
+                    .map(MpcG1Affine::from_public)
+                    .collect();
+                let msm = if self == &Computation::Msm {
+                    <ME<S> as PairingEngine>::G1Affine::multi_scalar_mul(&mpc_bases, &shares)
+                } else {
+                    mpc_bases
+                        .iter()
+                        .zip(&shares)
+                        .map(|(b, s)| b.scalar_mul(*s))
+                        .fold(<ME<S> as PairingEngine>::G1Projective::zero(), |a, b| a + b)
                 };
-                let mpc_powers = powers_to_mpc::<GszPairingShare<ark_bls12_377::Bls12_377>>(powers);
-                let (commit, rand) =
-                    ark_poly_commit::kzg10::KZG10::commit(&mpc_powers, &poly, None, None).unwrap();
-                //println!("{:?}", commit);
-                // let commit = commit_from_mpc_spdz(commit);
-                // println!("{:?}", commit);
-                //end_timer!(timer);
-                vec![]
+                assert_eq!(msm.reveal(), expected);
             }
-
-            Computation::Msm => {
-                let rng = &mut rand::rngs::StdRng::from_seed([0u8; 32]);
-                let ps: Vec<MFrg> = (0..inputs.len()).map(|_| MFrg::public_rand(rng)).collect();
-                //let sum: MFrg = inputs.iter().zip(ps.iter()).map(|(a, b)| *a * b).sum();
-                let mut public_gens =
-                    vec![<MEg as PairingEngine>::G1Affine::prime_subgroup_generator(); inputs.len()];
-                for (g, c) in public_gens.iter_mut().zip(ps.iter()) {
-                    *g = g.scalar_mul(*c).into();
+            Computation::GroupOps => {
+                let g = <E as PairingEngine>::G1Projective::prime_subgroup_generator();
+                let c = Fr::rand(rng);
+                let points: Vec<MpcGroup<_, S::G1ProjectiveShare>> = shares
+                    .iter()
+                    .map(|s| MpcGroup::from_public(g) * s)
+                    .collect();
+                // Sums, differences, doublings, and public scalings, in turn.
+                let out = points
+                    .iter()
+                    .enumerate()
+                    .fold(MpcGroup::zero(), |acc, (i, p)| match i % 4 {
+                        0 => acc + p,
+                        1 => acc - p,
+                        2 => acc.double() + p,
+                        _ => acc + *p * MpcField::from_public(c),
+                    });
+                let expected =
+                    inputs
+                        .iter()
+                        .enumerate()
+                        .fold(Fr::zero(), |acc, (i, x)| match i % 4 {
+                            0 => acc + x,
+                            1 => acc - x,
+                            2 => acc.double() + x,
+                            _ => acc + *x * c,
+                        });
+                assert_eq!(out.reveal(), Group::mul(&g, &expected));
+            }
+            Computation::PairingDh => {
+                for (s, i) in shares.chunks_exact(2).zip(inputs.chunks_exact(2)) {
+                    let e = mpc_pairing::<S>(s[0], s[1]);
+                    assert_eq!(e.reveal(), pairing_pow(i[0] * i[1]));
                 }
-                let mut msm =
-                    <MEg as PairingEngine>::G1Affine::multi_scalar_mul(&public_gens, &inputs);
-                // let mut expected = <MEg as PairingEngine>::G1Projective::prime_subgroup_generator()
-                //     .scalar_mul(&sum);
-                //msm.publicize();
-                // expected.publicize();
-
-                //println!("{}", msm);
-                // assert_eq!(msm, expected);
-                vec![]
             }
-
-            Computation::Ecdsa => {
-                let rng = &mut test_rng();
-
-                let random_bytes: Vec<Vec<u8>> = (0..inputs.len()).map(|_| (0..16).map(|_| rng.gen()).collect()).collect();
-                let parameters : SPNGParam<Fr> = poseidon_parameters_for_test();
-                for bytes in random_bytes {
-                    ecdsa_gsz(MFrg::king_share(Fr::rand(rng), rng), 
-                                MFrg::king_share(Fr::rand(rng), rng), 
-                                <MEg as PairingEngine>::G1Projective::prime_subgroup_generator(),
-                                 &bytes, parameters.clone());
+            Computation::PairingProd => {
+                let g1 = <ME<S> as PairingEngine>::G1Projective::prime_subgroup_generator();
+                let g2 = <ME<S> as PairingEngine>::G2Projective::prime_subgroup_generator();
+                let pairs: Vec<_> = shares
+                    .chunks_exact(2)
+                    .map(|s| {
+                        (
+                            Group::mul(&g1, &s[0]).into_affine().into(),
+                            Group::mul(&g2, &s[1]).into_affine().into(),
+                        )
+                    })
+                    .collect();
+                let product = ME::<S>::product_of_pairings(&pairs);
+                let exponent = inputs.chunks_exact(2).map(|i| i[0] * i[1]).sum();
+                assert_eq!(product.reveal(), pairing_pow(exponent));
+            }
+            Computation::PairingDiv => {
+                for (s, i) in shares.chunks_exact(4).zip(inputs.chunks_exact(4)) {
+                    let e = mpc_pairing::<S>(s[0], s[1]) / mpc_pairing::<S>(s[2], s[3]);
+                    assert_eq!(e.reveal(), pairing_pow(i[0] * i[1] - i[2] * i[3]));
                 }
-                vec![]
             }
-
-            c => unimplemented!("Cannot run_dh {:?}", c),
-        };
-        // println!("Outputs:");
-        // for (i, v) in outputs.iter().enumerate() {
-        //     println!("  {}: {}", i, v);
-        // }
-        outputs
-    }
-    fn run_spdz(&self, inputs: Vec<MFrs>) -> Vec<MFrs> {
-        let outputs: Vec<MFrs> = match self {
-            Computation::KzgCommit => {
-                //let timer = start_timer!(|| "KZG Commitment");
-                //let ipt_reveal: Vec<Fr> = inputs.iter().map(|x| x.reveal()).collect();
-                //println!("REVEALED VALUES{:?}", ipt_reveal);
-                let poly = MPs::from_coefficients_slice(&inputs);
-                let rng = &mut ark_std::test_rng();
-                let pp = ark_poly_commit::kzg10::KZG10::<
-                    ark_bls12_377::Bls12_377,
-                    ark_poly::univariate::DensePolynomial<ark_bls12_377::Fr>,
-                >::setup(inputs.len() - 1, true, rng)
-                .unwrap();
-                let powers_of_gamma_g = (0..inputs.len())
-                    .map(|i| pp.powers_of_gamma_g[&i])
-                    .collect::<Vec<_>>();
-                let powers = ark_poly_commit::kzg10::Powers::<ark_bls12_377::Bls12_377> {
-                    powers_of_g: Cow::Borrowed(&pp.powers_of_g),
-                    powers_of_gamma_g: Cow::Owned(powers_of_gamma_g),
+            Computation::Groth16 => groth::mpc_test_prove_and_verify::<E, S>(n),
+            Computation::Marlin => marlin::mpc_test_prove_and_verify::<S>(n),
+            Computation::Plonk => plonk::mpc_test_prove_and_verify::<S>(n),
+            Computation::PlonkLookups => plonk::mpc_lookups_prove_and_verify::<S>(n),
+            Computation::PolyEval => {
+                let z = Fr::rand(rng);
+                let poly = MP::<S>::from_coefficients_slice(&shares);
+                let y = poly.evaluate(&MFr::<S>::from_public(z));
+                assert_eq!(y.reveal(), P::from_coefficients_slice(inputs).evaluate(&z));
+            }
+            Computation::PolyDiv => {
+                // Divide by a public (X - z)(X - w).
+                let (z, w) = (Fr::rand(rng), Fr::rand(rng));
+                let den = P::from_coefficients_vec(vec![z * w, -(z + w), Fr::one()]);
+                let mpc_den = MP::<S>::from_coefficients_vec(
+                    den.coeffs
+                        .iter()
+                        .cloned()
+                        .map(MFr::<S>::from_public)
+                        .collect(),
+                );
+                let num = MP::<S>::from_coefficients_slice(&shares);
+                let (q, r) = DenseOrSparsePolynomial::from(&num)
+                    .divide_with_q_and_r(&(&mpc_den).into())
+                    .unwrap();
+                let (eq, er) = DenseOrSparsePolynomial::from(P::from_coefficients_slice(inputs))
+                    .divide_with_q_and_r(&(&den).into())
+                    .unwrap();
+                assert_eq!(q.coeffs.reveal(), eq.coeffs);
+                assert_eq!(r.coeffs.reveal(), er.coeffs);
+            }
+            Computation::Kzg | Computation::KzgCommit => {
+                let (powers, vk) = kzg_setup(n);
+                let mpc_powers = powers_to_mpc::<S>(powers.clone());
+                let poly = MP::<S>::from_coefficients_slice(&shares);
+                let plain = P::from_coefficients_slice(inputs);
+                let (comm, rand) = MpcKzg::<S>::commit(&mpc_powers, &poly, None, None).unwrap();
+                let expected = Kzg::commit(&powers, &plain, None, None).unwrap().0;
+                if self == &Computation::Kzg {
+                    let z = Fr::rand(rng);
+                    let pf = MpcKzg::<S>::open(&mpc_powers, &poly, MFr::<S>::from_public(z), &rand)
+                        .unwrap()
+                        .reveal();
+                    assert!(Kzg::check(&vk, &expected, z, plain.evaluate(&z), &pf).unwrap());
+                }
+                assert_eq!(comm.reveal(), expected);
+            }
+            Computation::KzgZk | Computation::KzgZkBatch => {
+                let (powers, vk) = kzg_setup(n);
+                let mpc_powers = powers_to_mpc::<S>(powers);
+                // The inputs, and the inputs backwards: one polynomial, or two for a batch.
+                let plains: Vec<P> = if self == &Computation::KzgZkBatch {
+                    let backwards: Vec<Fr> = inputs.iter().rev().cloned().collect();
+                    vec![
+                        P::from_coefficients_slice(inputs),
+                        P::from_coefficients_vec(backwards),
+                    ]
+                } else {
+                    vec![P::from_coefficients_slice(inputs)]
                 };
-                let mpc_powers = powers_to_mpc::<SpdzPairingShare<ark_bls12_377::Bls12_377>>(powers);
-                let (commit, rand) =
-                    ark_poly_commit::kzg10::KZG10::commit(&mpc_powers, &poly, None, None).unwrap();
-                //println!("{:?}", commit);
-                // let commit = commit_from_mpc_spdz(commit);
-                // println!("{:?}", commit);
-                //end_timer!(timer);
-                vec![]
+                let mut comms = Vec::new();
+                let mut points = Vec::new();
+                let mut values = Vec::new();
+                let mut pfs = Vec::new();
+                for plain in &plains {
+                    let poly = MP::<S>::from_coefficients_vec(MFr::<S>::king_share_batch(
+                        plain.coeffs.clone(),
+                        rng,
+                    ));
+                    let (comm, rand) =
+                        MpcKzg::<S>::commit(&mpc_powers, &poly, Some(1), Some(rng)).unwrap();
+                    let z = Fr::rand(rng);
+                    let pf = MpcKzg::<S>::open(&mpc_powers, &poly, MFr::<S>::from_public(z), &rand)
+                        .unwrap();
+                    comms.push(comm.reveal());
+                    pfs.push(pf.reveal());
+                    points.push(z);
+                    values.push(plain.evaluate(&z));
+                }
+                if self == &Computation::KzgZkBatch {
+                    assert!(Kzg::batch_check(&vk, &comms, &points, &values, &pfs, rng).unwrap());
+                } else {
+                    assert!(Kzg::check(&vk, &comms[0], points[0], values[0], &pfs[0]).unwrap());
+                }
             }
-
-            Computation::Msm => {
-                let rng = &mut rand::rngs::StdRng::from_seed([0u8; 32]);
-                let ps: Vec<MFrs> = (0..inputs.len()).map(|_| MFrs::public_rand(rng)).collect();
-                // let sum: MFrs = inputs.iter().zip(ps.iter()).map(|(a, b)| *a * b).sum();
-                let mut public_gens =
-                    vec![<MEs as PairingEngine>::G1Affine::prime_subgroup_generator(); inputs.len()];
-                for (g, c) in public_gens.iter_mut().zip(ps.iter()) {
-                    *g = g.scalar_mul(*c).into();
+            Computation::MarlinPc | Computation::PcTwoCom | Computation::MarlinPcBatch => {
+                let two = self != &Computation::MarlinPc;
+                let hiding = if self == &Computation::MarlinPcBatch {
+                    Some(1)
+                } else {
+                    None
+                };
+                let pp = MarlinPc::setup(n, None, rng).unwrap();
+                let (ck, vk) = MarlinPc::trim(&pp, n, 1, None).unwrap();
+                let mpc_ck = Reveal::from_public(ck);
+                let mut plains = vec![P::from_coefficients_slice(inputs)];
+                if two {
+                    plains.push(P::from_coefficients_vec(
+                        inputs.iter().rev().cloned().collect(),
+                    ));
+                }
+                let polys: Vec<_> = plains
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| {
+                        let coeffs = MFr::<S>::king_share_batch(p.coeffs.clone(), rng);
+                        let poly = MP::<S>::from_coefficients_vec(coeffs);
+                        LabeledPolynomial::new(format!("p{}", i), poly, None, hiding)
+                    })
+                    .collect();
+                let (comms, rands) = MpcMarlinPc::<S>::commit(&mpc_ck, &polys, Some(rng)).unwrap();
+                let challenge = Fr::rand(rng);
+                let pub_comms = comms.clone().reveal();
+                if self == &Computation::MarlinPcBatch {
+                    // Each polynomial at its own point, and the first at both.
+                    let points: Vec<Fr> = (0..2).map(|_| Fr::rand(rng)).collect();
+                    let queries = [(0, 0), (0, 1), (1, 1)];
+                    let query_set: QuerySet<Fr> = queries
+                        .iter()
+                        .map(|&(p, z)| (format!("p{}", p), (format!("z{}", z), points[z])))
+                        .collect();
+                    let mpc_query_set: QuerySet<MFr<S>> = query_set
+                        .iter()
+                        .map(|(p, (l, z))| (p.clone(), (l.clone(), MFr::<S>::from_public(*z))))
+                        .collect();
+                    let evals = queries
+                        .iter()
+                        .map(|&(p, z)| {
+                            (
+                                (format!("p{}", p), points[z]),
+                                plains[p].evaluate(&points[z]),
+                            )
+                        })
+                        .collect();
+                    let pf = MpcMarlinPc::<S>::batch_open(
+                        &mpc_ck,
+                        &polys,
+                        &comms,
+                        &mpc_query_set,
+                        MFr::<S>::from_public(challenge),
+                        &rands,
+                        Some(rng),
+                    )
+                    .unwrap()
+                    .reveal();
+                    assert!(MarlinPc::batch_check(
+                        &vk, &pub_comms, &query_set, &evals, &pf, challenge, rng
+                    )
+                    .unwrap());
+                } else {
+                    let z = Fr::rand(rng);
+                    let pf = MpcMarlinPc::<S>::open(
+                        &mpc_ck,
+                        &polys,
+                        &comms,
+                        &MFr::<S>::from_public(z),
+                        MFr::<S>::from_public(challenge),
+                        &rands,
+                        Some(rng),
+                    )
+                    .unwrap()
+                    .reveal();
+                    let values = plains.iter().map(|p| p.evaluate(&z));
+                    assert!(MarlinPc::check(
+                        &vk,
+                        &pub_comms,
+                        &z,
+                        values,
+                        &pf,
+                        challenge,
+                        Some(rng)
+                    )
+                    .unwrap());
                 }
-                let mut msm =
-                    <MEs as PairingEngine>::G1Affine::multi_scalar_mul(&public_gens, &inputs);
-                // let mut expected = <MEs as PairingEngine>::G1Projective::prime_subgroup_generator()
-                //     .scalar_mul(&sum);
-                // msm.publicize();
-                // expected.publicize();
-
-                // //println!("{}", msm);
-                // assert_eq!(msm, expected);
-                vec![]
             }
-
             Computation::Ecdsa => {
-                let rng = &mut test_rng();
-                let random_bytes: Vec<Vec<u8>> = (0..inputs.len()).map(|_| (0..16).map(|_| rng.gen()).collect()).collect();
-                let parameters : SPNGParam<Fr> = poseidon_parameters_for_test();
+                let random_bytes: Vec<Vec<u8>> = (0..n)
+                    .map(|_| (0..16).map(|_| rng.gen()).collect())
+                    .collect();
+                let x = MFr::<S>::king_share(Fr::rand(rng), rng);
                 for bytes in random_bytes {
-                    ecdsa_spdz(MFrs::king_share(Fr::rand(rng), rng), 
-                                MFrs::king_share(Fr::rand(rng), rng), 
-                                <MEs as PairingEngine>::G1Projective::prime_subgroup_generator(),
-                                 &bytes, parameters.clone());
+                    ecdsa::<S>(x, &bytes);
                 }
-                vec![]
             }
-
-
-            c => unimplemented!("Cannot run_dh {:?}", c),
-        };
-        // println!("Outputs:");
-        // for (i, v) in outputs.iter().enumerate() {
-        //     println!("  {}: {}", i, v);
-        // }
-        outputs
+        }
+        end_timer!(timer);
+        MFr::<S>::deinit_protocol();
     }
 }
 
-type E = ark_bls12_377::Bls12_377;
-type MEs = mm::MpcPairingEngine<E>;
-type MEg = hm::MpcPairingEngine<E>;
-type MFrs = mm::MpcField<Fr>;
-type MFrg = hm::MpcField<Fr>;
-type P = ark_poly::univariate::DensePolynomial<Fr>;
-type MPs = ark_poly::univariate::DensePolynomial<MFrs>;
-type MPg = ark_poly::univariate::DensePolynomial<MFrg>;
-trait Pc = ark_poly_commit::PolynomialCommitment<Fr, DensePolynomial<Fr>>;
-type MarlinPc = marlin_pc::MarlinKZG10<E, P>;
-
 fn main() -> () {
     let opt = Opt::from_args();
     if opt.debug {
@@ -405,69 +573,92 @@ fn main() -> () {
     } else {
         env_logger::init();
     }
-    let domain = opt.domain();
-    MpcMultiNet::init_from_file(opt.hosts.to_str().unwrap(), opt.party as usize);
+    MpcMultiNet::try_init_from_file(opt.hosts.to_str().unwrap(), opt.party as usize)
+        .unwrap_or_else(|e| e.exit());
     debug!("Start");
-    if opt.spdz {
-        let mut rng = test_rng();
-        let inputs: Vec<MFrs> = (0..opt.args).map(|_| MFrs::king_share(Fr::rand(&mut rng), &mut rng)).collect();
-        // let inputs = opt
-        //     .args
-        //     .iter()
-        //     .map(|i| mm::MpcField::<Fr>::from_add_shared(Fr::from(*i)))
-        //     .collect::<Vec<_>>();
-        // println!("Inputs:");
-        // for (i, v) in inputs.iter().enumerate() {
-        //     println!("  {}: {}", i, v);
-        // }
-        match domain {
-            ComputationDomain::BlsPairing => {
-                //let timer = start_timer!(|| "spdz for kzg");
-                let mut outputs = opt.computation.run_spdz(inputs);
-                //end_timer!(timer);
-                outputs.iter_mut().for_each(|c| c.publicize());
-                // println!("Public Outputs:");
-                // for (i, v) in outputs.iter().enumerate() {
-                //     println!("  {}: {}", i, v);
-                // }
-            }
-            // ComputationDomain::Group | ComputationDomain::G1 => {
-            //     let generator = mm::MpcGroup::<ark_bls12_377::G1Projective>::from_public(ark_bls12_377::G1Projective::prime_subgroup_generator());
-            //     opt.computation.run_group::<mm::MpcGroup<ark_bls12_377::G1Projective>>(inputs, generator);
-            // }
-            // ComputationDomain::BlsPairing => {
-            //     let mut outputs = opt.computation.run_spdz(inputs);
-            //     outputs.iter_mut().for_each(|c| c.publicize());
-            //     println!("Public Outputs:");
-            //     for (i, v) in outputs.iter().enumerate() {
-            //         println!("  {}: {}", i, v);
-            //     }
-            // }
-            d => panic!("Bad domain: {:?}", d),
-        }
-    } else {
-        let mut rng = test_rng();
-        let inputs: Vec<MFrg> = (0..opt.args).map(|_| MFrg::king_share(Fr::rand(&mut rng), &mut rng)).collect();
-        // println!("Inputs:");
-        // for (i, v) in inputs.iter().enumerate() {
-        //     println!("  {}: {}", i, v);
-        // }
-        match domain {   
-            ComputationDomain::BlsPairing => {
-                //let timer = start_timer!(|| "gsz for kzg");
-                let mut outputs = opt.computation.run_gsz(inputs);
-                //end_timer!(timer);
-                outputs.iter_mut().for_each(|c| c.publicize());
-                // println!("Public Outputs:");
-                // for (i, v) in outputs.iter().enumerate() {
-                //     println!("  {}: {}", i, v);
-                // }
-            }
-            d => panic!("Bad domain: {:?}", d),
-        }
-    }
-
+    mpc_net::catch(|| compute(&opt)).unwrap_or_else(|e| e.exit());
     debug!("Stats: {:#?}", MpcMultiNet::stats());
     MpcMultiNet::deinit();
     debug!("Done");
 }
+
+fn compute(opt: &Opt) {
+    let rng = &mut test_rng();
+    let inputs: Vec<Fr> = (0..opt.args).map(|_| Fr::rand(rng)).collect();
+    match opt.alg {
+        MpcAlg::Spdz => opt
+            .computation
+            .run::<SpdzPairingShare<E>>(&inputs, opt.use_g2),
+        MpcAlg::Hbc => opt
+            .computation
+            .run::<AdditivePairingShare<E>>(&inputs, opt.use_g2),
+        MpcAlg::Gsz => opt
+            .computation
+            .run::<GszPairingShare<E>>(&inputs, opt.use_g2),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mpc_net::LocalSimNet;
+
+    /// Runs `computation` on a few inputs, with three parties in this process.
+    fn run_in_sim<S: PairingShare<E>>(computation: Computation) {
+        let rng = &mut test_rng();
+        let inputs: Vec<Fr> = (0..4).map(|_| Fr::rand(rng)).collect();
+        LocalSimNet::run(3, move |_| computation.run::<S>(&inputs, false));
+    }
+
+    /// A module of tests, one for each computation, that run it on `$share`s.
+    macro_rules! sim_tests {
+        ($alg:ident: $share:ty) => {
+            mod $alg {
+                use super::*;
+                type S = $share;
+                sim_tests!(@tests
+                    fft: Fft,
+                    sum: Sum,
+                    product: Product,
+                    pproduct: PProduct,
+                    commit: Commit,
+                    merkle: Merkle,
+                    fri: Fri,
+                    dh: Dh,
+                    naive_msm: NaiveMsm,
+                    group_ops: GroupOps,
+                    pairing_dh: PairingDh,
+                    pairing_prod: PairingProd,
+                    pairing_div: PairingDiv,
+                    groth16: Groth16,
+                    marlin: Marlin,
+                    poly_eval: PolyEval,
+                    marlin_pc: MarlinPc,
+                    marlin_pc_batch: MarlinPcBatch,
+                    msm: Msm,
+                    kzg: Kzg,
+                    kzg_commit: KzgCommit,
+                    kzg_zk: KzgZk,
+                    kzg_zk_batch: KzgZkBatch,
+                    pc_two_com: PcTwoCom,
+                    plonk: Plonk,
+                    plonk_lookups: PlonkLookups,
+                    poly_div: PolyDiv,
+                    ecdsa: Ecdsa,
+                );
+            }
+        };
+        (@tests $($name:ident: $computation:ident,)*) => {
+            $(
+                #[test]
+                fn $name() {
+                    run_in_sim::<S>(Computation::$computation);
+                }
+            )*
+        };
+    }
+
+    sim_tests!(hbc: AdditivePairingShare<E>);
+    sim_tests!(spdz: SpdzPairingShare<E>);
+    sim_tests!(gsz: GszPairingShare<E>);
+}
@@ -1,18 +1,20 @@
 use super::silly::MySillyCircuit;
 use ark_ec::PairingEngine;
-use ark_groth16::{generate_random_parameters, prepare_verifying_key, verify_proof, ProvingKey};
+use ark_groth16::{prepare_verifying_key, verify_proof, ProvingKey};
 use ark_std::{test_rng, UniformRand};
 use mpc_algebra::*;
 use mpc_algebra::Reveal;
 
 pub mod prover;
 pub mod r1cs_to_qap;
+pub mod setup;
 
 pub fn mpc_test_prove_and_verify<E: PairingEngine, S: PairingShare<E>>(n_iters: usize) {
     let rng = &mut test_rng();
 
     let params =
-        generate_random_parameters::<E, _, _>(MySillyCircuit { a: None, b: None }, rng).unwrap();
+        setup::ceremony::<E, _, _>(MySillyCircuit { a: None, b: None }, &mut rand::thread_rng())
+            .unwrap();
 
     let pvk = prepare_verifying_key::<E>(&params.vk);
     let mpc_params = ProvingKey::from_public(params);
@@ -0,0 +1,557 @@
+//! A Groth16 setup ceremony, with no trusted dealer.
+//!
+//! The parties take turns, in order, as in [BGM17](https://eprint.iacr.org/2017/1050).
+//!
+//! * Phase 1 is powers of tau. The accumulator holds `tau^i` in G1 and G2, `alpha tau^i` and
+//!   `beta tau^i` in G1, and `beta` in G2; each party multiplies its own `tau`, `alpha` and `beta`
+//!   into it.
+//! * Phase 2 is specific to the circuit. The keys are computed from the accumulator in public, with
+//!   `gamma = delta = 1`; each party then multiplies its own `delta` into them.
+//!
+//! Each contribution comes with proofs of knowledge of its secrets, bound to the state that it
+//! updates, and every other party checks it against that state. A contribution that does not
+//! check is a [NetError::Abort], blamed on its author. The keys are ordinary Groth16 keys, and are
+//! sound if any one party drew its secrets honestly and forgot them.
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{Field, One, PrimeField, Zero};
+use ark_groth16::{ProvingKey, VerifyingKey};
+use ark_marlin::rng::FiatShamirRng;
+use ark_poly::{EvaluationDomain, GeneralEvaluationDomain};
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, Matrix, OptimizationGoal,
+    Result as R1CSResult, SynthesisError, SynthesisMode,
+};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use ark_std::{end_timer, start_timer, UniformRand};
+use blake2::Blake2s;
+use mpc_algebra::channel::MpcSerNet;
+use mpc_net::{MpcMultiNet, MpcNet, NetError};
+use rand::{Rng, RngCore};
+use sha2::{Digest, Sha256};
+
+type D<F> = GeneralEvaluationDomain<F>;
+
+/// The powers-of-tau accumulator, for domains of up to `n` elements.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Powers<E: PairingEngine> {
+    /// `tau^i` in G1, for `i < 2n - 1`
+    pub tau_g1: Vec<E::G1Affine>,
+    /// `tau^i` in G2, for `i < n`
+    pub tau_g2: Vec<E::G2Affine>,
+    /// `alpha tau^i` in G1, for `i < n`
+    pub alpha_tau_g1: Vec<E::G1Affine>,
+    /// `beta tau^i` in G1, for `i < n`
+    pub beta_tau_g1: Vec<E::G1Affine>,
+    pub beta_g2: E::G2Affine,
+}
+
+impl<E: PairingEngine> Powers<E> {
+    /// The accumulator before any contribution: every secret is 1.
+    pub fn new(n: usize) -> Self {
+        assert!(n >= 2, "powers of tau need a domain of at least 2 elements");
+        let g1 = E::G1Affine::prime_subgroup_generator();
+        let g2 = E::G2Affine::prime_subgroup_generator();
+        Self {
+            tau_g1: vec![g1; 2 * n - 1],
+            tau_g2: vec![g2; n],
+            alpha_tau_g1: vec![g1; n],
+            beta_tau_g1: vec![g1; n],
+            beta_g2: g2,
+        }
+    }
+
+    /// The largest domain that this accumulator supports
+    pub fn size(&self) -> usize {
+        self.tau_g2.len()
+    }
+}
+
+/// A Schnorr proof of knowledge of `x`, for `public = x g` in G2: a commitment `r = k g` to a
+/// random `k`, and `s = k + e x` for the challenge `e`.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct PoK<E: PairingEngine> {
+    pub public: E::G2Affine,
+    pub r: E::G2Affine,
+    pub s: E::Fr,
+}
+
+impl<E: PairingEngine> PoK<E> {
+    fn new<R: Rng>(x: &E::Fr, context: &[u8], rng: &mut R) -> Self {
+        let g = E::G2Affine::prime_subgroup_generator();
+        let k = E::Fr::rand(rng);
+        let public = g.scalar_mul(*x).into_affine();
+        let r = g.scalar_mul(k).into_affine();
+        let e = Self::challenge(&public, &r, context);
+        Self {
+            public,
+            r,
+            s: k + e * x,
+        }
+    }
+
+    fn verify(&self, context: &[u8]) -> bool {
+        let g = E::G2Affine::prime_subgroup_generator();
+        let e = Self::challenge(&self.public, &self.r, context);
+        !self.public.is_zero()
+            && g.scalar_mul(self.s) == self.public.scalar_mul(e).add_mixed(&self.r)
+    }
+
+    /// `H(tag || public || r || context)`, as a scalar
+    fn challenge(public: &E::G2Affine, r: &E::G2Affine, context: &[u8]) -> E::Fr {
+        let mut bytes = b"groth16 setup pok".to_vec();
+        public.serialize(&mut bytes).unwrap();
+        r.serialize(&mut bytes).unwrap();
+        bytes.extend_from_slice(context);
+        E::Fr::from_le_bytes_mod_order(&Sha256::digest(&bytes))
+    }
+}
+
+/// A party's turn in phase 1: the new accumulator, and proofs of knowledge of its secrets.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Phase1Contribution<E: PairingEngine> {
+    pub powers: Powers<E>,
+    pub tau: PoK<E>,
+    pub alpha: PoK<E>,
+    pub beta: PoK<E>,
+}
+
+/// A party's turn in phase 2: the parts of the proving key that `delta` changes, and a proof of
+/// knowledge of `delta`.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Phase2Contribution<E: PairingEngine> {
+    pub delta_g1: E::G1Affine,
+    pub delta_g2: E::G2Affine,
+    pub h_query: Vec<E::G1Affine>,
+    pub l_query: Vec<E::G1Affine>,
+    pub delta: PoK<E>,
+}
+
+/// Runs the whole ceremony for `circuit`: powers of tau for its domain, then phase 2.
+///
+/// Each party draws its secrets from `rng`, which must be its own and unpredictable.
+pub fn ceremony<E, C, R>(circuit: C, rng: &mut R) -> R1CSResult<ProvingKey<E>>
+where
+    E: PairingEngine,
+    C: ConstraintSynthesizer<E::Fr>,
+    R: Rng,
+{
+    let cs = synthesize(circuit)?;
+    let domain = D::<E::Fr>::new(cs.num_constraints() + cs.num_instance_variables())
+        .ok_or(SynthesisError::PolynomialDegreeTooLarge)?;
+    let powers = powers_of_tau::<E, R>(domain.size().max(2), rng);
+    phase2(cs, &powers, rng)
+}
+
+/// Phase 1, for domains of up to `n` elements.
+pub fn powers_of_tau<E: PairingEngine, R: Rng>(n: usize, rng: &mut R) -> Powers<E> {
+    let _op = mpc_net::op("powers_of_tau");
+    let timer = start_timer!(|| "Powers of tau");
+    let mut powers = Powers::<E>::new(n);
+    let mut transcript = hash(&powers);
+    for party in 0..MpcMultiNet::n_parties() {
+        let mine = if party == MpcMultiNet::party_id() {
+            Some(contribute_phase1(&powers, &transcript, party, rng))
+        } else {
+            None
+        };
+        let contribution = take_turn(party, mine);
+        let next_transcript = hash(&contribution);
+        if party != MpcMultiNet::party_id() {
+            verify_phase1(&powers, &contribution, &transcript, &next_transcript, party)
+                .unwrap_or_else(|reason| blame(party, "powers of tau", reason));
+        }
+        powers = contribution.powers;
+        transcript = next_transcript;
+    }
+    end_timer!(timer);
+    powers
+}
+
+/// Phase 2: the keys for the synthesized circuit `cs`, from the accumulator `powers`.
+pub fn phase2<E: PairingEngine, R: Rng>(
+    cs: ConstraintSystemRef<E::Fr>,
+    powers: &Powers<E>,
+    rng: &mut R,
+) -> R1CSResult<ProvingKey<E>> {
+    let _op = mpc_net::op("phase2");
+    let timer = start_timer!(|| "Groth16 phase 2");
+    let mut pk = keys_from_powers(cs, powers)?;
+    let mut transcript = hash(&pk);
+    for party in 0..MpcMultiNet::n_parties() {
+        let mine = if party == MpcMultiNet::party_id() {
+            Some(contribute_phase2(&pk, &transcript, party, rng))
+        } else {
+            None
+        };
+        let contribution = take_turn(party, mine);
+        let next_transcript = hash(&contribution);
+        if party != MpcMultiNet::party_id() {
+            verify_phase2(&pk, &contribution, &transcript, &next_transcript, party)
+                .unwrap_or_else(|reason| blame(party, "phase 2", reason));
+        }
+        pk.delta_g1 = contribution.delta_g1;
+        pk.vk.delta_g2 = contribution.delta_g2;
+        pk.h_query = contribution.h_query;
+        pk.l_query = contribution.l_query;
+        transcript = next_transcript;
+    }
+    end_timer!(timer);
+    Ok(pk)
+}
+
+fn synthesize<F: Field, C: ConstraintSynthesizer<F>>(
+    circuit: C,
+) -> R1CSResult<ConstraintSystemRef<F>> {
+    let cs = ConstraintSystem::new_ref();
+    cs.set_optimization_goal(OptimizationGoal::Constraints);
+    cs.set_mode(SynthesisMode::Setup);
+    circuit.generate_constraints(cs.clone())?;
+    cs.finalize();
+    Ok(cs)
+}
+
+/// The keys for `cs`, with `gamma = delta = 1`, computed in public from the accumulator.
+pub fn keys_from_powers<E: PairingEngine>(
+    cs: ConstraintSystemRef<E::Fr>,
+    powers: &Powers<E>,
+) -> R1CSResult<ProvingKey<E>> {
+    let timer = start_timer!(|| "Keys from powers");
+    let num_constraints = cs.num_constraints();
+    let num_inputs = cs.num_instance_variables();
+    let num_vars = num_inputs + cs.num_witness_variables();
+    let domain = D::<E::Fr>::new(num_constraints + num_inputs)
+        .ok_or(SynthesisError::PolynomialDegreeTooLarge)?;
+    let m = domain.size();
+    if m > powers.size() {
+        return Err(SynthesisError::PolynomialDegreeTooLarge);
+    }
+
+    let l_g1 = lagrange_at_tau(&domain, &powers.tau_g1[..m]);
+    let l_g2 = lagrange_at_tau(&domain, &powers.tau_g2[..m]);
+    let alpha_l = lagrange_at_tau(&domain, &powers.alpha_tau_g1[..m]);
+    let beta_l = lagrange_at_tau(&domain, &powers.beta_tau_g1[..m]);
+
+    let matrices = cs.to_matrices().unwrap();
+    let mut a_cols = columns(&matrices.a, num_vars);
+    let b_cols = columns(&matrices.b, num_vars);
+    let c_cols = columns(&matrices.c, num_vars);
+    // As in the QAP reduction, each input also has a row of its own in A.
+    for (k, (rows, coeffs)) in a_cols.iter_mut().take(num_inputs).enumerate() {
+        rows.push(num_constraints + k);
+        coeffs.push(E::Fr::one());
+    }
+    let a = column_msms(&a_cols, &l_g1);
+    let b_g1 = column_msms(&b_cols, &l_g1);
+    let b_g2 = column_msms(&b_cols, &l_g2);
+    // beta A + alpha B + C
+    let abc: Vec<E::G1Projective> = column_msms(&a_cols, &beta_l)
+        .into_iter()
+        .zip(column_msms(&b_cols, &alpha_l))
+        .zip(column_msms(&c_cols, &l_g1))
+        .map(|((a, b), c)| a + b + c)
+        .collect();
+
+    // Z(tau) tau^i, where Z(X) = X^m - 1 as the domain is a subgroup
+    let h: Vec<E::G1Projective> = (0..m - 1)
+        .map(|i| powers.tau_g1[i + m].into_projective() - powers.tau_g1[i].into_projective())
+        .collect();
+
+    let g1 = E::G1Affine::prime_subgroup_generator();
+    let g2 = E::G2Affine::prime_subgroup_generator();
+    let vk = VerifyingKey {
+        alpha_g1: powers.alpha_tau_g1[0],
+        beta_g2: powers.beta_g2,
+        gamma_g2: g2,
+        delta_g2: g2,
+        gamma_abc_g1: E::G1Projective::batch_normalization_into_affine(&abc[..num_inputs]),
+    };
+    end_timer!(timer);
+    Ok(ProvingKey {
+        vk,
+        beta_g1: powers.beta_tau_g1[0],
+        delta_g1: g1,
+        a_query: E::G1Projective::batch_normalization_into_affine(&a),
+        b_g1_query: E::G1Projective::batch_normalization_into_affine(&b_g1),
+        b_g2_query: E::G2Projective::batch_normalization_into_affine(&b_g2),
+        h_query: E::G1Projective::batch_normalization_into_affine(&h),
+        l_query: E::G1Projective::batch_normalization_into_affine(&abc[num_inputs..]),
+    })
+}
+
+/// The Lagrange polynomials of `domain`, at `tau`, from `tau^i` for `i < |domain|`.
+fn lagrange_at_tau<G: AffineCurve>(domain: &D<G::ScalarField>, powers: &[G]) -> Vec<G> {
+    let l = domain.ifft(
+        &powers
+            .iter()
+            .map(|p| p.into_projective())
+            .collect::<Vec<_>>(),
+    );
+    G::Projective::batch_normalization_into_affine(&l)
+}
+
+/// The columns of the sparse matrix `m`, over `n` variables, as rows and coefficients.
+fn columns<F: Field>(m: &Matrix<F>, n: usize) -> Vec<(Vec<usize>, Vec<F>)> {
+    let mut cols = vec![(Vec::new(), Vec::new()); n];
+    for (i, row) in m.iter().enumerate() {
+        for (coeff, j) in row {
+            cols[*j].0.push(i);
+            cols[*j].1.push(*coeff);
+        }
+    }
+    cols
+}
+
+/// `sum_i coeffs[i] bases[rows[i]]` for each column, with one MSM per column.
+fn column_msms<G: AffineCurve>(
+    cols: &[(Vec<usize>, Vec<G::ScalarField>)],
+    bases: &[G],
+) -> Vec<G::Projective> {
+    cols.iter()
+        .map(|(rows, coeffs)| {
+            let col_bases: Vec<G> = rows.iter().map(|i| bases[*i]).collect();
+            G::multi_scalar_mul(&col_bases, coeffs)
+        })
+        .collect()
+}
+
+fn contribute_phase1<E: PairingEngine, R: Rng>(
+    old: &Powers<E>,
+    transcript: &[u8],
+    party: usize,
+    rng: &mut R,
+) -> Phase1Contribution<E> {
+    let tau = nonzero_rand::<E::Fr, R>(rng);
+    let alpha = nonzero_rand::<E::Fr, R>(rng);
+    let beta = nonzero_rand::<E::Fr, R>(rng);
+    let powers = Powers {
+        tau_g1: scale_powers(&old.tau_g1, E::Fr::one(), tau),
+        tau_g2: scale_powers(&old.tau_g2, E::Fr::one(), tau),
+        alpha_tau_g1: scale_powers(&old.alpha_tau_g1, alpha, tau),
+        beta_tau_g1: scale_powers(&old.beta_tau_g1, beta, tau),
+        beta_g2: old.beta_g2.scalar_mul(beta).into_affine(),
+    };
+    Phase1Contribution {
+        powers,
+        tau: PoK::new(&tau, &context(transcript, party, "tau"), rng),
+        alpha: PoK::new(&alpha, &context(transcript, party, "alpha"), rng),
+        beta: PoK::new(&beta, &context(transcript, party, "beta"), rng),
+    }
+}
+
+/// Checks that `new` updates `old` by the secrets that it proves knowledge of. The checks draw
+/// their randomness from `seed`, a hash of `new`.
+fn verify_phase1<E: PairingEngine>(
+    old: &Powers<E>,
+    new: &Phase1Contribution<E>,
+    transcript: &[u8],
+    seed: &[u8],
+    party: usize,
+) -> Result<(), &'static str> {
+    let p = &new.powers;
+    if p.tau_g1.len() != old.tau_g1.len()
+        || p.tau_g2.len() != old.tau_g2.len()
+        || p.alpha_tau_g1.len() != old.alpha_tau_g1.len()
+        || p.beta_tau_g1.len() != old.beta_tau_g1.len()
+    {
+        return Err("wrong number of powers");
+    }
+    let g1 = E::G1Affine::prime_subgroup_generator();
+    let g2 = E::G2Affine::prime_subgroup_generator();
+    if p.tau_g1[0] != g1 || p.tau_g2[0] != g2 {
+        return Err("moved the generators");
+    }
+    for (pok, secret) in &[
+        (&new.tau, "tau"),
+        (&new.alpha, "alpha"),
+        (&new.beta, "beta"),
+    ] {
+        if !pok.verify(&context(transcript, party, secret)) {
+            return Err("bad proof of knowledge");
+        }
+    }
+
+    // Each secret was multiplied into the old accumulator
+    if E::pairing(p.tau_g1[1], g2) != E::pairing(old.tau_g1[1], new.tau.public)
+        || E::pairing(p.alpha_tau_g1[0], g2) != E::pairing(old.alpha_tau_g1[0], new.alpha.public)
+        || E::pairing(p.beta_tau_g1[0], g2) != E::pairing(old.beta_tau_g1[0], new.beta.public)
+    {
+        return Err("update does not match its proofs of knowledge");
+    }
+    if E::pairing(p.beta_tau_g1[0], g2) != E::pairing(g1, p.beta_g2) {
+        return Err("inconsistent beta");
+    }
+
+    // And the rest are its powers
+    let rng = &mut FiatShamirRng::<Blake2s>::from_seed(&seed.to_vec());
+    let tau_g1 = p.tau_g1[1];
+    let tau_g2 = p.tau_g2[1];
+    for xs in &[&p.tau_g1, &p.alpha_tau_g1, &p.beta_tau_g1] {
+        let (x, x_next) = shifted_combinations(xs, rng);
+        if E::pairing(x_next, g2) != E::pairing(x, tau_g2) {
+            return Err("G1 powers are not powers of tau");
+        }
+    }
+    let (y, y_next) = shifted_combinations(&p.tau_g2, rng);
+    if E::pairing(tau_g1, y) != E::pairing(g1, y_next) {
+        return Err("G2 powers are not powers of tau");
+    }
+    Ok(())
+}
+
+fn contribute_phase2<E: PairingEngine, R: Rng>(
+    pk: &ProvingKey<E>,
+    transcript: &[u8],
+    party: usize,
+    rng: &mut R,
+) -> Phase2Contribution<E> {
+    let delta = nonzero_rand::<E::Fr, R>(rng);
+    let delta_inv = delta.inverse().unwrap();
+    Phase2Contribution {
+        delta_g1: pk.delta_g1.scalar_mul(delta).into_affine(),
+        delta_g2: pk.vk.delta_g2.scalar_mul(delta).into_affine(),
+        h_query: scale_powers(&pk.h_query, delta_inv, E::Fr::one()),
+        l_query: scale_powers(&pk.l_query, delta_inv, E::Fr::one()),
+        delta: PoK::new(&delta, &context(transcript, party, "delta"), rng),
+    }
+}
+
+/// Checks that `new` multiplies `delta` into `pk`, and divides the H and L queries by it, for the
+/// `delta` that it proves knowledge of. The checks draw their randomness from `seed`, a hash of
+/// `new`.
+fn verify_phase2<E: PairingEngine>(
+    pk: &ProvingKey<E>,
+    new: &Phase2Contribution<E>,
+    transcript: &[u8],
+    seed: &[u8],
+    party: usize,
+) -> Result<(), &'static str> {
+    if new.h_query.len() != pk.h_query.len() || new.l_query.len() != pk.l_query.len() {
+        return Err("wrong number of queries");
+    }
+    if !new.delta.verify(&context(transcript, party, "delta")) {
+        return Err("bad proof of knowledge");
+    }
+    let g1 = E::G1Affine::prime_subgroup_generator();
+    let g2 = E::G2Affine::prime_subgroup_generator();
+    if E::pairing(new.delta_g1, g2) != E::pairing(pk.delta_g1, new.delta.public) {
+        return Err("update does not match its proof of knowledge");
+    }
+    if E::pairing(new.delta_g1, g2) != E::pairing(g1, new.delta_g2) {
+        return Err("inconsistent delta");
+    }
+    let rng = &mut FiatShamirRng::<Blake2s>::from_seed(&seed.to_vec());
+    let old_queries = [&pk.h_query[..], &pk.l_query[..]].concat();
+    let new_queries = [&new.h_query[..], &new.l_query[..]].concat();
+    let rs: Vec<E::Fr> = (0..old_queries.len()).map(|_| E::Fr::rand(rng)).collect();
+    let old_sum = E::G1Affine::multi_scalar_mul(&old_queries, &rs);
+    let new_sum = E::G1Affine::multi_scalar_mul(&new_queries, &rs);
+    if E::pairing(new_sum, new.delta_g2) != E::pairing(old_sum, pk.vk.delta_g2) {
+        return Err("queries not divided by delta");
+    }
+    Ok(())
+}
+
+/// `party`'s turn: it broadcasts its contribution, `mine`, and everyone gets it.
+fn take_turn<T: CanonicalSerialize + CanonicalDeserialize>(party: usize, mine: Option<T>) -> T {
+    MpcMultiNet::broadcast(&mine)
+        .swap_remove(party)
+        .unwrap_or_else(|| blame(party, "setup", "no contribution"))
+}
+
+fn blame(party: usize, phase: &str, reason: &str) -> ! {
+    NetError::Abort {
+        party,
+        reason: format!("bad {} contribution: {}", phase, reason),
+    }
+    .raise()
+}
+
+/// What `party`'s proof of knowledge of `secret` is bound to: the transcript so far.
+fn context(transcript: &[u8], party: usize, secret: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(secret.as_bytes());
+    bytes.extend_from_slice(&(party as u64).to_le_bytes());
+    bytes.extend_from_slice(transcript);
+    bytes
+}
+
+fn hash<T: CanonicalSerialize>(x: &T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    x.serialize(&mut bytes).unwrap();
+    Sha256::digest(&bytes).to_vec()
+}
+
+fn nonzero_rand<F: Field, R: Rng>(rng: &mut R) -> F {
+    loop {
+        let x = F::rand(rng);
+        if !x.is_zero() {
+            return x;
+        }
+    }
+}
+
+/// `xs[i] s r^i`
+fn scale_powers<G: AffineCurve>(xs: &[G], s: G::ScalarField, r: G::ScalarField) -> Vec<G> {
+    let mut s_i = s;
+    let scaled: Vec<G::Projective> = xs
+        .iter()
+        .map(|x| {
+            let y = x.scalar_mul(s_i);
+            s_i *= r;
+            y
+        })
+        .collect();
+    G::Projective::batch_normalization_into_affine(&scaled)
+}
+
+/// `sum_i r_i xs[i]` and `sum_i r_i xs[i + 1]`, for random `r_i`. If the second is the first
+/// times `t`, then, but for a negligible chance, each `xs[i + 1]` is `xs[i]` times `t`.
+fn shifted_combinations<G: AffineCurve, R: RngCore>(
+    xs: &[G],
+    rng: &mut R,
+) -> (G::Projective, G::Projective) {
+    let rs: Vec<G::ScalarField> = (1..xs.len()).map(|_| G::ScalarField::rand(rng)).collect();
+    (
+        G::multi_scalar_mul(&xs[..xs.len() - 1], &rs),
+        G::multi_scalar_mul(&xs[1..], &rs),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_377::Bls12_377 as E;
+    use ark_std::test_rng;
+
+    #[test]
+    fn phase1_checks() {
+        let rng = &mut test_rng();
+        let old = Powers::<E>::new(4);
+        let transcript = hash(&old);
+        let good = contribute_phase1(&old, &transcript, 1, rng);
+        assert!(verify_phase1(&old, &good, &transcript, &hash(&good), 1).is_ok());
+        // Bound to its author
+        assert!(verify_phase1(&old, &good, &transcript, &hash(&good), 2).is_err());
+
+        let mut bad = good.clone();
+        bad.powers.tau_g1[3] = bad.powers.tau_g1[2];
+        assert!(verify_phase1(&old, &bad, &transcript, &hash(&bad), 1).is_err());
+        let mut bad = good.clone();
+        bad.powers.alpha_tau_g1 = old.alpha_tau_g1.clone();
+        assert!(verify_phase1(&old, &bad, &transcript, &hash(&bad), 1).is_err());
+    }
+
+    #[test]
+    fn phase2_checks() {
+        let rng = &mut test_rng();
+        let cs = synthesize(crate::silly::MySillyCircuit { a: None, b: None }).unwrap();
+        let pk = keys_from_powers(cs, &Powers::<E>::new(16)).unwrap();
+        let transcript = hash(&pk);
+        let good = contribute_phase2(&pk, &transcript, 0, rng);
+        assert!(verify_phase2(&pk, &good, &transcript, &hash(&good), 0).is_ok());
+
+        let mut bad = good.clone();
+        bad.l_query = pk.l_query.clone();
+        assert!(verify_phase2(&pk, &bad, &transcript, &hash(&bad), 0).is_err());
+    }
+}
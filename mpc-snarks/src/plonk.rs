@@ -1,20 +1,18 @@
-use ark_ff::UniformRand;
+use ark_ff::{Field, UniformRand};
 use ark_poly::univariate::DensePolynomial;
 use ark_poly_commit::marlin_pc::MarlinKZG10;
-use ark_std::{end_timer, start_timer, test_rng};
-use mpc_algebra::honest_but_curious::*;
+use ark_std::{end_timer, rand::Rng, start_timer, test_rng};
+use mpc_algebra::{MpcField, MpcPairingEngine, PairingShare, Reveal};
 use mpc_plonk::*;
-use mpc_algebra::Reveal;
 use std::collections::HashMap;
 
 type F = ark_bls12_377::Fr;
 type E = ark_bls12_377::Bls12_377;
-type ME = MpcPairingEngine<ark_bls12_377::Bls12_377>;
-type MF = MpcField<F>;
-type MpcMarlinKZG10 = MarlinKZG10<ME, DensePolynomial<MF>>;
+type MF<S> = MpcField<F, <S as PairingShare<E>>::FrShare>;
+type MpcMarlinKZG10<S> = MarlinKZG10<MpcPairingEngine<E, S>, DensePolynomial<MF<S>>>;
 type LocalMarlinKZG10 = MarlinKZG10<E, DensePolynomial<F>>;
 type LocalPlonk = mpc_plonk::Plonk<F, LocalMarlinKZG10>;
-type MpcPlonk = mpc_plonk::Plonk<MF, MpcMarlinKZG10>;
+type MpcPlonk<S> = mpc_plonk::Plonk<MF<S>, MpcMarlinKZG10<S>>;
 
 pub fn local_test_prove_and_verify(n_iters: usize) {
     use relations::{flat::*, structured::*};
@@ -37,10 +35,10 @@ pub fn local_test_prove_and_verify(n_iters: usize) {
     let srs = LocalPlonk::universal_setup(steps.next_power_of_two(), setup_rng);
     let (pk, vk) = LocalPlonk::circuit_setup(&srs, &v_circ);
     let pf = LocalPlonk::prove(&pk, &circ, zk_rng);
-    LocalPlonk::verify(&vk, &v_circ, pf, &public);
+    assert!(LocalPlonk::verify(&vk, &v_circ, pf, &public).unwrap());
 }
 
-pub fn mpc_test_prove_and_verify(n_iters: usize) {
+pub fn mpc_test_prove_and_verify<S: PairingShare<E>>(n_iters: usize) {
     use relations::{flat::*, structured::*};
     let steps = n_iters;
 
@@ -54,18 +52,78 @@ pub fn mpc_test_prove_and_verify(n_iters: usize) {
 
     // data circuit
     let data_rng = &mut test_rng();
-    let start = MF::rand(data_rng);
+    let start = MF::<S>::king_share(F::rand(data_rng), data_rng);
     let res = (0..steps).fold(start, |a, _| a * a);
     let public: HashMap<String, F> = vec![("out".to_owned(), res.reveal())]
         .into_iter()
         .collect();
-    let c = PlonkCircuit::<MF>::new_squaring_circuit(steps, Some(start));
+    let c = PlonkCircuit::<MF<S>>::new_squaring_circuit(steps, Some(start));
     let circ = CircuitLayout::from_circuit(&c);
 
     let t = start_timer!(|| "timed section");
     let mpc_pk = ProverKey::from_public(pk);
-    let mpc_pf = MpcPlonk::prove(&mpc_pk, &circ, &mut test_rng());
+    let mpc_pf = MpcPlonk::<S>::prove(&mpc_pk, &circ, &mut rand::thread_rng());
     let pf = mpc_pf.reveal();
     end_timer!(t);
-    LocalPlonk::verify(&vk, &v_circ, pf, &public);
+    assert!(LocalPlonk::verify(&vk, &v_circ, pf, &public).unwrap());
+}
+
+/// `n` range-checked 4-bit pairs, and the sum of their XORs as the output.
+fn xor_circuit<Fr: Field>(
+    n: usize,
+    values: Option<&[[Fr; 3]]>,
+) -> relations::structured::PlonkCircuit<Fr> {
+    use relations::structured::*;
+    let mut c = PlonkCircuit::new(values.is_some());
+    let range = c.new_range_table(4);
+    let xor = c.new_xor_table(4);
+    let mut out = c.new_const(Fr::zero());
+    for i in 0..n {
+        let x = c.new_var(|| values.unwrap()[i][0]);
+        let y = c.new_var(|| values.unwrap()[i][1]);
+        c.lookup(range, x, x, x);
+        c.lookup(range, y, y, y);
+        let z = c.new_lookup(xor, x, y, || values.unwrap()[i][2]);
+        out = c.new_sum(out, z);
+    }
+    c.pad_to_power_of_2();
+    c.publicize_var(out, "out".to_owned());
+    c
+}
+
+/// Proves `n_lookups` range-checked XORs over shared wires, and verifies the proof.
+pub fn mpc_lookups_prove_and_verify<S: PairingShare<E>>(n_lookups: usize) {
+    use relations::flat::*;
+
+    // empty circuit
+    let v_c = xor_circuit::<F>(n_lookups, None);
+    let v_circ = CircuitLayout::from_circuit(&v_c);
+    // setup
+    let setup_rng = &mut test_rng();
+    let srs = LocalPlonk::universal_setup(v_c.n_gates(), setup_rng);
+    let (pk, vk) = LocalPlonk::circuit_setup(&srs, &v_circ);
+
+    // data circuit: the lookups' wires are shared, and sorted without opening them
+    let data_rng = &mut test_rng();
+    let values: Vec<F> = (0..n_lookups)
+        .flat_map(|_| {
+            let (x, y) = (data_rng.gen_range(0u64, 16), data_rng.gen_range(0u64, 16));
+            vec![F::from(x), F::from(y), F::from(x ^ y)]
+        })
+        .collect();
+    let out: F = values.chunks_exact(3).map(|v| v[2]).sum();
+    let public: HashMap<String, F> = vec![("out".to_owned(), out)].into_iter().collect();
+    let mpc_values: Vec<[MF<S>; 3]> = MF::<S>::king_share_batch(values, data_rng)
+        .chunks_exact(3)
+        .map(|v| [v[0], v[1], v[2]])
+        .collect();
+    let c = xor_circuit(n_lookups, Some(&mpc_values));
+    let circ = CircuitLayout::from_circuit(&c);
+
+    let t = start_timer!(|| "timed section");
+    let mpc_pk = ProverKey::from_public(pk);
+    let mpc_pf = MpcPlonk::<S>::prove(&mpc_pk, &circ, &mut rand::thread_rng());
+    let pf = mpc_pf.reveal();
+    end_timer!(t);
+    assert!(LocalPlonk::verify(&vk, &v_circ, pf, &public).unwrap());
 }
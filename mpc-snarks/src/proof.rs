@@ -12,11 +12,12 @@ use ark_std::{end_timer, start_timer};
 use blake2::Blake2s;
 use clap::arg_enum;
 use log::debug;
+use mpc_algebra::share::spdz::{SpdzFieldShare, SpdzPairingShare};
 use mpc_algebra::{channel, MpcPairingEngine, PairingShare, Reveal};
 use mpc_net::{MpcMultiNet, MpcNet, MpcTwoNet};
 use structopt::StructOpt;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 mod groth;
 mod marlin;
@@ -24,12 +25,17 @@ mod silly;
 
 const TIMED_SECTION_LABEL: &str = "timed section";
 
+/// Benchmarks write their proof and verifier key to `out_dir`, if given and supported.
 trait SnarkBench {
-    fn local<E: PairingEngine>(n: usize, timer_label: &str);
-    fn ark_local<E: PairingEngine>(_n: usize, _timer_label: &str) {
+    fn local<E: PairingEngine>(n: usize, timer_label: &str, out_dir: Option<&Path>);
+    fn ark_local<E: PairingEngine>(_n: usize, _timer_label: &str, _out_dir: Option<&Path>) {
         unimplemented!("ark benchmark for {}", std::any::type_name::<Self>())
     }
-    fn mpc<E: PairingEngine, S: PairingShare<E>>(n: usize, timer_label: &str);
+    fn mpc<E: PairingEngine, S: PairingShare<E>>(
+        n: usize,
+        timer_label: &str,
+        out_dir: Option<&Path>,
+    );
 }
 
 mod squarings {
@@ -68,11 +74,12 @@ mod squarings {
         use super::*;
         use crate::ark_groth16::{generate_random_parameters, prepare_verifying_key, verify_proof};
         use crate::groth::prover::create_random_proof;
+        use crate::groth::setup::ceremony;
 
         pub struct Groth16Bench;
 
         impl SnarkBench for Groth16Bench {
-            fn local<E: PairingEngine>(n: usize, timer_label: &str) {
+            fn local<E: PairingEngine>(n: usize, timer_label: &str, _out_dir: Option<&Path>) {
                 let rng = &mut test_rng();
                 let circ_no_data = RepeatedSquaringCircuit::without_data(n);
 
@@ -90,7 +97,7 @@ mod squarings {
                 assert!(verify_proof(&pvk, &proof, &public_inputs).unwrap());
             }
 
-            fn ark_local<E: PairingEngine>(n: usize, timer_label: &str) {
+            fn ark_local<E: PairingEngine>(n: usize, timer_label: &str, _out_dir: Option<&Path>) {
                 let rng = &mut test_rng();
                 let circ_no_data = RepeatedSquaringCircuit::without_data(n);
 
@@ -109,11 +116,15 @@ mod squarings {
                 assert!(verify_proof(&pvk, &proof, &public_inputs).unwrap());
             }
 
-            fn mpc<E: PairingEngine, S: PairingShare<E>>(n: usize, timer_label: &str) {
+            fn mpc<E: PairingEngine, S: PairingShare<E>>(
+                n: usize,
+                timer_label: &str,
+                _out_dir: Option<&Path>,
+            ) {
                 let rng = &mut test_rng();
                 let circ_no_data = RepeatedSquaringCircuit::without_data(n);
 
-                let params = generate_random_parameters::<E, _, _>(circ_no_data, rng).unwrap();
+                let params = ceremony::<E, _, _>(circ_no_data, &mut rand::thread_rng()).unwrap();
 
                 let pvk = prepare_verifying_key::<E>(&params.vk);
                 let mpc_params = Reveal::from_public(params);
@@ -129,9 +140,13 @@ mod squarings {
                 MpcMultiNet::reset_stats();
                 let timer = start_timer!(|| timer_label);
                 let proof = channel::without_cheating(|| {
-                    let pf = create_random_proof::<MpcPairingEngine<E, S>, _, _>(circ_data, &mpc_params, rng)
-                        .unwrap();
+                    let pf = {
+                        let _op = mpc_net::op("create_random_proof");
+                        create_random_proof::<MpcPairingEngine<E, S>, _, _>(circ_data, &mpc_params, rng)
+                            .unwrap()
+                    };
                     let reveal_timer = start_timer!(|| "reveal");
+                    let _op = mpc_net::op("reveal");
                     let pf = pf.reveal();
                     end_timer!(reveal_timer);
                     pf
@@ -155,7 +170,7 @@ mod squarings {
         pub struct MarlinBench;
 
         impl SnarkBench for MarlinBench {
-            fn local<E: PairingEngine>(n: usize, timer_label: &str) {
+            fn local<E: PairingEngine>(n: usize, timer_label: &str, _out_dir: Option<&Path>) {
                 let rng = &mut test_rng();
                 let circ_no_data = RepeatedSquaringCircuit::without_data(n);
 
@@ -173,7 +188,11 @@ mod squarings {
                 assert!(KzgMarlin::<E::Fr, E>::verify(&vk, &public_inputs, &proof, rng).unwrap());
             }
 
-            fn mpc<E: PairingEngine, S: PairingShare<E>>(n: usize, timer_label: &str) {
+            fn mpc<E: PairingEngine, S: PairingShare<E>>(
+                n: usize,
+                timer_label: &str,
+                _out_dir: Option<&Path>,
+            ) {
                 let rng = &mut test_rng();
                 let circ_no_data = RepeatedSquaringCircuit::without_data(n);
 
@@ -195,12 +214,16 @@ mod squarings {
                 let timer = start_timer!(|| timer_label);
                 let zk_rng = &mut test_rng();
                 let proof = channel::without_cheating(|| {
-                    KzgMarlin::<
-                        <MpcPairingEngine<E, S> as PairingEngine>::Fr,
-                        MpcPairingEngine<E, S>,
-                    >::prove(&mpc_pk, circ_data, zk_rng)
-                    .unwrap()
-                    .reveal()
+                    let pf = {
+                        let _op = mpc_net::op("prove");
+                        KzgMarlin::<
+                            <MpcPairingEngine<E, S> as PairingEngine>::Fr,
+                            MpcPairingEngine<E, S>,
+                        >::prove(&mpc_pk, circ_data, zk_rng)
+                        .unwrap()
+                    };
+                    let _op = mpc_net::op("reveal");
+                    pf.reveal()
                 });
                 end_timer!(timer);
                 assert!(KzgMarlin::<E::Fr, E>::verify(&vk, &public_inputs, &proof, rng).unwrap());
@@ -212,10 +235,12 @@ mod squarings {
         use super::*;
         use ark_poly::univariate::DensePolynomial;
         use ark_poly_commit::marlin::marlin_pc::MarlinKZG10;
+        use ark_serialize::CanonicalSerialize;
         use mpc_algebra::Reveal;
         use mpc_plonk::relations::flat::CircuitLayout;
-        use mpc_plonk::relations::structured::PlonkCircuit;
+        use mpc_plonk::relations::structured::{Gate, PlonkCircuit};
         use mpc_plonk::*;
+        use std::collections::HashMap;
 
         fn plonk_squaring_circuit<F: Field>(c: RepeatedSquaringCircuit<F>) -> PlonkCircuit<F> {
             let n_gates = c.chain.len() as u32 - 1;
@@ -224,9 +249,10 @@ mod squarings {
             let mut this = PlonkCircuit {
                 n_vars,
                 pub_vars: std::iter::once((last_var, "out".to_owned())).collect(),
-                prods: (0..(n_vars - 1)).map(|i| (i, i, i + 1)).collect(),
-                sums: Vec::new(),
+                gates: (0..(n_vars - 1)).map(|i| Gate::prod(i, i, i + 1)).collect(),
                 values: c.chain.into_iter().collect(),
+                tables: Vec::new(),
+                lookups: Default::default(),
             };
             this.pad_to_power_of_2();
             this
@@ -235,8 +261,42 @@ mod squarings {
 
         pub struct PlonkBench;
 
+        /// Write the proof, verifier key and public inputs to `proof.bin`, `vk.bin` and
+        /// `public.bin` in `dir`, for an independent verifier.
+        ///
+        /// The public inputs are written as their names and then their values, sorted by name.
+        fn write_out<F: Field>(
+            dir: &Path,
+            pf: &impl CanonicalSerialize,
+            vk: &impl CanonicalSerialize,
+            public_inputs: &HashMap<String, F>,
+        ) {
+            let mut inputs: Vec<(&String, &F)> = public_inputs.iter().collect();
+            inputs.sort_by_key(|(name, _)| *name);
+            let names: Vec<String> = inputs.iter().map(|(name, _)| (*name).clone()).collect();
+            let values: Vec<F> = inputs.iter().map(|(_, value)| **value).collect();
+            let mut proof = Vec::new();
+            pf.serialize(&mut proof).unwrap();
+            let mut key = Vec::new();
+            vk.serialize(&mut key).unwrap();
+            let mut public = Vec::new();
+            names.serialize(&mut public).unwrap();
+            values.serialize(&mut public).unwrap();
+            std::fs::create_dir_all(dir)
+                .unwrap_or_else(|e| panic!("Could not create {}: {}", dir.display(), e));
+            for (name, bytes) in vec![
+                ("proof.bin", proof),
+                ("vk.bin", key),
+                ("public.bin", public),
+            ] {
+                let path = dir.join(name);
+                std::fs::write(&path, bytes)
+                    .unwrap_or_else(|e| panic!("Could not write {}: {}", path.display(), e));
+            }
+        }
+
         impl SnarkBench for PlonkBench {
-            fn local<E: PairingEngine>(n: usize, timer_label: &str) {
+            fn local<E: PairingEngine>(n: usize, timer_label: &str, out_dir: Option<&Path>) {
                 let rng = &mut test_rng();
                 let circ_no_data = plonk_squaring_circuit(RepeatedSquaringCircuit::without_data(n));
                 let circ_no_data = CircuitLayout::from_circuit(&circ_no_data);
@@ -249,17 +309,27 @@ mod squarings {
                     std::iter::once(("out".to_owned(), circ_data.chain.last().unwrap().unwrap()))
                         .collect();
                 let setup_rng = &mut test_rng();
-                let zk_rng = &mut test_rng();
+                let zk_rng = &mut rand::thread_rng();
                 let srs =
                     MarlinPcPlonk::<E::Fr, E>::universal_setup(n.next_power_of_two(), setup_rng);
                 let (pk, vk) = MarlinPcPlonk::<E::Fr, E>::circuit_setup(&srs, &circ_no_data);
                 let timer = start_timer!(|| timer_label);
                 let pf = MarlinPcPlonk::<E::Fr, E>::prove(&pk, &plonk_circ_data, zk_rng);
                 end_timer!(timer);
-                MarlinPcPlonk::<E::Fr, E>::verify(&vk, &circ_no_data, pf, &public_inputs);
+                if let Some(dir) = out_dir {
+                    write_out(dir, &pf, &vk, &public_inputs);
+                }
+                assert!(
+                    MarlinPcPlonk::<E::Fr, E>::verify(&vk, &circ_no_data, pf, &public_inputs)
+                        .unwrap()
+                );
             }
 
-            fn mpc<E: PairingEngine, S: PairingShare<E>>(n: usize, timer_label: &str) {
+            fn mpc<E: PairingEngine, S: PairingShare<E>>(
+                n: usize,
+                timer_label: &str,
+                out_dir: Option<&Path>,
+            ) {
                 let rng = &mut test_rng();
                 let circ_no_data = plonk_squaring_circuit(RepeatedSquaringCircuit::without_data(n));
                 let circ_no_data = CircuitLayout::from_circuit(&circ_no_data);
@@ -277,7 +347,7 @@ mod squarings {
                 ))
                 .collect();
                 let setup_rng = &mut test_rng();
-                let zk_rng = &mut test_rng();
+                let zk_rng = &mut rand::thread_rng();
                 let srs =
                     MarlinPcPlonk::<E::Fr, E>::universal_setup(n.next_power_of_two(), setup_rng);
                 let (pk, vk) = MarlinPcPlonk::<E::Fr, E>::circuit_setup(&srs, &circ_no_data);
@@ -285,18 +355,28 @@ mod squarings {
                 MpcMultiNet::reset_stats();
                 let t = start_timer!(|| timer_label);
                 let pf = channel::without_cheating(|| {
-                    let pf = MarlinPcPlonk::<
-                        <MpcPairingEngine<E, S> as PairingEngine>::Fr,
-                        MpcPairingEngine<E, S>,
-                    >::prove(&mpc_pk, &plonk_circ_data, zk_rng);
+                    let pf = {
+                        let _op = mpc_net::op("prove");
+                        MarlinPcPlonk::<
+                            <MpcPairingEngine<E, S> as PairingEngine>::Fr,
+                            MpcPairingEngine<E, S>,
+                        >::prove(&mpc_pk, &plonk_circ_data, zk_rng)
+                    };
 
                     let reveal_timer = start_timer!(|| "reveal");
+                    let _op = mpc_net::op("reveal");
                     let pf = pf.reveal();
                     end_timer!(reveal_timer);
                     pf
                 });
                 end_timer!(t);
-                MarlinPcPlonk::<E::Fr, E>::verify(&vk, &circ_no_data, pf, &public_inputs);
+                if let (Some(dir), true) = (out_dir, MpcMultiNet::am_king()) {
+                    write_out(dir, &pf, &vk, &public_inputs);
+                }
+                assert!(
+                    MarlinPcPlonk::<E::Fr, E>::verify(&vk, &circ_no_data, pf, &public_inputs)
+                        .unwrap()
+                );
             }
         }
     }
@@ -344,6 +424,165 @@ mod squarings {
     }
 }
 
+mod comparisons {
+    use super::*;
+    use ark_ff::PrimeField;
+    use ark_r1cs_std::{
+        alloc::AllocVar,
+        eq::EqGadget,
+        fields::{fp::FpVar, FieldVar},
+    };
+    use std::cmp::Ordering;
+
+    /// Proves that `count` of the witnesses `xs` are less than the public `bound`.
+    ///
+    /// Unlike the squaring circuit, the comparison bits are only computed during proving, so an
+    /// MPC prover runs the r1cs-std comparison gadget on shared witnesses.
+    #[derive(Clone)]
+    struct ComparisonCircuit<F: Field> {
+        bound: Option<F>,
+        count: Option<F>,
+        xs: Vec<Option<F>>,
+    }
+
+    impl<F: Field> ComparisonCircuit<F> {
+        fn without_data(comparisons: usize) -> Self {
+            Self {
+                bound: None,
+                count: None,
+                xs: vec![None; comparisons],
+            }
+        }
+        fn from_values(bound: u64, xs: Vec<u64>) -> Self {
+            let count = xs.iter().filter(|x| **x < bound).count() as u64;
+            Self {
+                bound: Some(F::from(bound)),
+                count: Some(F::from(count)),
+                xs: xs.into_iter().map(|x| Some(F::from(x))).collect(),
+            }
+        }
+    }
+
+    fn random_values<R: rand::Rng>(comparisons: usize, rng: &mut R) -> (u64, Vec<u64>) {
+        let bound = rng.gen();
+        (bound, (0..comparisons).map(|_| rng.gen()).collect())
+    }
+
+    fn mpc_comparison_circuit<Fr: Field, MFr: Field + Reveal<Base = Fr>>(
+        bound: u64,
+        xs: Vec<u64>,
+    ) -> ComparisonCircuit<MFr> {
+        let circ = ComparisonCircuit::<Fr>::from_values(bound, xs);
+        let rng = &mut test_rng();
+        let mut values: Vec<Fr> = circ.xs.into_iter().map(Option::unwrap).collect();
+        values.push(circ.count.unwrap());
+        let mut shares = MFr::king_share_batch(values, rng);
+        let count = shares.pop();
+        ComparisonCircuit {
+            bound: circ.bound.map(MFr::from_public),
+            count,
+            xs: shares.into_iter().map(Some).collect(),
+        }
+    }
+
+    impl<ConstraintF: PrimeField> ConstraintSynthesizer<ConstraintF>
+        for ComparisonCircuit<ConstraintF>
+    {
+        fn generate_constraints(
+            self,
+            cs: ConstraintSystemRef<ConstraintF>,
+        ) -> Result<(), SynthesisError> {
+            let bound = FpVar::new_input(cs.clone(), || {
+                self.bound.ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let count = FpVar::new_input(cs.clone(), || {
+                self.count.ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let mut sum = FpVar::zero();
+            for x in &self.xs {
+                let x =
+                    FpVar::new_witness(cs.clone(), || x.ok_or(SynthesisError::AssignmentMissing))?;
+                sum += FpVar::from(x.is_cmp(&bound, Ordering::Less, false)?);
+            }
+            sum.enforce_equal(&count)
+        }
+    }
+
+    pub mod groth {
+        use super::*;
+        use crate::ark_groth16::{generate_random_parameters, prepare_verifying_key, verify_proof};
+        use crate::groth::prover::create_random_proof;
+        use crate::groth::setup::ceremony;
+
+        pub struct Groth16Bench;
+
+        impl SnarkBench for Groth16Bench {
+            fn local<E: PairingEngine>(n: usize, timer_label: &str, _out_dir: Option<&Path>) {
+                let rng = &mut test_rng();
+                let circ_no_data = ComparisonCircuit::without_data(n);
+
+                let params = generate_random_parameters::<E, _, _>(circ_no_data, rng).unwrap();
+
+                let pvk = prepare_verifying_key::<E>(&params.vk);
+
+                let (bound, xs) = random_values(n, rng);
+                let circ_data = ComparisonCircuit::from_values(bound, xs);
+                let public_inputs = vec![circ_data.bound.unwrap(), circ_data.count.unwrap()];
+                let timer = start_timer!(|| timer_label);
+                let proof = create_random_proof::<E, _, _>(circ_data, &params, rng).unwrap();
+                end_timer!(timer);
+
+                assert!(verify_proof(&pvk, &proof, &public_inputs).unwrap());
+            }
+
+            fn mpc<E: PairingEngine, S: PairingShare<E>>(
+                n: usize,
+                timer_label: &str,
+                _out_dir: Option<&Path>,
+            ) {
+                let rng = &mut test_rng();
+                let circ_no_data = ComparisonCircuit::without_data(n);
+
+                let params = ceremony::<E, _, _>(circ_no_data, &mut rand::thread_rng()).unwrap();
+
+                let pvk = prepare_verifying_key::<E>(&params.vk);
+                let mpc_params = Reveal::from_public(params);
+
+                let (bound, xs) = random_values(n, rng);
+                let circ_data = mpc_comparison_circuit::<
+                    E::Fr,
+                    <MpcPairingEngine<E, S> as PairingEngine>::Fr,
+                >(bound, xs);
+                let public_inputs = vec![
+                    circ_data.bound.unwrap().reveal(),
+                    circ_data.count.unwrap().reveal(),
+                ];
+                MpcMultiNet::reset_stats();
+                let timer = start_timer!(|| timer_label);
+                let proof = channel::without_cheating(|| {
+                    let pf = {
+                        let _op = mpc_net::op("create_random_proof");
+                        create_random_proof::<MpcPairingEngine<E, S>, _, _>(
+                            circ_data,
+                            &mpc_params,
+                            rng,
+                        )
+                        .unwrap()
+                    };
+                    let reveal_timer = start_timer!(|| "reveal");
+                    let _op = mpc_net::op("reveal");
+                    let pf = pf.reveal();
+                    end_timer!(reveal_timer);
+                    pf
+                });
+                end_timer!(timer);
+
+                assert!(verify_proof(&pvk, &proof, &public_inputs).unwrap());
+            }
+        }
+    }
+}
+
 #[derive(Debug, StructOpt)]
 struct ShareInfo {
     /// File with list of hosts
@@ -357,14 +596,29 @@ struct ShareInfo {
     /// Use spdz?
     #[structopt(long)]
     alg: MpcAlg,
+
+    /// Write a Chrome trace of this party's network rounds here
+    #[structopt(long, parse(from_os_str))]
+    trace: Option<PathBuf>,
 }
 
 impl ShareInfo {
     fn setup(&self) {
-        MpcMultiNet::init_from_file(self.hosts.to_str().unwrap(), self.party as usize)
+        MpcMultiNet::try_init_from_file(self.hosts.to_str().unwrap(), self.party as usize)
+            .unwrap_or_else(|e| e.exit());
+        if self.trace.is_some() {
+            MpcMultiNet::start_trace();
+        }
     }
     fn teardown(&self) {
         debug!("Stats: {:#?}", MpcMultiNet::stats());
+        if let (Some(path), Some(trace)) = (&self.trace, MpcMultiNet::take_trace()) {
+            for (op, cost) in trace.summary() {
+                debug!("{}: {:?}", op, cost);
+            }
+            std::fs::write(path, trace.to_chrome_trace())
+                .unwrap_or_else(|e| panic!("Could not write trace to {}: {}", path.display(), e));
+        }
         MpcMultiNet::deinit();
     }
     fn run<E: PairingEngine, B: SnarkBench>(
@@ -373,23 +627,29 @@ impl ShareInfo {
         computation_size: usize,
         _b: B,
         timed_label: &str,
+        out_dir: Option<&Path>,
     ) {
-        match computation {
-            Computation::Squaring => match self.alg {
-                MpcAlg::Spdz => B::mpc::<E, mpc_algebra::share::spdz::SpdzPairingShare<E>>(
-                    computation_size,
-                    timed_label,
-                ),
+        mpc_net::catch(|| match computation {
+            Computation::Squaring | Computation::Comparisons => match self.alg {
+                MpcAlg::Spdz => {
+                    // Sample the MAC key, and set up OT for the offline phase.
+                    SpdzFieldShare::<E::Fr>::init_protocol();
+                    B::mpc::<E, SpdzPairingShare<E>>(computation_size, timed_label, out_dir);
+                    SpdzFieldShare::<E::Fr>::deinit_protocol();
+                }
                 MpcAlg::Hbc => B::mpc::<E, mpc_algebra::share::add::AdditivePairingShare<E>>(
                     computation_size,
                     timed_label,
+                    out_dir,
                 ),
                 MpcAlg::Gsz => B::mpc::<E, mpc_algebra::share::gsz20::GszPairingShare<E>>(
                     computation_size,
                     timed_label,
+                    out_dir,
                 ),
             },
-        }
+        })
+        .unwrap_or_else(|e| e.exit())
     }
 }
 
@@ -406,6 +666,7 @@ arg_enum! {
     #[derive(PartialEq, Debug, Clone, Copy)]
     pub enum Computation {
         Squaring,
+        Comparisons,
     }
 }
 
@@ -448,14 +709,15 @@ impl FieldOpt {
         computation_size: usize,
         b: B,
         timed_label: &str,
+        out_dir: Option<&Path>,
     ) {
         self.setup();
         match self {
             FieldOpt::Mpc { party_info, .. } => {
-                party_info.run::<E, B>(computation, computation_size, b, timed_label)
+                party_info.run::<E, B>(computation, computation_size, b, timed_label, out_dir)
             }
-            FieldOpt::Local => B::local::<E>(computation_size, timed_label),
-            FieldOpt::ArkLocal => B::ark_local::<E>(computation_size, timed_label),
+            FieldOpt::Local => B::local::<E>(computation_size, timed_label, out_dir),
+            FieldOpt::ArkLocal => B::ark_local::<E>(computation_size, timed_label, out_dir),
         }
         self.teardown();
     }
@@ -476,6 +738,10 @@ struct Opt {
     #[structopt(long, default_value = "10")]
     computation_size: usize,
 
+    /// Write the proof, verifier key and public inputs to this directory (Plonk only)
+    #[structopt(long, parse(from_os_str))]
+    out_dir: Option<PathBuf>,
+
     #[structopt(subcommand)]
     field: FieldOpt,
 }
@@ -485,24 +751,290 @@ impl Opt {}
 fn main() {
     let opt = Opt::from_args();
     env_logger::init();
-    match opt.proof_system {
-        ProofSystem::Groth16 => opt.field.run::<ark_bls12_377::Bls12_377, _>(
+    let out_dir = opt.out_dir.as_deref();
+    if out_dir.is_some() && !matches!(opt.proof_system, ProofSystem::Plonk) {
+        eprintln!(
+            "Writing out the proof is only implemented for Plonk, not {}",
+            opt.proof_system
+        );
+        std::process::exit(1)
+    }
+    match (opt.computation, opt.proof_system) {
+        (Computation::Comparisons, ProofSystem::Groth16) => {
+            opt.field.run::<ark_bls12_377::Bls12_377, _>(
+                opt.computation,
+                opt.computation_size,
+                comparisons::groth::Groth16Bench,
+                TIMED_SECTION_LABEL,
+                out_dir,
+            )
+        }
+        (Computation::Comparisons, p) => {
+            eprintln!(
+                "The comparisons computation is only implemented for Groth16, not {}",
+                p
+            );
+            std::process::exit(1)
+        }
+        (_, ProofSystem::Groth16) => opt.field.run::<ark_bls12_377::Bls12_377, _>(
             opt.computation,
             opt.computation_size,
             squarings::groth::Groth16Bench,
             TIMED_SECTION_LABEL,
+            out_dir,
         ),
-        ProofSystem::Plonk => opt.field.run::<ark_bls12_377::Bls12_377, _>(
+        (_, ProofSystem::Plonk) => opt.field.run::<ark_bls12_377::Bls12_377, _>(
             opt.computation,
             opt.computation_size,
             squarings::plonk::PlonkBench,
             TIMED_SECTION_LABEL,
+            out_dir,
         ),
-        ProofSystem::Marlin => opt.field.run::<ark_bls12_377::Bls12_377, _>(
+        (_, ProofSystem::Marlin) => opt.field.run::<ark_bls12_377::Bls12_377, _>(
             opt.computation,
             opt.computation_size,
             squarings::marlin::MarlinBench,
             TIMED_SECTION_LABEL,
+            out_dir,
         ),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ark_groth16::{generate_random_parameters, prepare_verifying_key, verify_proof};
+    use crate::groth::prover::create_random_proof;
+    use ark_bls12_377::{Bls12_377 as E, Fr};
+    use ark_ff::{PrimeField, Zero};
+    use ark_r1cs_std::{
+        alloc::AllocVar,
+        boolean::{AllocatedBit, Boolean},
+        eq::EqGadget,
+        fields::fp::FpVar,
+    };
+    use ark_sponge::constraints::CryptographicSpongeVar;
+    use ark_sponge::poseidon::{constraints::PoseidonSpongeVar, PoseidonSponge};
+    use ark_sponge::{
+        poseidon_parameters_for_test, CryptographicSponge, FieldBasedCryptographicSponge,
+    };
+    use mpc_algebra::share::add::AdditivePairingShare;
+    use mpc_net::LocalSimNet;
+
+    type MFr<S> = <MpcPairingEngine<E, S> as PairingEngine>::Fr;
+
+    fn hash(xs: &[Fr]) -> Fr {
+        let mut sponge = PoseidonSponge::new(&poseidon_parameters_for_test());
+        sponge.absorb(&xs);
+        sponge.squeeze_native_field_elements(1)[0]
+    }
+
+    fn hash_var<F: PrimeField>(
+        cs: ConstraintSystemRef<F>,
+        xs: &[FpVar<F>],
+    ) -> Result<FpVar<F>, SynthesisError> {
+        let mut sponge = PoseidonSpongeVar::new(cs, &poseidon_parameters_for_test());
+        sponge.absorb(&xs)?;
+        Ok(sponge.squeeze_field_elements(1)?.remove(0))
+    }
+
+    fn witnesses<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        values: &[Option<F>],
+    ) -> Result<Vec<FpVar<F>>, SynthesisError> {
+        values
+            .iter()
+            .map(|v| FpVar::new_witness(cs.clone(), || v.ok_or(SynthesisError::AssignmentMissing)))
+            .collect()
+    }
+
+    /// Knows a Poseidon preimage of `digest`.
+    #[derive(Clone)]
+    struct PoseidonCircuit<F> {
+        digest: Option<F>,
+        preimage: Vec<Option<F>>,
+    }
+
+    impl<F: PrimeField> ConstraintSynthesizer<F> for PoseidonCircuit<F> {
+        fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+            let digest = FpVar::new_input(cs.clone(), || {
+                self.digest.ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let preimage = witnesses(&cs, &self.preimage)?;
+            hash_var(cs, &preimage)?.enforce_equal(&digest)
+        }
+    }
+
+    /// Knows a leaf, and a path from it to `root` in a Merkle tree hashed with Poseidon. Each step
+    /// of the path has the sibling, and a bit that is one when the sibling is on the left.
+    #[derive(Clone)]
+    struct MerkleCircuit<F> {
+        root: Option<F>,
+        leaf: Option<F>,
+        siblings: Vec<Option<F>>,
+        sibling_is_left: Vec<Option<F>>,
+    }
+
+    impl<F: PrimeField> ConstraintSynthesizer<F> for MerkleCircuit<F> {
+        fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+            let root = FpVar::new_input(cs.clone(), || {
+                self.root.ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let mut node = witnesses(&cs, &[self.leaf])?.remove(0);
+            let siblings = witnesses(&cs, &self.siblings)?;
+            for (sibling, is_left) in siblings.iter().zip(&self.sibling_is_left) {
+                let is_left = AllocatedBit::new_witness_from_field(cs.clone(), || {
+                    is_left.ok_or(SynthesisError::AssignmentMissing)
+                })?;
+                let is_left = Boolean::from(is_left);
+                let left = is_left.select(sibling, &node)?;
+                let right = is_left.select(&node, sibling)?;
+                node = hash_var(cs.clone(), &[left, right])?;
+            }
+            node.enforce_equal(&root)
+        }
+    }
+
+    /// Knows pairs of field elements, `n_equal` of them equal, that are unequal unless equal.
+    #[derive(Clone)]
+    struct EqualityCircuit<F> {
+        n_equal: Option<F>,
+        pairs: Vec<Option<F>>,
+    }
+
+    impl<F: PrimeField> ConstraintSynthesizer<F> for EqualityCircuit<F> {
+        fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+            let n_equal = FpVar::new_input(cs.clone(), || {
+                self.n_equal.ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let values = witnesses(&cs, &self.pairs)?;
+            let mut count = FpVar::Constant(F::zero());
+            for pair in values.chunks_exact(2) {
+                let equal = pair[0].is_eq(&pair[1])?;
+                pair[0].conditional_enforce_not_equal(&pair[1], &equal.not())?;
+                count += FpVar::from(equal);
+            }
+            count.enforce_equal(&n_equal)
+        }
+    }
+
+    /// Shares `values` from the king, within a party.
+    fn share<S: PairingShare<E>>(values: &[Fr]) -> Vec<Option<MFr<S>>> {
+        MFr::<S>::king_share_batch(values.to_vec(), &mut test_rng())
+            .into_iter()
+            .map(Some)
+            .collect()
+    }
+
+    /// Sets up Groth16 for `shape`, then has three parties prove `circuit()` and checks the proof
+    /// against `public_inputs`.
+    fn groth16_in_sim<S: PairingShare<E>, C: ConstraintSynthesizer<MFr<S>>>(
+        shape: impl ConstraintSynthesizer<Fr>,
+        public_inputs: Vec<Fr>,
+        circuit: impl Fn() -> C + Send + Sync + 'static,
+    ) {
+        let params = generate_random_parameters::<E, _, _>(shape, &mut test_rng()).unwrap();
+        let pvk = prepare_verifying_key::<E>(&params.vk);
+        LocalSimNet::run(3, move |_| {
+            MFr::<S>::init_protocol();
+            let mpc_params = Reveal::from_public(params.clone());
+            let proof = create_random_proof::<MpcPairingEngine<E, S>, _, _>(
+                circuit(),
+                &mpc_params,
+                &mut test_rng(),
+            )
+            .unwrap()
+            .reveal();
+            assert!(verify_proof(&pvk, &proof, &public_inputs).unwrap());
+            MFr::<S>::deinit_protocol();
+        });
+    }
+
+    fn poseidon_preimage<S: PairingShare<E>>() {
+        let rng = &mut test_rng();
+        let preimage: Vec<Fr> = (0..3).map(|_| Fr::rand(rng)).collect();
+        let digest = hash(&preimage);
+        let shape = PoseidonCircuit {
+            digest: None,
+            preimage: vec![None; 3],
+        };
+        groth16_in_sim::<S, _>(shape, vec![digest], move || PoseidonCircuit {
+            digest: Some(MFr::<S>::from_public(digest)),
+            preimage: share::<S>(&preimage),
+        });
+    }
+
+    fn merkle_path<S: PairingShare<E>>() {
+        let rng = &mut test_rng();
+        let leaf = Fr::rand(rng);
+        let siblings: Vec<Fr> = (0..3).map(|_| Fr::rand(rng)).collect();
+        let sibling_is_left: Vec<Fr> = [1u64, 0, 1].iter().map(|b| Fr::from(*b)).collect();
+        let root = siblings
+            .iter()
+            .zip(&sibling_is_left)
+            .fold(leaf, |node, (sibling, is_left)| {
+                if is_left.is_zero() {
+                    hash(&[node, *sibling])
+                } else {
+                    hash(&[*sibling, node])
+                }
+            });
+        let shape = MerkleCircuit {
+            root: None,
+            leaf: None,
+            siblings: vec![None; 3],
+            sibling_is_left: vec![None; 3],
+        };
+        groth16_in_sim::<S, _>(shape, vec![root], move || {
+            let mut shares = share::<S>(&[&[leaf][..], &siblings, &sibling_is_left].concat());
+            let sibling_is_left = shares.split_off(4);
+            let siblings = shares.split_off(1);
+            MerkleCircuit {
+                root: Some(MFr::<S>::from_public(root)),
+                leaf: shares[0],
+                siblings,
+                sibling_is_left,
+            }
+        });
+    }
+
+    fn equalities<S: PairingShare<E>>() {
+        let rng = &mut test_rng();
+        let x = Fr::rand(rng);
+        let pairs = vec![x, x, x, Fr::rand(rng), Fr::zero(), Fr::zero()];
+        let n_equal = Fr::from(2u64);
+        let shape = EqualityCircuit {
+            n_equal: None,
+            pairs: vec![None; 6],
+        };
+        groth16_in_sim::<S, _>(shape, vec![n_equal], move || EqualityCircuit {
+            n_equal: Some(MFr::<S>::from_public(n_equal)),
+            pairs: share::<S>(&pairs),
+        });
+    }
+
+    #[test]
+    fn equalities_hbc() {
+        equalities::<AdditivePairingShare<E>>();
+    }
+
+    #[test]
+    fn poseidon_preimage_hbc() {
+        poseidon_preimage::<AdditivePairingShare<E>>();
+    }
+
+    #[test]
+    fn poseidon_preimage_spdz() {
+        poseidon_preimage::<SpdzPairingShare<E>>();
+    }
+
+    #[test]
+    fn merkle_path_hbc() {
+        merkle_path::<AdditivePairingShare<E>>();
+    }
+
+    #[test]
+    fn merkle_path_spdz() {
+        merkle_path::<SpdzPairingShare<E>>();
+    }
+}
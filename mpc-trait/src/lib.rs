@@ -5,6 +5,12 @@ use std::borrow::Cow;
 pub trait MpcWire: Clone {
     type Public = Self;
     fn publicize(&mut self) {}
+    /// Publicize many values at once: implementations open them in one round.
+    fn batch_publicize(selfs: &mut [Self]) {
+        for s in selfs {
+            s.publicize();
+        }
+    }
     fn is_shared(&self) -> bool {
         false
     }
@@ -35,9 +41,7 @@ impl<A: MpcWire, B: MpcWire, C: MpcWire, D: MpcWire> MpcWire for (A, B, C, D) {
 
 impl<T: MpcWire> MpcWire for Vec<T> {
     fn publicize(&mut self) {
-        for x in self {
-            x.publicize();
-        }
+        T::batch_publicize(self);
     }
     fn is_shared(&self) -> bool {
         for x in self {
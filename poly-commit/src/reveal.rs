@@ -180,12 +180,38 @@ impl<E: Field> MpcWire for LabeledPolynomial<E, DensePolynomial<E>> {
 
 impl<C: PCCommitment + MpcWire> MpcWire for LabeledCommitment<C> {
     struct_mpc_wire_simp_impl!(LabeledCommitment; commitment);
+    fn batch_publicize(selfs: &mut [Self]) {
+        let mut cs: Vec<C> = selfs.iter().map(|c| c.commitment.clone()).collect();
+        C::batch_publicize(&mut cs);
+        for (s, c) in selfs.iter_mut().zip(cs) {
+            s.commitment = c;
+        }
+    }
 }
 
 impl<C: PairingEngine> MpcWire for Commitment<C> {
     struct_mpc_wire_simp_impl!(Commitment; comm, shifted_comm);
+    fn batch_publicize(selfs: &mut [Self]) {
+        let mut comms: Vec<kzg10::Commitment<C>> = selfs.iter().map(|c| c.comm).collect();
+        comms.extend(selfs.iter().filter_map(|c| c.shifted_comm));
+        kzg10::Commitment::batch_publicize(&mut comms);
+        let mut comms = comms.into_iter();
+        for s in selfs.iter_mut() {
+            s.comm = comms.next().unwrap();
+        }
+        for s in selfs.iter_mut().filter(|c| c.shifted_comm.is_some()) {
+            s.shifted_comm = comms.next();
+        }
+    }
 }
 
 impl<C: PairingEngine> MpcWire for kzg10::Commitment<C> {
     struct_mpc_wire_simp_impl!(kzg10::Commitment; 0);
+    fn batch_publicize(selfs: &mut [Self]) {
+        let mut gs: Vec<C::G1Affine> = selfs.iter().map(|c| c.0).collect();
+        C::G1Affine::batch_publicize(&mut gs);
+        for (s, g) in selfs.iter_mut().zip(gs) {
+            s.0 = g;
+        }
+    }
 }
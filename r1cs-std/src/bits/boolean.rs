@@ -29,9 +29,13 @@ pub(crate) fn bool_to_field<F: Field>(val: impl Borrow<bool>) -> F {
 
 impl<F: Field> AllocatedBit<F> {
     /// Get the assigned value for `self`.
+    ///
+    /// A secret-shared value is missing here: use `field_value` instead.
     pub fn value(&self) -> Result<bool, SynthesisError> {
         let value = self.cs.assigned_value(self.variable).get()?;
-        if value.is_zero() {
+        if value.is_shared() {
+            Err(SynthesisError::AssignmentMissing)
+        } else if value.is_zero() {
             Ok(false)
         } else if value.is_one() {
             Ok(true)
@@ -40,17 +44,39 @@ impl<F: Field> AllocatedBit<F> {
         }
     }
 
+    /// Get the assigned value for `self`, as zero or one. Unlike `value`, this is available for
+    /// secret-shared values too.
+    pub fn field_value(&self) -> Result<F, SynthesisError> {
+        self.cs.assigned_value(self.variable).get()
+    }
+
     /// Get the R1CS variable for `self`.
     pub fn variable(&self) -> Variable {
         self.variable
     }
 
-    /// Allocate a witness variable without a booleanity check.
-    fn new_witness_without_booleanity_check<T: Borrow<bool>>(
+    /// Allocate a witness variable, with a booleanity check, from its value as zero or one. This
+    /// can allocate secret-shared bits.
+    pub fn new_witness_from_field(
+        cs: impl Into<Namespace<F>>,
+        f: impl FnOnce() -> Result<F, SynthesisError>,
+    ) -> Result<Self, SynthesisError> {
+        let cs = cs.into().cs();
+        let variable = cs.new_witness_variable(f)?;
+
+        // Constrain: (1 - a) * a = 0
+        cs.enforce_constraint(lc!() + Variable::One - variable, lc!() + variable, lc!())?;
+
+        Ok(Self { variable, cs })
+    }
+
+    /// Allocate a witness variable without a booleanity check. The value is computed in the field,
+    /// so that operations on secret-shared bits work.
+    fn new_witness_without_booleanity_check(
         cs: ConstraintSystemRef<F>,
-        f: impl FnOnce() -> Result<T, SynthesisError>,
+        f: impl FnOnce() -> Result<F, SynthesisError>,
     ) -> Result<Self, SynthesisError> {
-        let variable = cs.new_witness_variable(|| f().map(bool_to_field))?;
+        let variable = cs.new_witness_variable(f)?;
         Ok(Self { variable, cs })
     }
 
@@ -59,7 +85,8 @@ impl<F: Field> AllocatedBit<F> {
     #[tracing::instrument(target = "r1cs")]
     pub fn xor(&self, b: &Self) -> Result<Self, SynthesisError> {
         let result = Self::new_witness_without_booleanity_check(self.cs.clone(), || {
-            Ok(self.value()? ^ b.value()?)
+            let (a, b) = (self.field_value()?, b.field_value()?);
+            Ok(a + b - (a * b).double())
         })?;
 
         // Constrain (a + a) * (b) = (a + b - c)
@@ -91,7 +118,7 @@ impl<F: Field> AllocatedBit<F> {
     #[tracing::instrument(target = "r1cs")]
     pub fn and(&self, b: &Self) -> Result<Self, SynthesisError> {
         let result = Self::new_witness_without_booleanity_check(self.cs.clone(), || {
-            Ok(self.value()? & b.value()?)
+            Ok(self.field_value()? * b.field_value()?)
         })?;
 
         // Constrain (a) * (b) = (c), ensuring c is 1 iff
@@ -110,7 +137,8 @@ impl<F: Field> AllocatedBit<F> {
     #[tracing::instrument(target = "r1cs")]
     pub fn or(&self, b: &Self) -> Result<Self, SynthesisError> {
         let result = Self::new_witness_without_booleanity_check(self.cs.clone(), || {
-            Ok(self.value()? | b.value()?)
+            let (a, b) = (self.field_value()?, b.field_value()?);
+            Ok(a + b - a * b)
         })?;
 
         // Constrain (1 - a) * (1 - b) = (c), ensuring c is 1 iff
@@ -128,7 +156,8 @@ impl<F: Field> AllocatedBit<F> {
     #[tracing::instrument(target = "r1cs")]
     pub fn and_not(&self, b: &Self) -> Result<Self, SynthesisError> {
         let result = Self::new_witness_without_booleanity_check(self.cs.clone(), || {
-            Ok(self.value()? & !b.value()?)
+            let a = self.field_value()?;
+            Ok(a - a * b.field_value()?)
         })?;
 
         // Constrain (a) * (1 - b) = (c), ensuring c is 1 iff
@@ -146,7 +175,8 @@ impl<F: Field> AllocatedBit<F> {
     #[tracing::instrument(target = "r1cs")]
     pub fn nor(&self, b: &Self) -> Result<Self, SynthesisError> {
         let result = Self::new_witness_without_booleanity_check(self.cs.clone(), || {
-            Ok(!(self.value()? | b.value()?))
+            let (a, b) = (self.field_value()?, b.field_value()?);
+            Ok(F::one() - a - b + a * b)
         })?;
 
         // Constrain (1 - a) * (1 - b) = (c), ensuring c is 1 iff
@@ -251,6 +281,16 @@ impl<F: Field> R1CSVar<F> for Boolean<F> {
 }
 
 impl<F: Field> Boolean<F> {
+    /// The value of `self`, as zero or one. Unlike `value`, this is available for secret-shared
+    /// values too.
+    pub fn field_value(&self) -> Result<F, SynthesisError> {
+        match self {
+            Boolean::Constant(c) => Ok(bool_to_field(c)),
+            Boolean::Is(ref v) => v.field_value(),
+            Boolean::Not(ref v) => v.field_value().map(|b| F::one() - b),
+        }
+    }
+
     /// The constant `true`.
     pub const TRUE: Self = Boolean::Constant(true);
 
@@ -621,18 +661,11 @@ impl<F: Field> Boolean<F> {
         // a constant.
         let should_construct_value = (!cs.is_in_setup_mode()) || bits.is_constant();
         if should_construct_value {
-            let bits = bits.iter().map(|b| b.value().unwrap()).collect::<Vec<_>>();
-            let bytes = bits
-                .chunks(8)
-                .map(|c| {
-                    let mut value = 0u8;
-                    for (i, &bit) in c.iter().enumerate() {
-                        value += (bit as u8) << i;
-                    }
-                    value
-                })
-                .collect::<Vec<_>>();
-            value = Some(F::from_le_bytes_mod_order(&bytes));
+            value = Some(
+                bits.iter()
+                    .rev()
+                    .fold(F::zero(), |acc, b| acc.double() + b.field_value().unwrap()),
+            );
         }
 
         if bits.is_constant() {
@@ -917,8 +950,8 @@ impl<F: Field> CondSelectGadget<F> for Boolean<F> {
                     let cs = cond.cs();
                     let result: Boolean<F> =
                         AllocatedBit::new_witness_without_booleanity_check(cs.clone(), || {
-                            let cond = cond.value()?;
-                            Ok(if cond { a.value()? } else { b.value()? })
+                            let (a, b) = (a.field_value()?, b.field_value()?);
+                            Ok(b + cond.field_value()? * (a - b))
                         })?
                         .into();
                     // a = self; b = other; c = cond;
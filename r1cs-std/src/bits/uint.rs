@@ -193,6 +193,10 @@ macro_rules! make_uint {
                     // Keep track of the resulting value
                     let mut result_value = Some(BigUint::zero());
 
+                    // Keep track of the resulting value in the field too: unlike
+                    // `result_value`, it is known for secret-shared bits
+                    let mut field_value = Some(F::zero());
+
                     // This is a linear combination that we will enforce to be "zero"
                     let mut lc = LinearCombination::zero();
 
@@ -217,6 +221,7 @@ macro_rules! make_uint {
                         // the linear combination
                         let mut coeff = F::one();
                         for bit in &op.bits {
+                            field_value = field_value.and_then(|v| Some(v + coeff * bit.field_value().ok()?));
                             match *bit {
                                 Boolean::Is(ref bit) => {
                                     all_constants = false;
@@ -259,13 +264,20 @@ macro_rules! make_uint {
 
                     // Storage area for the resulting bits
                     let mut result_bits = vec![];
+                    let field_bits = match result_value {
+                        Some(_) => None,
+                        None => field_value.map(|v| v.to_field_bits_le()),
+                    };
 
                     // Allocate each bit_gadget of the result
                     let mut coeff = F::one();
                     let mut i = 0;
                     while max_value != BigUint::zero() {
                         // Allocate the bit_gadget
-                        let b = AllocatedBit::new_witness(cs.clone(), || result_value.clone().map(|v| (v >> i) & BigUint::one() == BigUint::one()).get())?;
+                        let b = match field_bits {
+                            Some(ref bits) => AllocatedBit::new_witness_from_field(cs.clone(), || Ok(bits[i]))?,
+                            None => AllocatedBit::new_witness(cs.clone(), || result_value.clone().map(|v| (v >> i) & BigUint::one() == BigUint::one()).get())?,
+                        };
 
                         // Subtract this bit_gadget from the linear combination to ensure the sums
                         // balance out
@@ -6,6 +6,7 @@ use ark_relations::r1cs::{
 use core::borrow::Borrow;
 
 use crate::{
+    boolean::AllocatedBit,
     fields::{FieldOpsBounds, FieldVar},
     prelude::*,
     Assignment, ToConstraintFieldGadget, Vec,
@@ -75,7 +76,7 @@ impl<F: PrimeField> From<Boolean<F>> for FpVar<F> {
             let cs = other.cs();
             let variable = cs.new_lc(other.lc()).unwrap();
             Self::Var(AllocatedFp::new(
-                other.value().ok().map(|b| F::from(b as u8)),
+                other.field_value().ok(),
                 variable,
                 cs,
             ))
@@ -98,7 +99,7 @@ impl<F: PrimeField> AllocatedFp<F> {
     pub fn from(other: Boolean<F>) -> Self {
         let cs = other.cs();
         let variable = cs.new_lc(other.lc()).unwrap();
-        Self::new(other.value().ok().map(|b| F::from(b as u8)), variable, cs)
+        Self::new(other.field_value().ok(), variable, cs)
     }
 
     /// Returns the value assigned to `self` in the underlying constraint system
@@ -245,7 +246,7 @@ impl<F: PrimeField> AllocatedFp<F> {
     #[tracing::instrument(target = "r1cs")]
     pub fn inverse(&self) -> Result<Self, SynthesisError> {
         let inverse = Self::new_witness(self.cs.clone(), || {
-            Ok(self.value.get()?.inverse().unwrap_or_else(F::zero))
+            Ok(self.value.get()?.inverse_or_zero())
         })?;
 
         self.cs.enforce_constraint(
@@ -299,15 +300,18 @@ impl<F: PrimeField> AllocatedFp<F> {
     /// This requires three constraints.
     #[tracing::instrument(target = "r1cs")]
     pub fn is_neq(&self, other: &Self) -> Result<Boolean<F>, SynthesisError> {
-        let is_not_equal = Boolean::new_witness(self.cs.clone(), || {
-            Ok(self.value.get()? != other.value.get()?)
-        })?;
+        // Computed in the field, so that secret-shared values work too: with
+        // `d = self - other`, `is_not_equal = d / d` (or zero) and
+        // `multiplier = 1 / d` (or one). For shared values, `inverse_or_zero`
+        // tests `d` for zero without revealing whether they are equal.
+        let difference = self.value.and_then(|a| Some(a - other.value?));
+        let inverse = difference.map(|d| d.inverse_or_zero());
+        let is_not_equal = Boolean::from(AllocatedBit::new_witness_from_field(
+            self.cs.clone(),
+            || Ok(difference.get()? * inverse.get()?),
+        )?);
         let multiplier = self.cs.new_witness_variable(|| {
-            if is_not_equal.value()? {
-                (self.value.get()? - other.value.get()?).inverse().get()
-            } else {
-                Ok(F::one())
-            }
+            Ok(inverse.get()? + F::one() - is_not_equal.field_value()?)
         })?;
 
         // Completeness:
@@ -391,11 +395,15 @@ impl<F: PrimeField> AllocatedFp<F> {
         should_enforce: &Boolean<F>,
     ) -> Result<(), SynthesisError> {
         let multiplier = Self::new_witness(self.cs.clone(), || {
-            if should_enforce.value()? {
-                (self.value.get()? - other.value.get()?).inverse().get()
-            } else {
-                Ok(F::zero())
+            // Not inverted unless enforced: when a public `should_enforce` is false,
+            // `d` may well be zero. A shared `should_enforce` is not known, but then
+            // `inverse_or_zero` hides whether `d` is zero.
+            let s = should_enforce.field_value()?;
+            if !s.is_shared() && s.is_zero() {
+                return Ok(F::zero());
             }
+            let d = self.value.get()? - other.value.get()?;
+            Ok(s * d.inverse_or_zero())
         })?;
 
         self.cs.enforce_constraint(
@@ -426,26 +434,17 @@ impl<F: PrimeField> ToBitsGadget<F> for AllocatedFp<F> {
     #[tracing::instrument(target = "r1cs")]
     fn to_non_unique_bits_le(&self) -> Result<Vec<Boolean<F>>, SynthesisError> {
         let cs = self.cs.clone();
-        use ark_ff::BitIteratorBE;
-        let mut bits = if let Some(value) = self.value {
-            let field_char = BitIteratorBE::new(F::characteristic());
-            let bits: Vec<_> = BitIteratorBE::new(value.into_repr())
-                .zip(field_char)
-                .skip_while(|(_, c)| !c)
-                .map(|(b, _)| Some(b))
-                .collect();
+        let bits = if let Some(value) = self.value {
+            let bits: Vec<_> = value.to_field_bits_le().into_iter().map(Some).collect();
             assert_eq!(bits.len(), F::Params::MODULUS_BITS as usize);
             bits
         } else {
             vec![None; F::Params::MODULUS_BITS as usize]
         };
 
-        // Convert to little-endian
-        bits.reverse();
-
         let bits: Vec<_> = bits
             .into_iter()
-            .map(|b| Boolean::new_witness(cs.clone(), || b.get()))
+            .map(|b| AllocatedBit::new_witness_from_field(cs.clone(), || b.get()).map(Boolean::from))
             .collect::<Result<_, _>>()?;
 
         let mut lc = LinearCombination::zero();
@@ -519,8 +518,8 @@ impl<F: PrimeField> CondSelectGadget<F> for AllocatedFp<F> {
             _ => {
                 let cs = cond.cs();
                 let result = Self::new_witness(cs.clone(), || {
-                    cond.value()
-                        .and_then(|c| if c { true_val } else { false_val }.value.get())
+                    let (t, f) = (true_val.value.get()?, false_val.value.get()?);
+                    Ok(f + cond.field_value()? * (t - f))
                 })?;
                 // a = self; b = other; c = cond;
                 //
@@ -548,10 +547,11 @@ impl<F: PrimeField> TwoBitLookupGadget<F> for AllocatedFp<F> {
         debug_assert_eq!(b.len(), 2);
         debug_assert_eq!(c.len(), 4);
         let result = Self::new_witness(b.cs(), || {
-            let lsb = usize::from(b[0].value()?);
-            let msb = usize::from(b[1].value()?);
-            let index = lsb + (msb << 1);
-            Ok(c[index])
+            let (lsb, msb) = (b[0].field_value()?, b[1].field_value()?);
+            Ok(c[0]
+                + lsb * (c[1] - &c[0])
+                + msb * (c[2] - &c[0])
+                + lsb * msb * (c[3] - &c[2] - &c[1] + &c[0]))
         })?;
         let one = Variable::One;
         b.cs().enforce_constraint(
@@ -576,18 +576,12 @@ impl<F: PrimeField> ThreeBitCondNegLookupGadget<F> for AllocatedFp<F> {
         debug_assert_eq!(b.len(), 3);
         debug_assert_eq!(c.len(), 4);
         let result = Self::new_witness(b.cs(), || {
-            let lsb = usize::from(b[0].value()?);
-            let msb = usize::from(b[1].value()?);
-            let index = lsb + (msb << 1);
-            let intermediate = c[index];
-
-            let is_negative = b[2].value()?;
-            let y = if is_negative {
-                -intermediate
-            } else {
-                intermediate
-            };
-            Ok(y)
+            let (lsb, msb) = (b[0].field_value()?, b[1].field_value()?);
+            let y = c[0]
+                + lsb * (c[1] - &c[0])
+                + msb * (c[2] - &c[0])
+                + b0b1.field_value()? * (c[3] - &c[2] - &c[1] + &c[0]);
+            Ok(y - (y * b[2].field_value()?).double())
         })?;
 
         let y_lc = b0b1.lc() * (c[3] - &c[2] - &c[1] + &c[0])
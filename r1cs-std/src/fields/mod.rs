@@ -162,7 +162,7 @@ pub trait FieldVar<F: Field, ConstraintF: Field>:
         let d_inv = if self.is_constant() || d.is_constant() {
             d.inverse()?
         } else {
-            Self::new_witness(self.cs(), || Ok(d.value()?.inverse().unwrap_or(F::zero())))?
+            Self::new_witness(self.cs(), || Ok(d.value()?.inverse_or_zero()))?
         };
         Ok(d_inv * self)
     }
@@ -166,7 +166,7 @@ where
             // Allocate a variable whose value is either `self.z.inverse()` if the inverse exists,
             // and is zero otherwise.
             let z_inv = F::new_witness(ark_relations::ns!(cs, "z_inverse"), || {
-                Ok(self.z.value()?.inverse().unwrap_or_else(P::BaseField::zero))
+                Ok(self.z.value()?.inverse_or_zero())
             })?;
             // The inverse exists if `!self.is_zero()`.
             // This means that `z_inv * self.z = 1` if `self.is_not_zero()`, and